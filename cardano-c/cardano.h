@@ -207,7 +207,8 @@ cardano_xprv *cardano_wallet_root_key(cardano_wallet *wallet_ptr);
 * \param [in] wallet A pointer to a wallet created with `cardano_wallet_new` 
 * \param [in] alias A C string that can be used to retrieve an account from a wallet
 * \param [in] index The derivation key 
-* \returns pointer to the created account that must be freed with `cardano_account_delete` 
+* \returns pointer to the created account that must be freed with `cardano_account_delete`,
+*          or NULL if the index is not a valid account index (it must be lower than 0x80000000)
 */
 cardano_account *cardano_account_create(cardano_wallet *wallet, const char *alias, unsigned int index);
 
@@ -233,6 +234,26 @@ void cardano_account_delete(cardano_account *account);
 unsigned long cardano_account_generate_addresses(cardano_account *account, int internal, unsigned int from_index, unsigned long num_indices, char *addresses_ptr[], uint32_t protocol_magic);
 void cardano_account_delete_addresses(char *addresses_ptr[], unsigned long length);
 
+/*******/
+/* Fee */
+/*******/
+
+/*!
+* The parameters of the linear fee algorithm, in milli-lovelaces: the fee of a
+* transaction is `constant + coefficient * size_in_bytes`
+* \sa cardano_linear_fee_default()
+*/
+typedef struct cardano_linear_fee {
+    uint64_t constant;
+    uint64_t coefficient;
+} cardano_linear_fee_t;
+
+/*!
+* \brief The fee parameters of the mainnet
+* \param [out] out the parameters
+*/
+void cardano_linear_fee_default(cardano_linear_fee_t *out);
+
 /****************/
 /* Transactions */
 /****************/
@@ -456,18 +477,41 @@ cardano_transaction_error_t cardano_transaction_builder_add_input(cardano_transa
 * Note: that the calculation is not done again if more inputs and outputs are added after this call,
 * and in most typical cases this should be the last addition to the transaction.
 *
+* The fee are computed with the fee parameters of the mainnet.
+*
 * \param [in] tb the builder for the transaction
 * \param [in] change_addr used for the change (leftover values) output 
 * \returns 0 for success 0! for failure
+* \sa cardano_transaction_builder_add_change_addr_with_fee_alg()
 */
 cardano_result cardano_transaction_builder_add_change_addr(cardano_transaction_builder *tb, cardano_address *change_addr);
 
 /*!
-* \brief Calculate the fee for the transaction with a linear algorithm
+* \brief Same as `cardano_transaction_builder_add_change_addr()`, with the given fee parameters
+* \param [in] tb the builder for the transaction
+* \param [in] change_addr used for the change (leftover values) output
+* \param [in] fee the parameters of the fee algorithm
+* \returns 0 for success 0! for failure
+* \sa cardano_linear_fee_default()
+*/
+cardano_result cardano_transaction_builder_add_change_addr_with_fee_alg(cardano_transaction_builder *tb, cardano_address *change_addr, const cardano_linear_fee_t *fee);
+
+/*!
+* \brief Calculate the fee for the transaction with the linear algorithm of the mainnet
 * \returns fee
+* \sa cardano_transaction_builder_fee_with_fee_alg()
 */
 uint64_t cardano_transaction_builder_fee(cardano_transaction_builder *tb);
 
+/*!
+* \brief Calculate the fee for the transaction with a linear algorithm
+* \param [in] tb the builder for the transaction
+* \param [in] fee the parameters of the fee algorithm
+* \returns fee
+* \sa cardano_linear_fee_default()
+*/
+uint64_t cardano_transaction_builder_fee_with_fee_alg(cardano_transaction_builder *tb, const cardano_linear_fee_t *fee);
+
 /*!
 * struct for representing the sign in cardano_transaction_coin_diff_t
 * \sa cardano_transaction_coin_diff
@@ -494,7 +538,8 @@ typedef struct cardano_transaction_coin_diff {
 void cardano_transaction_balance_delete(cardano_transaction_coin_diff_t *balance);
 
 /*!
-* Try to return the differential between the outputs (including fees) and the inputs
+* Try to return the differential between the outputs (including fees) and the inputs,
+* the fee are computed with the fee parameters of the mainnet
 * \param [in] tb the builder for the transaction
 * \param [out] out a pointer to a cardano_transaction_coin_diff_t where: 
 *   - (sign == DIFF_ZERO) means we have a balanced transaction where inputs === outputs
@@ -504,10 +549,20 @@ void cardano_transaction_balance_delete(cardano_transaction_coin_diff_t *balance
 * and the value field indicates the quantity (in -1 and 1 cases)
 * Excessive input goes to larger fee.
 * \returns CARDANO_TRANSACTION_SUCCESS | CARDANO_TRANSACTION_COIN_OUT_OF_BOUNDS if the total is too big
-* \sa cardano_transaction_balance_delete()
+* \sa cardano_transaction_balance_delete() cardano_transaction_builder_balance_with_fee_alg()
 */
 cardano_transaction_error_t cardano_transaction_builder_balance(cardano_transaction_builder *tb, cardano_transaction_coin_diff_t **out);
 
+/*!
+* Same as `cardano_transaction_builder_balance()`, with the given fee parameters
+* \param [in] tb the builder for the transaction
+* \param [in] fee the parameters of the fee algorithm
+* \param [out] out see `cardano_transaction_builder_balance()`
+* \returns CARDANO_TRANSACTION_SUCCESS | CARDANO_TRANSACTION_COIN_OUT_OF_BOUNDS if the total is too big
+* \sa cardano_transaction_balance_delete() cardano_linear_fee_default()
+*/
+cardano_transaction_error_t cardano_transaction_builder_balance_with_fee_alg(cardano_transaction_builder *tb, const cardano_linear_fee_t *fee, cardano_transaction_coin_diff_t **out);
+
 /*!
 * Try to return the differential between the outputs (excluding fees) and the inputs
 * \param [in] tb the builder for the transaction
@@ -573,6 +628,108 @@ cardano_transaction_error_t cardano_transaction_finalized_add_witness(cardano_tr
 cardano_transaction_error_t cardano_transaction_finalized_output(cardano_transaction_finalized *tf, cardano_signed_transaction **txaux);
 void cardano_transaction_signed_delete(cardano_signed_transaction *txaux);
 
+/*******************/
+/* Input selection */
+/*******************/
+
+/* input selection error definitions */
+typedef enum _input_selection_error
+{
+    CARDANO_INPUT_SELECTION_SUCCESS = 0,
+    /*!There are no utxos to select the inputs from*/
+    CARDANO_INPUT_SELECTION_NO_INPUTS = 1,
+    /*!There are no outputs to pay for*/
+    CARDANO_INPUT_SELECTION_NO_OUTPUTS = 2,
+    /*!The utxos do not cover the outputs and the fee*/
+    CARDANO_INPUT_SELECTION_NOT_ENOUGH_INPUT = 3,
+    /*!A value is greater than the maximum allowed coin value*/
+    CARDANO_INPUT_SELECTION_COIN_OUT_OF_BOUNDS = 4,
+    /*!The transaction could not be built (e.g. it is too big)*/
+    CARDANO_INPUT_SELECTION_FAILURE = 5,
+} cardano_input_selection_error_t;
+
+/*!
+* The input selection algorithms
+*/
+typedef enum _input_selection_algorithm
+{
+    /*!spend the largest utxos first*/
+    CARDANO_INPUT_SELECTION_LARGEST_FIRST = 0,
+    /*!select random utxos, then improve the selection to get a change close to the value of the outputs*/
+    CARDANO_INPUT_SELECTION_RANDOM_IMPROVE = 1,
+} cardano_input_selection_algorithm_t;
+
+/*!
+* An unspent transaction output, and the pointer to it
+* \sa cardano_transaction_output_ptr_new() cardano_transaction_output_new()
+*/
+typedef struct cardano_utxo {
+    cardano_txoptr *txoptr;
+    cardano_txoutput *output;
+} cardano_utxo_t;
+
+typedef struct cardano_input_selection_result cardano_input_selection_result;
+
+/*!
+* \brief Select the inputs to spend to pay for the given outputs
+*
+* The fee is estimated with the linear algorithm of the given parameters,
+* the left over (if any) goes to an output to the change address.
+* \param [in] algorithm the input selection algorithm to use
+* \param [in] utxos the utxos to select the inputs from, NULL for none
+* \param [in] utxos_size number of utxos
+* \param [in] outputs the outputs of the transaction, NULL for none
+* \param [in] outputs_size number of outputs
+* \param [in] change_addr address of the change output
+* \param [in] fee the parameters of the fee algorithm, see `cardano_linear_fee_default()`
+* \param [out] result the selection. Use `cardano_input_selection_result_delete()` to release the memory
+* \returns CARDANO_INPUT_SELECTION_SUCCESS | CARDANO_INPUT_SELECTION_NO_INPUTS | CARDANO_INPUT_SELECTION_NO_OUTPUTS
+* | CARDANO_INPUT_SELECTION_NOT_ENOUGH_INPUT | CARDANO_INPUT_SELECTION_COIN_OUT_OF_BOUNDS | CARDANO_INPUT_SELECTION_FAILURE
+*/
+cardano_input_selection_error_t cardano_input_selection_compute(
+    cardano_input_selection_algorithm_t algorithm,
+    cardano_utxo_t utxos[],
+    size_t utxos_size,
+    cardano_txoutput *outputs[],
+    size_t outputs_size,
+    cardano_address *change_addr,
+    const cardano_linear_fee_t *fee,
+    cardano_input_selection_result **result);
+
+/*!
+* \brief Release the memory allocated by `cardano_input_selection_compute()`
+*/
+void cardano_input_selection_result_delete(cardano_input_selection_result *result);
+
+/*!
+* \brief The fee estimated for the transaction spending the selected inputs
+*/
+uint64_t cardano_input_selection_result_fee(cardano_input_selection_result *result);
+
+/*!
+* \brief The value of the change output, 0 if there is no change
+*/
+uint64_t cardano_input_selection_result_change(cardano_input_selection_result *result);
+
+/*!
+* \brief Get references to the selected inputs
+* \param [in] result an input selection result
+* \param [out] out_array array of pointers to cardano_txoptr that you can read with
+* `cardano_transaction_txoptr_txid` and `cardano_transaction_txoptr_index`.
+* You should NOT call cardano_transaction_output_ptr_delete with any of this pointers.
+* <br> Use `cardano_input_selection_result_delete_inputs` to delete the array of pointers
+* \param [out] size the size of the array
+*/
+void cardano_input_selection_result_get_inputs(
+    cardano_input_selection_result *result,
+    cardano_txoptr *(*out_array[]),
+    size_t *size);
+
+/*!
+* \brief Release the memory allocated by `cardano_input_selection_result_get_inputs`
+*/
+void cardano_input_selection_result_delete_inputs(cardano_txoptr *inputs[], size_t size);
+
 /****************/
 /* Block */
 /****************/
@@ -675,6 +832,60 @@ void cardano_block_delete_hash(char *hash);
 */
 void cardano_block_header_delete(cardano_block_header *header);
 
+/****************/
+/* Storage */
+/****************/
+
+/*!
+* \struct cardano_storage
+* \brief Opaque handler for a block storage (as created by the cardano-cli)
+*/
+typedef struct cardano_storage cardano_storage;
+
+/*!
+*\brief Open the storage at the given path
+*\param [in] path the root directory of the storage
+*\param [out] out_storage a pointer to the storage, you should call `cardano_storage_delete()` on this pointer
+*\returns CARDANO_RESULT_ERROR if the storage cannot be opened (e.g. the path is not the directory of an existing storage)
+*\relatesalso cardano_storage
+*/
+cardano_result cardano_storage_open(const char *path, cardano_storage **out_storage);
+
+/*!
+*\brief release the memory allocated with `cardano_storage_open`
+*\relatesalso cardano_storage
+*/
+void cardano_storage_delete(cardano_storage *storage);
+
+/*!
+*\brief Callback called with every block of a range
+*\param [in] bytes the block in its raw representation (see `cardano_raw_block_decode()`),
+*    only valid for the duration of the call
+*\param [in] size the size in bytes of the block
+*\param [in] user_data the pointer given to `cardano_storage_iterate_range()`
+*\returns 0 to carry on with the next block, anything else to stop the iteration
+*/
+typedef int (*cardano_storage_block_callback)(const uint8_t *bytes, size_t size, void *user_data);
+
+/*!
+*\brief Call `callback` with every block from `from` to `to` (both included), in chain order
+*\param [in] storage
+*\param [in] from the hash (in hexadecimal representation) of the first block
+*\param [in] to the hash (in hexadecimal representation) of the last block
+*\param [in] callback
+*\param [in] user_data passed on to `callback`
+*\returns CARDANO_RESULT_ERROR if a hash is invalid, `from` is not an ancestor of `to`
+*    or a block cannot be read. Stopping the iteration from the callback is not an error.
+*\relatesalso cardano_storage
+*/
+cardano_result cardano_storage_iterate_range(
+    cardano_storage *storage,
+    const char *from,
+    const char *to,
+    cardano_storage_block_callback callback,
+    void *user_data
+);
+
 #ifdef __cplusplus
 }
 #endif
@@ -0,0 +1,23 @@
+use cardano::fee::{LinearFee, Milli};
+use std::ptr;
+use types::*;
+
+impl<'a> From<&'a LinearFeeParameters> for LinearFee {
+    fn from(parameters: &'a LinearFeeParameters) -> Self {
+        fn milli(v: u64) -> Milli {
+            Milli::new(v / 1000, v % 1000)
+        }
+        LinearFee::new(milli(parameters.constant), milli(parameters.coefficient))
+    }
+}
+
+/// the fee parameters of the mainnet
+#[no_mangle]
+pub extern "C" fn cardano_linear_fee_default(out: *mut LinearFeeParameters) {
+    let fee = LinearFee::default();
+    let parameters = LinearFeeParameters {
+        constant: fee.constant.as_millis(),
+        coefficient: fee.coefficient.as_millis(),
+    };
+    unsafe { ptr::write(out, parameters) };
+}
@@ -0,0 +1,121 @@
+use cardano::fee::LinearFee;
+use cardano::input_selection::{InputSelectionAlgorithm, LargestFirst, RandomImprove};
+use cardano::tx::TxOut;
+use cardano::txutils::{Input, OutputPolicy};
+use std::{ptr, slice};
+use types::*;
+
+/// the C array, a NULL pointer being an empty array
+unsafe fn slice_or_empty<'a, T>(data: *const T, size: usize) -> &'a [T] {
+    if data.is_null() {
+        &[]
+    } else {
+        slice::from_raw_parts(data, size)
+    }
+}
+
+/// Select the inputs to spend, from the given utxos, to pay for the
+/// given outputs. The left over (if any) is sent to `change_addr`.
+///
+/// The fee is estimated with the linear fee algorithm of the given
+/// parameters. On success, use `cardano_input_selection_result_delete` to
+/// free the returned result.
+#[no_mangle]
+pub extern "C" fn cardano_input_selection_compute(
+    algorithm: SelectionAlgorithm,
+    c_utxos: *const Utxo,
+    utxos_size: usize,
+    c_outputs: *const TransactionOutputPtr,
+    outputs_size: usize,
+    change_addr: AddressPtr,
+    fee: *const LinearFeeParameters,
+    result_out: *mut InputSelectionResultPtr,
+) -> CardanoInputSelectionErrorCode {
+    let utxos = unsafe { slice_or_empty(c_utxos, utxos_size) };
+    let outputs = unsafe { slice_or_empty(c_outputs, outputs_size) };
+    let change_addr = unsafe { change_addr.as_ref() }.expect("Not a NULL PTR");
+    let fee = unsafe { fee.as_ref() }.expect("Not a NULL PTR");
+
+    let inputs: Vec<Input<()>> = utxos
+        .iter()
+        .map(|utxo| {
+            let txoptr = unsafe { utxo.txoptr.as_ref() }.expect("Not a NULL PTR");
+            let output = unsafe { utxo.output.as_ref() }.expect("Not a NULL PTR");
+            Input::new(txoptr.clone(), output.clone(), ())
+        })
+        .collect();
+    let outputs: Vec<TxOut> = outputs
+        .iter()
+        .map(|output| unsafe { output.as_ref() }.expect("Not a NULL PTR").clone())
+        .collect();
+
+    let fee_algorithm = LinearFee::from(fee);
+    let output_policy = OutputPolicy::One(change_addr.clone());
+    let result = match algorithm {
+        SelectionAlgorithm::LargestFirst => {
+            LargestFirst::from(inputs).compute(&fee_algorithm, outputs, &output_policy)
+        }
+        SelectionAlgorithm::RandomImprove => {
+            RandomImprove::from(inputs).compute(&fee_algorithm, outputs, &output_policy)
+        }
+    };
+
+    match result {
+        Ok(result) => {
+            unsafe { ptr::write(result_out, Box::into_raw(Box::new(result))) };
+            CardanoInputSelectionErrorCode::success()
+        }
+        Err(err) => err.into(),
+    }
+}
+
+#[no_mangle]
+pub extern "C" fn cardano_input_selection_result_delete(result: InputSelectionResultPtr) {
+    unsafe { Box::from_raw(result) };
+}
+
+/// the fee estimated for the transaction spending the selected inputs
+#[no_mangle]
+pub extern "C" fn cardano_input_selection_result_fee(result: InputSelectionResultPtr) -> u64 {
+    let result = unsafe { result.as_ref() }.expect("Not a NULL PTR");
+    u64::from(result.estimated_fees.to_coin())
+}
+
+/// the value sent to the change address, 0 if there is no change
+#[no_mangle]
+pub extern "C" fn cardano_input_selection_result_change(result: InputSelectionResultPtr) -> u64 {
+    let result = unsafe { result.as_ref() }.expect("Not a NULL PTR");
+    result.estimated_change.map(u64::from).unwrap_or(0)
+}
+
+#[no_mangle]
+pub extern "C" fn cardano_input_selection_result_get_inputs(
+    result: InputSelectionResultPtr,
+    out_array: *mut *mut TransactionOutputPointerPtr,
+    out_size: *mut usize,
+) {
+    let result = unsafe { result.as_mut() }.expect("Not a NULL PTR");
+    let mut inputs = result
+        .selected_inputs
+        .iter_mut()
+        .map(|input| &mut input.ptr as TransactionOutputPointerPtr)
+        .collect::<Vec<TransactionOutputPointerPtr>>()
+        .into_boxed_slice();
+
+    let ptr = inputs.as_mut_ptr();
+    let size = inputs.len();
+    std::mem::forget(inputs);
+
+    unsafe {
+        ptr::write(out_array, ptr);
+        ptr::write(out_size, size);
+    }
+}
+
+#[no_mangle]
+pub extern "C" fn cardano_input_selection_result_delete_inputs(
+    inputs: *mut TransactionOutputPointerPtr,
+    size: usize,
+) {
+    unsafe { Box::from_raw(slice::from_raw_parts_mut(inputs, size)) };
+}
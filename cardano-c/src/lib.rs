@@ -1,9 +1,13 @@
 extern crate cardano;
+extern crate cardano_storage;
 
 pub mod address;
 pub mod bip39;
 pub mod block;
+pub mod fee;
+pub mod input_selection;
 pub mod key;
+pub mod storage;
 pub mod transaction;
 pub mod types;
 pub mod wallet;
@@ -11,7 +15,10 @@ pub mod wallet;
 pub use address::*;
 pub use bip39::*;
 pub use block::*;
+pub use fee::*;
+pub use input_selection::*;
 pub use key::*;
+pub use storage::*;
 pub use transaction::*;
 pub use types::*;
 pub use wallet::*;
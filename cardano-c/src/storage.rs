@@ -0,0 +1,121 @@
+use cardano::block::HeaderHash;
+use cardano_storage::types::StorageFileType;
+use cardano_storage::{config::StorageConfig, BlockLocation, Error, Result, Storage};
+use std::ffi;
+use std::os::raw::{c_char, c_int, c_void};
+use std::path::PathBuf;
+use std::ptr;
+use std::str::FromStr;
+use types::{CardanoResult, StoragePtr};
+
+/// Callback invoked with every raw block of a range, returning anything
+/// other than 0 stops the iteration
+pub type BlockCallback = extern "C" fn(*const u8, usize, *mut c_void) -> c_int;
+
+fn ffi_header_hash(c_hash: *const c_char) -> Option<HeaderHash> {
+    let hash = unsafe { ffi::CStr::from_ptr(c_hash) }.to_str().ok()?;
+    HeaderHash::from_str(hash).ok()
+}
+
+/// the locations of the blocks from `from` to `to`, in chain order. Only
+/// the headers are decoded, to walk back from `to` to `from`. The walk stops
+/// at the date of `from`, failing if `from` is not an ancestor of `to`.
+fn range_locations(
+    storage: &Storage,
+    from: &HeaderHash,
+    to: HeaderHash,
+) -> Result<Vec<BlockLocation>> {
+    let from_location = storage.block_location(&from.clone().into())?;
+    let from_date = storage
+        .read_block_at(&from_location)?
+        .decode_header()?
+        .blockdate();
+
+    let mut locations = Vec::new();
+    let mut hash = to;
+    while &hash != from {
+        let location = storage.block_location(&hash.clone().into())?;
+        let header = storage.read_block_at(&location)?.decode_header()?;
+        if header.blockdate() <= from_date {
+            return Err(Error::BlockNotFound(from.clone().into()));
+        }
+        locations.push(location);
+        hash = header.previous_header();
+    }
+    locations.push(from_location);
+    locations.reverse();
+    Ok(locations)
+}
+
+/// a storage was created in `config`'s directory: `Storage::init` would
+/// create the missing directories of any directory it is given.
+fn has_storage_layout(config: &StorageConfig) -> bool {
+    [
+        StorageFileType::Pack,
+        StorageFileType::Index,
+        StorageFileType::Tag,
+    ]
+    .iter()
+    .all(|filetype| config.get_filetype_dir(*filetype).is_dir())
+}
+
+#[no_mangle]
+pub extern "C" fn cardano_storage_open(
+    c_path: *const c_char,
+    out_storage: *mut StoragePtr,
+) -> CardanoResult {
+    let path = match unsafe { ffi::CStr::from_ptr(c_path) }.to_str() {
+        Ok(path) => PathBuf::from(path),
+        Err(_) => return CardanoResult::failure(),
+    };
+    // opening is not creating: only open an existing storage
+    let config = StorageConfig::new(&path);
+    if !has_storage_layout(&config) {
+        return CardanoResult::failure();
+    }
+    let storage = match Storage::init(&config) {
+        Ok(storage) => storage,
+        Err(_) => return CardanoResult::failure(),
+    };
+    let pointer = Box::into_raw(Box::new(storage));
+    unsafe { ptr::write(out_storage, pointer) };
+    CardanoResult::success()
+}
+
+#[no_mangle]
+pub extern "C" fn cardano_storage_delete(storage: StoragePtr) {
+    unsafe { Box::from_raw(storage) };
+}
+
+/// Call `callback` with every raw block from `c_from` to `c_to` (both
+/// included), in chain order. Only the locations of the blocks of the
+/// range are held in memory, the blocks are read one at a time.
+#[no_mangle]
+pub extern "C" fn cardano_storage_iterate_range(
+    storage: StoragePtr,
+    c_from: *const c_char,
+    c_to: *const c_char,
+    callback: BlockCallback,
+    user_data: *mut c_void,
+) -> CardanoResult {
+    let storage = unsafe { storage.as_ref() }.expect("Not a NULL PTR");
+    let (from, to) = match (ffi_header_hash(c_from), ffi_header_hash(c_to)) {
+        (Some(from), Some(to)) => (from, to),
+        _ => return CardanoResult::failure(),
+    };
+    let locations = match range_locations(storage, &from, to) {
+        Ok(locations) => locations,
+        Err(_) => return CardanoResult::failure(),
+    };
+    for location in locations {
+        let raw_block = match storage.read_block_at(&location) {
+            Ok(raw_block) => raw_block,
+            Err(_) => return CardanoResult::failure(),
+        };
+        let bytes = raw_block.as_ref();
+        if callback(bytes.as_ptr(), bytes.len(), user_data) != 0 {
+            break;
+        }
+    }
+    CardanoResult::success()
+}
@@ -83,17 +83,20 @@ pub extern "C" fn cardano_transaction_builder_add_input(
     }
 }
 
-#[no_mangle]
-pub extern "C" fn cardano_transaction_builder_add_change_addr(
+fn ffi_linear_fee(fee: *const LinearFeeParameters) -> LinearFee {
+    LinearFee::from(unsafe { fee.as_ref() }.expect("Not a NULL PTR"))
+}
+
+fn add_change_addr(
     tb: TransactionBuilderPtr,
     change_addr: AddressPtr,
+    fee: &LinearFee,
 ) -> CardanoResult {
     let builder = unsafe { tb.as_mut() }.expect("Not a NULL PTR");
     let addr = unsafe { change_addr.as_ref() }.expect("Not a NULL PTR");
-    let fee = LinearFee::default();
 
     let output_policy = OutputPolicy::One(addr.clone());
-    if let Ok(_) = builder.add_output_policy(&fee, &output_policy) {
+    if let Ok(_) = builder.add_output_policy(fee, &output_policy) {
         CardanoResult::success()
     } else {
         CardanoResult::failure()
@@ -101,11 +104,26 @@ pub extern "C" fn cardano_transaction_builder_add_change_addr(
 }
 
 #[no_mangle]
-pub extern "C" fn cardano_transaction_builder_fee(tb: TransactionBuilderPtr) -> u64 {
+pub extern "C" fn cardano_transaction_builder_add_change_addr(
+    tb: TransactionBuilderPtr,
+    change_addr: AddressPtr,
+) -> CardanoResult {
+    add_change_addr(tb, change_addr, &LinearFee::default())
+}
+
+#[no_mangle]
+pub extern "C" fn cardano_transaction_builder_add_change_addr_with_fee_alg(
+    tb: TransactionBuilderPtr,
+    change_addr: AddressPtr,
+    fee: *const LinearFeeParameters,
+) -> CardanoResult {
+    add_change_addr(tb, change_addr, &ffi_linear_fee(fee))
+}
+
+fn builder_fee(tb: TransactionBuilderPtr, fee_algo: &LinearFee) -> u64 {
     let builder = unsafe { tb.as_mut() }.expect("Not a NULL PTR");
-    let fee_algo = LinearFee::default();
 
-    if let Ok(fee) = builder.calculate_fee(&fee_algo) {
+    if let Ok(fee) = builder.calculate_fee(fee_algo) {
         u64::from(fee.to_coin())
     } else {
         // failed to calculate transaction fee, return zero
@@ -114,12 +132,25 @@ pub extern "C" fn cardano_transaction_builder_fee(tb: TransactionBuilderPtr) ->
 }
 
 #[no_mangle]
-pub extern "C" fn cardano_transaction_builder_balance(
+pub extern "C" fn cardano_transaction_builder_fee(tb: TransactionBuilderPtr) -> u64 {
+    builder_fee(tb, &LinearFee::default())
+}
+
+#[no_mangle]
+pub extern "C" fn cardano_transaction_builder_fee_with_fee_alg(
+    tb: TransactionBuilderPtr,
+    fee: *const LinearFeeParameters,
+) -> u64 {
+    builder_fee(tb, &ffi_linear_fee(fee))
+}
+
+fn builder_balance(
     tb: TransactionBuilderPtr,
+    fee: &LinearFee,
     out: *mut *mut Balance,
 ) -> CardanoTransactionErrorCode {
     let builder = unsafe { tb.as_mut() }.expect("Not a NULL PTR");
-    let balance: Box<Balance> = match builder.balance(&LinearFee::default()) {
+    let balance: Box<Balance> = match builder.balance(fee) {
         Ok(v) => Box::new(v.into()),
         Err(e) => return e.into(),
     };
@@ -129,6 +160,23 @@ pub extern "C" fn cardano_transaction_builder_balance(
     CardanoTransactionErrorCode::success()
 }
 
+#[no_mangle]
+pub extern "C" fn cardano_transaction_builder_balance(
+    tb: TransactionBuilderPtr,
+    out: *mut *mut Balance,
+) -> CardanoTransactionErrorCode {
+    builder_balance(tb, &LinearFee::default(), out)
+}
+
+#[no_mangle]
+pub extern "C" fn cardano_transaction_builder_balance_with_fee_alg(
+    tb: TransactionBuilderPtr,
+    fee: *const LinearFeeParameters,
+    out: *mut *mut Balance,
+) -> CardanoTransactionErrorCode {
+    builder_balance(tb, &ffi_linear_fee(fee), out)
+}
+
 #[no_mangle]
 pub extern "C" fn cardano_transaction_builder_balance_without_fees(
     tb: TransactionBuilderPtr,
@@ -2,9 +2,11 @@ use cardano::address;
 use cardano::block;
 use cardano::coin::CoinDiff;
 use cardano::hdwallet;
+use cardano::input_selection;
 use cardano::tx;
 use cardano::txbuild;
 use cardano::wallet::bip44;
+use cardano_storage::Storage;
 use std::os::raw::c_int;
 
 /// C result type, where 0 is success and !0 is failure
@@ -100,6 +102,80 @@ impl From<txbuild::Error> for CardanoTransactionErrorCode {
     }
 }
 
+#[repr(C)]
+pub struct CardanoInputSelectionErrorCode(c_int);
+
+impl CardanoInputSelectionErrorCode {
+    pub fn success() -> Self {
+        CardanoInputSelectionErrorCode(0)
+    }
+
+    ///No utxos to select the inputs from
+    pub fn no_inputs() -> Self {
+        CardanoInputSelectionErrorCode(1)
+    }
+
+    ///No outputs to pay for
+    pub fn no_outputs() -> Self {
+        CardanoInputSelectionErrorCode(2)
+    }
+
+    ///The utxos do not cover the outputs and the fee
+    pub fn not_enough_input() -> Self {
+        CardanoInputSelectionErrorCode(3)
+    }
+
+    ///value is to big, max = 45000000000000000
+    pub fn coin_out_of_bounds() -> Self {
+        CardanoInputSelectionErrorCode(4)
+    }
+
+    ///The transaction could not be built (e.g. it is too big)
+    pub fn failure() -> Self {
+        CardanoInputSelectionErrorCode(5)
+    }
+}
+
+impl From<input_selection::Error> for CardanoInputSelectionErrorCode {
+    fn from(err: input_selection::Error) -> Self {
+        match err {
+            input_selection::Error::NoInputs => Self::no_inputs(),
+            input_selection::Error::NoOutputs => Self::no_outputs(),
+            input_selection::Error::NotEnoughInput => Self::not_enough_input(),
+            input_selection::Error::NotEnoughFees => Self::not_enough_input(),
+            input_selection::Error::CoinError(_) => Self::coin_out_of_bounds(),
+            input_selection::Error::TxBuildError(_)
+            | input_selection::Error::FeeError(_)
+            | input_selection::Error::CborError(_) => Self::failure(),
+        }
+    }
+}
+
+/// The input selection algorithms
+#[repr(C)]
+pub enum SelectionAlgorithm {
+    /// spend the largest utxos first
+    LargestFirst,
+    /// random selection, then improve the selection to get a change
+    /// close to the value of the outputs
+    RandomImprove,
+}
+
+/// The parameters of the linear fee algorithm, in milli-lovelaces: the fee
+/// of a transaction is `constant + coefficient * size_in_bytes`
+#[repr(C)]
+pub struct LinearFeeParameters {
+    pub constant: u64,
+    pub coefficient: u64,
+}
+
+/// An unspent transaction output, and the pointer to it
+#[repr(C)]
+pub struct Utxo {
+    pub txoptr: TransactionOutputPointerPtr,
+    pub output: TransactionOutputPtr,
+}
+
 #[repr(C)]
 pub enum DiffType {
     Positive,
@@ -168,8 +244,14 @@ pub type TransactionBuilderPtr = *mut txbuild::TxBuilder;
 /// C pointer to a Transaction finalized;
 pub type TransactionFinalizedPtr = *mut txbuild::TxFinalized;
 
+/// C pointer to the result of an input selection;
+pub type InputSelectionResultPtr = *mut input_selection::InputSelectionResult<()>;
+
 /// C pointer to a Block;
 pub type BlockPtr = *mut block::Block;
 
 /// C pointer to a BlockHeader;
 pub type BlockHeaderPtr = *mut block::BlockHeader;
+
+/// C pointer to a Storage;
+pub type StoragePtr = *mut Storage;
@@ -4,7 +4,6 @@ use cardano::config::ProtocolMagic;
 use cardano::hdwallet;
 use cardano::hdwallet::XPrv;
 use cardano::wallet::bip44;
-use cardano::wallet::scheme::Wallet;
 
 use std::os::raw::c_char;
 use std::{ffi, ptr, slice};
@@ -76,6 +75,9 @@ pub extern "C" fn cardano_wallet_root_key(wallet_ptr: WalletPtr) -> XPrvPtr {
 /// an account with this given index. The alias here is only an handy tool
 /// to retrieve a created account from a wallet.
 ///
+/// Returns a null pointer if the index is not a valid BIP44 account index
+/// (i.e. it is not lower than `0x80000000`).
+///
 /// The returned object is not owned by any smart pointer or garbage collector.
 /// To avoid memory leak, use `cardano_account_delete`
 ///
@@ -88,7 +90,10 @@ pub extern "C" fn cardano_account_create(
     let wallet = unsafe { wallet_ptr.as_mut() }.expect("Not a NULL PTR");
     let account_alias = unsafe { ffi::CStr::from_ptr(account_alias).to_string_lossy() };
 
-    let account = wallet.create_account(&account_alias, account_index);
+    let account = match wallet.try_create_account(&account_alias, account_index) {
+        Ok(account) => account,
+        Err(_) => return ptr::null_mut(),
+    };
     let account = Box::new(account.public());
 
     Box::into_raw(account)
@@ -0,0 +1,164 @@
+#include <stdio.h>
+#include <stdlib.h>
+#include <stdint.h>
+#include <string.h>
+#include "../cardano.h"
+#include "unity/unity.h"
+
+//Variables for the setUp function
+cardano_wallet *wallet;
+cardano_account *account;
+cardano_address *input_address;
+cardano_address *output_address;
+cardano_txoptr *utxo_ptrs[2];
+cardano_txoutput *utxo_outputs[2];
+cardano_utxo_t utxos[2];
+cardano_linear_fee_t fee;
+
+//Constants
+static uint32_t PROTOCOL_MAGIC = 1;
+static const uint8_t static_wallet_entropy[16] = {0, 1, 2, 3, 4, 5, 6, 7, 8, 9, 10, 11, 12, 13, 14, 15};
+static uint8_t txid[32] = {0};
+
+void setUp()
+{
+    cardano_wallet_new(
+        static_wallet_entropy,
+        sizeof(static_wallet_entropy),
+        "password",
+        strlen("password"),
+        &wallet);
+
+    account = cardano_account_create(wallet, "main", 0);
+
+    char *addresses[2];
+    size_t NUMBER_OF_ADDRESSES = sizeof(addresses) / sizeof(char *);
+
+    cardano_account_generate_addresses(account, 0, 0, NUMBER_OF_ADDRESSES, addresses, PROTOCOL_MAGIC);
+
+    input_address = cardano_address_import_base58(addresses[0]);
+    output_address = cardano_address_import_base58(addresses[1]);
+
+    cardano_account_delete_addresses(addresses, NUMBER_OF_ADDRESSES);
+
+    utxo_ptrs[0] = cardano_transaction_output_ptr_new(txid, 0);
+    utxo_outputs[0] = cardano_transaction_output_new(input_address, 1000000);
+    utxo_ptrs[1] = cardano_transaction_output_ptr_new(txid, 1);
+    utxo_outputs[1] = cardano_transaction_output_new(input_address, 5000000);
+
+    for (int i = 0; i < 2; i++)
+    {
+        utxos[i].txoptr = utxo_ptrs[i];
+        utxos[i].output = utxo_outputs[i];
+    }
+
+    cardano_linear_fee_default(&fee);
+}
+
+void tearDown()
+{
+    for (int i = 0; i < 2; i++)
+    {
+        cardano_transaction_output_ptr_delete(utxo_ptrs[i]);
+        cardano_transaction_output_delete(utxo_outputs[i]);
+    }
+
+    cardano_address_delete(input_address);
+
+    cardano_address_delete(output_address);
+
+    cardano_account_delete(account);
+
+    cardano_wallet_delete(wallet);
+}
+
+void test_largest_first_selects_largest_utxo()
+{
+    cardano_txoutput *outputs[1] = {cardano_transaction_output_new(output_address, 2000000)};
+    cardano_input_selection_result *result;
+
+    cardano_input_selection_error_t rc = cardano_input_selection_compute(
+        CARDANO_INPUT_SELECTION_LARGEST_FIRST, utxos, 2, outputs, 1, input_address, &fee, &result);
+
+    TEST_ASSERT_EQUAL(CARDANO_INPUT_SELECTION_SUCCESS, rc);
+
+    cardano_txoptr **inputs;
+    size_t inputs_size;
+    cardano_input_selection_result_get_inputs(result, &inputs, &inputs_size);
+
+    TEST_ASSERT_EQUAL(1, inputs_size);
+    TEST_ASSERT_EQUAL(1, cardano_transaction_txoptr_index(inputs[0]));
+
+    uint64_t fee = cardano_input_selection_result_fee(result);
+    uint64_t change = cardano_input_selection_result_change(result);
+    TEST_ASSERT_TRUE(fee > 0);
+    TEST_ASSERT_EQUAL_UINT64(5000000, 2000000 + fee + change);
+
+    cardano_input_selection_result_delete_inputs(inputs, inputs_size);
+    cardano_input_selection_result_delete(result);
+    cardano_transaction_output_delete(outputs[0]);
+}
+
+void test_random_improve_not_enough_input()
+{
+    cardano_txoutput *outputs[1] = {cardano_transaction_output_new(output_address, 10000000)};
+    cardano_input_selection_result *result;
+
+    cardano_input_selection_error_t rc = cardano_input_selection_compute(
+        CARDANO_INPUT_SELECTION_RANDOM_IMPROVE, utxos, 2, outputs, 1, input_address, &fee, &result);
+
+    TEST_ASSERT_EQUAL(CARDANO_INPUT_SELECTION_NOT_ENOUGH_INPUT, rc);
+
+    cardano_transaction_output_delete(outputs[0]);
+}
+
+void test_no_outputs()
+{
+    cardano_input_selection_result *result;
+
+    cardano_input_selection_error_t rc = cardano_input_selection_compute(
+        CARDANO_INPUT_SELECTION_LARGEST_FIRST, utxos, 2, utxo_outputs, 0, input_address, &fee, &result);
+
+    TEST_ASSERT_EQUAL(CARDANO_INPUT_SELECTION_NO_OUTPUTS, rc);
+}
+
+void test_null_utxos()
+{
+    cardano_txoutput *outputs[1] = {cardano_transaction_output_new(output_address, 2000000)};
+    cardano_input_selection_result *result;
+
+    cardano_input_selection_error_t rc = cardano_input_selection_compute(
+        CARDANO_INPUT_SELECTION_LARGEST_FIRST, NULL, 2, outputs, 1, input_address, &fee, &result);
+
+    TEST_ASSERT_EQUAL(CARDANO_INPUT_SELECTION_NO_INPUTS, rc);
+
+    cardano_transaction_output_delete(outputs[0]);
+}
+
+void test_custom_fee()
+{
+    cardano_txoutput *outputs[1] = {cardano_transaction_output_new(output_address, 2000000)};
+    cardano_input_selection_result *result;
+    cardano_linear_fee_t constant_fee = {.constant = 1000000, .coefficient = 0};
+
+    cardano_input_selection_error_t rc = cardano_input_selection_compute(
+        CARDANO_INPUT_SELECTION_LARGEST_FIRST, utxos, 2, outputs, 1, input_address, &constant_fee, &result);
+
+    TEST_ASSERT_EQUAL(CARDANO_INPUT_SELECTION_SUCCESS, rc);
+    TEST_ASSERT_EQUAL_UINT64(1000, cardano_input_selection_result_fee(result));
+    TEST_ASSERT_EQUAL_UINT64(5000000 - 2000000 - 1000, cardano_input_selection_result_change(result));
+
+    cardano_input_selection_result_delete(result);
+    cardano_transaction_output_delete(outputs[0]);
+}
+
+int main(void)
+{
+    UNITY_BEGIN();
+    RUN_TEST(test_largest_first_selects_largest_utxo);
+    RUN_TEST(test_random_improve_not_enough_input);
+    RUN_TEST(test_no_outputs);
+    RUN_TEST(test_null_utxos);
+    RUN_TEST(test_custom_fee);
+    return UNITY_END();
+}
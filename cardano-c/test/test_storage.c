@@ -0,0 +1,131 @@
+#define _XOPEN_SOURCE 700
+
+#include <stdio.h>
+#include <stdlib.h>
+#include <stdint.h>
+#include <string.h>
+#include <errno.h>
+#include <sys/stat.h>
+#include "../cardano.h"
+#include "unity/unity.h"
+#include "raw_block.h"
+
+static const char *BLOCK_HASH = "2314379b272c68c3ceca75d9d58aea689149e302c6462331ef3a3bb1c4f1ed98";
+static const char *UNKNOWN_HASH = "0aaa0652f9316734a49889a02fb79010ca7524202dc8934485d52023dce9be2d";
+
+/* the header of the blob files: magic, file type (BLOB) and version 1 */
+static const uint8_t BLOB_HEADER[] = {
+    0xfe, 'C', 'A', 'R', 'D', 'A', 'N', 'O', 'B', 'L', 'O', 'B', 0x0, 0x0, 0x0, 0x1};
+
+static char storage_path[] = "/tmp/cardano-c-storage-XXXXXX";
+
+/* the directories of a storage, as the storage library creates them */
+static void make_storage_layout(void)
+{
+    const char *dirs[] = {"blob", "index", "pack", "tag"};
+    char dir_path[256];
+    for (size_t i = 0; i < sizeof(dirs) / sizeof(dirs[0]); i++) {
+        snprintf(dir_path, sizeof(dir_path), "%s/%s", storage_path, dirs[i]);
+        TEST_ASSERT_TRUE(mkdir(dir_path, 0700) == 0 || errno == EEXIST);
+    }
+}
+
+/* a storage with RAW_BLOCK as a loose block */
+static cardano_storage *open_storage(void)
+{
+    cardano_storage *storage;
+    make_storage_layout();
+    TEST_ASSERT_EQUAL(CARDANO_RESULT_SUCCESS, cardano_storage_open(storage_path, &storage));
+
+    char blob_path[256];
+    snprintf(blob_path, sizeof(blob_path), "%s/blob/%s", storage_path, BLOCK_HASH);
+    FILE *blob = fopen(blob_path, "wb");
+    TEST_ASSERT_NOT_NULL(blob);
+    fwrite(BLOB_HEADER, 1, sizeof(BLOB_HEADER), blob);
+    fwrite(RAW_BLOCK, 1, sizeof(RAW_BLOCK), blob);
+    fclose(blob);
+    return storage;
+}
+
+static int count_blocks(const uint8_t *bytes, size_t size, void *user_data)
+{
+    TEST_ASSERT_EQUAL(sizeof(RAW_BLOCK), size);
+    TEST_ASSERT_EQUAL_HEX8_ARRAY(RAW_BLOCK, bytes, size);
+    (*(int *)user_data)++;
+    return 0;
+}
+
+static int stop(const uint8_t *bytes, size_t size, void *user_data)
+{
+    (*(int *)user_data)++;
+    return 1;
+}
+
+void test_open_missing_storage(void)
+{
+    cardano_storage *storage;
+    cardano_result rc = cardano_storage_open("/tmp/cardano-c-storage-does-not-exist", &storage);
+    TEST_ASSERT_EQUAL(CARDANO_RESULT_ERROR, rc);
+}
+
+/* must run before the storage layout is created in storage_path */
+void test_open_not_a_storage(void)
+{
+    cardano_storage *storage;
+    cardano_result rc = cardano_storage_open(storage_path, &storage);
+    TEST_ASSERT_EQUAL(CARDANO_RESULT_ERROR, rc);
+
+    char pack_path[256];
+    snprintf(pack_path, sizeof(pack_path), "%s/pack", storage_path);
+    struct stat st;
+    TEST_ASSERT_NOT_EQUAL(0, stat(pack_path, &st));
+}
+
+void test_iterate_range(void)
+{
+    cardano_storage *storage = open_storage();
+    int count = 0;
+
+    cardano_result rc = cardano_storage_iterate_range(storage, BLOCK_HASH, BLOCK_HASH, count_blocks, &count);
+    TEST_ASSERT_EQUAL(CARDANO_RESULT_SUCCESS, rc);
+    TEST_ASSERT_EQUAL(1, count);
+
+    count = 0;
+    rc = cardano_storage_iterate_range(storage, BLOCK_HASH, BLOCK_HASH, stop, &count);
+    TEST_ASSERT_EQUAL(CARDANO_RESULT_SUCCESS, rc);
+    TEST_ASSERT_EQUAL(1, count);
+
+    cardano_storage_delete(storage);
+}
+
+void test_iterate_invalid_range(void)
+{
+    cardano_storage *storage = open_storage();
+    int count = 0;
+
+    /* the parent of the block is not in the storage */
+    cardano_result rc = cardano_storage_iterate_range(storage, UNKNOWN_HASH, BLOCK_HASH, count_blocks, &count);
+    TEST_ASSERT_EQUAL(CARDANO_RESULT_ERROR, rc);
+    rc = cardano_storage_iterate_range(storage, BLOCK_HASH, UNKNOWN_HASH, count_blocks, &count);
+    TEST_ASSERT_EQUAL(CARDANO_RESULT_ERROR, rc);
+    rc = cardano_storage_iterate_range(storage, "not a hash", BLOCK_HASH, count_blocks, &count);
+    TEST_ASSERT_EQUAL(CARDANO_RESULT_ERROR, rc);
+    TEST_ASSERT_EQUAL(0, count);
+
+    cardano_storage_delete(storage);
+}
+
+int main(void)
+{
+    if (mkdtemp(storage_path) == NULL) {
+        perror("mkdtemp");
+        return 1;
+    }
+
+    UNITY_BEGIN();
+    RUN_TEST(test_open_missing_storage);
+    RUN_TEST(test_open_not_a_storage);
+    RUN_TEST(test_iterate_range);
+    RUN_TEST(test_iterate_invalid_range);
+    return UNITY_END();
+}
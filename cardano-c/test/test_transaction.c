@@ -205,6 +205,30 @@ void test_transaction_balance_zero() {
     TEST_ASSERT_EQUAL(DIFF_ZERO, (*balance).sign);
 }
 
+void test_transaction_custom_fee() {
+    /* a flat fee of 1000 lovelaces */
+    cardano_linear_fee_t flat_fee = { .constant = 1000000, .coefficient = 0 };
+
+    cardano_transaction_builder_add_input(txbuilder, input, 10000);
+    cardano_transaction_builder_add_output(txbuilder, output);
+
+    TEST_ASSERT_EQUAL(1000, cardano_transaction_builder_fee_with_fee_alg(txbuilder, &flat_fee));
+
+    /* the functions without fee parameters use the ones of the mainnet */
+    cardano_linear_fee_t mainnet_fee;
+    cardano_linear_fee_default(&mainnet_fee);
+    TEST_ASSERT_EQUAL(cardano_transaction_builder_fee(txbuilder),
+                      cardano_transaction_builder_fee_with_fee_alg(txbuilder, &mainnet_fee));
+
+    cardano_transaction_coin_diff_t *balance;
+    cardano_transaction_error_t rc = cardano_transaction_builder_balance_with_fee_alg(txbuilder, &flat_fee, &balance);
+
+    TEST_ASSERT_EQUAL(CARDANO_TRANSACTION_SUCCESS, rc);
+    TEST_ASSERT_EQUAL(DIFF_POSITIVE, (*balance).sign);
+    TEST_ASSERT_EQUAL(10000 - 1000 - 1000, (*balance).value);
+    cardano_transaction_balance_delete(balance);
+}
+
 void test_transaction_builder_balance_too_big() {
     cardano_txoptr *input1 = cardano_transaction_output_ptr_new(txid, 1);
     cardano_txoptr *input2 = cardano_transaction_output_ptr_new(txid, 2);
@@ -383,6 +407,7 @@ int main(void)
     RUN_TEST(test_transaction_balance_zero);
     RUN_TEST(test_transaction_balance_negative);
     RUN_TEST(test_transaction_balance_positive);
+    RUN_TEST(test_transaction_custom_fee);
     RUN_TEST(test_transaction_builder_balance_too_big);
     RUN_TEST(test_transaction_balance_without_fee_zero);
     RUN_TEST(test_transaction_balance_without_fee_negative);
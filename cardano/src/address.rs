@@ -19,10 +19,11 @@ use config::NetworkMagic;
 use hdpayload::HDAddressPayload;
 use hdwallet::XPub;
 use redeem;
-use util::{base58, try_from_slice::TryFromSlice};
+use util::{base58, hex, try_from_slice::TryFromSlice};
 
 use std::{
-    fmt,
+    collections::BTreeMap,
+    error, fmt,
     io::{BufRead, Write},
     str::FromStr,
 };
@@ -227,7 +228,10 @@ pub struct Attributes {
     pub derivation_path: Option<HDAddressPayload>,
     pub stake_distribution: StakeDistribution,
     pub network_magic: NetworkMagic,
-    // attr_remains ? whatever...
+    /// the attributes this implementation does not know about, by key
+    /// (their value is kept encoded). They are part of the address hash,
+    /// so they are kept to serialize the address back as it was.
+    pub remains: BTreeMap<u64, Vec<u8>>,
 }
 impl Attributes {
     pub fn new_bootstrap_era(hdap: Option<HDAddressPayload>, network_magic: NetworkMagic) -> Self {
@@ -235,6 +239,7 @@ impl Attributes {
             derivation_path: hdap,
             stake_distribution: StakeDistribution::BootstrapEraDistr,
             network_magic,
+            remains: BTreeMap::new(),
         }
     }
     pub fn new_single_key(
@@ -246,6 +251,7 @@ impl Attributes {
             derivation_path: hdap,
             stake_distribution: StakeDistribution::new_single_key(pubk),
             network_magic,
+            remains: BTreeMap::new(),
         }
     }
 }
@@ -270,6 +276,7 @@ impl cbor_event::se::Serialize for Attributes {
         if let NetworkMagic::Magic(_) = &self.network_magic {
             len += 1
         };
+        len += self.remains.len() as u64;
         let serializer = serializer.write_map(cbor_event::Len::Len(len))?;
         let serializer = match &self.stake_distribution {
             &StakeDistribution::BootstrapEraDistr => serializer,
@@ -289,6 +296,11 @@ impl cbor_event::se::Serialize for Attributes {
                 .write_unsigned_integer(ATTRIBUTE_NAME_TAG_NETWORK_MAGIC)?
                 .write_bytes(cbor!(&(network_magic as u32))?)?,
         };
+        for (key, value) in self.remains.iter() {
+            serializer
+                .write_unsigned_integer(*key)?
+                .write_bytes(value)?;
+        }
         Ok(serializer)
     }
 }
@@ -307,6 +319,7 @@ impl cbor_event::de::Deserialize for Attributes {
         let mut stake_distribution = StakeDistribution::BootstrapEraDistr;
         let mut derivation_path = None;
         let mut network_magic = NetworkMagic::NoMagic;
+        let mut remains = BTreeMap::new();
         while len > 0 {
             let key = reader.unsigned_integer()?;
             match key {
@@ -319,10 +332,8 @@ impl cbor_event::de::Deserialize for Attributes {
                     network_magic = NetworkMagic::Magic(n);
                 }
                 _ => {
-                    return Err(cbor_event::Error::CustomError(format!(
-                        "invalid Attribute key {}",
-                        key
-                    )));
+                    // every attribute value is encoded as bytes
+                    remains.insert(key, reader.bytes()?);
                 }
             }
             len -= 1;
@@ -331,6 +342,7 @@ impl cbor_event::de::Deserialize for Attributes {
             derivation_path,
             stake_distribution,
             network_magic,
+            remains,
         })
     }
 }
@@ -442,6 +454,82 @@ impl From<ExtendedAddr> for Addr {
     }
 }
 
+impl Addr {
+    /// the address in the given textual encoding (`Display` uses base58)
+    pub fn encode(&self, encoding: AddrEncoding) -> String {
+        encoding.encode(&self.0)
+    }
+}
+
+/// The textual encodings of the addresses
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+pub enum AddrEncoding {
+    /// the usual encoding of the addresses
+    Base58,
+    /// the hexadecimal encoding of the CBOR serialization
+    Hex,
+}
+impl AddrEncoding {
+    /// every address encoding, in the order of detection
+    pub const ALL: [AddrEncoding; 2] = [AddrEncoding::Hex, AddrEncoding::Base58];
+
+    pub fn encode(self, bytes: &[u8]) -> String {
+        match self {
+            AddrEncoding::Base58 => base58::encode(bytes),
+            AddrEncoding::Hex => hex::encode(bytes),
+        }
+    }
+
+    pub fn decode(self, s: &str) -> Result<Vec<u8>, ParseExtendedAddrError> {
+        match self {
+            AddrEncoding::Base58 => base58::decode(s).map_err(ParseExtendedAddrError::Base58Error),
+            AddrEncoding::Hex => hex::decode(s).map_err(ParseExtendedAddrError::HexError),
+        }
+    }
+
+    /// true if the string looks like an address in this encoding
+    ///
+    /// The serialized addresses start with the CBOR tag 24 of the
+    /// serialized address (`0x82 0xd8 0x18`). In base58, they always start
+    /// with a letter and never contain a `0`.
+    fn matches(self, s: &str) -> bool {
+        match self {
+            AddrEncoding::Base58 => true,
+            AddrEncoding::Hex => {
+                s.len() % 2 == 0
+                    && s.to_lowercase().starts_with("82d818")
+                    && s.chars().all(|c| c.is_ascii_hexdigit())
+            }
+        }
+    }
+
+    /// the encoding of the given address string
+    pub fn detect(s: &str) -> Self {
+        *AddrEncoding::ALL
+            .iter()
+            .find(|encoding| encoding.matches(s))
+            .unwrap_or(&AddrEncoding::Base58)
+    }
+}
+impl fmt::Display for AddrEncoding {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            AddrEncoding::Base58 => write!(f, "base58"),
+            AddrEncoding::Hex => write!(f, "hex"),
+        }
+    }
+}
+impl FromStr for AddrEncoding {
+    type Err = String;
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "base58" => Ok(AddrEncoding::Base58),
+            "hex" => Ok(AddrEncoding::Hex),
+            _ => Err(format!("unknown address encoding `{}'", s)),
+        }
+    }
+}
+
 impl fmt::Display for Addr {
     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
         write!(f, "{}", base58::encode(&self.0))
@@ -561,13 +649,44 @@ impl ExtendedAddr {
 pub enum ParseExtendedAddrError {
     EncodingError(cbor_event::Error),
     Base58Error(base58::Error),
+    HexError(hex::Error),
+}
+impl fmt::Display for ParseExtendedAddrError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            ParseExtendedAddrError::EncodingError(_) => write!(f, "invalid address"),
+            ParseExtendedAddrError::Base58Error(_) => write!(f, "invalid base58 address"),
+            ParseExtendedAddrError::HexError(_) => write!(f, "invalid hexadecimal address"),
+        }
+    }
 }
+impl error::Error for ParseExtendedAddrError {
+    fn cause(&self) -> Option<&error::Error> {
+        match self {
+            ParseExtendedAddrError::EncodingError(err) => Some(err),
+            ParseExtendedAddrError::Base58Error(err) => Some(err),
+            ParseExtendedAddrError::HexError(err) => Some(err),
+        }
+    }
+}
+impl ExtendedAddr {
+    /// parse the address in the given encoding
+    pub fn from_str_with(s: &str, encoding: AddrEncoding) -> Result<Self, ParseExtendedAddrError> {
+        let bytes = encoding.decode(s)?;
+        Self::try_from_slice(&bytes).map_err(ParseExtendedAddrError::EncodingError)
+    }
+
+    /// the address in the given textual encoding (`Display` uses base58)
+    pub fn encode(&self, encoding: AddrEncoding) -> String {
+        self.to_address().encode(encoding)
+    }
+}
+/// parse the address, in any of the `AddrEncoding` (see
+/// `AddrEncoding::detect`)
 impl ::std::str::FromStr for ExtendedAddr {
     type Err = ParseExtendedAddrError;
     fn from_str(s: &str) -> Result<Self, Self::Err> {
-        let bytes = base58::decode(s).map_err(ParseExtendedAddrError::Base58Error)?;
-
-        Self::try_from_slice(&bytes).map_err(ParseExtendedAddrError::EncodingError)
+        Self::from_str_with(s, AddrEncoding::detect(s))
     }
 }
 impl TryFromSlice for ExtendedAddr {
@@ -680,10 +799,52 @@ impl<'de> serde::Deserialize<'de> for ExtendedAddr {
     }
 }
 
-pub type Script = [u8; 32]; // TODO
+/// A script, as found in the `ScriptASD` spending data (the validator) and
+/// in the script witnesses (validator and redeemer).
+///
+/// The script itself is kept serialized, only its version is interpreted.
+#[derive(Debug, PartialEq, Eq, Clone)]
+#[cfg_attr(feature = "generic-serialization", derive(Serialize, Deserialize))]
+pub struct Script {
+    pub version: u16,
+    pub script: Vec<u8>,
+}
+impl Script {
+    pub fn new(version: u16, script: Vec<u8>) -> Self {
+        Script {
+            version: version,
+            script: script,
+        }
+    }
+}
+impl cbor_event::se::Serialize for Script {
+    fn serialize<'se, W: Write>(
+        &self,
+        serializer: &'se mut Serializer<W>,
+    ) -> cbor_event::Result<&'se mut Serializer<W>> {
+        serializer
+            .write_array(cbor_event::Len::Len(2))?
+            .write_unsigned_integer(self.version as u64)?
+            .write_bytes(&self.script)
+    }
+}
+impl cbor_event::de::Deserialize for Script {
+    fn deserialize<R: BufRead>(reader: &mut Deserializer<R>) -> cbor_event::Result<Self> {
+        reader.tuple(2, "Script")?;
+        let version = reader.unsigned_integer()?;
+        if version > u16::max_value() as u64 {
+            return Err(cbor_event::Error::CustomError(format!(
+                "Invalid Script version: {}",
+                version
+            )));
+        }
+        let script = reader.bytes()?;
+        Ok(Script::new(version as u16, script))
+    }
+}
 
 const SPENDING_DATA_TAG_PUBKEY: u64 = 0;
-const SPENDING_DATA_TAG_SCRIPT: u64 = 1; // TODO
+const SPENDING_DATA_TAG_SCRIPT: u64 = 1;
 const SPENDING_DATA_TAG_REDEEM: u64 = 2;
 
 #[derive(Debug, PartialEq, Eq, Clone)]
@@ -703,12 +864,10 @@ impl cbor_event::se::Serialize for SpendingData {
                 .write_array(cbor_event::Len::Len(2))?
                 .write_unsigned_integer(SPENDING_DATA_TAG_PUBKEY)?
                 .serialize(pk),
-            &SpendingData::ScriptASD(_) => {
-                serializer
-                    .write_array(cbor_event::Len::Len(2))?
-                    .write_unsigned_integer(SPENDING_DATA_TAG_SCRIPT)?;
-                unimplemented!()
-            }
+            &SpendingData::ScriptASD(ref script) => serializer
+                .write_array(cbor_event::Len::Len(2))?
+                .write_unsigned_integer(SPENDING_DATA_TAG_SCRIPT)?
+                .serialize(script),
             &SpendingData::RedeemASD(ref pk) => serializer
                 .write_array(cbor_event::Len::Len(2))?
                 .write_unsigned_integer(SPENDING_DATA_TAG_REDEEM)?
@@ -793,6 +952,26 @@ mod tests {
         assert_eq!(ea, r);
     }
 
+    #[test]
+    fn parse_encoded_addresses() {
+        let seed = hdwallet::Seed::from_bytes([0; hdwallet::SEED_SIZE]);
+        let pk = hdwallet::XPrv::generate_from_seed(&seed).public();
+        let ea = ExtendedAddr::new_simple(pk, NetworkMagic::NoMagic);
+
+        for encoding in AddrEncoding::ALL.iter() {
+            let s = ea.encode(*encoding);
+            assert_eq!(AddrEncoding::detect(&s), *encoding);
+            assert_eq!(ExtendedAddr::from_str(&s).unwrap(), ea);
+            assert_eq!(ExtendedAddr::from_str_with(&s, *encoding).unwrap(), ea);
+        }
+        assert_eq!(ea.encode(AddrEncoding::Base58), format!("{}", ea));
+        assert_eq!(
+            ExtendedAddr::from_str(&ea.encode(AddrEncoding::Hex).to_uppercase()).unwrap(),
+            ea
+        );
+        assert!(ExtendedAddr::from_str_with(&format!("{}", ea), AddrEncoding::Hex).is_err());
+    }
+
     #[test]
     fn encode_decode_addr_type() {
         let addr_type_1 = AddrType::ATPubKey;
@@ -898,6 +1077,28 @@ mod tests {
         assert_eq!(r.attributes.network_magic, NetworkMagic::Magic(1097911063));
         assert_eq!(&bytes[..], &cbor!(r).unwrap()[..])
     }
+
+    #[test]
+    fn unknown_attributes_roundtrip() {
+        let seed = hdwallet::Seed::from_bytes([0; hdwallet::SEED_SIZE]);
+        let sk = hdwallet::XPrv::generate_from_seed(&seed);
+        let mut attributes = Attributes::new_bootstrap_era(None, NetworkMagic::Magic(633343913));
+        attributes.remains.insert(7, vec![0x18, 0x2a]);
+        let addr = ExtendedAddr::new(
+            AddrType::ATPubKey,
+            SpendingData::PubKeyASD(sk.public()),
+            attributes.clone(),
+        );
+
+        let bytes = cbor!(addr).unwrap();
+        let r = ExtendedAddr::try_from_slice(&bytes).unwrap();
+        assert_eq!(r.attributes, attributes);
+        assert_eq!(r, addr);
+        assert_eq!(&bytes[..], &cbor!(r).unwrap()[..]);
+
+        let r: ExtendedAddr = format!("{}", addr).parse().unwrap();
+        assert_eq!(r, addr);
+    }
 }
 
 #[cfg(feature = "with-bench")]
@@ -52,6 +52,7 @@ use cryptoxide::hmac::Hmac;
 use cryptoxide::pbkdf2::pbkdf2;
 use cryptoxide::sha2::Sha512;
 use std::{error, fmt, ops::Deref, result, str};
+use util::drbg::RandomGenerator;
 use util::{hex, securemem};
 
 /// Error regarding BIP39 operations
@@ -178,6 +179,16 @@ impl Entropy {
         entropy
     }
 
+    /// same as `generate`, drawing the entropy from a `RandomGenerator`
+    /// (e.g. a `util::drbg::Drbg` to always generate the same mnemonics
+    /// in tests)
+    pub fn generate_with<R: RandomGenerator>(t: Type, random_generator: &mut R) -> Self {
+        let bytes = [0u8; 32];
+        let mut entropy = Self::new(t, &bytes[..]);
+        random_generator.fill_bytes(entropy.as_mut());
+        entropy
+    }
+
     fn new(t: Type, bytes: &[u8]) -> Self {
         let mut e = match t {
             Type::Type9Words => Entropy::Entropy9([0u8; 12]),
@@ -824,6 +835,30 @@ impl Mnemonics {
         Mnemonics::from_mnemonics(vec)
     }
 
+    /// Check a complete mnemonic phrase, as typed by a user: all the words
+    /// are in the dictionary, the number of words is supported and the
+    /// checksum is valid.
+    ///
+    /// Along with [`Language::words_with_prefix`] to complete the words
+    /// being typed, this is meant for interactive recovery UIs.
+    ///
+    /// [`Language::words_with_prefix`]: ./dictionary/trait.Language.html#method.words_with_prefix
+    ///
+    /// # Error
+    ///
+    /// May fail with a [`LanguageError`](enum.Error.html#variant.LanguageError)
+    /// for an unknown word, [`WrongNumberOfWords`](enum.Error.html#variant.WrongNumberOfWords)
+    /// or [`InvalidChecksum`](enum.Error.html#variant.InvalidChecksum).
+    ///
+    pub fn check_phrase<D>(dic: &D, mnemonics: &str) -> Result<Self>
+    where
+        D: dictionary::Language,
+    {
+        let mnemonics = Mnemonics::from_string(dic, mnemonics)?;
+        Entropy::from_mnemonics(&mnemonics)?;
+        Ok(mnemonics)
+    }
+
     /// Construct the `Mnemonics` from the given array of `MnemonicIndex`.
     ///
     /// # Error
@@ -851,7 +886,7 @@ pub mod dictionary {
 
     use std::{error, fmt, result};
 
-    use super::MnemonicIndex;
+    use super::{MnemonicIndex, MAX_MNEMONIC_VALUE};
 
     /// Errors associated to a given language/dictionary
     #[derive(Debug, PartialEq, Eq, PartialOrd, Ord, Clone)]
@@ -882,6 +917,16 @@ pub mod dictionary {
         fn separator(&self) -> &'static str;
         fn lookup_mnemonic(&self, word: &str) -> Result<MnemonicIndex>;
         fn lookup_word(&self, mnemonic: MnemonicIndex) -> Result<String>;
+
+        /// the words of the dictionary starting with the given prefix, in
+        /// the order of the dictionary. Handy to complete the mnemonic
+        /// words while they are typed.
+        fn words_with_prefix(&self, prefix: &str) -> Vec<String> {
+            (0..=MAX_MNEMONIC_VALUE)
+                .filter_map(|m| self.lookup_word(MnemonicIndex(m)).ok())
+                .filter(|word| word.starts_with(prefix))
+                .collect()
+        }
     }
 
     /// Default Dictionary basic support for the different main languages.
@@ -919,6 +964,13 @@ pub mod dictionary {
         fn lookup_word(&self, mnemonic: MnemonicIndex) -> Result<String> {
             Ok(unsafe { self.words.get_unchecked(mnemonic.0 as usize) }).map(|s| String::from(*s))
         }
+        fn words_with_prefix(&self, prefix: &str) -> Vec<String> {
+            self.words
+                .iter()
+                .filter(|word| word.starts_with(prefix))
+                .map(|word| String::from(*word))
+                .collect()
+        }
     }
 
     /// default English dictionary as provided by the
@@ -1021,6 +1073,42 @@ mod test {
         assert_eq!(entropy, entropy2);
     }
 
+    #[test]
+    fn words_with_prefix() {
+        let dic = &dictionary::ENGLISH;
+
+        assert_eq!(dic.words_with_prefix("zo"), vec!["zone", "zoo"]);
+        assert_eq!(dic.words_with_prefix("abandon"), vec!["abandon"]);
+        assert!(dic.words_with_prefix("zz").is_empty());
+        assert_eq!(dic.words_with_prefix("").len(), 2048);
+    }
+
+    #[test]
+    fn check_phrase() {
+        let dic = &dictionary::ENGLISH;
+
+        let valid = "abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon about";
+        assert!(Mnemonics::check_phrase(dic, valid).is_ok());
+
+        let checksum = "abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon";
+        match Mnemonics::check_phrase(dic, checksum) {
+            Err(Error::InvalidChecksum(_, _)) => {}
+            r => panic!("expected an invalid checksum, got {:?}", r.map(|_| ())),
+        }
+
+        let incomplete = "abandon abandon abandon";
+        assert_eq!(
+            Mnemonics::check_phrase(dic, incomplete).map(|_| ()),
+            Err(Error::WrongNumberOfWords(3))
+        );
+
+        let unknown = "abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon abandan";
+        match Mnemonics::check_phrase(dic, unknown) {
+            Err(Error::LanguageError(_)) => {}
+            r => panic!("expected an unknown word, got {:?}", r.map(|_| ())),
+        }
+    }
+
     #[test]
     fn mnemonic_7f() {
         let entropy = Entropy::Entropy12([0x7f; 16]);
@@ -1037,6 +1125,18 @@ mod test {
         assert_eq!(entropy, entropy2);
     }
 
+    #[test]
+    fn generate_with_drbg() {
+        use util::drbg::Drbg;
+
+        let entropy = Entropy::generate_with(Type::Type15Words, &mut Drbg::new(b"seed"));
+        assert_eq!(
+            entropy,
+            Entropy::generate_with(Type::Type15Words, &mut Drbg::new(b"seed"))
+        );
+        assert!(entropy != Entropy::generate_with(Type::Type15Words, &mut Drbg::new(b"other")));
+    }
+
     #[derive(Debug)]
     struct TestVector {
         entropy: &'static str,
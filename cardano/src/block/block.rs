@@ -12,11 +12,18 @@ use super::super::config::ProtocolMagic;
 use super::boundary;
 use super::date::BlockDate;
 use super::normal;
+use super::stream;
 use super::types::{BlockVersion, ChainDifficulty, HeaderHash};
-use crate::tx::TxAux;
+use super::update;
+use crate::tx::{TxAux, TxId};
 use cbor_event::{self, de::Deserialize, de::Deserializer, se::Serializer};
 use chain_core;
 
+#[cfg(feature = "generic-serialization")]
+use serde;
+#[cfg(feature = "generic-serialization")]
+use util::hex;
+
 #[derive(Debug, Clone)]
 pub struct RawBlockHeaderMultiple(pub Vec<u8>);
 
@@ -55,10 +62,21 @@ impl RawBlock {
         let mut de = Deserializer::from(Cursor::new(&self.0));
         de.deserialize_complete()
     }
+    /// Decode the whole block, checking its encoding, and return its
+    /// header. See `decode_header` to only decode the header.
     pub fn to_header(&self) -> cbor_event::Result<RawBlockHeader> {
-        // TODO optimise if possible with the CBOR structure by skipping some prefix and some suffix ...
-        let blk = self.decode()?;
-        Ok(blk.header().to_raw())
+        Ok(self.decode()?.header().to_raw())
+    }
+    /// Decode only the header of the block, see `stream::decode_header`.
+    pub fn decode_header(&self) -> cbor_event::Result<BlockHeader> {
+        let mut de = Deserializer::from(Cursor::new(&self.0));
+        stream::decode_header(&mut de)
+    }
+    /// The ids of the transactions of the block, without decoding the rest
+    /// of the block, see `stream::decode_tx_ids`.
+    pub fn tx_ids(&self) -> cbor_event::Result<Vec<TxId>> {
+        let mut de = Deserializer::from(Cursor::new(&self.0));
+        stream::decode_tx_ids(&mut de)
     }
 }
 
@@ -113,15 +131,20 @@ impl chain_core::property::Header for BlockHeader {
         }
     }
 
+    /// the boundary blocks do not have a version, they are given the
+    /// initial version of the protocol (`0.0.0`).
     fn version(&self) -> Self::Version {
         match self {
-            BlockHeader::BoundaryBlockHeader(ref _header) => unimplemented!(),
+            BlockHeader::BoundaryBlockHeader(ref _header) => BlockVersion::new(0, 0, 0),
             BlockHeader::MainBlockHeader(ref header) => header.extra_data.block_version,
         }
     }
 
+    /// the chain difficulty of the block: the number of main blocks of
+    /// the chain up to this block (a boundary block has the chain
+    /// difficulty of the main block preceding it).
     fn chain_length(&self) -> Self::ChainLength {
-        unimplemented!()
+        ChainLength(u64::from(self.difficulty()) as usize)
     }
 }
 
@@ -215,6 +238,14 @@ impl<'a> BlockHeaderView<'a> {
 }
 
 impl BlockHeader {
+    /// borrow the header as a `BlockHeaderView`
+    pub fn view(&self) -> BlockHeaderView {
+        match self {
+            BlockHeader::BoundaryBlockHeader(hdr) => BlockHeaderView::Boundary(hdr),
+            BlockHeader::MainBlockHeader(hdr) => BlockHeaderView::Normal(hdr),
+        }
+    }
+
     pub fn get_previous_header(&self) -> HeaderHash {
         match self {
             &BlockHeader::BoundaryBlockHeader(ref blo) => blo.previous_header.clone(),
@@ -274,6 +305,165 @@ impl fmt::Display for BlockHeader {
     }
 }
 
+/// JSON representation of a block header (see the crate documentation):
+///
+/// ```text
+/// {
+///   "hash": "<hex>",
+///   "type": "boundary" | "main",
+///   "protocol_magic": <number>,
+///   "previous_header": "<hex>",
+///   "date": { "Boundary": <epoch> } | { "Normal": { "epoch": <epoch>, "slotid": <slot> } },
+///   "difficulty": <number>,
+///   // main blocks only:
+///   "leader_key": "<hex>",
+///   "block_version": "<major>.<minor>.<alt>",
+///   "software_version": { "application_name": "<name>", "application_version": <number> },
+///   "cbor": "<hex>"
+/// }
+/// ```
+///
+/// `cbor` is the encoding of the header, the only field read back by the
+/// `Deserialize` implementation (`hash`, when present, has to match).
+#[cfg(feature = "generic-serialization")]
+impl serde::Serialize for BlockHeader {
+    fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
+    where
+        S: serde::Serializer,
+    {
+        use serde::ser::SerializeMap;
+
+        let mut map = serializer.serialize_map(None)?;
+        map.serialize_entry("hash", &self.compute_hash())?;
+        match self {
+            BlockHeader::BoundaryBlockHeader(hdr) => {
+                map.serialize_entry("type", "boundary")?;
+                map.serialize_entry("protocol_magic", &hdr.protocol_magic)?;
+            }
+            BlockHeader::MainBlockHeader(hdr) => {
+                map.serialize_entry("type", "main")?;
+                map.serialize_entry("protocol_magic", &hdr.protocol_magic)?;
+            }
+        }
+        map.serialize_entry("previous_header", &self.get_previous_header())?;
+        map.serialize_entry("date", &self.get_blockdate())?;
+        map.serialize_entry("difficulty", &u64::from(self.difficulty()))?;
+        if let BlockHeader::MainBlockHeader(hdr) = self {
+            let software_version = &hdr.extra_data.software_version;
+            map.serialize_entry("leader_key", &hdr.consensus.leader_key)?;
+            map.serialize_entry(
+                "block_version",
+                &format!("{}", hdr.extra_data.block_version),
+            )?;
+            map.serialize_entry(
+                "software_version",
+                &JsonSoftwareVersion {
+                    application_name: software_version.application_name(),
+                    application_version: software_version.application_version,
+                },
+            )?;
+        }
+        map.serialize_entry("cbor", &hex::encode(&cbor!(self).unwrap()))?;
+        map.end()
+    }
+}
+#[cfg(feature = "generic-serialization")]
+impl<'de> serde::Deserialize<'de> for BlockHeader {
+    fn deserialize<D>(deserializer: D) -> Result<Self, D::Error>
+    where
+        D: serde::Deserializer<'de>,
+    {
+        use serde::de::Error;
+
+        let json = <JsonCbor as serde::Deserialize>::deserialize(deserializer)?;
+        let header = RawBlockHeader(json.decode_hex()?)
+            .decode()
+            .map_err(|err| D::Error::custom(format!("invalid block header: {}", err)))?;
+        match json.hash {
+            Some(ref hash) if hash != &header.compute_hash() => Err(D::Error::custom(format!(
+                "block header hash {} does not match its encoding",
+                hash
+            ))),
+            _ => Ok(header),
+        }
+    }
+}
+
+/// JSON representation of a block (see the crate documentation):
+///
+/// ```text
+/// {
+///   "header": <BlockHeader>,
+///   // boundary blocks only:
+///   "slot_leaders": ["<stakeholder id hex>"...],
+///   // main blocks only:
+///   "transactions": [<TxAux>...],
+///   "cbor": "<hex>"
+/// }
+/// ```
+///
+/// `cbor` is the encoding of the block, the only field read back by the
+/// `Deserialize` implementation.
+#[cfg(feature = "generic-serialization")]
+impl serde::Serialize for Block {
+    fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
+    where
+        S: serde::Serializer,
+    {
+        use serde::ser::SerializeMap;
+
+        let mut map = serializer.serialize_map(Some(3))?;
+        map.serialize_entry("header", &self.get_header())?;
+        match self {
+            Block::BoundaryBlock(blk) => {
+                map.serialize_entry("slot_leaders", &blk.body.slot_leaders)?;
+            }
+            Block::MainBlock(blk) => {
+                let transactions: &[TxAux] = &blk.body.tx;
+                map.serialize_entry("transactions", transactions)?;
+            }
+        }
+        map.serialize_entry("cbor", &hex::encode(&cbor!(self).unwrap()))?;
+        map.end()
+    }
+}
+#[cfg(feature = "generic-serialization")]
+impl<'de> serde::Deserialize<'de> for Block {
+    fn deserialize<D>(deserializer: D) -> Result<Self, D::Error>
+    where
+        D: serde::Deserializer<'de>,
+    {
+        use serde::de::Error;
+
+        let json = <JsonCbor as serde::Deserialize>::deserialize(deserializer)?;
+        RawBlock(json.decode_hex()?)
+            .decode()
+            .map_err(|err| D::Error::custom(format!("invalid block: {}", err)))
+    }
+}
+
+/// the fields of the JSON representations read back by `Deserialize`
+#[cfg(feature = "generic-serialization")]
+#[derive(Deserialize)]
+struct JsonCbor {
+    #[serde(default)]
+    hash: Option<HeaderHash>,
+    cbor: String,
+}
+#[cfg(feature = "generic-serialization")]
+impl JsonCbor {
+    fn decode_hex<E: serde::de::Error>(&self) -> Result<Vec<u8>, E> {
+        hex::decode(&self.cbor).map_err(|err| E::custom(format!("invalid cbor field: {}", err)))
+    }
+}
+
+#[cfg(feature = "generic-serialization")]
+#[derive(Serialize)]
+struct JsonSoftwareVersion<'a> {
+    application_name: &'a str,
+    application_version: u32,
+}
+
 impl chain_core::property::Serialize for BlockHeader {
     type Error = cbor_event::Error;
 
@@ -336,6 +526,31 @@ impl Block {
         }
     }
 
+    /// the update proposal and votes of the block, if any
+    pub fn get_update_payload(&self) -> Option<&update::UpdatePayload> {
+        match self {
+            &Block::BoundaryBlock(_) => None,
+            &Block::MainBlock(ref blk) if blk.body.update.is_empty() => None,
+            &Block::MainBlock(ref blk) => Some(&blk.body.update),
+        }
+    }
+
+    /// the VSS certificates of the block, if any. The boundary blocks do
+    /// not carry any.
+    pub fn get_vss_certificates(&self) -> Option<&normal::VssCertificates> {
+        match self {
+            &Block::BoundaryBlock(_) => None,
+            &Block::MainBlock(ref blk) => {
+                let vss_certs = blk.body.ssc.get_vss_certificates();
+                if vss_certs.is_empty() {
+                    None
+                } else {
+                    Some(vss_certs)
+                }
+            }
+        }
+    }
+
     pub fn get_protocol_magic(&self) -> ProtocolMagic {
         match self {
             &Block::BoundaryBlock(ref blk) => blk.header.protocol_magic,
@@ -377,15 +592,30 @@ impl chain_core::property::Block for Block {
         }
     }
 
+    /// see the `Header` implementation of `BlockHeader`
     fn version(&self) -> Self::Version {
         match self {
             Block::MainBlock(ref block) => block.header.extra_data.block_version,
-            Block::BoundaryBlock(ref _block) => unimplemented!(),
+            Block::BoundaryBlock(ref _block) => BlockVersion::new(0, 0, 0),
         }
     }
 
+    /// see the `Header` implementation of `BlockHeader`
     fn chain_length(&self) -> Self::ChainLength {
-        unimplemented!()
+        ChainLength(u64::from(self.header().difficulty()) as usize)
+    }
+}
+
+impl<'a> chain_core::property::HasMessages<'a> for &'a Block {
+    type Message = TxAux;
+    type Messages = std::slice::Iter<'a, TxAux>;
+
+    /// the transactions of the block (none for a boundary block)
+    fn messages(self) -> Self::Messages {
+        match self {
+            Block::MainBlock(ref block) => block.body.tx.iter(),
+            Block::BoundaryBlock(_) => (&[]).iter(),
+        }
     }
 }
 
@@ -593,6 +823,73 @@ mod test {
     fn check_main_block() {
         check_blockheader_serialization(&MAINBLOCK_HEX[..], MAINBLOCK_HASH);
     }
+
+    #[test]
+    fn boundary_header_properties() {
+        use super::super::types::BlockVersion;
+        use chain_core::property::Header;
+
+        let mut de = Deserializer::from(Cursor::new(&GENESISBLOCK_HEX[..]));
+        let header: super::BlockHeader = de.deserialize().unwrap();
+        assert_eq!(header.version(), BlockVersion::new(0, 0, 0));
+        assert_eq!(header.chain_length(), super::ChainLength(0));
+    }
+
+    #[cfg(feature = "generic-serialization")]
+    #[test]
+    fn blockheader_json() {
+        use serde_json;
+
+        let mut de = Deserializer::from(Cursor::new(&GENESISBLOCK_HEX[..]));
+        let header: super::BlockHeader = de.deserialize().unwrap();
+        let json = serde_json::to_value(&header).unwrap();
+        assert_eq!(json["hash"], GENESIS_HASH);
+        assert_eq!(json["type"], "boundary");
+        assert!(json.get("leader_key").is_none());
+
+        let mut de = Deserializer::from(Cursor::new(&MAINBLOCK_HEX[..]));
+        let header: super::BlockHeader = de.deserialize().unwrap();
+        let json = serde_json::to_value(&header).unwrap();
+        assert_eq!(json["hash"], MAINBLOCK_HASH);
+        assert_eq!(json["type"], "main");
+        assert_eq!(json["software_version"]["application_name"], "cardano-sl");
+    }
+
+    #[cfg(feature = "generic-serialization")]
+    #[test]
+    fn blockheader_json_round_trip() {
+        use super::RawBlockHeader;
+        use serde_json;
+
+        for raw in &[&GENESISBLOCK_HEX[..], &MAINBLOCK_HEX[..]] {
+            let header = RawBlockHeader(raw.to_vec()).decode().unwrap();
+            let json = serde_json::to_string(&header).unwrap();
+            let decoded: super::BlockHeader = serde_json::from_str(&json).unwrap();
+            assert_eq!(cbor!(&decoded).unwrap(), raw.to_vec());
+
+            // the hash has to match the encoding
+            let mut value = serde_json::to_value(&header).unwrap();
+            value["hash"] = serde_json::Value::from("0".repeat(64));
+            assert!(serde_json::from_value::<super::BlockHeader>(value).is_err());
+        }
+    }
+
+    #[cfg(feature = "generic-serialization")]
+    #[test]
+    fn block_json_round_trip() {
+        use super::super::fake::{raw, FakeChain};
+        use super::{HeaderHash, ProtocolMagic};
+        use serde_json;
+
+        let protocol_magic = ProtocolMagic::from(328429219);
+        let mut chain = FakeChain::new(protocol_magic, HeaderHash::new(b"genesis"));
+        for block in &[chain.next_boundary(), chain.next_main()] {
+            let json = serde_json::to_string(block).unwrap();
+            let decoded: super::Block = serde_json::from_str(&json).unwrap();
+            assert_eq!(raw(&decoded).as_ref(), raw(block).as_ref());
+        }
+        assert!(serde_json::from_str::<super::Block>(r#"{ "cbor": "zz" }"#).is_err());
+    }
 }
 
 #[cfg(test)]
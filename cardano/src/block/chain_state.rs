@@ -1,30 +1,40 @@
 use address;
 use block::*;
-use config::{GenesisData, ProtocolMagic};
+use coin;
+use config::{EpochParameters, GenesisData, ProtocolMagic};
 use fee;
 use hash;
+use hdwallet;
 use std::collections::BTreeMap;
 use tx::{self, TxOut, TxoPointer};
 
 pub type Utxos = BTreeMap<TxoPointer, TxOut>;
 
+/// heavyweight delegations in effect: the key each stakeholder delegated
+/// the signing of its blocks to
+pub type Delegations = BTreeMap<address::StakeholderId, hdwallet::XPub>;
+
 #[derive(Debug, Clone, Eq, PartialEq)]
 pub struct ChainState {
     // FIXME: maybe we should just keep a ref to GenesisData?  Though
     // I guess at least the fee policy could change in an update.
     pub protocol_magic: ProtocolMagic,
     pub fee_policy: fee::LinearFee,
+    pub epoch_parameters: EpochParameters,
 
     pub last_block: HeaderHash,
     pub last_date: Option<super::BlockDate>,
     pub last_boundary_block: Option<HeaderHash>,
     pub slot_leaders: Option<Vec<address::StakeholderId>>,
     pub utxos: Utxos,
+    pub delegations: Delegations,
     pub chain_length: u64,
 
     // Some stats.
     pub nr_transactions: u64,
     pub spent_txos: u64,
+    /// sum of the fees paid by the transactions of the chain
+    pub fees: coin::Coin,
 }
 
 impl ChainState {
@@ -56,17 +66,44 @@ impl ChainState {
             );
         }
 
+        // The genesis stakeholders' initial delegations.
+        let delegations = genesis_data
+            .boot_stakeholders
+            .iter()
+            .map(|(id, stakeholder)| (*id, stakeholder.delegate_pk))
+            .collect();
+
         ChainState {
             protocol_magic: genesis_data.protocol_magic,
             fee_policy: genesis_data.fee_policy,
+            epoch_parameters: genesis_data.epoch_parameters(),
             last_block: genesis_data.genesis_prev.clone(),
             last_date: None,
             last_boundary_block: None,
             slot_leaders: None,
             utxos,
+            delegations,
             chain_length: 0,
             nr_transactions: 0,
             spent_txos: 0,
+            fees: coin::Coin::zero(),
+        }
+    }
+
+    /// the key `stakeholder` delegated the signing of its blocks to, if any
+    pub fn delegate_of(&self, stakeholder: &address::StakeholderId) -> Option<&hdwallet::XPub> {
+        self.delegations.get(stakeholder)
+    }
+
+    /// apply the heavyweight delegation certificates of the block
+    pub fn apply_delegations(&mut self, blk: &normal::Block) {
+        for psk in blk.body.delegation.iter() {
+            let issuer = address::StakeholderId::new(&psk.issuer_pk);
+            if psk.is_revocation() {
+                self.delegations.remove(&issuer);
+            } else {
+                self.delegations.insert(issuer, psk.delegate_pk);
+            }
         }
     }
 }
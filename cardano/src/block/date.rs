@@ -1,5 +1,6 @@
 use super::types::{EpochId, EpochSlotId, SlotId};
 use chain_core::property;
+use config::EpochParameters;
 
 use std::{
     cmp::{Ord, Ordering},
@@ -27,6 +28,8 @@ impl property::BlockDate for BlockDate {
     }
 }
 
+/// the number of slots between two dates, assuming the epochs of the mainnet
+/// (see `slot_number_with` for the other networks)
 impl ::std::ops::Sub<BlockDate> for BlockDate {
     type Output = usize;
     fn sub(self, rhs: Self) -> Self::Output {
@@ -80,7 +83,7 @@ impl BlockDate {
                 epoch: e,
                 slotid: 0,
             }),
-            &BlockDate::Normal(ref s) => BlockDate::Normal(s.next()), // TODO next should wrap after full epoch
+            &BlockDate::Normal(ref s) => BlockDate::Normal(s.next()), // does not wrap, see next_with
         }
     }
 
@@ -90,10 +93,25 @@ impl BlockDate {
             _ => false,
         }
     }
+    /// the date following this one in an epoch of the given length: the
+    /// last slot of an epoch is followed by the boundary of the next one.
+    pub fn next_with(&self, parameters: &EpochParameters) -> Self {
+        match self {
+            &BlockDate::Normal(ref s) if s.slotid as usize + 1 >= parameters.slots_per_epoch => {
+                BlockDate::Boundary(s.epoch + 1)
+            }
+            _ => self.next(),
+        }
+    }
+    /// the absolute slot number, assuming the epochs of the mainnet
+    /// (see `slot_number_with`)
     pub fn slot_number(&self) -> usize {
+        self.slot_number_with(&EpochParameters::default())
+    }
+    pub fn slot_number_with(&self, parameters: &EpochParameters) -> usize {
         match self {
-            BlockDate::Boundary(eid) => (*eid as usize) * 21600, // TODO de-hardcode this value
-            BlockDate::Normal(sid) => sid.slot_number(),
+            BlockDate::Boundary(eid) => (*eid as usize) * parameters.slots_per_epoch,
+            BlockDate::Normal(sid) => sid.slot_number_with(parameters),
         }
     }
 }
@@ -177,8 +195,55 @@ impl Error for BlockDateParseError {
 mod tests {
     use super::BlockDate;
     use block::EpochSlotId;
+    use config::EpochParameters;
     use std::error::Error;
 
+    #[test]
+    fn slot_numbers() {
+        let mainnet = EpochParameters::default();
+        let custom = EpochParameters::new(10);
+        let date = EpochSlotId {
+            epoch: 2,
+            slotid: 42,
+        };
+        assert_eq!(BlockDate::Normal(date).slot_number(), 2 * 21600 + 42);
+        assert_eq!(
+            BlockDate::Normal(date).slot_number_with(&custom),
+            2 * 100 + 42
+        );
+        assert_eq!(BlockDate::Boundary(3).slot_number_with(&custom), 300);
+        assert_eq!(
+            EpochSlotId::from_slot_number(date.slot_number_with(&mainnet), &mainnet),
+            date
+        );
+        assert_eq!(EpochSlotId::from_slot_number(242, &custom), date);
+        assert!(!date.is_valid(&custom));
+    }
+
+    #[test]
+    fn next_wraps_after_the_last_slot() {
+        let custom = EpochParameters::new(10);
+        let last = BlockDate::Normal(EpochSlotId {
+            epoch: 2,
+            slotid: 99,
+        });
+        assert_eq!(last.next_with(&custom), BlockDate::Boundary(3));
+        assert_eq!(
+            BlockDate::Boundary(3).next_with(&custom),
+            BlockDate::Normal(EpochSlotId {
+                epoch: 3,
+                slotid: 0
+            })
+        );
+        assert_eq!(
+            last.next_with(&EpochParameters::default()),
+            BlockDate::Normal(EpochSlotId {
+                epoch: 2,
+                slotid: 100
+            })
+        );
+    }
+
     #[test]
     fn parse_bare_epoch() {
         let date = "42".parse::<BlockDate>().unwrap();
@@ -0,0 +1,223 @@
+//! Fake chains, to test the code handling blocks.
+//!
+//! The blocks produced by `FakeChain` are well formed and follow each other
+//! (hash, date and chain difficulty), the main blocks are signed by a fixed
+//! delegate of a fixed issuer. They carry no transactions.
+
+use super::normal::{BodyProof, DlgPayload, SscPayload, TxPayload};
+use super::sign::{BlockSignature, MainToSign, ProxySecretKey};
+use super::types::{
+    BlockHeaderAttributes, BlockVersion, ChainDifficulty, EpochId, EpochSlotId, HeaderExtraData,
+    HeaderHash, SoftwareVersion,
+};
+use super::{boundary, normal, update, Block, BlockDate, BlockHeader, RawBlock};
+use address::StakeholderId;
+use cbor_event::Value;
+use config::ProtocolMagic;
+use hash::Blake2b256;
+use hdwallet::{Seed, Signature, XPrv, SEED_SIZE, SIGNATURE_SIZE};
+use std::collections::BTreeMap;
+use tags;
+
+fn empty_attributes() -> Value {
+    Value::Object(BTreeMap::new())
+}
+
+/// the encoding of the block, as stored and sent over the network
+pub fn raw(block: &Block) -> RawBlock {
+    RawBlock::from_dat(cbor!(block).unwrap())
+}
+
+/// Produce the blocks of a chain, starting with the genesis epoch
+/// boundary block. Clone it to fork the chain.
+#[derive(Debug, Clone)]
+pub struct FakeChain {
+    protocol_magic: ProtocolMagic,
+    genesis_prev: HeaderHash,
+    delegate: XPrv,
+    psk: ProxySecretKey,
+    epoch_slots: usize,
+    tip: Option<BlockHeader>,
+}
+
+impl FakeChain {
+    /// `genesis_prev` is the hash of the genesis data, the parent of the
+    /// first block.
+    pub fn new(protocol_magic: ProtocolMagic, genesis_prev: HeaderHash) -> Self {
+        let issuer = XPrv::generate_from_seed(&Seed::from_bytes([1; SEED_SIZE]));
+        let delegate = XPrv::generate_from_seed(&Seed::from_bytes([2; SEED_SIZE]));
+        let psk = ProxySecretKey::sign(&issuer, delegate.public(), 0, protocol_magic);
+        FakeChain {
+            protocol_magic,
+            genesis_prev,
+            delegate,
+            psk,
+            epoch_slots: 0,
+            tip: None,
+        }
+    }
+
+    /// list the issuer as the leader of the first `epoch_slots` slots in
+    /// the boundary blocks, as `ChainState::verify_block` expects
+    pub fn with_slot_leaders(mut self, epoch_slots: usize) -> Self {
+        self.epoch_slots = epoch_slots;
+        self
+    }
+
+    /// the key delegated to by the issuer of the main blocks
+    pub fn delegation(&self) -> &ProxySecretKey {
+        &self.psk
+    }
+
+    /// the header of the last block produced
+    pub fn tip(&self) -> Option<&BlockHeader> {
+        self.tip.as_ref()
+    }
+
+    fn parent(&self) -> (HeaderHash, ChainDifficulty) {
+        match &self.tip {
+            None => (self.genesis_prev.clone(), ChainDifficulty::from(0)),
+            Some(tip) => (tip.compute_hash(), tip.difficulty()),
+        }
+    }
+
+    /// the epoch boundary block of the epoch following the tip's
+    pub fn next_boundary(&mut self) -> Block {
+        let epoch = match self.tip.as_ref().map(|tip| tip.get_blockdate()) {
+            None => 0,
+            Some(BlockDate::Boundary(epoch)) => epoch + 1,
+            Some(BlockDate::Normal(slot)) => slot.epoch + 1,
+        };
+        self.boundary_at(epoch)
+    }
+
+    /// the epoch boundary block of `epoch`, after the tip
+    pub fn boundary_at(&mut self, epoch: EpochId) -> Block {
+        let (previous_header, chain_difficulty) = self.parent();
+        let body = boundary::Body {
+            slot_leaders: vec![StakeholderId::new(&self.psk.issuer_pk); self.epoch_slots],
+        };
+        let header = boundary::BlockHeader::new(
+            self.protocol_magic,
+            previous_header,
+            boundary::BodyProof(Blake2b256::new(&cbor!(&body).unwrap())),
+            boundary::Consensus {
+                epoch,
+                chain_difficulty,
+            },
+            BlockHeaderAttributes(empty_attributes()),
+        );
+        self.tip = Some(BlockHeader::BoundaryBlockHeader(header.clone()));
+        Block::BoundaryBlock(boundary::Block {
+            header,
+            body,
+            extra: Value::Array(vec![empty_attributes()]),
+        })
+    }
+
+    /// the main block of the slot following the tip
+    pub fn next_main(&mut self) -> Block {
+        let slot_id = self.next_slot();
+        self.main_at(slot_id)
+    }
+
+    /// the main block of the slot following the tip, carrying the
+    /// heavyweight delegation certificates `psks`
+    pub fn next_main_delegating(&mut self, psks: Vec<ProxySecretKey>) -> Block {
+        let slot_id = self.next_slot();
+        self.main_block(slot_id, DlgPayload(psks))
+    }
+
+    fn next_slot(&self) -> EpochSlotId {
+        match self.tip.as_ref().map(|tip| tip.get_blockdate()) {
+            None => EpochSlotId {
+                epoch: 0,
+                slotid: 0,
+            },
+            Some(BlockDate::Boundary(epoch)) => EpochSlotId { epoch, slotid: 0 },
+            Some(BlockDate::Normal(slot)) => slot.next(),
+        }
+    }
+
+    /// the main block of `slot_id`, after the tip
+    pub fn main_at(&mut self, slot_id: EpochSlotId) -> Block {
+        self.main_block(slot_id, DlgPayload(Vec::new()))
+    }
+
+    fn main_block(&mut self, slot_id: EpochSlotId, delegation: DlgPayload) -> Block {
+        let (previous_header, difficulty) = self.parent();
+        let body = normal::Body::new(
+            TxPayload::empty(),
+            SscPayload::fake(),
+            delegation,
+            update::UpdatePayload {
+                proposal: None,
+                votes: Vec::new(),
+            },
+        );
+        let extra = Value::Array(vec![empty_attributes()]);
+        let extra_data = HeaderExtraData::new(
+            BlockVersion::new(0, 0, 0),
+            SoftwareVersion::default(),
+            BlockHeaderAttributes(empty_attributes()),
+            Blake2b256::new(&cbor!(&extra).unwrap()),
+        );
+        let mut header = normal::BlockHeader::new(
+            self.protocol_magic,
+            previous_header,
+            BodyProof::generate_from_body(&body),
+            normal::Consensus {
+                slot_id,
+                leader_key: self.psk.issuer_pk,
+                chain_difficulty: ChainDifficulty::from(u64::from(difficulty) + 1),
+                block_signature: BlockSignature::Signature(Signature::from_bytes(
+                    [0; SIGNATURE_SIZE],
+                )),
+            },
+            extra_data,
+        );
+        let proxy_sig = MainToSign::from_header(&header).proxy_sign(
+            self.protocol_magic,
+            tags::SigningTag::MainBlockHeavy,
+            self.psk.clone(),
+            &self.delegate,
+        );
+        header.consensus.block_signature = BlockSignature::ProxyHeavy(proxy_sig);
+        self.tip = Some(BlockHeader::MainBlockHeader(header.clone()));
+        Block::MainBlock(normal::Block::new(header, body, extra))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use block::{verify_block, verify_header_chain};
+
+    #[test]
+    fn fake_chain_verifies() {
+        let protocol_magic = ProtocolMagic::from(328429219);
+        let mut chain = FakeChain::new(protocol_magic, HeaderHash::new(b"genesis"));
+        let blocks = vec![
+            chain.next_boundary(),
+            chain.next_main(),
+            chain.next_main(),
+            chain.next_boundary(),
+            chain.next_main(),
+        ];
+        for block in blocks.iter() {
+            let decoded = raw(block).decode().unwrap();
+            let hash = decoded.header().compute_hash();
+            verify_block(&hash, &decoded).unwrap();
+        }
+        let headers: Vec<_> = blocks.iter().map(|b| b.get_header()).collect();
+        verify_header_chain(protocol_magic, None, headers.iter()).unwrap();
+        assert_eq!(
+            chain.tip().unwrap().get_blockdate(),
+            BlockDate::Normal(EpochSlotId {
+                epoch: 1,
+                slotid: 0
+            })
+        );
+        assert_eq!(chain.tip().unwrap().difficulty(), ChainDifficulty::from(3));
+    }
+}
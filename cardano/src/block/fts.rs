@@ -0,0 +1,366 @@
+//! Slot leader election (Ouroboros Classic "follow the satoshi").
+//!
+//! Given the stake distribution at the start of an epoch and the shared
+//! seed of the epoch (the epoch nonce), compute which stakeholder is the
+//! leader of each slot. Every coin has the same chance to be selected for a
+//! slot, so a stakeholder is elected proportionally to its stake.
+//!
+//! The random numbers are drawn the same way as in the Haskell
+//! implementation (the `ChaChaDRG` of cryptonite seeded with the shared
+//! seed), so the schedule matches the one found in the boundary blocks.
+
+use address::StakeholderId;
+use block::types::SlotId;
+use block::{normal, verify};
+use coin::{self, Coin};
+use std::{collections::BTreeMap, error, fmt};
+
+pub const SHARED_SEED_SIZE: usize = 32;
+
+/// The seed the slot leaders of an epoch are derived from
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct SharedSeed([u8; SHARED_SEED_SIZE]);
+impl SharedSeed {
+    pub fn from_bytes(bytes: [u8; SHARED_SEED_SIZE]) -> Self {
+        SharedSeed(bytes)
+    }
+
+    /// combine two seeds (the seed of an epoch is the combination of the
+    /// secrets revealed by the participants of the SSC protocol)
+    pub fn xor(&self, other: &Self) -> Self {
+        let mut bytes = self.0;
+        for (byte, other_byte) in bytes.iter_mut().zip(other.0.iter()) {
+            *byte ^= other_byte;
+        }
+        SharedSeed(bytes)
+    }
+}
+impl AsRef<[u8]> for SharedSeed {
+    fn as_ref(&self) -> &[u8] {
+        &self.0
+    }
+}
+
+/// Stake of each stakeholder at the start of an epoch
+pub type StakeDistribution = BTreeMap<StakeholderId, Coin>;
+
+#[derive(Debug)]
+pub enum Error {
+    /// there is no stake at all to elect a leader from
+    NoStake,
+    /// the total stake is not a valid amount of coins
+    InvalidTotalStake(coin::Error),
+}
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Error::NoStake => write!(f, "no stake to elect the slot leaders from"),
+            Error::InvalidTotalStake(_) => write!(f, "invalid total stake"),
+        }
+    }
+}
+impl error::Error for Error {
+    fn cause(&self) -> Option<&error::Error> {
+        match self {
+            Error::NoStake => None,
+            Error::InvalidTotalStake(ref err) => Some(err),
+        }
+    }
+}
+impl From<coin::Error> for Error {
+    fn from(e: coin::Error) -> Self {
+        Error::InvalidTotalStake(e)
+    }
+}
+
+/// number of rounds of the ChaCha used by cryptonite's `ChaChaDRG`
+const CHACHA_ROUNDS: usize = 8;
+
+fn quarter_round(state: &mut [u32; 16], a: usize, b: usize, c: usize, d: usize) {
+    state[a] = state[a].wrapping_add(state[b]);
+    state[d] = (state[d] ^ state[a]).rotate_left(16);
+    state[c] = state[c].wrapping_add(state[d]);
+    state[b] = (state[b] ^ state[c]).rotate_left(12);
+    state[a] = state[a].wrapping_add(state[b]);
+    state[d] = (state[d] ^ state[a]).rotate_left(8);
+    state[c] = state[c].wrapping_add(state[d]);
+    state[b] = (state[b] ^ state[c]).rotate_left(7);
+}
+
+/// the first block of the ChaCha keystream of `key` and `nonce` (the
+/// original ChaCha, with a 64 bits counter starting at 0)
+fn chacha_block(rounds: usize, key: &[u8; 32], nonce: &[u8; 8]) -> [u8; 64] {
+    let mut input = [0u32; 16];
+    // "expand 32-byte k"
+    input[0] = 0x6170_7865;
+    input[1] = 0x3320_646e;
+    input[2] = 0x7962_2d32;
+    input[3] = 0x6b20_6574;
+    for (i, word) in key.chunks(4).enumerate() {
+        input[4 + i] = u32::from_le_bytes([word[0], word[1], word[2], word[3]]);
+    }
+    for (i, word) in nonce.chunks(4).enumerate() {
+        input[14 + i] = u32::from_le_bytes([word[0], word[1], word[2], word[3]]);
+    }
+
+    let mut state = input;
+    for _ in 0..rounds / 2 {
+        quarter_round(&mut state, 0, 4, 8, 12);
+        quarter_round(&mut state, 1, 5, 9, 13);
+        quarter_round(&mut state, 2, 6, 10, 14);
+        quarter_round(&mut state, 3, 7, 11, 15);
+        quarter_round(&mut state, 0, 5, 10, 15);
+        quarter_round(&mut state, 1, 6, 11, 12);
+        quarter_round(&mut state, 2, 7, 8, 13);
+        quarter_round(&mut state, 3, 4, 9, 14);
+    }
+
+    let mut block = [0u8; 64];
+    for (i, bytes) in block.chunks_mut(4).enumerate() {
+        bytes.copy_from_slice(&state[i].wrapping_add(input[i]).to_le_bytes());
+    }
+    block
+}
+
+/// Deterministic random generator, compatible with cryptonite's `ChaChaDRG`
+///
+/// Every 16 bytes of output consume a ChaCha8 block, the rest of the
+/// block is used as the key and nonce of the next one.
+struct ChaChaDrg {
+    key: [u8; 32],
+    nonce: [u8; 8],
+}
+impl ChaChaDrg {
+    fn new(seed: &SharedSeed) -> Self {
+        // the seed is interpreted as a big endian integer, and converted
+        // back to the 40 bytes (key and nonce) the generator is made of
+        let mut bytes = [0u8; 40];
+        bytes[40 - SHARED_SEED_SIZE..].copy_from_slice(&seed.0);
+        let mut drg = ChaChaDrg {
+            key: [0; 32],
+            nonce: [0; 8],
+        };
+        drg.key.copy_from_slice(&bytes[0..32]);
+        drg.nonce.copy_from_slice(&bytes[32..40]);
+        drg
+    }
+
+    fn fill_bytes(&mut self, dst: &mut [u8]) {
+        for chunk in dst.chunks_mut(16) {
+            let block = chacha_block(CHACHA_ROUNDS, &self.key, &self.nonce);
+            chunk.copy_from_slice(&block[40..40 + chunk.len()]);
+            self.key.copy_from_slice(&block[0..32]);
+            self.nonce.copy_from_slice(&block[32..40]);
+        }
+    }
+
+    /// uniformly distributed random number in `[0, n)`
+    fn random_number(&mut self, n: u64) -> u64 {
+        assert!(n > 0);
+        let bits = 64 - n.leading_zeros() as usize;
+        let size = ::std::cmp::max(4, (bits + 7) / 8);
+        let range_mod = (1u128 << (size * 8)) % n as u128;
+        let mut buf = [0u8; 8];
+        loop {
+            self.fill_bytes(&mut buf[..size]);
+            let x = buf[..size]
+                .iter()
+                .fold(0u128, |acc, b| (acc << 8) | *b as u128);
+            if x >= range_mod {
+                return (x % n as u128) as u64;
+            }
+        }
+    }
+}
+
+/// Compute the leader of each of the `epoch_slots` slots of an epoch.
+///
+/// The stakeholders are considered in the order of their identifiers.
+pub fn follow_the_satoshi(
+    epoch_slots: usize,
+    seed: &SharedSeed,
+    stakes: &StakeDistribution,
+) -> Result<Vec<StakeholderId>, Error> {
+    let total = coin::sum_coins(stakes.values().cloned())?;
+    if total == Coin::zero() {
+        return Err(Error::NoStake);
+    }
+
+    // pick a coin for every slot, the leader is the owner of the coin
+    let mut drg = ChaChaDrg::new(seed);
+    let mut coin_indices: Vec<(usize, u64)> = (0..epoch_slots)
+        .map(|slot| (slot, drg.random_number(u64::from(total)) + 1))
+        .collect();
+    coin_indices.sort_by_key(|&(_, coin_index)| coin_index);
+
+    let mut leaders = vec![None; epoch_slots];
+    let mut coin_indices = coin_indices.into_iter().peekable();
+    let mut covered = 0u64;
+    for (stakeholder, stake) in stakes.iter() {
+        covered += u64::from(*stake);
+        while let Some(&(slot, coin_index)) = coin_indices.peek() {
+            if coin_index > covered {
+                break;
+            }
+            leaders[slot] = Some(*stakeholder);
+            coin_indices.next();
+        }
+    }
+
+    // every coin index is at most `total`, so every slot has a leader
+    Ok(leaders.into_iter().map(|leader| leader.unwrap()).collect())
+}
+
+/// The leader of the given slot, in the schedule computed by
+/// `follow_the_satoshi`
+pub fn slot_leader(leaders: &[StakeholderId], slot: SlotId) -> Option<&StakeholderId> {
+    leaders.get(slot as usize)
+}
+
+/// Check the block was issued by the leader of its slot, in the schedule
+/// computed by `follow_the_satoshi`.
+///
+/// The block signature is not checked, see `verify_block`.
+pub fn verify_slot_leader(
+    leaders: &[StakeholderId],
+    header: &normal::BlockHeader,
+) -> Result<(), verify::Error> {
+    let leader = slot_leader(leaders, header.consensus.slot_id.slotid)
+        .ok_or(verify::Error::NonExistentSlot)?;
+    if leader != &StakeholderId::new(&header.consensus.leader_key) {
+        return Err(verify::Error::WrongSlotLeader);
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use block::fake::FakeChain;
+    use block::types::{EpochSlotId, HeaderHash};
+    use block::Block;
+    use config::ProtocolMagic;
+    use hdwallet::{XPub, XPUB_SIZE};
+    use util::hex;
+
+    fn stakeholder(seed: u8) -> StakeholderId {
+        StakeholderId::new(&XPub::from_bytes([seed; XPUB_SIZE]))
+    }
+
+    #[test]
+    fn single_stakeholder_leads_every_slot() {
+        let mut stakes = StakeDistribution::new();
+        stakes.insert(stakeholder(1), Coin::new(1000).unwrap());
+        stakes.insert(stakeholder(2), Coin::zero());
+
+        let seed = SharedSeed::from_bytes([42; SHARED_SEED_SIZE]);
+        let leaders = follow_the_satoshi(100, &seed, &stakes).unwrap();
+        assert_eq!(leaders.len(), 100);
+        assert!(leaders.iter().all(|l| l == &stakeholder(1)));
+    }
+
+    #[test]
+    fn schedule_is_deterministic_and_proportional() {
+        let mut stakes = StakeDistribution::new();
+        stakes.insert(stakeholder(1), Coin::new(1_000000).unwrap());
+        stakes.insert(stakeholder(2), Coin::new(3_000000).unwrap());
+
+        let seed = SharedSeed::from_bytes([7; SHARED_SEED_SIZE]);
+        let leaders = follow_the_satoshi(2000, &seed, &stakes).unwrap();
+        assert_eq!(leaders, follow_the_satoshi(2000, &seed, &stakes).unwrap());
+
+        let nb_2 = leaders.iter().filter(|l| *l == &stakeholder(2)).count();
+        assert!(nb_2 > 1300 && nb_2 < 1700, "{} slots out of 2000", nb_2);
+
+        let other_seed = SharedSeed::from_bytes([8; SHARED_SEED_SIZE]);
+        assert_ne!(
+            leaders,
+            follow_the_satoshi(2000, &other_seed, &stakes).unwrap()
+        );
+    }
+
+    #[test]
+    fn chacha_known_answers() {
+        // ChaCha test vectors (draft-strombergson-chacha-test-vectors,
+        // TC1: all zero key and IV), the 20 rounds one to check the
+        // implementation and the 8 rounds one, as used by cryptonite
+        assert_eq!(
+            hex::encode(&chacha_block(20, &[0; 32], &[0; 8])),
+            "76b8e0ada0f13d90405d6ae55386bd28bdd219b8a08ded1aa836efcc8b770dc7\
+             da41597c5157488d7724e03fb8d84a376a43b8f41518a11cc387b669b2ee6586"
+        );
+        assert_eq!(CHACHA_ROUNDS, 8);
+        assert_eq!(
+            hex::encode(&chacha_block(CHACHA_ROUNDS, &[0; 32], &[0; 8])),
+            "3e00ef2f895f40d67f5bb8e81f09a5a12c840ec3ce9a7f3b181be188ef711a1e\
+             984ce172b9216f419f445367456d5619314a42a3da86b001387bfdb80e0cfe42"
+        );
+    }
+
+    #[test]
+    fn drg_output() {
+        // Not cross-checked against cryptonite (no mainnet or testnet
+        // schedule is available offline): pins the output of the
+        // generator so that it doesn't change unnoticed.
+        let seed = SharedSeed::from_bytes([7; SHARED_SEED_SIZE]);
+        let mut bytes = [0; 32];
+        ChaChaDrg::new(&seed).fill_bytes(&mut bytes);
+        assert_eq!(
+            hex::encode(&bytes),
+            "3645a1df4a036a677483a4323e8c137c9e1f20b31a42e2c799e39c414578293a"
+        );
+
+        let mut drg = ChaChaDrg::new(&seed);
+        let numbers: Vec<_> = (0..4)
+            .map(|_| drg.random_number(45_000_000_000_000_000))
+            .collect();
+        assert_eq!(
+            numbers,
+            vec![
+                44507271624540898,
+                34982464616216628,
+                7652285971880305,
+                34389405999004757
+            ]
+        );
+    }
+
+    #[test]
+    fn block_issued_by_slot_leader() {
+        let mut chain = FakeChain::new(ProtocolMagic::from(1), HeaderHash::new(b"genesis"));
+        let issuer = StakeholderId::new(&chain.delegation().issuer_pk);
+        let mut stakes = StakeDistribution::new();
+        stakes.insert(issuer, Coin::new(1000).unwrap());
+        stakes.insert(stakeholder(1), Coin::new(1000).unwrap());
+
+        let seed = SharedSeed::from_bytes([3; SHARED_SEED_SIZE]);
+        let leaders = follow_the_satoshi(100, &seed, &stakes).unwrap();
+        let led_by =
+            |leader: &StakeholderId| leaders.iter().position(|l| l == leader).unwrap() as SlotId;
+        let header =
+            |chain: &mut FakeChain, slotid| match chain.main_at(EpochSlotId { epoch: 0, slotid }) {
+                Block::MainBlock(blk) => blk.header,
+                Block::BoundaryBlock(_) => unreachable!(),
+            };
+
+        chain.next_boundary();
+        let hdr = header(&mut chain, led_by(&issuer));
+        assert!(verify_slot_leader(&leaders, &hdr).is_ok());
+        let hdr = header(&mut chain, led_by(&stakeholder(1)));
+        match verify_slot_leader(&leaders, &hdr) {
+            Err(verify::Error::WrongSlotLeader) => {}
+            res => panic!("expected WrongSlotLeader, got {:?}", res),
+        }
+        let hdr = header(&mut chain, 100);
+        match verify_slot_leader(&leaders, &hdr) {
+            Err(verify::Error::NonExistentSlot) => {}
+            res => panic!("expected NonExistentSlot, got {:?}", res),
+        }
+    }
+
+    #[test]
+    fn no_stake() {
+        let seed = SharedSeed::from_bytes([0; SHARED_SEED_SIZE]);
+        assert!(follow_the_satoshi(10, &seed, &StakeDistribution::new()).is_err());
+    }
+}
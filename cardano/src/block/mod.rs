@@ -7,8 +7,12 @@ pub mod block;
 pub mod boundary; /* boundary block related value */
 pub mod chain_state;
 pub mod date;
+#[cfg(any(test, feature = "fake"))]
+pub mod fake;
+pub mod fts;
 pub mod normal; /* normal block related value */
 pub mod sign;
+pub mod stream;
 pub mod types;
 pub mod update;
 pub mod verify;
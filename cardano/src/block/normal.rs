@@ -4,9 +4,9 @@ use std::{
     fmt,
     io::{BufRead, Write},
 };
-use {address, hash::Blake2b256, hdwallet, tx, vss};
+use {address, hash::Blake2b256, hdwallet, tags, tx, vss};
 
-use super::sign::BlockSignature;
+use super::sign::{BlockSignature, ProxySecretKey};
 use super::types;
 use super::types::{ChainDifficulty, EpochSlotId, HeaderExtraData, HeaderHash, SscProof};
 use super::update;
@@ -318,13 +318,48 @@ impl cbor_event::de::Deserialize for SscPayload {
     }
 }
 
+/// Heavyweight delegation certificates of a block. A certificate whose
+/// issuer is also the delegate revokes the issuer's delegation.
 #[derive(Debug, Clone)]
-pub struct DlgPayload(pub cbor_event::Value);
+pub struct DlgPayload(pub Vec<ProxySecretKey>);
+impl DlgPayload {
+    pub fn empty() -> Self {
+        DlgPayload(Vec::new())
+    }
+    pub fn iter(&self) -> ::std::slice::Iter<ProxySecretKey> {
+        self.0.iter()
+    }
+}
 
 impl cbor_event::de::Deserialize for DlgPayload {
     fn deserialize<R: BufRead>(raw: &mut Deserializer<R>) -> cbor_event::Result<Self> {
-        let payload = raw.deserialize()?;
-        Ok(DlgPayload(payload))
+        let mut certificates = Vec::new();
+        match raw.array()? {
+            cbor_event::Len::Len(len) => {
+                for _ in 0..len {
+                    certificates.push(raw.deserialize()?);
+                }
+            }
+            cbor_event::Len::Indefinite => {
+                while {
+                    let t = raw.cbor_type()?;
+                    if t == cbor_event::Type::Special {
+                        let special = raw.special()?;
+                        if special != cbor_event::Special::Break {
+                            return Err(cbor_event::Error::CustomError(format!(
+                                "Invalid DlgPayload: unexpected {:?}",
+                                special
+                            )));
+                        }
+                        false
+                    } else {
+                        certificates.push(raw.deserialize()?);
+                        true
+                    }
+                } {}
+            }
+        }
+        Ok(DlgPayload(certificates))
     }
 }
 impl cbor_event::se::Serialize for DlgPayload {
@@ -332,7 +367,7 @@ impl cbor_event::se::Serialize for DlgPayload {
         &self,
         serializer: &'se mut Serializer<W>,
     ) -> cbor_event::Result<&'se mut Serializer<W>> {
-        serializer.serialize(&self.0)
+        cbor_event::se::serialize_indefinite_array(self.0.iter(), serializer)
     }
 }
 
@@ -570,6 +605,13 @@ impl VssCertificates {
         self.serialize_for_proof(&mut buf).unwrap();
         Blake2b256::new(&buf.finalize())
     }
+
+    /// the certificate of the given stakeholder, if any
+    pub fn get(&self, stakeholder: &address::StakeholderId) -> Option<&VssCertificate> {
+        self.0
+            .iter()
+            .find(|vss_cert| &vss_cert.stakeholder_id() == stakeholder)
+    }
 }
 
 impl ::std::ops::Deref for VssCertificates {
@@ -607,6 +649,33 @@ pub struct VssCertificate {
     pub signature: vss::Signature,
     pub signing_key: hdwallet::XPub,
 }
+impl VssCertificate {
+    /// the stakeholder the certificate is for
+    pub fn stakeholder_id(&self) -> address::StakeholderId {
+        address::StakeholderId::new(&self.signing_key)
+    }
+
+    /// the certificate is valid until its expiry epoch (included)
+    pub fn is_expired(&self, epoch: types::EpochId) -> bool {
+        epoch > self.expiry_epoch
+    }
+
+    /// check the signature of the VSS key and the expiry epoch by the
+    /// signing key
+    pub fn verify_signature(&self, protocol_magic: ProtocolMagic) -> bool {
+        let mut buf = vec![tags::SigningTag::VssCert as u8];
+        Serializer::new(&mut buf)
+            .serialize(&protocol_magic)
+            .and_then(|se| se.write_array(cbor_event::Len::Len(2)))
+            .and_then(|se| se.serialize(&self.vss_key))
+            .and_then(|se| se.serialize(&self.expiry_epoch))
+            .expect("serialize the VSS certificate in memory");
+        self.signing_key.verify(
+            &buf,
+            &hdwallet::Signature::<()>::from_bytes(*self.signature.to_bytes()),
+        )
+    }
+}
 impl cbor_event::se::Serialize for VssCertificate {
     fn serialize<'se, W: Write>(
         &self,
@@ -51,22 +51,60 @@ impl<'a> MainToSign<'a> {
         tag: tags::SigningTag,
         proxy_sig: &ProxySignature,
     ) -> bool {
-        verify_signature_with(protocol_magic, tag, proxy_sig, self)
+        verify_signature_with(
+            protocol_magic,
+            tag,
+            &proxy_sig.psk.issuer_pk,
+            &proxy_sig.psk.delegate_pk,
+            &proxy_sig.sig,
+            self,
+        )
+    }
+
+    pub fn verify_light_proxy_sig(
+        &self,
+        protocol_magic: ProtocolMagic,
+        proxy_sig: &LightProxySignature,
+    ) -> bool {
+        verify_signature_with(
+            protocol_magic,
+            tags::SigningTag::MainBlockLight,
+            &proxy_sig.psk.issuer_pk,
+            &proxy_sig.psk.delegate_pk,
+            &proxy_sig.sig,
+            self,
+        )
+    }
+
+    /// Sign with `delegate_prv`, the private key of the delegate of `psk`,
+    /// to create a heavyweight delegation signature.
+    pub fn proxy_sign(
+        &self,
+        protocol_magic: ProtocolMagic,
+        tag: tags::SigningTag,
+        psk: ProxySecretKey,
+        delegate_prv: &hdwallet::XPrv,
+    ) -> ProxySignature {
+        let buf = data_to_sign_with(protocol_magic, tag, &psk.issuer_pk, self);
+        ProxySignature {
+            sig: delegate_prv.sign(&buf),
+            psk,
+        }
     }
 }
 
-fn verify_signature_with<T>(
+fn data_to_sign_with<T>(
     protocol_magic: ProtocolMagic,
     tag: tags::SigningTag,
-    proxy_sig: &ProxySignature,
+    issuer_pk: &hdwallet::XPub,
     data: &T,
-) -> bool
+) -> Vec<u8>
 where
     T: se::Serialize,
 {
     let mut buf = vec!['0' as u8, '1' as u8];
 
-    buf.extend(proxy_sig.psk.issuer_pk.as_ref());
+    buf.extend(issuer_pk.as_ref());
     buf.push(tag as u8);
 
     se::Serializer::new(&mut buf)
@@ -75,9 +113,25 @@ where
         .serialize(data)
         .unwrap();
 
-    proxy_sig.psk.delegate_pk.verify(
+    buf
+}
+
+fn verify_signature_with<T>(
+    protocol_magic: ProtocolMagic,
+    tag: tags::SigningTag,
+    issuer_pk: &hdwallet::XPub,
+    delegate_pk: &hdwallet::XPub,
+    sig: &hdwallet::Signature<()>,
+    data: &T,
+) -> bool
+where
+    T: se::Serialize,
+{
+    let buf = data_to_sign_with(protocol_magic, tag, issuer_pk, data);
+
+    delegate_pk.verify(
         &buf,
-        &hdwallet::Signature::<()>::from_bytes(*proxy_sig.sig.to_bytes()),
+        &hdwallet::Signature::<()>::from_bytes(*sig.to_bytes()),
     )
 }
 
@@ -156,21 +210,117 @@ impl ProxySecretKey {
         omega: u64,
         protocol_magic: ProtocolMagic,
     ) -> Vec<u8> {
-        // Yes, this really is
-        // CBOR-in-byte-vector-in-CBOR-in-byte-vector...
-        let mut buf2 = vec!['0' as u8, '0' as u8];
-        buf2.extend(delegate_pk.as_ref());
-        se::Serializer::new(&mut buf2).serialize(&omega).unwrap();
-
-        let mut buf = vec![];
-        buf.push(tags::SigningTag::ProxySK as u8);
-        se::Serializer::new(&mut buf)
-            .serialize(&protocol_magic)
-            .unwrap()
-            .write_bytes(buf2)
-            .unwrap();
-
-        buf
+        proxy_cert_data_to_sign(delegate_pk, &omega, protocol_magic)
+    }
+
+    /// true if this certificate revokes the previous delegation of
+    /// the issuer (i.e. the issuer delegates to itself)
+    pub fn is_revocation(&self) -> bool {
+        self.issuer_pk == self.delegate_pk
+    }
+}
+
+fn proxy_cert_data_to_sign<O>(
+    delegate_pk: &hdwallet::XPub,
+    omega: &O,
+    protocol_magic: ProtocolMagic,
+) -> Vec<u8>
+where
+    O: se::Serialize,
+{
+    // Yes, this really is
+    // CBOR-in-byte-vector-in-CBOR-in-byte-vector...
+    let mut buf2 = vec!['0' as u8, '0' as u8];
+    buf2.extend(delegate_pk.as_ref());
+    se::Serializer::new(&mut buf2).serialize(omega).unwrap();
+
+    let mut buf = vec![];
+    buf.push(tags::SigningTag::ProxySK as u8);
+    se::Serializer::new(&mut buf)
+        .serialize(&protocol_magic)
+        .unwrap()
+        .write_bytes(buf2)
+        .unwrap();
+
+    buf
+}
+
+/// Lightweight delegation certificate: the issuer allows the delegate
+/// to sign blocks for the epochs in the range `omega` (inclusive).
+///
+/// Unlike the heavyweight certificates, they are not part of the
+/// delegation payload but only found in the signature of the blocks.
+#[derive(Debug, Clone)]
+pub struct LightProxySecretKey {
+    pub omega: (EpochId, EpochId),
+    pub issuer_pk: hdwallet::XPub,
+    pub delegate_pk: hdwallet::XPub,
+    pub cert: ProxyCert,
+}
+
+impl cbor_event::se::Serialize for LightProxySecretKey {
+    fn serialize<'se, W: Write>(
+        &self,
+        serializer: &'se mut Serializer<W>,
+    ) -> cbor_event::Result<&'se mut Serializer<W>> {
+        serializer
+            .write_array(cbor_event::Len::Len(4))?
+            .serialize(&self.omega)?
+            .serialize(&self.issuer_pk)?
+            .serialize(&self.delegate_pk)?
+            .serialize(&self.cert)
+    }
+}
+
+impl cbor_event::de::Deserialize for LightProxySecretKey {
+    fn deserialize<R: BufRead>(raw: &mut Deserializer<R>) -> cbor_event::Result<Self> {
+        raw.tuple(4, "LightProxySecretKey")?;
+
+        raw.tuple(2, "LightProxySecretKey::omega")?;
+        let first_epoch = raw.unsigned_integer()?;
+        let last_epoch = raw.unsigned_integer()?;
+        let issuer_pk = cbor_event::de::Deserialize::deserialize(raw)?;
+        let delegate_pk = cbor_event::de::Deserialize::deserialize(raw)?;
+        let cert = cbor_event::de::Deserialize::deserialize(raw)?;
+
+        Ok(LightProxySecretKey {
+            omega: (first_epoch, last_epoch),
+            issuer_pk,
+            delegate_pk,
+            cert,
+        })
+    }
+}
+
+impl LightProxySecretKey {
+    /// Verify that 'cert' is a signature from 'issuer_pk' over
+    /// 'delegate_pk' and 'omega'.
+    pub fn verify(&self, protocol_magic: ProtocolMagic) -> bool {
+        let buf = proxy_cert_data_to_sign(&self.delegate_pk, &self.omega, protocol_magic);
+        self.issuer_pk.verify(&buf, &self.cert)
+    }
+
+    /// Use 'issuer_prv' to sign 'delegate_pk' and 'omega' to create a
+    /// LightProxySecretKey.
+    pub fn sign(
+        issuer_prv: &hdwallet::XPrv,
+        delegate_pk: hdwallet::XPub,
+        omega: (EpochId, EpochId),
+        protocol_magic: ProtocolMagic,
+    ) -> Self {
+        let buf = proxy_cert_data_to_sign(&delegate_pk, &omega, protocol_magic);
+
+        Self {
+            omega,
+            issuer_pk: issuer_prv.public(),
+            delegate_pk,
+            cert: issuer_prv.sign(&buf),
+        }
+    }
+
+    /// true if the certificate allows to sign blocks of the given epoch
+    pub fn covers(&self, epoch: EpochId) -> bool {
+        self.omega.0 <= epoch && epoch <= self.omega.1
     }
 }
 
@@ -203,10 +353,39 @@ impl cbor_event::de::Deserialize for ProxySignature {
     }
 }
 
+#[derive(Debug, Clone)]
+pub struct LightProxySignature {
+    pub psk: LightProxySecretKey,
+    pub sig: hdwallet::Signature<()>,
+}
+
+impl cbor_event::se::Serialize for LightProxySignature {
+    fn serialize<'se, W: Write>(
+        &self,
+        serializer: &'se mut Serializer<W>,
+    ) -> cbor_event::Result<&'se mut Serializer<W>> {
+        serializer
+            .write_array(cbor_event::Len::Len(2))?
+            .serialize(&self.psk)?
+            .serialize(&self.sig)
+    }
+}
+
+impl cbor_event::de::Deserialize for LightProxySignature {
+    fn deserialize<R: BufRead>(raw: &mut Deserializer<R>) -> cbor_event::Result<Self> {
+        raw.tuple(2, "LightProxySignature")?;
+
+        let psk = cbor_event::de::Deserialize::deserialize(raw)?;
+        let sig = cbor_event::de::Deserialize::deserialize(raw)?;
+
+        Ok(LightProxySignature { psk, sig })
+    }
+}
+
 #[derive(Debug, Clone)]
 pub enum BlockSignature {
     Signature(hdwallet::Signature<SignData>),
-    ProxyLight(Vec<cbor_event::Value>), // TODO: decode
+    ProxyLight(LightProxySignature),
     ProxyHeavy(ProxySignature),
 }
 impl BlockSignature {
@@ -227,12 +406,10 @@ impl cbor_event::se::Serialize for BlockSignature {
                 .write_array(cbor_event::Len::Len(2))?
                 .write_unsigned_integer(0)?
                 .serialize(sig),
-            &BlockSignature::ProxyLight(ref v) => {
-                let serializer = serializer
-                    .write_array(cbor_event::Len::Len(2))?
-                    .write_unsigned_integer(1)?;
-                cbor_event::se::serialize_fixed_array(v.iter(), serializer)
-            }
+            &BlockSignature::ProxyLight(ref v) => serializer
+                .write_array(cbor_event::Len::Len(2))?
+                .write_unsigned_integer(1)?
+                .serialize(v),
             &BlockSignature::ProxyHeavy(ref v) => serializer
                 .write_array(cbor_event::Len::Len(2))?
                 .write_unsigned_integer(2)?
@@ -263,6 +440,7 @@ mod tests {
 
     use super::sign;
     use base64;
+    use cbor_event;
     use hdwallet;
     use std::str::FromStr;
 
@@ -298,4 +476,28 @@ mod tests {
 
         assert!(psk.verify(pm));
     }
+
+    #[test]
+    fn test_light_psk_sign() {
+        let pm = 328429219.into();
+
+        let issuer_prv = hdwallet::XPrv::from_str("b8b054ec1b92dd4542db35e2f813f013a8d7ee9f53255b26f3ef3dafb74e11462545bd9c85aa0a6f6719a933eba16909c1a2fa0bbb58e9cd98bf9ddbb79f7d50fcfc22db8155f8d6ca0e3a975cb1b6aa5d6e7609b30c99877e469db06b5d5016").unwrap();
+        let delegate_pk = hdwallet::XPub::from_str("695b380fc72ae7d830d46f902a7c9d4057a4b9a7a0be235b87fdf51e698619e033aac8d93fd4cb82785973bb943f2047ddd1e664d4e185e7be634722e108389a").unwrap();
+
+        let mut psk = sign::LightProxySecretKey::sign(&issuer_prv, delegate_pk, (2, 5), pm);
+
+        assert!(psk.verify(pm));
+        assert!(psk.covers(2) && psk.covers(5));
+        assert!(!psk.covers(1) && !psk.covers(6));
+
+        let mut raw =
+            cbor_event::de::Deserializer::from(::std::io::Cursor::new(cbor!(&psk).unwrap()));
+        let decoded: sign::LightProxySecretKey = raw.deserialize().unwrap();
+        assert_eq!(decoded.omega, psk.omega);
+        assert_eq!(decoded.cert, psk.cert);
+
+        psk.omega = (2, 6);
+
+        assert!(!psk.verify(pm));
+    }
 }
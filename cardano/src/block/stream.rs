@@ -0,0 +1,99 @@
+//! Partial decoding of the blocks
+//!
+//! Decoding a whole `Block` decodes (and allocates) every part of its body,
+//! while many users only need the header of the block (to follow the chain)
+//! or the ids of its transactions (to index them). The functions of this
+//! module read only what they need from the CBOR stream, which can be any
+//! `BufRead` (e.g. a pack file), and stop there.
+
+use super::super::cbor::hs::util::decode_sum_type;
+use super::block::BlockHeader;
+use super::{boundary, normal};
+use cbor_event::{self, de::Deserializer};
+use std::io::BufRead;
+use tx::{Tx, TxId, TxWitness};
+
+/// Decode the header of the block, without decoding its body.
+///
+/// The remainder of the block is left in `raw`.
+pub fn decode_header<R: BufRead>(raw: &mut Deserializer<R>) -> cbor_event::Result<BlockHeader> {
+    match decode_sum_type(raw)? {
+        0 => {
+            raw.tuple(3, "Block")?;
+            let hdr: boundary::BlockHeader = raw.deserialize()?;
+            Ok(BlockHeader::BoundaryBlockHeader(hdr))
+        }
+        1 => {
+            raw.tuple(3, "Block")?;
+            let hdr: normal::BlockHeader = raw.deserialize()?;
+            Ok(BlockHeader::MainBlockHeader(hdr))
+        }
+        idx => Err(cbor_event::Error::CustomError(format!(
+            "Unsupported Block: {}",
+            idx
+        ))),
+    }
+}
+
+/// Decode the header of the block and the ids of its transactions (none
+/// for a boundary block), without decoding the rest of its body.
+///
+/// The transactions are decoded one at a time to compute their id, the
+/// witnesses are decoded but not kept.
+pub fn decode_header_tx_ids<R: BufRead>(
+    raw: &mut Deserializer<R>,
+) -> cbor_event::Result<(BlockHeader, Vec<TxId>)> {
+    let header = decode_header(raw)?;
+    let mut ids = Vec::new();
+    if let BlockHeader::MainBlockHeader(_) = header {
+        raw.tuple(4, "Body")?;
+        let len = raw.array()?;
+        if len != cbor_event::Len::Indefinite {
+            return Err(cbor_event::Error::CustomError(format!(
+                "Unexpected TxPayload length: {:?}",
+                len
+            )));
+        }
+        while raw.cbor_type()? != cbor_event::Type::Special {
+            raw.tuple(2, "TxAux")?;
+            let tx: Tx = raw.deserialize()?;
+            let _: TxWitness = raw.deserialize()?;
+            ids.push(tx.id());
+        }
+        let special = raw.special()?;
+        if special != cbor_event::Special::Break {
+            return Err(cbor_event::Error::CustomError(format!(
+                "Unexpected end of TxPayload: {:?}",
+                special
+            )));
+        }
+    }
+    Ok((header, ids))
+}
+
+/// The ids of the transactions of the block, see `decode_header_tx_ids`.
+pub fn decode_tx_ids<R: BufRead>(raw: &mut Deserializer<R>) -> cbor_event::Result<Vec<TxId>> {
+    decode_header_tx_ids(raw).map(|(_, ids)| ids)
+}
+
+#[cfg(test)]
+mod tests {
+    use block::verify::tests::{BLOCK1, BLOCK2, BLOCK3};
+    use block::{Block, RawBlock};
+
+    #[test]
+    fn decode_header_tx_ids() {
+        for raw in &[BLOCK1, BLOCK2, BLOCK3] {
+            let rblk = RawBlock(raw.to_vec());
+            let blk = rblk.decode().unwrap();
+            let hdr = rblk.decode_header().unwrap();
+            assert_eq!(hdr.compute_hash(), blk.header().compute_hash());
+
+            let ids: Vec<_> = match &blk {
+                Block::BoundaryBlock(_) => vec![],
+                Block::MainBlock(mblk) => mblk.body.tx.iter().map(|txaux| txaux.tx.id()).collect(),
+            };
+            assert_eq!(rblk.tx_ids().unwrap(), ids);
+        }
+    }
+}
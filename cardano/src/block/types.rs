@@ -1,5 +1,6 @@
 use super::normal::SscPayload;
 use cbor_event::{self, de::Deserializer, se::Serializer};
+use config::EpochParameters;
 use hash::Blake2b256;
 use util::{hex, try_from_slice::TryFromSlice};
 
@@ -231,7 +232,8 @@ impl SscProof {
     }
 }
 
-#[derive(Debug, Clone, Copy)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+#[cfg_attr(feature = "generic-serialization", derive(Serialize, Deserialize))]
 pub struct ChainDifficulty(u64);
 
 impl fmt::Display for ChainDifficulty {
@@ -266,8 +268,24 @@ impl EpochSlotId {
             slotid: self.slotid + 1,
         }
     }
+    /// the absolute slot number, assuming the epochs of the mainnet
+    /// (see `slot_number_with`)
     pub fn slot_number(&self) -> usize {
-        (self.epoch as usize) * 21600 + (self.slotid as usize)
+        self.slot_number_with(&EpochParameters::default())
+    }
+    pub fn slot_number_with(&self, parameters: &EpochParameters) -> usize {
+        (self.epoch as usize) * parameters.slots_per_epoch + (self.slotid as usize)
+    }
+    /// the slot of the given absolute slot number
+    pub fn from_slot_number(slot_number: usize, parameters: &EpochParameters) -> Self {
+        EpochSlotId {
+            epoch: (slot_number / parameters.slots_per_epoch) as EpochId,
+            slotid: (slot_number % parameters.slots_per_epoch) as SlotId,
+        }
+    }
+    /// whether the slot exists in an epoch of the given length
+    pub fn is_valid(&self, parameters: &EpochParameters) -> bool {
+        (self.slotid as usize) < parameters.slots_per_epoch
     }
 }
 impl fmt::Display for EpochSlotId {
@@ -276,6 +294,8 @@ impl fmt::Display for EpochSlotId {
     }
 }
 
+/// the number of slots between two slots, assuming the epochs of the mainnet
+/// (see `slot_number_with` for the other networks)
 impl ::std::ops::Sub<EpochSlotId> for EpochSlotId {
     type Output = usize;
     fn sub(self, rhs: Self) -> Self::Output {
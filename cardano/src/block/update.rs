@@ -1,5 +1,6 @@
 use super::types;
 use cbor_event::{self, de::Deserializer, se::Serializer};
+use fee::{LinearFee, Milli};
 use hash::{self, Blake2b256};
 use hdwallet;
 
@@ -14,6 +15,25 @@ pub struct UpdatePayload {
     pub proposal: Option<UpdateProposal>,
     pub votes: Vec<UpdateVote>,
 }
+impl UpdatePayload {
+    pub fn is_empty(&self) -> bool {
+        self.proposal.is_none() && self.votes.is_empty()
+    }
+}
+impl fmt::Display for UpdatePayload {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        if self.is_empty() {
+            return write!(f, "<no update>");
+        }
+        if let Some(ref proposal) = self.proposal {
+            writeln!(f, "{}", proposal)?;
+        }
+        for vote in self.votes.iter() {
+            writeln!(f, "{}", vote)?;
+        }
+        Ok(())
+    }
+}
 
 impl cbor_event::se::Serialize for UpdatePayload {
     fn serialize<'se, W: Write>(
@@ -84,6 +104,34 @@ pub struct UpdateProposal {
 pub type UpAttributes = types::Attributes;
 pub type SystemTag = String;
 
+impl UpdateProposal {
+    /// the identifier of the proposal, used by the votes to refer to it
+    pub fn id(&self) -> UpId {
+        Blake2b256::new(&cbor!(self).unwrap())
+    }
+}
+impl fmt::Display for UpdateProposal {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        writeln!(f, "update proposal {}", self.id())?;
+        writeln!(f, "  block version: {}", self.block_version)?;
+        writeln!(
+            f,
+            "  software version: {}:{}",
+            self.software_version.application_name(),
+            self.software_version.application_version
+        )?;
+        write!(f, "{}", self.block_version_mod)?;
+        for (system_tag, data) in self.data.iter() {
+            writeln!(f, "  update data for {}:", system_tag)?;
+            writeln!(f, "    app diff hash: {}", data.app_diff_hash)?;
+            writeln!(f, "    package hash: {}", data.pkg_hash)?;
+            writeln!(f, "    updater hash: {}", data.updater_hash)?;
+            writeln!(f, "    metadata hash: {}", data.metadata_hash)?;
+        }
+        write!(f, "  proposed by: {}", self.from)
+    }
+}
+
 impl cbor_event::se::Serialize for UpdateProposal {
     fn serialize<'se, W: Write>(
         &self,
@@ -134,12 +182,40 @@ pub struct BlockVersionModifier {
     pub unlock_stake_epoch: Option<types::EpochId>,
 }
 
+impl fmt::Display for BlockVersionModifier {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        fn field<T: fmt::Debug>(
+            f: &mut fmt::Formatter,
+            name: &str,
+            value: &Option<T>,
+        ) -> fmt::Result {
+            match value {
+                None => Ok(()),
+                Some(value) => writeln!(f, "  {}: {:?}", name, value),
+            }
+        }
+        field(f, "script version", &self.script_version)?;
+        field(f, "slot duration (ms)", &self.slot_duration)?;
+        field(f, "max block size", &self.max_block_size)?;
+        field(f, "max header size", &self.max_header_size)?;
+        field(f, "max tx size", &self.max_tx_size)?;
+        field(f, "max proposal size", &self.max_proposal_size)?;
+        field(f, "mpc threshold", &self.mpc_thd)?;
+        field(f, "heavy delegation threshold", &self.heavy_del_thd)?;
+        field(f, "update vote threshold", &self.update_vote_thd)?;
+        field(f, "update proposal threshold", &self.update_proposal_thd)?;
+        field(f, "update implicit", &self.update_implicit)?;
+        field(f, "softfork rule", &self.softfork_rule)?;
+        field(f, "tx fee policy", &self.tx_fee_policy)?;
+        field(f, "unlock stake epoch", &self.unlock_stake_epoch)
+    }
+}
+
 impl cbor_event::se::Serialize for BlockVersionModifier {
     fn serialize<'se, W: Write>(
         &self,
         serializer: &'se mut Serializer<W>,
     ) -> cbor_event::Result<&'se mut Serializer<W>> {
-        assert!(self.tx_fee_policy.is_none()); // not tested yet
         serializer
             .write_array(cbor_event::Len::Len(14))?
             .serialize(&self.script_version)?
@@ -184,7 +260,97 @@ impl cbor_event::de::Deserialize for BlockVersionModifier {
 pub type ScriptVersion = u16;
 pub type Millisecond = u64;
 pub type FlatSlotId = u64;
-pub type TxFeePolicy = cbor_event::Value; // TODO
+
+const TX_FEE_POLICY_TX_SIZE_LINEAR: u64 = 0;
+
+/// number of nano-lovelaces in a lovelace, the unit of the coefficients
+/// of `TxFeePolicy::TxSizeLinear`
+const NANO_PER_LOVELACE: u64 = 1_000_000_000;
+
+/// The transaction fee policy, as set by the update proposals
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum TxFeePolicy {
+    /// `fee = constant + coefficient * size_of_tx_in_bytes`, the values are
+    /// expressed in nano-lovelaces.
+    TxSizeLinear { constant: u64, coefficient: u64 },
+    /// a policy unknown to this version of the protocol, kept as is
+    Unknown(u64, Vec<u8>),
+}
+impl TxFeePolicy {
+    /// the equivalent `LinearFee`, if the policy is known and its
+    /// coefficients can be expressed in milli-lovelaces without loss
+    pub fn to_linear_fee(&self) -> Option<LinearFee> {
+        fn to_milli(nano: u64) -> Option<Milli> {
+            if nano % 1_000_000 != 0 {
+                return None;
+            }
+            Some(Milli::new(
+                nano / NANO_PER_LOVELACE,
+                (nano / 1_000_000) % 1000,
+            ))
+        }
+        match self {
+            TxFeePolicy::TxSizeLinear {
+                constant,
+                coefficient,
+            } => Some(LinearFee::new(
+                to_milli(*constant)?,
+                to_milli(*coefficient)?,
+            )),
+            TxFeePolicy::Unknown(_, _) => None,
+        }
+    }
+}
+
+impl cbor_event::se::Serialize for TxFeePolicy {
+    fn serialize<'se, W: Write>(
+        &self,
+        serializer: &'se mut Serializer<W>,
+    ) -> cbor_event::Result<&'se mut Serializer<W>> {
+        serializer.write_array(cbor_event::Len::Len(2))?;
+        match self {
+            TxFeePolicy::TxSizeLinear {
+                constant,
+                coefficient,
+            } => serializer
+                .write_unsigned_integer(TX_FEE_POLICY_TX_SIZE_LINEAR)?
+                .write_tag(24)?
+                .write_bytes(&cbor!(&(constant, coefficient))?),
+            TxFeePolicy::Unknown(tag, bytes) => serializer
+                .write_unsigned_integer(*tag)?
+                .write_tag(24)?
+                .write_bytes(bytes),
+        }
+    }
+}
+
+impl cbor_event::de::Deserialize for TxFeePolicy {
+    fn deserialize<R: BufRead>(raw: &mut Deserializer<R>) -> cbor_event::Result<Self> {
+        raw.tuple(2, "TxFeePolicy")?;
+        let policy = raw.unsigned_integer()?;
+        let tag = raw.tag()?;
+        if tag != 24 {
+            return Err(cbor_event::Error::CustomError(format!(
+                "Invalid Tag: {} but expected 24",
+                tag
+            )));
+        }
+        let bytes = raw.bytes()?;
+        match policy {
+            TX_FEE_POLICY_TX_SIZE_LINEAR => {
+                let mut raw = Deserializer::from(std::io::Cursor::new(bytes));
+                raw.tuple(2, "TxFeePolicy::TxSizeLinear")?;
+                let constant = raw.unsigned_integer()?;
+                let coefficient = raw.unsigned_integer()?;
+                Ok(TxFeePolicy::TxSizeLinear {
+                    constant,
+                    coefficient,
+                })
+            }
+            _ => Ok(TxFeePolicy::Unknown(policy, bytes)),
+        }
+    }
+}
 
 #[derive(Debug, Clone)]
 pub struct UpdateData {
@@ -257,6 +423,18 @@ pub struct UpdateVote {
 
 pub type UpId = hash::Blake2b256; // UpdateProposal
 
+impl fmt::Display for UpdateVote {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(
+            f,
+            "vote {} for proposal {} by {}",
+            if self.decision { "for" } else { "against" },
+            self.proposal_id,
+            self.key
+        )
+    }
+}
+
 impl cbor_event::se::Serialize for UpdateVote {
     fn serialize<'se, W: Write>(
         &self,
@@ -312,6 +490,35 @@ mod tests {
     use super::*;
     use hash::Blake2b256;
 
+    #[test]
+    fn tx_fee_policy_encode_decode() {
+        let policy = TxFeePolicy::TxSizeLinear {
+            constant: 155381_000000000,
+            coefficient: 43_946000000,
+        };
+        assert!(cbor_event::test_encode_decode(&policy).unwrap());
+        assert_eq!(
+            policy.to_linear_fee(),
+            Some(LinearFee::new(Milli::new(155381, 0), Milli::new(43, 946)))
+        );
+
+        // the fee can't be expressed in milli-lovelaces
+        let policy = TxFeePolicy::TxSizeLinear {
+            constant: 155381_000000000,
+            coefficient: 43_946000001,
+        };
+        assert_eq!(policy.to_linear_fee(), None);
+        let policy = TxFeePolicy::TxSizeLinear {
+            constant: 155381_000500000,
+            coefficient: 43_946000000,
+        };
+        assert_eq!(policy.to_linear_fee(), None);
+
+        let policy = TxFeePolicy::Unknown(3, vec![0x80]);
+        assert!(cbor_event::test_encode_decode(&policy).unwrap());
+        assert_eq!(policy.to_linear_fee(), None);
+    }
+
     #[test]
     fn debug_update_proof() {
         let h = UpdateProof(Blake2b256::new(&[0; 32]));
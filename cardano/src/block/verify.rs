@@ -14,19 +14,22 @@ use std::{
     error, fmt,
 };
 use tags;
-use tx;
+use tx::{self, ScriptEvaluator};
 
 #[derive(Debug)]
 pub enum Error {
     BadBlockSig,
+    BadDelegationCertSig,
     BadTxWitness,
     BadUpdateProposalSig,
     BadUpdateVoteSig,
     BadVssCertSig,
+    DuplicateDelegationIssuers,
     DuplicateInputs,
     DuplicateSigningKeys,
     DuplicateVSSKeys,
     EncodingError(cbor_event::Error),
+    LightDelegationOutOfRange,
     UnexpectedWitnesses,
     MissingWitnesses,
     RedeemOutput,
@@ -46,17 +49,32 @@ pub enum Error {
 
     // Used by verify_block_in_chain.
     WrongPreviousBlock(HeaderHash, HeaderHash), // actual, expected
+    WrongChainDifficulty(ChainDifficulty, ChainDifficulty), // actual, expected
     NonExistentSlot,
     BlockDateInPast,
     BlockDateInFuture,
     WrongSlotLeader,
-    MissingUtxo,
+    MissingUtxo(tx::TxoPointer),
     InputsTooBig,
     OutputsTooBig,
-    OutputsExceedInputs,
+    /// the sum of the fees paid in the chain exceeds the limit
+    FeesTooBig,
+    OutputsExceedInputs {
+        inputs: coin::Coin,
+        outputs: coin::Coin,
+        fee: coin::Coin,
+    },
     FeeError(fee::Error),
-    AddressMismatch,
-    DuplicateTxo,
+    AddressMismatch(tx::TxoPointer),
+    DuplicateTxo(tx::TxoPointer),
+    /// the transaction was not applied to the utxo state, because of
+    /// the given (first) violation of the ledger rules
+    InvalidTransaction(tx::TxId, Box<Error>),
+    /// a script witness was not accepted
+    ScriptError(tx::ScriptError),
+    /// the block is signed directly by its issuer, without delegation,
+    /// which is not verified
+    UnsupportedBlockSignature,
 }
 
 impl fmt::Display for Error {
@@ -64,14 +82,22 @@ impl fmt::Display for Error {
         use self::Error::*;
         match self {
             BadBlockSig => write!(f, "invalid block signature"),
+            BadDelegationCertSig => write!(f, "invalid delegation certificate signature"),
             BadTxWitness => write!(f, "invalid transaction witness"),
             BadUpdateProposalSig => write!(f, "invalid update proposal signature"),
             BadUpdateVoteSig => write!(f, "invalid update vote signature"),
             BadVssCertSig => write!(f, "invalid VSS certificate signature"),
+            DuplicateDelegationIssuers => {
+                write!(f, "multiple delegation certificates from the same issuer")
+            }
             DuplicateInputs => write!(f, "duplicated inputs"),
             DuplicateSigningKeys => write!(f, "duplicated signing keys"),
             DuplicateVSSKeys => write!(f, "duplicated VSS keys"),
             EncodingError(_error) => write!(f, "encoding error"),
+            LightDelegationOutOfRange => write!(
+                f,
+                "lightweight delegation certificate does not cover the block's epoch"
+            ),
             UnexpectedWitnesses => write!(f, "transaction has more witnesses than inputs"),
             MissingWitnesses => write!(f, "transaction has more inputs than witnesses"),
             RedeemOutput => write!(f, "invalid redeem output"),
@@ -92,6 +118,11 @@ impl fmt::Display for Error {
                 "block has parent {} while {} was expected",
                 actual, expected
             ),
+            WrongChainDifficulty(actual, expected) => write!(
+                f,
+                "block has chain difficulty {} while {} was expected",
+                actual, expected
+            ),
             NonExistentSlot => write!(f, "slot does not have a leader"),
             BlockDateInPast => write!(f, "block's slot or epoch is earlier than its parent"),
             BlockDateInFuture => write!(f, "block is in a future epoch"),
@@ -99,17 +130,38 @@ impl fmt::Display for Error {
                 f,
                 "block was not signed by the slot leader indicated in the genesis block"
             ),
-            MissingUtxo => write!(
+            MissingUtxo(ptr) => write!(
                 f,
-                "transaction spends an input that doesn't exist or has already been spent"
+                "transaction spends an input ({}) that doesn't exist or has already been spent",
+                ptr
             ),
             InputsTooBig => write!(f, "sum of inputs exceeds limit"),
             OutputsTooBig => write!(f, "sum of outputs exceeds limit"),
-            OutputsExceedInputs => write!(f, "sum of outputs is larger than sum of inputs and fee"),
+            FeesTooBig => write!(f, "sum of fees exceeds limit"),
+            OutputsExceedInputs {
+                inputs,
+                outputs,
+                fee,
+            } => write!(
+                f,
+                "sum of outputs ({}) is larger than sum of inputs ({}) and fee ({})",
+                outputs, inputs, fee
+            ),
             FeeError(_) => write!(f, "fee calculation failed"),
             WrongRedeemTxId => write!(f, "transaction input's ID does not match redeem public key"),
-            AddressMismatch => write!(f, "transaction input witness does not match utxo address"),
-            DuplicateTxo => write!(f, "transaction has an output that already exists"),
+            AddressMismatch(ptr) => write!(
+                f,
+                "transaction input witness does not match the address of utxo {}",
+                ptr
+            ),
+            DuplicateTxo(ptr) => {
+                write!(f, "transaction has an output ({}) that already exists", ptr)
+            }
+            InvalidTransaction(txid, _) => write!(f, "transaction {} is invalid", txid),
+            ScriptError(_) => write!(f, "invalid script witness"),
+            UnsupportedBlockSignature => {
+                write!(f, "block signature without delegation is not supported")
+            }
         }
     }
 }
@@ -120,11 +172,19 @@ impl From<cbor_event::Error> for Error {
     }
 }
 
+impl From<tx::ScriptError> for Error {
+    fn from(e: tx::ScriptError) -> Self {
+        Error::ScriptError(e)
+    }
+}
+
 impl error::Error for Error {
     fn cause(&self) -> Option<&error::Error> {
         match self {
             Error::EncodingError(ref error) => Some(error),
             Error::FeeError(ref error) => Some(error),
+            Error::InvalidTransaction(_, ref error) => Some(error.as_ref()),
+            Error::ScriptError(ref error) => Some(error),
             _ => None,
         }
     }
@@ -184,7 +244,7 @@ impl normal::Block {
         body.ssc.get_vss_certificates().verify(hdr.protocol_magic)?;
 
         // check delegation
-        // TODO
+        body.delegation.verify(hdr.protocol_magic)?;
 
         // check update
         body.update.verify(hdr.protocol_magic)?;
@@ -212,9 +272,31 @@ impl normal::Block {
 
         // check consensus
         // FIXME: check slotid?
-        match &hdr.consensus.block_signature {
-            BlockSignature::Signature(_) => panic!("not implemented"),
-            BlockSignature::ProxyLight(_) => panic!("not implemented"),
+        hdr.verify_signature()
+    }
+}
+
+impl normal::BlockHeader {
+    /// check the block was signed by the delegate of its issuer
+    fn verify_signature(&self) -> Result<(), Error> {
+        match &self.consensus.block_signature {
+            BlockSignature::Signature(_) => return Err(Error::UnsupportedBlockSignature),
+            BlockSignature::ProxyLight(proxy_sig) => {
+                if !proxy_sig.psk.covers(self.consensus.slot_id.epoch) {
+                    return Err(Error::LightDelegationOutOfRange);
+                }
+
+                if !proxy_sig.psk.verify(self.protocol_magic) {
+                    return Err(Error::BadDelegationCertSig);
+                }
+
+                // verify the signature
+                let to_sign = MainToSign::from_header(self);
+
+                if !to_sign.verify_light_proxy_sig(self.protocol_magic, proxy_sig) {
+                    return Err(Error::BadBlockSig);
+                }
+            }
             BlockSignature::ProxyHeavy(proxy_sig) => {
                 // check against self-signed PSKs
                 if proxy_sig.psk.issuer_pk == proxy_sig.psk.delegate_pk {
@@ -222,10 +304,10 @@ impl normal::Block {
                 }
 
                 // verify the signature
-                let to_sign = MainToSign::from_header(&hdr);
+                let to_sign = MainToSign::from_header(self);
 
                 if !to_sign.verify_proxy_sig(
-                    hdr.protocol_magic,
+                    self.protocol_magic,
                     tags::SigningTag::MainBlockHeavy,
                     proxy_sig,
                 ) {
@@ -238,6 +320,85 @@ impl normal::Block {
     }
 }
 
+/// Verify a chain of block headers, without the blocks' bodies.
+///
+/// Every header is checked to have the given protocol magic, to follow
+/// the previous header (its hash, date and chain difficulty) and, for the
+/// main blocks, to be signed by the delegate of its issuer. The first
+/// header is checked against `parent`, a header already known to be valid;
+/// if `None`, the first header is only checked on its own.
+///
+/// The bodies not being available, neither the proofs of the headers nor
+/// the slot leaders are verified: this is meant for the light clients
+/// trusting the content of the blocks of a valid header chain.
+pub fn verify_header_chain<'a, I>(
+    protocol_magic: ProtocolMagic,
+    parent: Option<&BlockHeader>,
+    headers: I,
+) -> Result<(), Error>
+where
+    I: IntoIterator<Item = &'a BlockHeader>,
+{
+    let mut parent = parent.map(|hdr| (hdr.compute_hash(), hdr.get_blockdate(), hdr.difficulty()));
+    for hdr in headers {
+        let magic = match hdr {
+            BlockHeader::BoundaryBlockHeader(h) => h.protocol_magic,
+            BlockHeader::MainBlockHeader(h) => h.protocol_magic,
+        };
+        if magic != protocol_magic {
+            return Err(Error::WrongMagic);
+        }
+
+        let date = hdr.get_blockdate();
+        let difficulty = hdr.difficulty();
+        if let Some((parent_hash, parent_date, parent_difficulty)) = parent {
+            let previous = hdr.get_previous_header();
+            if previous != parent_hash {
+                return Err(Error::WrongPreviousBlock(previous, parent_hash));
+            }
+
+            if date <= parent_date {
+                return Err(Error::BlockDateInPast);
+            }
+            if date.is_boundary() && date.get_epochid() > parent_date.get_epochid() + 1 {
+                return Err(Error::BlockDateInFuture);
+            }
+
+            // only the main blocks count in the chain difficulty
+            let expected = if hdr.is_boundary_block() {
+                parent_difficulty
+            } else {
+                ChainDifficulty::from(u64::from(parent_difficulty) + 1)
+            };
+            if difficulty != expected {
+                return Err(Error::WrongChainDifficulty(difficulty, expected));
+            }
+        }
+
+        if let BlockHeader::MainBlockHeader(h) = hdr {
+            h.verify_signature()?;
+        }
+
+        parent = Some((hdr.compute_hash(), date, difficulty));
+    }
+    Ok(())
+}
+
+impl Verify for normal::DlgPayload {
+    fn verify(&self, protocol_magic: ProtocolMagic) -> Result<(), Error> {
+        let mut issuers = BTreeSet::new();
+        for psk in self.iter() {
+            if !issuers.insert(address::StakeholderId::new(&psk.issuer_pk)) {
+                return Err(Error::DuplicateDelegationIssuers);
+            }
+            if !psk.verify(protocol_magic) {
+                return Err(Error::BadDelegationCertSig);
+            }
+        }
+        Ok(())
+    }
+}
+
 impl Verify for update::UpdatePayload {
     fn verify(&self, protocol_magic: ProtocolMagic) -> Result<(), Error> {
         if let Some(proposal) = &self.proposal {
@@ -300,6 +461,9 @@ impl Verify for tx::TxAux {
         }
 
         self.witness.iter().try_for_each(|in_witness| {
+            if let tx::TxInWitness::ScriptWitness(validator, redeemer) = in_witness {
+                tx::NoScriptEvaluation.evaluate(protocol_magic, validator, redeemer, &self.tx)?;
+            }
             if !in_witness.verify_tx(protocol_magic, &self.tx) {
                 return Err(Error::BadTxWitness);
             }
@@ -334,21 +498,8 @@ impl Verify for VssCertificates {
         }
 
         // verify every certificate's signature
-        for vss_cert in self.iter() {
-            let mut buf = vec![];
-            buf.push(tags::SigningTag::VssCert as u8);
-            se::Serializer::new(&mut buf)
-                .serialize(&protocol_magic)?
-                .write_array(cbor_event::Len::Len(2))?
-                .serialize(&vss_cert.vss_key)?
-                .serialize(&vss_cert.expiry_epoch)?;
-
-            if !vss_cert.signing_key.verify(
-                &buf,
-                &Signature::<()>::from_bytes(*vss_cert.signature.to_bytes()),
-            ) {
-                return Err(Error::BadVssCertSig);
-            }
+        if !self.iter().all(|x| x.verify_signature(protocol_magic)) {
+            return Err(Error::BadVssCertSig);
         }
 
         Ok(())
@@ -416,12 +567,14 @@ impl Verify for update::UpdateVote {
 }
 
 #[cfg(test)]
-mod tests {
+pub(crate) mod tests {
     use self::normal::DlgPayload;
+    use self::sign::BlockSignature;
     use address;
     use block::*;
     use cbor_event;
     use coin;
+    use hdwallet::{Signature, SIGNATURE_SIZE};
     use merkle;
     use std::fmt::Debug;
     use std::mem;
@@ -518,6 +671,21 @@ mod tests {
             expect_error(&verify_block(&hash, &blk), Error::BadTxWitness);
         }
 
+        // use a script witness, scripts are not supported
+        {
+            let mut blk = blk.clone();
+            if let Block::MainBlock(mblk) = &mut blk {
+                mblk.body.tx[0].witness[0] = tx::TxInWitness::ScriptWitness(
+                    address::Script::new(0, vec![1]),
+                    address::Script::new(0, vec![2]),
+                );
+            }
+            match verify_block(&hash, &blk) {
+                Err(Error::ScriptError(tx::ScriptError::NotSupported)) => {}
+                res => panic!("Expected scripts not supported but got {:?}", res),
+            }
+        }
+
         // create a zero output
         {
             let mut blk = blk.clone();
@@ -695,7 +863,11 @@ mod tests {
         {
             let mut blk = blk2.clone();
             if let Block::MainBlock(mblk) = &mut blk {
-                mblk.body.delegation = DlgPayload(cbor_event::Value::U64(123));
+                let psk = match &mblk.header.consensus.block_signature {
+                    BlockSignature::ProxyHeavy(proxy_sig) => proxy_sig.psk.clone(),
+                    _ => unreachable!(),
+                };
+                mblk.body.delegation = DlgPayload(vec![psk]);
             }
             expect_error(&verify_block(&hash2, &blk), Error::WrongDelegationProof);
         }
@@ -711,9 +883,118 @@ mod tests {
         // TODO: SelfSignedPSK, WrongBoundaryProof
     }
 
-    // a block with 6 transactions
+    #[test]
+    fn test_verify_header_chain() {
+        let blk = RawBlock(BLOCK1.to_vec()).decode().unwrap();
+        let hdr = blk.get_header();
+        let protocol_magic = blk.get_protocol_magic();
+        assert!(verify_header_chain(protocol_magic, None, &[hdr.clone()]).is_ok());
+
+        expect_error(
+            &verify_header_chain(ProtocolMagic::from(1), None, &[hdr.clone()]),
+            Error::WrongMagic,
+        );
+
+        // a header does not follow itself
+        let hash = hdr.compute_hash();
+        expect_error(
+            &verify_header_chain(protocol_magic, Some(&hdr), &[hdr.clone()]),
+            Error::WrongPreviousBlock(hash.clone(), hash),
+        );
+
+        // invalidate the block signature
+        let mut hdr = hdr;
+        if let BlockHeader::MainBlockHeader(h) = &mut hdr {
+            h.consensus.chain_difficulty = ChainDifficulty::from(1);
+        }
+        expect_error(
+            &verify_header_chain(protocol_magic, None, &[hdr.clone()]),
+            Error::BadBlockSig,
+        );
+
+        // signed without delegation
+        if let BlockHeader::MainBlockHeader(h) = &mut hdr {
+            h.consensus.block_signature =
+                BlockSignature::Signature(Signature::from_bytes([0; SIGNATURE_SIZE]));
+        }
+        expect_error(
+            &verify_header_chain(protocol_magic, None, &[hdr]),
+            Error::UnsupportedBlockSignature,
+        );
+    }
+
+    #[test]
+    fn test_verify_header_chain_of_blocks() {
+        let protocol_magic = ProtocolMagic::from(328429219);
+        let mut chain = fake::FakeChain::new(protocol_magic, HeaderHash::new(b"genesis"));
+        let headers: Vec<_> = vec![
+            chain.next_boundary(),
+            chain.next_main(),
+            chain.next_main(),
+            chain.next_boundary(),
+            chain.next_main(),
+        ]
+        .iter()
+        .map(|blk| blk.get_header())
+        .collect();
+        assert!(verify_header_chain(protocol_magic, None, &headers).is_ok());
+        assert!(verify_header_chain(protocol_magic, Some(&headers[1]), &headers[2..]).is_ok());
+
+        // a header is missing
+        let skipping: Vec<_> = headers
+            .iter()
+            .enumerate()
+            .filter(|(i, _)| *i != 2)
+            .map(|(_, hdr)| hdr)
+            .collect();
+        expect_error(
+            &verify_header_chain(protocol_magic, None, skipping),
+            Error::WrongPreviousBlock(headers[3].get_previous_header(), headers[1].compute_hash()),
+        );
+
+        // the headers are out of order
+        let swapped = vec![&headers[0], &headers[2], &headers[1]];
+        expect_error(
+            &verify_header_chain(protocol_magic, None, swapped),
+            Error::WrongPreviousBlock(headers[2].get_previous_header(), headers[0].compute_hash()),
+        );
+
+        // the parent is not the previous header
+        expect_error(
+            &verify_header_chain(protocol_magic, Some(&headers[0]), &headers[2..]),
+            Error::WrongPreviousBlock(headers[2].get_previous_header(), headers[0].compute_hash()),
+        );
+
+        // the chain difficulty does not grow by one
+        let mut wrong_difficulty = headers.clone();
+        if let BlockHeader::MainBlockHeader(h) = &mut wrong_difficulty[2] {
+            h.consensus.chain_difficulty = ChainDifficulty::from(1);
+        }
+        expect_error(
+            &verify_header_chain(protocol_magic, Some(&headers[1]), &wrong_difficulty[2..]),
+            Error::WrongChainDifficulty(ChainDifficulty::from(1), ChainDifficulty::from(2)),
+        );
+    }
+
+    #[test]
+    fn test_vss_certificates() {
+        let blk = RawBlock(BLOCK1.to_vec()).decode().unwrap();
+        assert!(blk.get_vss_certificates().is_none());
+
+        let blk = RawBlock(BLOCK3.to_vec()).decode().unwrap();
+        let vss_certs = blk.get_vss_certificates().unwrap();
+        assert!(!vss_certs.is_empty());
+        for vss_cert in vss_certs.iter() {
+            assert!(vss_cert.verify_signature(blk.get_protocol_magic()));
+            assert!(!vss_cert.is_expired(vss_cert.expiry_epoch));
+            assert!(vss_cert.is_expired(vss_cert.expiry_epoch + 1));
+            let found = vss_certs.get(&vss_cert.stakeholder_id()).unwrap();
+            assert_eq!(found.vss_key, vss_cert.vss_key);
+        }
+    }
+
     const HEADER_HASH1: &str = "ae443ffffe52cc29de83312d2819b3955fc306ce65ae6aa5b26f1d3c76e91842";
-    const BLOCK1: &'static [u8] = &[
+    pub(crate) const BLOCK1: &'static [u8] = &[
         130, 1, 131, 133, 26, 37, 192, 15, 169, 88, 32, 143, 34, 167, 105, 182, 150, 66, 32, 255,
         10, 81, 134, 23, 91, 234, 166, 95, 163, 1, 164, 32, 9, 182, 196, 50, 7, 84, 107, 55, 169,
         7, 8, 132, 131, 6, 88, 32, 132, 17, 132, 183, 197, 80, 62, 62, 154, 179, 254, 210, 98, 186,
@@ -859,7 +1140,7 @@ mod tests {
 
     // a block with an update payload and vote
     const HEADER_HASH2: &str = "6da1c6dffaa21dd72034dae5fcafb1dea8dc0ff9d246910f76e8f8a91fc8fe4c";
-    const BLOCK2: &'static [u8] = &[
+    pub(crate) const BLOCK2: &'static [u8] = &[
         130, 1, 131, 133, 26, 37, 192, 15, 169, 88, 32, 159, 185, 213, 249, 53, 208, 58, 143, 109,
         225, 163, 182, 41, 57, 245, 132, 195, 90, 16, 43, 106, 178, 38, 184, 22, 50, 117, 191, 190,
         8, 155, 209, 132, 131, 0, 88, 32, 14, 87, 81, 192, 38, 229, 67, 178, 232, 171, 46, 176, 96,
@@ -932,7 +1213,7 @@ mod tests {
 
     // a block with a VSS certificate
     const HEADER_HASH3: &str = "4c64f52a24d01ac5f66c4d23eec2e009c9e81d54279b5f2b6aaedf75d3ee7047";
-    const BLOCK3: &'static [u8] = &[
+    pub(crate) const BLOCK3: &'static [u8] = &[
         130, 1, 131, 133, 26, 37, 192, 15, 169, 88, 32, 209, 226, 244, 50, 10, 63, 168, 77, 148,
         141, 125, 21, 56, 67, 105, 17, 139, 229, 234, 17, 76, 161, 243, 88, 238, 246, 102, 241,
         171, 227, 216, 116, 132, 131, 0, 88, 32, 14, 87, 81, 192, 38, 229, 67, 178, 232, 171, 46,
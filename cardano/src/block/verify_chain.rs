@@ -2,12 +2,14 @@ use address;
 use block::*;
 use coin;
 use fee::FeeAlgorithm;
-use tx::{TxAux, TxInWitness, TxoPointer};
+use std::collections::BTreeSet;
+use tx::{TxAux, TxId, TxInWitness, TxoPointer};
 
 impl ChainState {
     /// Verify a block in the context of the chain. Regardless of
     /// errors, the chain state is updated to reflect the changes
-    /// introduced by this block.
+    /// introduced by this block, except for the invalid transactions
+    /// which are not applied to the utxo state.
     /// FIXME: we may want to return all errors rather than just the first.
     pub fn verify_block(&mut self, block_hash: &HeaderHash, blk: &Block) -> Result<(), Error> {
         let mut res = Ok(());
@@ -41,11 +43,14 @@ impl ChainState {
             }
         };
 
-        // Update the utxos from the transactions.
+        // Update the utxos from the transactions, in order: a
+        // transaction may spend the outputs of a previous one.
         if let Block::MainBlock(blk) = blk {
             for txaux in blk.body.tx.iter() {
-                add_error(&mut res, self.verify_tx(txaux));
+                add_error(&mut res, self.apply_tx(txaux));
             }
+
+            self.apply_delegations(blk);
         }
 
         res
@@ -100,25 +105,19 @@ impl ChainState {
             Block::BoundaryBlock(_) => {}
 
             Block::MainBlock(blk) => {
-                let slot_id = blk.header.consensus.slot_id.slotid as usize;
-
-                match &self.slot_leaders {
-                    Some(ref slot_leaders) => {
-                        if slot_id >= slot_leaders.len() {
-                            return Err(Error::NonExistentSlot);
-                        }
-
-                        let slot_leader = &slot_leaders[slot_id];
-                        // Note: the block signature was already checked in
-                        // verify_block, so here we only check the leader key
-                        // against the genesis block.
-                        if slot_leader
-                            != &address::StakeholderId::new(&blk.header.consensus.leader_key)
-                        {
-                            return Err(Error::WrongSlotLeader);
-                        }
-                    }
-                    None => {}
+                if !blk
+                    .header
+                    .consensus
+                    .slot_id
+                    .is_valid(&self.epoch_parameters)
+                {
+                    return Err(Error::NonExistentSlot);
+                }
+                // Note: the block signature was already checked in
+                // verify_block, so here we only check the leader key
+                // against the boundary block.
+                if let Some(ref slot_leaders) = self.slot_leaders {
+                    fts::verify_slot_leader(slot_leaders, &blk.header)?;
                 }
             }
         };
@@ -126,81 +125,107 @@ impl ChainState {
         Ok(())
     }
 
-    /// Verify that a transaction only spends unspent transaction
-    /// outputs (utxos), and update the utxo state.
-    fn verify_tx(&mut self, txaux: &TxAux) -> Result<(), Error> {
+    /// Verify a transaction against the utxo state and, if it is
+    /// valid, apply it: its inputs are spent, its outputs are added
+    /// to the utxo state and its fee is accounted for.
+    ///
+    /// An invalid transaction leaves the utxo state untouched, the
+    /// first violation of the ledger rules is returned.
+    fn apply_tx(&mut self, txaux: &TxAux) -> Result<(), Error> {
         self.nr_transactions += 1;
 
-        let mut res = Ok(());
         let tx = &txaux.tx;
         let id = tx.id();
+        let fee = self
+            .verify_tx(&id, txaux)
+            .map_err(|err| Error::InvalidTransaction(id, Box::new(err)))?;
+        // The fees are paid from existing coins, so their sum cannot
+        // exceed the total supply, unless the utxo state is corrupt.
+        let fees = (self.fees + fee)
+            .map_err(|_| Error::InvalidTransaction(id, Box::new(Error::FeesTooBig)))?;
+
+        for txin in tx.inputs.iter() {
+            self.utxos.remove(txin);
+            self.spent_txos += 1;
+        }
+        for (index, output) in tx.outputs.iter().enumerate() {
+            self.utxos
+                .insert(TxoPointer::new(id, index as u32), output.clone());
+        }
+        self.fees = fees;
 
-        // Look up the utxos corresponding to the inputs and remove
-        // them from the utxo map to prevent double spending. Also
-        // check that the utxo address matches the witness
-        // (i.e. that the witness is actually authorized to spend
-        // this utxo).
-        // Note: inputs/witnesses size mismatches are detected in
-        // verify::verify_block().
+        Ok(())
+    }
+
+    /// Check a transaction against the ledger rules, without modifying
+    /// the utxo state:
+    ///
+    /// * every input is an unspent transaction output, spent only once;
+    /// * the witness of every input is authorized to spend it;
+    /// * the outputs do not already exist;
+    /// * the inputs cover the outputs and the minimal fee.
+    ///
+    /// Returns the fee paid by the transaction.
+    /// Note: inputs/witnesses size mismatches are detected in
+    /// verify::verify_block().
+    fn verify_tx(&self, id: &TxId, txaux: &TxAux) -> Result<coin::Coin, Error> {
+        let tx = &txaux.tx;
+
+        let mut spent = BTreeSet::new();
         let mut input_amount = coin::Coin::zero();
         let mut nr_redeems = 0;
         for (txin, in_witness) in tx.inputs.iter().zip(txaux.witness.iter()) {
-            match self.utxos.remove(&txin) {
-                None => {
-                    add_error(&mut res, Err(Error::MissingUtxo));
-                }
-                Some(txout) => {
-                    self.spent_txos += 1;
-
-                    let witness_address = match in_witness {
-                        TxInWitness::PkWitness(pubkey, _) => address::ExtendedAddr::new(
-                            address::AddrType::ATPubKey,
-                            address::SpendingData::PubKeyASD(*pubkey),
-                            txout.address.attributes.clone(),
-                        ),
-
-                        TxInWitness::ScriptWitness(_, _) => {
-                            panic!("script witnesses are not implemented")
-                        }
-
-                        TxInWitness::RedeemWitness(pubkey, _) => {
-                            nr_redeems += 1;
-
-                            address::ExtendedAddr::new(
-                                address::AddrType::ATRedeem,
-                                address::SpendingData::RedeemASD(*pubkey),
-                                txout.address.attributes.clone(),
-                            )
-                        }
-                    };
-
-                    if witness_address != txout.address {
-                        add_error(&mut res, Err(Error::AddressMismatch));
-                    }
+            if !spent.insert(txin) {
+                return Err(Error::DuplicateInputs);
+            }
 
-                    match input_amount + txout.value {
-                        Ok(x) => {
-                            input_amount = x;
-                        }
-                        Err(coin::Error::OutOfBound(_)) => {
-                            add_error(&mut res, Err(Error::InputsTooBig))
-                        }
-                        Err(err) => unreachable!("{}", err),
-                    }
+            let txout = match self.utxos.get(txin) {
+                None => return Err(Error::MissingUtxo(txin.clone())),
+                Some(txout) => txout,
+            };
+
+            // Check that the utxo address matches the witness
+            // (i.e. that the witness is actually authorized to spend
+            // this utxo).
+            let witness_address = match in_witness {
+                TxInWitness::PkWitness(pubkey, _) => address::ExtendedAddr::new(
+                    address::AddrType::ATPubKey,
+                    address::SpendingData::PubKeyASD(*pubkey),
+                    txout.address.attributes.clone(),
+                ),
+
+                TxInWitness::ScriptWitness(validator, _) => address::ExtendedAddr::new(
+                    address::AddrType::ATScript,
+                    address::SpendingData::ScriptASD(validator.clone()),
+                    txout.address.attributes.clone(),
+                ),
+
+                TxInWitness::RedeemWitness(pubkey, _) => {
+                    nr_redeems += 1;
+
+                    address::ExtendedAddr::new(
+                        address::AddrType::ATRedeem,
+                        address::SpendingData::RedeemASD(*pubkey),
+                        txout.address.attributes.clone(),
+                    )
                 }
+            };
+
+            if witness_address != txout.address {
+                return Err(Error::AddressMismatch(txin.clone()));
             }
+
+            input_amount = (input_amount + txout.value).map_err(|_| Error::InputsTooBig)?;
         }
 
-        // Calculate the output amount.
+        // Calculate the output amount, and check the outputs are new.
         let mut output_amount = coin::Coin::zero();
-        for output in &tx.outputs {
-            match output_amount + output.value {
-                Ok(x) => {
-                    output_amount = x;
-                }
-                Err(coin::Error::OutOfBound(_)) => add_error(&mut res, Err(Error::OutputsTooBig)),
-                Err(err) => unreachable!("{}", err),
+        for (index, output) in tx.outputs.iter().enumerate() {
+            let ptr = TxoPointer::new(*id, index as u32);
+            if self.utxos.contains_key(&ptr) {
+                return Err(Error::DuplicateTxo(ptr));
             }
+            output_amount = (output_amount + output.value).map_err(|_| Error::OutputsTooBig)?;
         }
 
         // Calculate the minimum fee. The fee is 0 if all inputs are
@@ -208,47 +233,24 @@ impl ChainState {
         let min_fee = if nr_redeems == tx.inputs.len() {
             coin::Coin::zero()
         } else {
-            match self.fee_policy.calculate_for_txaux(&txaux) {
-                Ok(fee) => fee.to_coin(),
-                Err(err) => {
-                    add_error(&mut res, Err(Error::FeeError(err)));
-                    coin::Coin::zero()
-                }
-            }
-        };
-
-        let output_plus_fee = match output_amount + min_fee {
-            Ok(x) => x,
-            Err(coin::Error::OutOfBound(_)) => {
-                add_error(&mut res, Err(Error::OutputsTooBig));
-                output_amount
-            }
-            Err(err) => unreachable!("{}", err),
+            self.fee_policy
+                .calculate_for_txaux(txaux)
+                .map_err(Error::FeeError)?
+                .to_coin()
         };
 
         // Check that total outputs + minimal fee <= total inputs.
+        let output_plus_fee = (output_amount + min_fee).map_err(|_| Error::OutputsTooBig)?;
         if output_plus_fee > input_amount {
-            add_error(&mut res, Err(Error::OutputsExceedInputs));
-        }
-
-        // Add the outputs to the utxo state.
-        for (index, output) in tx.outputs.iter().enumerate() {
-            if self
-                .utxos
-                .insert(
-                    TxoPointer {
-                        id,
-                        index: index as u32,
-                    },
-                    output.clone(),
-                )
-                .is_some()
-            {
-                add_error(&mut res, Err(Error::DuplicateTxo));
-            }
+            return Err(Error::OutputsExceedInputs {
+                inputs: input_amount,
+                outputs: output_amount,
+                fee: min_fee,
+            });
         }
 
-        res
+        // Whatever the outputs do not spend goes to the fee.
+        Ok((input_amount - output_amount).unwrap())
     }
 }
 
@@ -259,3 +261,133 @@ fn add_error(res: &mut Result<(), Error>, err: Result<(), Error>) {
         *res = err;
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use config::EpochParameters;
+    use fee::LinearFee;
+    use hdwallet::{Signature, SIGNATURE_SIZE};
+    use std::collections::BTreeMap;
+    use test_utils::{mk_address, mk_xpub};
+    use tx::{Tx, TxOut, TxWitness};
+
+    fn mk_chain_state(utxos: Utxos) -> ChainState {
+        ChainState {
+            protocol_magic: 0.into(),
+            fee_policy: LinearFee::default(),
+            epoch_parameters: EpochParameters::default(),
+            last_block: HeaderHash::new(&[0]),
+            last_date: None,
+            last_boundary_block: None,
+            slot_leaders: None,
+            utxos,
+            delegations: BTreeMap::new(),
+            chain_length: 0,
+            nr_transactions: 0,
+            spent_txos: 0,
+            fees: coin::Coin::zero(),
+        }
+    }
+
+    /// a transaction spending `input`, owned by the key `seed`
+    fn mk_txaux(input: &TxoPointer, seed: u8, outputs: Vec<TxOut>) -> TxAux {
+        let sig = Signature::from_bytes([0; SIGNATURE_SIZE]);
+        TxAux::new(
+            Tx::new_with(vec![input.clone()], outputs),
+            TxWitness::from(vec![TxInWitness::PkWitness(mk_xpub(seed), sig)]),
+        )
+    }
+
+    fn violation(res: Result<(), Error>) -> Error {
+        match res {
+            Err(Error::InvalidTransaction(_, err)) => *err,
+            res => panic!("expected an invalid transaction, got {:?}", res),
+        }
+    }
+
+    #[test]
+    fn apply_tx_and_double_spend() {
+        let input = TxoPointer::new(TxId::new(&[1]), 0);
+        let mut utxos = Utxos::new();
+        utxos.insert(
+            input.clone(),
+            TxOut::new(mk_address(1), coin::Coin::new(10_000000).unwrap()),
+        );
+        let mut chain_state = mk_chain_state(utxos);
+
+        let output = TxOut::new(mk_address(2), coin::Coin::new(9_000000).unwrap());
+        let txaux = mk_txaux(&input, 1, vec![output.clone()]);
+        chain_state.apply_tx(&txaux).unwrap();
+
+        let id = txaux.tx.id();
+        assert!(!chain_state.utxos.contains_key(&input));
+        assert_eq!(
+            chain_state.utxos.get(&TxoPointer::new(id, 0)),
+            Some(&output)
+        );
+        assert_eq!(chain_state.fees, coin::Coin::new(1_000000).unwrap());
+
+        let before = chain_state.clone();
+        let spend_again = mk_txaux(&input, 1, vec![TxOut::new(mk_address(3), output.value)]);
+        match violation(chain_state.apply_tx(&spend_again)) {
+            Error::MissingUtxo(ref ptr) if ptr == &input => {}
+            err => panic!("unexpected error {:?}", err),
+        }
+        assert_eq!(chain_state.utxos, before.utxos);
+        assert_eq!(chain_state.fees, before.fees);
+    }
+
+    #[test]
+    fn invalid_tx_is_not_applied() {
+        let input = TxoPointer::new(TxId::new(&[1]), 0);
+        let mut utxos = Utxos::new();
+        utxos.insert(
+            input.clone(),
+            TxOut::new(mk_address(1), coin::Coin::new(1_000000).unwrap()),
+        );
+        let mut chain_state = mk_chain_state(utxos.clone());
+
+        // value not conserved
+        let output = TxOut::new(mk_address(2), coin::Coin::new(1_000000).unwrap());
+        match violation(chain_state.apply_tx(&mk_txaux(&input, 1, vec![output.clone()]))) {
+            Error::OutputsExceedInputs {
+                inputs, outputs, ..
+            } => {
+                assert_eq!(inputs, coin::Coin::new(1_000000).unwrap());
+                assert_eq!(outputs, output.value);
+            }
+            err => panic!("unexpected error {:?}", err),
+        }
+
+        // witness of another key
+        let output = TxOut::new(mk_address(2), coin::Coin::new(1000).unwrap());
+        match violation(chain_state.apply_tx(&mk_txaux(&input, 2, vec![output]))) {
+            Error::AddressMismatch(ref ptr) if ptr == &input => {}
+            err => panic!("unexpected error {:?}", err),
+        }
+
+        assert_eq!(chain_state.utxos, utxos);
+        assert_eq!(chain_state.fees, coin::Coin::zero());
+    }
+
+    #[test]
+    fn fees_overflow() {
+        let input = TxoPointer::new(TxId::new(&[1]), 0);
+        let mut utxos = Utxos::new();
+        utxos.insert(
+            input.clone(),
+            TxOut::new(mk_address(1), coin::Coin::new(10_000000).unwrap()),
+        );
+        let mut chain_state = mk_chain_state(utxos.clone());
+        chain_state.fees = coin::Coin::new(coin::MAX_COIN).unwrap();
+
+        let output = TxOut::new(mk_address(2), coin::Coin::new(9_000000).unwrap());
+        match violation(chain_state.apply_tx(&mk_txaux(&input, 1, vec![output]))) {
+            Error::FeesTooBig => {}
+            err => panic!("unexpected error {:?}", err),
+        }
+        assert_eq!(chain_state.utxos, utxos);
+        assert_eq!(chain_state.fees, coin::Coin::new(coin::MAX_COIN).unwrap());
+    }
+}
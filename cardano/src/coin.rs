@@ -6,7 +6,7 @@
 
 use cbor_event::{self, de::Deserializer, se::Serializer};
 use std::cmp::Ordering;
-use std::{fmt, io::BufRead, ops, result};
+use std::{fmt, io::BufRead, iter, ops, result};
 
 /// maximum value of a Lovelace.
 pub const MAX_COIN: u64 = 45_000_000_000__000_000;
@@ -43,6 +43,7 @@ impl ::std::error::Error for Error {}
 pub type Result<T> = result::Result<T, Error>;
 
 /// A differential value between 2 coins 'a' and 'b', similar to 'a - b'
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
 pub enum CoinDiff {
     /// First value is greater than second value by n coins where n is not 0. // a == b + n
     Positive(Coin),
@@ -112,6 +113,50 @@ impl Coin {
             Ordering::Less => CoinDiff::Negative(Coin(against.0 - self.0)),
         }
     }
+
+    /// add the 2 coins, failing with `Error::OutOfBound` if the sum is
+    /// greater than `MAX_COIN` (same as `self + other`).
+    pub fn checked_add(self, other: Self) -> Result<Self> {
+        Coin::new(self.0 + other.0)
+    }
+
+    /// subtract `other`, failing with `Error::Negative` if it is greater
+    /// than `self` (same as `self - other`).
+    pub fn checked_sub(self, other: Self) -> Result<Self> {
+        if other.0 > self.0 {
+            Err(Error::Negative)
+        } else {
+            Ok(Coin(self.0 - other.0))
+        }
+    }
+
+    /// add the 2 coins, the sum is capped to `MAX_COIN`.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use cardano::coin::{Coin, MAX_COIN};
+    ///
+    /// let max = Coin::new(MAX_COIN).unwrap();
+    /// assert_eq!(max.saturating_add(Coin::unit()), max);
+    /// ```
+    pub fn saturating_add(self, other: Self) -> Self {
+        Coin(::std::cmp::min(self.0 + other.0, MAX_COIN))
+    }
+
+    /// subtract `other`, the difference is `0` if `other` is greater
+    /// than `self`.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use cardano::coin::Coin;
+    ///
+    /// assert_eq!(Coin::zero().saturating_sub(Coin::unit()), Coin::zero());
+    /// ```
+    pub fn saturating_sub(self, other: Self) -> Self {
+        Coin(self.0.saturating_sub(other.0))
+    }
 }
 impl fmt::Display for Coin {
     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
@@ -145,33 +190,25 @@ impl cbor_event::de::Deserialize for Coin {
 impl ops::Add for Coin {
     type Output = Result<Coin>;
     fn add(self, other: Coin) -> Self::Output {
-        Coin::new(self.0 + other.0)
+        self.checked_add(other)
     }
 }
 impl<'a> ops::Add<&'a Coin> for Coin {
     type Output = Result<Coin>;
     fn add(self, other: &'a Coin) -> Self::Output {
-        Coin::new(self.0 + other.0)
+        self.checked_add(*other)
     }
 }
 impl ops::Sub for Coin {
     type Output = Result<Coin>;
     fn sub(self, other: Coin) -> Self::Output {
-        if other.0 > self.0 {
-            Err(Error::Negative)
-        } else {
-            Ok(Coin(self.0 - other.0))
-        }
+        self.checked_sub(other)
     }
 }
 impl<'a> ops::Sub<&'a Coin> for Coin {
     type Output = Result<Coin>;
     fn sub(self, other: &'a Coin) -> Self::Output {
-        if other.0 > self.0 {
-            Err(Error::Negative)
-        } else {
-            Ok(Coin(self.0 - other.0))
-        }
+        self.checked_sub(*other)
     }
 }
 // this instance is necessary to chain the substraction operations
@@ -200,11 +237,87 @@ impl From<u32> for Coin {
     }
 }
 
+/// Running sum of coins.
+///
+/// The first error (the sum going over `MAX_COIN`) is kept: adding more
+/// coins to an invalid sum does not make it valid again.
+///
+/// # Example
+///
+/// ```
+/// use cardano::coin::{Coin, CoinSum, MAX_COIN};
+///
+/// let mut sum = CoinSum::new();
+/// sum.add(Coin::unit());
+/// assert_eq!(sum.total(), Ok(Coin::unit()));
+///
+/// sum.add(Coin::new(MAX_COIN).unwrap());
+/// assert!(sum.total().is_err());
+/// ```
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+pub struct CoinSum(Result<Coin>);
+impl CoinSum {
+    pub fn new() -> Self {
+        CoinSum(Ok(Coin::zero()))
+    }
+
+    pub fn add(&mut self, coin: Coin) -> &mut Self {
+        self.0 = self.0.and_then(|total| total.checked_add(coin));
+        self
+    }
+
+    pub fn total(&self) -> Result<Coin> {
+        self.0
+    }
+}
+impl Default for CoinSum {
+    fn default() -> Self {
+        CoinSum::new()
+    }
+}
+impl iter::Extend<Coin> for CoinSum {
+    fn extend<I: IntoIterator<Item = Coin>>(&mut self, iter: I) {
+        for coin in iter {
+            self.add(coin);
+            if self.0.is_err() {
+                break;
+            }
+        }
+    }
+}
+impl iter::FromIterator<Coin> for CoinSum {
+    fn from_iter<I: IntoIterator<Item = Coin>>(iter: I) -> Self {
+        let mut sum = CoinSum::new();
+        sum.extend(iter);
+        sum
+    }
+}
+
+/// Extension of the iterators of `Coin`, to sum them without panicking
+/// on overflow.
+///
+/// # Example
+///
+/// ```
+/// use cardano::coin::{Coin, SumCoins};
+///
+/// let coins = vec![Coin::unit(), Coin::unit()];
+/// assert_eq!(coins.into_iter().sum_coins(), Coin::new(2));
+/// ```
+pub trait SumCoins: Iterator<Item = Coin> + Sized {
+    /// the sum of the coins, or `Error::OutOfBound` if it is greater than
+    /// `MAX_COIN`.
+    fn sum_coins(self) -> Result<Coin> {
+        self.collect::<CoinSum>().total()
+    }
+}
+impl<I: Iterator<Item = Coin>> SumCoins for I {}
+
 pub fn sum_coins<I>(coin_iter: I) -> Result<Coin>
 where
     I: Iterator<Item = Coin>,
 {
-    coin_iter.fold(Coin::new(0), |acc, ref c| acc.and_then(|v| v + *c))
+    coin_iter.sum_coins()
 }
 
 #[cfg(test)]
@@ -212,6 +325,33 @@ mod test {
     use super::super::util::arbitrary::Wrapper;
     use super::*;
 
+    #[test]
+    fn checked_and_saturating_operations() {
+        let max = Coin::new(MAX_COIN).unwrap();
+        let one = Coin::unit();
+
+        assert_eq!(max.checked_add(one), Err(Error::OutOfBound(MAX_COIN + 1)));
+        assert_eq!(Coin::zero().checked_sub(one), Err(Error::Negative));
+        assert_eq!(max.saturating_add(max), max);
+        assert_eq!(one.saturating_sub(max), Coin::zero());
+        assert_eq!(max.checked_sub(one), Coin::new(MAX_COIN - 1));
+        assert_eq!(
+            one.differential(max),
+            CoinDiff::Negative(Coin(MAX_COIN - 1))
+        );
+    }
+
+    #[test]
+    fn sum_coins_overflow() {
+        let max = Coin::new(MAX_COIN).unwrap();
+        assert_eq!(vec![max].into_iter().sum_coins(), Ok(max));
+        assert_eq!(
+            vec![max, Coin::unit(), max].into_iter().sum_coins(),
+            Err(Error::OutOfBound(MAX_COIN + 1))
+        );
+        assert_eq!(Vec::<Coin>::new().into_iter().sum_coins(), Ok(Coin::zero()));
+    }
+
     quickcheck! {
         // test a given u32 is always a valid value for a `Coin`
         fn coin_from_u32_always_valid(v: u32) -> bool {
@@ -124,6 +124,44 @@ impl Default for Config {
     }
 }
 
+/// epoch stability depth (`k`) of the mainnet
+pub const MAINNET_EPOCH_STABILITY_DEPTH: usize = 2160;
+
+/// The length of the epochs of a network.
+///
+/// An epoch is `10 * k` slots long, `k` being the epoch stability depth
+/// of the genesis data (the security parameter of the protocol).
+///
+/// # Default
+///
+/// The default value is set to the mainnet
+///
+/// ```
+/// use cardano::config::{EpochParameters};
+///
+/// assert_eq!(EpochParameters::default().slots_per_epoch, 21600);
+/// ```
+///
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+#[cfg_attr(feature = "generic-serialization", derive(Serialize, Deserialize))]
+pub struct EpochParameters {
+    pub epoch_stability_depth: usize, // a.k.a. 'k'
+    pub slots_per_epoch: usize,
+}
+impl EpochParameters {
+    pub fn new(epoch_stability_depth: usize) -> Self {
+        EpochParameters {
+            epoch_stability_depth,
+            slots_per_epoch: epoch_stability_depth * 10,
+        }
+    }
+}
+impl Default for EpochParameters {
+    fn default() -> Self {
+        EpochParameters::new(MAINNET_EPOCH_STABILITY_DEPTH)
+    }
+}
+
 /// A subset of the genesis data. The genesis data is a JSON file
 /// whose canonicalized form has the hash 'genesis_prev', which is the
 /// parent of the genesis block of epoch 0. (Note that "genesis data"
@@ -143,6 +181,12 @@ pub struct GenesisData {
     pub boot_stakeholders: BTreeMap<address::StakeholderId, BootStakeholder>,
 }
 
+impl GenesisData {
+    pub fn epoch_parameters(&self) -> EpochParameters {
+        EpochParameters::new(self.epoch_stability_depth)
+    }
+}
+
 #[derive(Debug, Clone, PartialEq, Eq)]
 pub struct BootStakeholder {
     pub weight: BootStakeWeight,
@@ -1,13 +1,18 @@
 //! Fee calculation and fee algorithms
 
+use address::{AddrType, Attributes, ExtendedAddr, SpendingData, StakeDistribution};
 use cbor_event;
 use coin;
 use coin::Coin;
+use config::NetworkMagic;
+use hdpayload::{HDAddressPayload, MAX_PAYLOAD_SIZE};
+use hdwallet::{XPub, XPUB_SIZE};
 use std::{
+    collections::BTreeMap,
     ops::{Add, Mul},
     result,
 };
-use tx::{txaux_serialize_size, Tx, TxAux, TxInWitness};
+use tx::{txaux_serialize_size, Tx, TxAux, TxId, TxInWitness, TxOut, TxoPointer};
 
 /// A fee value that represent either a fee to pay, or a fee paid.
 #[derive(PartialEq, Eq, PartialOrd, Ord, Debug, Clone, Copy)]
@@ -166,6 +171,40 @@ impl Default for LinearFee {
     }
 }
 
+/// Upper bound of the fee of a transaction with `n_inputs` inputs and
+/// `n_outputs` outputs (plus a change output if `has_change`), to give an
+/// estimate before the inputs are selected.
+///
+/// Every component is sized with its largest encoding: addresses with a
+/// derivation path, a stake distribution and a network magic, maximum
+/// values and indices, and public key witnesses.
+pub fn estimate<F: FeeAlgorithm>(
+    n_inputs: usize,
+    n_outputs: usize,
+    has_change: bool,
+    algorithm: &F,
+) -> Result<Fee> {
+    let xpub = XPub::from_bytes([0; XPUB_SIZE]);
+    let attributes = Attributes {
+        derivation_path: Some(HDAddressPayload::from_vec(vec![0; MAX_PAYLOAD_SIZE])),
+        stake_distribution: StakeDistribution::new_single_key(&xpub),
+        network_magic: NetworkMagic::Magic(u32::max_value()),
+        remains: BTreeMap::new(),
+    };
+    let address = ExtendedAddr::new(
+        AddrType::ATPubKey,
+        SpendingData::PubKeyASD(xpub),
+        attributes,
+    );
+    let output = TxOut::new(address, Coin::new(coin::MAX_COIN)?);
+    let input = TxoPointer::new(TxId::new(&[]), u32::max_value());
+
+    let n_outputs = if has_change { n_outputs + 1 } else { n_outputs };
+    let tx = Tx::new_with(vec![input; n_inputs], vec![output; n_outputs]);
+    let witnesses = vec![TxInWitness::fake(); n_inputs];
+    algorithm.calculate_for_txaux_component(&tx, &witnesses)
+}
+
 #[cfg(test)]
 mod test {
     use super::*;
@@ -186,6 +225,28 @@ mod test {
         assert_eq!((v / 1000000) as u64, n.to_integral_trunc());
     }
 
+    #[test]
+    fn estimate_is_an_upper_bound() {
+        use txbuild::TxBuilder;
+
+        let alg = LinearFee::default();
+        let xpub = XPub::from_bytes([1; XPUB_SIZE]);
+        let address = ExtendedAddr::new_simple(xpub, NetworkMagic::NoMagic);
+        let mut builder = TxBuilder::new();
+        for index in 0..3 {
+            let ptr = TxoPointer::new(TxId::new(&[1]), index);
+            builder.add_input(&ptr, Coin::new(1_000000).unwrap());
+        }
+        builder.add_output_value(&TxOut::new(address.clone(), Coin::new(1_500000).unwrap()));
+        builder.add_output_value(&TxOut::new(address, Coin::new(1_000000).unwrap()));
+        let fee = builder.calculate_fee(&alg).unwrap();
+
+        let upper_bound = estimate(3, 1, true, &alg).unwrap();
+        assert!(upper_bound >= fee, "{:?} < {:?}", upper_bound, fee);
+        assert!(upper_bound < estimate(4, 1, true, &alg).unwrap());
+        assert!(upper_bound > estimate(3, 1, false, &alg).unwrap());
+    }
+
     #[test]
     fn check_fee_add() {
         test_milli_add_eq(10124128_192, 802_504);
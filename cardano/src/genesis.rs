@@ -0,0 +1,380 @@
+//! Auditing of the genesis data.
+//!
+//! A custom network is bootstrapped from a hand crafted genesis file, and
+//! mistakes in it (a balance entered twice, a delegation certificate made
+//! for another protocol magic...) only show up once the chain is running.
+//! `audit` cross-checks the `GenesisData` and lists all the issues found.
+
+use address::{Addr, StakeholderId};
+use block::{sign::ProxySecretKey, HeaderHash};
+use coin::{self, Coin, CoinSum};
+use config::GenesisData;
+use std::collections::{BTreeMap, HashMap};
+use std::fmt;
+use tx::redeem_pubkey_to_txid;
+
+/// An inconsistency found in the genesis data
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum AuditIssue {
+    /// the sum of the initial balances is over `coin::MAX_COIN`
+    TotalOverflow,
+    /// the sum of the initial balances is not the expected supply
+    SupplyMismatch { expected: Coin, actual: Coin },
+    /// the address has a balance in both the AVVM distribution and the
+    /// non-AVVM balances, the two initial utxos would collide
+    DuplicateAddress(Addr),
+    /// an initial balance of zero
+    ZeroBalance(Addr),
+    /// the boot stakeholder's id is not the hash of its issuer key
+    StakeholderIdMismatch(StakeholderId),
+    /// the boot stakeholder's delegation certificate is not signed by its
+    /// issuer key for the genesis' protocol magic
+    InvalidDelegationCertificate(StakeholderId),
+    /// the two boot stakeholders delegate to the same key
+    DuplicateDelegateKey(StakeholderId, StakeholderId),
+}
+impl fmt::Display for AuditIssue {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            AuditIssue::TotalOverflow => write!(
+                f,
+                "the sum of the initial balances is over {}",
+                coin::MAX_COIN
+            ),
+            AuditIssue::SupplyMismatch { expected, actual } => write!(
+                f,
+                "the initial balances sum to {}, expected {}",
+                actual, expected
+            ),
+            AuditIssue::DuplicateAddress(addr) => write!(
+                f,
+                "address {} is in both the AVVM distribution and the non-AVVM balances",
+                addr
+            ),
+            AuditIssue::ZeroBalance(addr) => write!(f, "address {} has no initial balance", addr),
+            AuditIssue::StakeholderIdMismatch(id) => write!(
+                f,
+                "boot stakeholder {} is not the hash of its issuer key",
+                id
+            ),
+            AuditIssue::InvalidDelegationCertificate(id) => write!(
+                f,
+                "boot stakeholder {} has an invalid delegation certificate",
+                id
+            ),
+            AuditIssue::DuplicateDelegateKey(id1, id2) => write!(
+                f,
+                "boot stakeholders {} and {} delegate to the same key",
+                id1, id2
+            ),
+        }
+    }
+}
+
+/// The result of `audit`
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct AuditReport {
+    /// the hash of the genesis data (see `GenesisData::genesis_prev`)
+    pub genesis_hash: HeaderHash,
+    /// sum of the AVVM distribution, `None` if over `coin::MAX_COIN`
+    pub avvm_total: Option<Coin>,
+    /// sum of the non-AVVM balances, `None` if over `coin::MAX_COIN`
+    pub non_avvm_total: Option<Coin>,
+    /// the initial supply, `None` if over `coin::MAX_COIN`
+    pub total: Option<Coin>,
+    pub nb_boot_stakeholders: usize,
+    pub issues: Vec<AuditIssue>,
+}
+impl AuditReport {
+    pub fn is_ok(&self) -> bool {
+        self.issues.is_empty()
+    }
+}
+impl fmt::Display for AuditReport {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let coin_or_overflow = |c: &Option<Coin>| match c {
+            Some(c) => format!("{}", c),
+            None => "overflow".to_owned(),
+        };
+        writeln!(f, "genesis hash: {}", self.genesis_hash)?;
+        writeln!(f, "AVVM total: {}", coin_or_overflow(&self.avvm_total))?;
+        writeln!(
+            f,
+            "non-AVVM total: {}",
+            coin_or_overflow(&self.non_avvm_total)
+        )?;
+        writeln!(f, "initial supply: {}", coin_or_overflow(&self.total))?;
+        writeln!(f, "boot stakeholders: {}", self.nb_boot_stakeholders)?;
+        if self.issues.is_empty() {
+            writeln!(f, "no issue found")
+        } else {
+            writeln!(f, "{} issue(s) found:", self.issues.len())?;
+            for issue in &self.issues {
+                writeln!(f, "  * {}", issue)?;
+            }
+            Ok(())
+        }
+    }
+}
+
+/// Cross-check the genesis data: the totals of the initial balances, the
+/// addresses having a balance twice and the boot stakeholders' delegation
+/// certificates.
+pub fn audit(genesis_data: &GenesisData) -> AuditReport {
+    let mut issues = Vec::new();
+
+    let mut avvm_addresses = BTreeMap::new();
+    for (pubkey, value) in &genesis_data.avvm_distr {
+        let (_, address) = redeem_pubkey_to_txid(pubkey, genesis_data.protocol_magic);
+        avvm_addresses.insert(Addr::from(address), *value);
+    }
+    for (address, value) in avvm_addresses
+        .iter()
+        .chain(genesis_data.non_avvm_balances.iter())
+    {
+        if *value == Coin::zero() {
+            issues.push(AuditIssue::ZeroBalance(address.clone()));
+        }
+    }
+    for address in genesis_data.non_avvm_balances.keys() {
+        if avvm_addresses.contains_key(address) {
+            issues.push(AuditIssue::DuplicateAddress(address.clone()));
+        }
+    }
+
+    let avvm_total = avvm_addresses.values().cloned().collect::<CoinSum>();
+    let non_avvm_total = genesis_data
+        .non_avvm_balances
+        .values()
+        .cloned()
+        .collect::<CoinSum>();
+    let total = avvm_total
+        .total()
+        .and_then(|avvm| non_avvm_total.total().and_then(|c| avvm.checked_add(c)))
+        .ok();
+    if total.is_none() {
+        issues.push(AuditIssue::TotalOverflow);
+    }
+
+    let mut delegates = HashMap::new();
+    for (id, stakeholder) in &genesis_data.boot_stakeholders {
+        if *id != StakeholderId::new(&stakeholder.issuer_pk) {
+            issues.push(AuditIssue::StakeholderIdMismatch(*id));
+        }
+        let psk = ProxySecretKey {
+            omega: 0,
+            issuer_pk: stakeholder.issuer_pk,
+            delegate_pk: stakeholder.delegate_pk,
+            cert: stakeholder.cert.clone(),
+        };
+        if !psk.verify(genesis_data.protocol_magic) {
+            issues.push(AuditIssue::InvalidDelegationCertificate(*id));
+        }
+        if let Some(other) = delegates.insert(stakeholder.delegate_pk, *id) {
+            issues.push(AuditIssue::DuplicateDelegateKey(other, *id));
+        }
+    }
+
+    AuditReport {
+        genesis_hash: genesis_data.genesis_prev.clone(),
+        avvm_total: avvm_total.total().ok(),
+        non_avvm_total: non_avvm_total.total().ok(),
+        total,
+        nb_boot_stakeholders: genesis_data.boot_stakeholders.len(),
+        issues,
+    }
+}
+
+/// Same as `audit`, also checking that the initial balances sum to
+/// `expected_supply`.
+pub fn audit_supply(genesis_data: &GenesisData, expected_supply: Coin) -> AuditReport {
+    let mut report = audit(genesis_data);
+    if let Some(actual) = report.total {
+        if actual != expected_supply {
+            report.issues.push(AuditIssue::SupplyMismatch {
+                expected: expected_supply,
+                actual,
+            });
+        }
+    }
+    report
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use address::ExtendedAddr;
+    use config::{BootStakeholder, ProtocolMagic};
+    use fee::LinearFee;
+    use hdwallet::{XPrv, XPub, XPRV_SIZE};
+    use redeem;
+    use std::str::FromStr;
+    use std::time::{Duration, SystemTime};
+
+    fn issuer_prv() -> XPrv {
+        XPrv::from_str("b8b054ec1b92dd4542db35e2f813f013a8d7ee9f53255b26f3ef3dafb74e11462545bd9c85aa0a6f6719a933eba16909c1a2fa0bbb58e9cd98bf9ddbb79f7d50fcfc22db8155f8d6ca0e3a975cb1b6aa5d6e7609b30c99877e469db06b5d5016").unwrap()
+    }
+
+    fn delegate_pk() -> XPub {
+        XPub::from_str("695b380fc72ae7d830d46f902a7c9d4057a4b9a7a0be235b87fdf51e698619e033aac8d93fd4cb82785973bb943f2047ddd1e664d4e185e7be634722e108389a").unwrap()
+    }
+
+    fn genesis_data() -> GenesisData {
+        let protocol_magic = ProtocolMagic::from(328429219);
+        let issuer_prv = issuer_prv();
+        let psk = ProxySecretKey::sign(&issuer_prv, delegate_pk(), 0, protocol_magic);
+
+        let mut boot_stakeholders = BTreeMap::new();
+        boot_stakeholders.insert(
+            StakeholderId::new(&psk.issuer_pk),
+            BootStakeholder {
+                weight: 1,
+                issuer_pk: psk.issuer_pk,
+                delegate_pk: psk.delegate_pk,
+                cert: psk.cert,
+            },
+        );
+
+        let mut avvm_distr = BTreeMap::new();
+        let redeem_key = redeem::PrivateKey::generate(&[1; 32]).unwrap().public();
+        avvm_distr.insert(redeem_key, Coin::new(1_000).unwrap());
+
+        let mut non_avvm_balances = BTreeMap::new();
+        let address = ExtendedAddr::new_simple(delegate_pk(), protocol_magic.into());
+        non_avvm_balances.insert(address.into(), Coin::new(2_000).unwrap());
+
+        GenesisData {
+            genesis_prev: HeaderHash::new(b"genesis"),
+            epoch_stability_depth: 2160,
+            start_time: SystemTime::UNIX_EPOCH,
+            slot_duration: Duration::from_secs(20),
+            protocol_magic,
+            fee_policy: LinearFee::default(),
+            avvm_distr,
+            non_avvm_balances,
+            boot_stakeholders,
+        }
+    }
+
+    #[test]
+    fn audit_valid_genesis() {
+        let genesis_data = genesis_data();
+        let report = audit_supply(&genesis_data, Coin::new(3_000).unwrap());
+        assert_eq!(report.issues, vec![]);
+        assert_eq!(report.genesis_hash, genesis_data.genesis_prev);
+        assert_eq!(report.avvm_total, Coin::new(1_000).ok());
+        assert_eq!(report.non_avvm_total, Coin::new(2_000).ok());
+        assert_eq!(report.total, Coin::new(3_000).ok());
+    }
+
+    #[test]
+    fn audit_supply_mismatch() {
+        let report = audit_supply(&genesis_data(), Coin::new(4_000).unwrap());
+        assert_eq!(
+            report.issues,
+            vec![AuditIssue::SupplyMismatch {
+                expected: Coin::new(4_000).unwrap(),
+                actual: Coin::new(3_000).unwrap(),
+            }]
+        );
+    }
+
+    #[test]
+    fn audit_duplicate_address() {
+        let mut genesis_data = genesis_data();
+        let (&redeem_key, _) = genesis_data.avvm_distr.iter().next().unwrap();
+        let (_, address) = redeem_pubkey_to_txid(&redeem_key, genesis_data.protocol_magic);
+        let address = Addr::from(address);
+        genesis_data
+            .non_avvm_balances
+            .insert(address.clone(), Coin::new(10).unwrap());
+        let report = audit(&genesis_data);
+        assert_eq!(report.issues, vec![AuditIssue::DuplicateAddress(address)]);
+    }
+
+    #[test]
+    fn audit_invalid_certificate() {
+        let mut genesis_data = genesis_data();
+        genesis_data.protocol_magic = ProtocolMagic::from(1);
+        let id = *genesis_data.boot_stakeholders.keys().next().unwrap();
+        let report = audit(&genesis_data);
+        assert!(report
+            .issues
+            .contains(&AuditIssue::InvalidDelegationCertificate(id)));
+    }
+
+    #[test]
+    fn audit_zero_balance() {
+        let mut genesis_data = genesis_data();
+        let address = genesis_data
+            .non_avvm_balances
+            .keys()
+            .next()
+            .unwrap()
+            .clone();
+        genesis_data
+            .non_avvm_balances
+            .insert(address.clone(), Coin::zero());
+        let report = audit(&genesis_data);
+        assert_eq!(report.issues, vec![AuditIssue::ZeroBalance(address)]);
+    }
+
+    #[test]
+    fn audit_stakeholder_id_mismatch() {
+        let mut genesis_data = genesis_data();
+        let id = *genesis_data.boot_stakeholders.keys().next().unwrap();
+        let stakeholder = genesis_data.boot_stakeholders.remove(&id).unwrap();
+        let wrong_id = StakeholderId::new(&delegate_pk());
+        genesis_data.boot_stakeholders.insert(wrong_id, stakeholder);
+        let report = audit(&genesis_data);
+        assert_eq!(
+            report.issues,
+            vec![AuditIssue::StakeholderIdMismatch(wrong_id)]
+        );
+    }
+
+    #[test]
+    fn audit_duplicate_delegate_key() {
+        let mut genesis_data = genesis_data();
+        let id = *genesis_data.boot_stakeholders.keys().next().unwrap();
+        let other_issuer = XPrv::normalize_bytes([2; XPRV_SIZE]);
+        let psk =
+            ProxySecretKey::sign(&other_issuer, delegate_pk(), 0, genesis_data.protocol_magic);
+        let other_id = StakeholderId::new(&psk.issuer_pk);
+        genesis_data.boot_stakeholders.insert(
+            other_id,
+            BootStakeholder {
+                weight: 1,
+                issuer_pk: psk.issuer_pk,
+                delegate_pk: psk.delegate_pk,
+                cert: psk.cert,
+            },
+        );
+        let report = audit(&genesis_data);
+        // reported for the second stakeholder met, in the order of the ids
+        let (first, second) = if id < other_id {
+            (id, other_id)
+        } else {
+            (other_id, id)
+        };
+        assert_eq!(
+            report.issues,
+            vec![AuditIssue::DuplicateDelegateKey(first, second)]
+        );
+    }
+
+    #[test]
+    fn audit_total_overflow() {
+        let mut genesis_data = genesis_data();
+        let address =
+            ExtendedAddr::new_simple(issuer_prv().public(), genesis_data.protocol_magic.into());
+        genesis_data
+            .non_avvm_balances
+            .insert(address.into(), Coin::new(coin::MAX_COIN).unwrap());
+        let report = audit(&genesis_data);
+        assert_eq!(report.issues, vec![AuditIssue::TotalOverflow]);
+        assert_eq!(report.avvm_total, Coin::new(1_000).ok());
+        assert_eq!(report.non_avvm_total, None);
+        assert_eq!(report.total, None);
+    }
+}
@@ -14,6 +14,7 @@ use cryptoxide::digest::Digest;
 use cryptoxide::sha3::Sha3;
 
 use cbor_event::{self, de::Deserializer, se::Serializer};
+use chain_core;
 use util::{hex, try_from_slice::TryFromSlice};
 
 #[cfg(feature = "generic-serialization")]
@@ -150,6 +151,20 @@ macro_rules! define_hash_object {
                 serializer.write_bytes(self.as_ref())
             }
         }
+        impl chain_core::property::Serialize for $hash_ty {
+            type Error = ::std::io::Error;
+            fn serialize<W: Write>(&self, mut writer: W) -> result::Result<(), Self::Error> {
+                writer.write_all(self.as_ref())
+            }
+        }
+        impl chain_core::property::Deserialize for $hash_ty {
+            type Error = ::std::io::Error;
+            fn deserialize<R: BufRead>(mut reader: R) -> result::Result<Self, Self::Error> {
+                let mut buffer = [0; Self::HASH_SIZE];
+                reader.read_exact(&mut buffer)?;
+                Ok(Self::from(buffer))
+            }
+        }
 
         #[cfg(feature = "generic-serialization")]
         impl serde::Serialize for $hash_ty {
@@ -107,8 +107,8 @@ impl Path {
         let mut raw = Deserializer::from(cursor);
         Ok(cbor_event::de::Deserialize::deserialize(&mut raw)?)
     }
-    fn cbor(&self) -> Vec<u8> {
-        cbor!(self).expect("Serialize the given Path in cbor")
+    fn cbor(&self) -> Result<Vec<u8>> {
+        Ok(cbor!(self)?)
     }
 }
 impl cbor_event::se::Serialize for Path {
@@ -195,12 +195,24 @@ impl HDKey {
     }
 
     pub fn encrypt_path(&self, derivation_path: &Path) -> HDAddressPayload {
-        let input = derivation_path.cbor();
+        let input = derivation_path
+            .cbor()
+            .expect("Serialize the given Path in cbor");
         let out = self.encrypt(&input);
 
         HDAddressPayload::from_vec(out)
     }
 
+    /// same as `encrypt_path`, but fails if the path is too long to fit
+    /// in a payload `decrypt_path` accepts (see `MAX_PAYLOAD_SIZE`).
+    pub fn try_encrypt_path(&self, derivation_path: &Path) -> Result<HDAddressPayload> {
+        let input = derivation_path.cbor()?;
+        if input.len() >= MAX_PAYLOAD_SIZE {
+            return Err(Error::PayloadIsTooLarge(input.len()));
+        }
+        Ok(HDAddressPayload::from_vec(self.encrypt(&input)))
+    }
+
     pub fn decrypt_path(&self, payload: &HDAddressPayload) -> Result<Path> {
         let out = self.decrypt(payload.as_ref())?;
         Path::from_cbor(&out)
@@ -321,7 +333,7 @@ mod tests {
     #[test]
     fn path_cbor_encoding() {
         let path = Path::new(vec![0, 1, 2]);
-        let cbor = path.cbor();
+        let cbor = path.cbor().unwrap();
         assert_eq!(path, Path::from_cbor(cbor.as_ref()).unwrap());
     }
 
@@ -353,7 +365,7 @@ mod tests {
     fn unit2() {
         let path = Path::new(vec![0, 1]);
         let expected = [0x9f, 0x00, 0x01, 0x0ff];
-        let cbor = path.cbor();
+        let cbor = path.cbor().unwrap();
         assert_eq!(&expected[..], &cbor[..])
     }
 
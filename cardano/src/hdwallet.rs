@@ -14,7 +14,7 @@ use cryptoxide::ed25519;
 use cryptoxide::ed25519::signature_extended;
 use cryptoxide::hmac::Hmac;
 use cryptoxide::mac::Mac;
-use cryptoxide::sha2::Sha512;
+use cryptoxide::sha2::{Sha256, Sha512};
 use cryptoxide::util::fixed_time_eq;
 
 use bip::bip39;
@@ -245,6 +245,38 @@ impl XPrv {
         Self::from_bytes(out)
     }
 
+    /// create the root private key the Ledger hardware wallet generates from
+    /// a BIP39 seed, so the same addresses can be derived in software (e.g.
+    /// for a watch-only wallet).
+    ///
+    /// The seed is hashed with the `"ed25519 seed"` key until the third
+    /// highest bit of the secret is cleared. The chain code is computed
+    /// separately. Ledger wallets derive their keys with `DerivationScheme::V2`.
+    pub fn generate_from_ledger_bip39(seed: &bip39::Seed) -> Self {
+        const LEDGER_SEED_KEY: &'static [u8] = b"ed25519 seed";
+        let mut out = [0u8; XPRV_SIZE];
+
+        let mut mac = Hmac::new(Sha256::new(), LEDGER_SEED_KEY);
+        mac.input(&[0x1]);
+        mac.input(seed.as_ref());
+        mac.raw_result(&mut out[64..96]);
+
+        let mut mac = Hmac::new(Sha512::new(), LEDGER_SEED_KEY);
+        mac.input(seed.as_ref());
+        mac.raw_result(&mut out[0..64]);
+        while (out[31] & 0b0010_0000) != 0 {
+            mac.reset();
+            mac.input(&out[0..64]);
+            mac.raw_result(&mut out[0..64]);
+        }
+
+        out[0] &= 0b1111_1000;
+        out[31] &= 0b0111_1111;
+        out[31] |= 0b0100_0000;
+
+        Self::from_bytes(out)
+    }
+
     /// takes the given raw bytes and perform some modifications to normalize
     /// it properly to a XPrv.
     ///
@@ -1118,6 +1150,28 @@ mod tests {
         seed_xprv_eq(&seed, &D1);
     }
 
+    #[test]
+    fn ledger_master_key() {
+        let vectors = [
+            (
+                "recall grace sport punch exhibit mad harbor stand obey short width stem awkward used stairs wool ugly trap season stove worth toward congress jaguar",
+                "a08cf85b564ecf3b947d8d4321fb96d70ee7bb760877e371899b14e2ccf88658104b884682b57efd97decbb318a45c05a527b9cc5c2f64f7352935a049ceea60680d52308194ccef2a18e6812b452a5815fbd7f5babc083856919aaf668fe7e4",
+            ),
+            // the first hash has the third highest bit set, and is hashed again
+            (
+                "correct cherry mammal bubble want mandate polar hazard crater better craft exotic choice fun tourist census gap lottery neglect address glow carry old business",
+                "587c6774357ecbf840d4db6404ff7af016dace0400769751ad2abfc77b9a3844cc71702520ef1a4d1b68b91187787a9b8faab0a9bb6b160de541b6ee62469901fc0beda0975fe4763beabd83b7051a5fd5cbce5b88e82c4bbaca265014e524bd",
+            ),
+        ];
+        for (words, expected) in vectors.iter() {
+            let mnemonics =
+                bip39::MnemonicString::new(&bip39::dictionary::ENGLISH, words.to_string()).unwrap();
+            let seed = bip39::Seed::from_mnemonic_string(&mnemonics, b"");
+            let xprv = XPrv::generate_from_ledger_bip39(&seed);
+            assert_eq!(hex::encode(xprv.as_ref()), *expected);
+        }
+    }
+
     fn derive_xprv_eq(parent_xprv: &XPrv, idx: DerivationIndex, expected_xprv: [u8; 96]) {
         let child_xprv = derive_private(parent_xprv, idx, DerivationScheme::V2);
         compare_xprv(child_xprv.as_ref(), &expected_xprv);
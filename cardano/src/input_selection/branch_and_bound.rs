@@ -0,0 +1,249 @@
+use super::*;
+use coin::CoinDiff;
+
+/// Maximum number of selections the search evaluates before giving up
+/// and using the fallback algorithm.
+const MAX_TRIES: usize = 10_000;
+
+/// Branch and bound input selection.
+///
+/// Search for a subset of the inputs matching exactly the outputs and the
+/// fee, so no change output is needed. A subset is also accepted if the
+/// left over is too small to pay for a change output (it then goes to the
+/// fee). This avoids creating new (and often small) UTxOs and reduces the
+/// fee paid.
+///
+/// If no such subset is found, the selection is delegated to a fallback
+/// algorithm, created on the inputs by `make_fallback` (e.g.
+/// `LargestFirst::from`).
+pub struct BranchAndBound<Addressing, Fallback> {
+    inputs: Vec<Input<Addressing>>,
+    make_fallback: Box<dyn Fn(Vec<Input<Addressing>>) -> Fallback + Send>,
+    fallback: Option<Fallback>,
+}
+impl<Addressing, Fallback> BranchAndBound<Addressing, Fallback> {
+    pub fn new<M>(mut inputs: Vec<Input<Addressing>>, make_fallback: M) -> Self
+    where
+        M: Fn(Vec<Input<Addressing>>) -> Fallback + Send + 'static,
+    {
+        // exploring the large inputs first finds a match faster
+        inputs.sort_unstable_by(|i1, i2| i2.value.value.cmp(&i1.value.value));
+        BranchAndBound {
+            inputs: inputs,
+            make_fallback: Box::new(make_fallback),
+            fallback: None,
+        }
+    }
+
+    /// the fallback algorithm, the inputs are handed over to it on first use
+    fn fallback(&mut self) -> &mut Fallback {
+        if self.fallback.is_none() {
+            let inputs = ::std::mem::replace(&mut self.inputs, Vec::new());
+            self.fallback = Some((self.make_fallback)(inputs));
+        }
+        self.fallback.as_mut().unwrap()
+    }
+
+    /// explore the selections that include or not the input at `index`, given the
+    /// inputs already added to `builder`. `remaining` is the total value of the
+    /// inputs from `index` onward.
+    fn explore<F>(
+        &self,
+        fee_algorithm: &F,
+        output_policy: &OutputPolicy,
+        builder: &TxBuilder,
+        index: usize,
+        remaining: Coin,
+        selection: &mut Vec<usize>,
+        tries: &mut usize,
+    ) -> Result<bool>
+    where
+        F: FeeAlgorithm,
+    {
+        if *tries == 0 {
+            return Ok(false);
+        }
+        *tries -= 1;
+
+        match builder
+            .balance(fee_algorithm)
+            .map_err(Error::TxBuildError)?
+        {
+            CoinDiff::Negative(missing) => {
+                // adding inputs also adds to the fee, `missing` is a lower bound
+                if index >= self.inputs.len() || remaining < missing {
+                    return Ok(false);
+                }
+            }
+            _ => {
+                // adding more inputs only makes the left over bigger
+                return match builder
+                    .clone()
+                    .add_output_policy(fee_algorithm, output_policy)
+                {
+                    Ok(ref change) if change.is_empty() => Ok(true),
+                    Err(txbuild::Error::TxOutputPolicyNotEnoughCoins(_)) => Ok(true),
+                    Ok(_) => Ok(false),
+                    Err(txbuild_err) => Err(Error::TxBuildError(txbuild_err)),
+                };
+            }
+        }
+
+        let input = &self.inputs[index];
+        let remaining = (remaining - input.value.value)?;
+
+        let mut with_input = builder.clone();
+        with_input.add_input_with_witness(&input.ptr, input.value.value, input.witness_kind);
+        selection.push(index);
+        if self.explore(
+            fee_algorithm,
+            output_policy,
+            &with_input,
+            index + 1,
+            remaining,
+            selection,
+            tries,
+        )? {
+            return Ok(true);
+        }
+        selection.pop();
+
+        self.explore(
+            fee_algorithm,
+            output_policy,
+            builder,
+            index + 1,
+            remaining,
+            selection,
+            tries,
+        )
+    }
+}
+
+impl<Addressing, Fallback> InputSelectionAlgorithm<Addressing>
+    for BranchAndBound<Addressing, Fallback>
+where
+    Fallback: InputSelectionAlgorithm<Addressing>,
+{
+    fn select_input<F>(
+        &mut self,
+        fee_algorithm: &F,
+        estimated_needed_output: Coin,
+    ) -> Result<Option<Input<Addressing>>>
+    where
+        F: FeeAlgorithm,
+    {
+        self.fallback()
+            .select_input(fee_algorithm, estimated_needed_output)
+    }
+
+    fn compute<F>(
+        &mut self,
+        fee_algorithm: &F,
+        outputs: Vec<TxOut>,
+        output_policy: &OutputPolicy,
+    ) -> Result<InputSelectionResult<Addressing>>
+    where
+        F: FeeAlgorithm,
+    {
+        if outputs.is_empty() {
+            return Err(Error::NoOutputs);
+        }
+
+        let mut builder = TxBuilder::new();
+        for output in outputs.iter() {
+            builder.add_output_value(output);
+        }
+
+        let total_input = coin::sum_coins(self.inputs.iter().map(|i| i.value.value))?;
+        let mut selection = Vec::new();
+        let mut tries = MAX_TRIES;
+        let found = self.explore(
+            fee_algorithm,
+            output_policy,
+            &builder,
+            0,
+            total_input,
+            &mut selection,
+            &mut tries,
+        )?;
+        if !found || selection.is_empty() {
+            return self
+                .fallback()
+                .compute(fee_algorithm, outputs, output_policy);
+        }
+
+        // take the selected inputs out, in reverse order so the indices stay valid
+        let mut selected = Vec::with_capacity(selection.len());
+        for index in selection.into_iter().rev() {
+            selected.push(self.inputs.remove(index));
+        }
+        selected.reverse();
+        for input in selected.iter() {
+            builder.add_input_with_witness(&input.ptr, input.value.value, input.witness_kind);
+        }
+
+        finalize_selection(builder, fee_algorithm, output_policy, selected)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use fee::LinearFee;
+    use input_selection::LargestFirst;
+    use test_utils::mk_address;
+    use tx::{TxId, TxoPointer};
+
+    fn mk_input(seed: u8, value: Coin) -> Input<()> {
+        let ptr = TxoPointer::new(TxId::new(&[seed]), seed as u32);
+        Input::new(ptr, TxOut::new(mk_address(seed), value), ())
+    }
+
+    /// create an input paying exactly for `output` and the fee of a
+    /// transaction with only this input
+    fn mk_exact_input(seed: u8, output: &TxOut, fee_alg: &LinearFee) -> Input<()> {
+        let input = mk_input(seed, Coin::zero());
+        let mut builder = TxBuilder::new();
+        builder.add_input_with_witness(&input.ptr, input.value.value, input.witness_kind);
+        builder.add_output_value(output);
+        let fee = builder.calculate_fee(fee_alg).unwrap();
+        mk_input(seed, (output.value + fee.to_coin()).unwrap())
+    }
+
+    #[test]
+    fn exact_match_avoids_change() {
+        let fee_alg = LinearFee::default();
+        let output = TxOut::new(mk_address(0), Coin::new(3_000000).unwrap());
+        let exact = mk_exact_input(2, &output, &fee_alg);
+        let inputs = vec![
+            mk_input(1, Coin::new(100_000000).unwrap()),
+            exact.clone(),
+            mk_input(3, Coin::new(500000).unwrap()),
+        ];
+
+        let mut bnb = BranchAndBound::new(inputs, LargestFirst::from);
+        let result = bnb
+            .compute(&fee_alg, vec![output], &OutputPolicy::One(mk_address(9)))
+            .unwrap();
+
+        assert_eq!(result.selected_inputs, vec![exact]);
+        assert_eq!(result.estimated_change, None);
+    }
+
+    #[test]
+    fn no_match_uses_fallback() {
+        let fee_alg = LinearFee::default();
+        let output = TxOut::new(mk_address(0), Coin::new(3_000000).unwrap());
+        let large = mk_input(1, Coin::new(100_000000).unwrap());
+        let inputs = vec![large.clone(), mk_input(2, Coin::new(500000).unwrap())];
+
+        let mut bnb = BranchAndBound::new(inputs, LargestFirst::from);
+        let result = bnb
+            .compute(&fee_alg, vec![output], &OutputPolicy::One(mk_address(9)))
+            .unwrap();
+
+        assert_eq!(result.selected_inputs, vec![large]);
+        assert!(result.estimated_change.is_some());
+    }
+}
@@ -6,9 +6,11 @@ use tx::TxOut;
 use txbuild::{self, TxBuilder};
 use txutils::{output_sum, Input, OutputPolicy};
 
+mod branch_and_bound;
 mod simple_selections;
 
-pub use self::simple_selections::{Blackjack, HeadFirst, LargestFirst};
+pub use self::branch_and_bound::BranchAndBound;
+pub use self::simple_selections::{Blackjack, HeadFirst, LargestFirst, RandomImprove};
 
 #[derive(Debug)]
 pub enum Error {
@@ -55,6 +57,12 @@ impl From<cbor_event::Error> for Error {
     }
 }
 
+impl From<txbuild::Error> for Error {
+    fn from(e: txbuild::Error) -> Error {
+        Error::TxBuildError(e)
+    }
+}
+
 impl ::std::error::Error for Error {
     fn cause(&self) -> Option<&::std::error::Error> {
         match self {
@@ -152,19 +160,19 @@ pub trait InputSelectionAlgorithm<Addressing> {
             builder.add_output_value(&output);
         }
 
-        let total_output = builder.get_output_total().unwrap();
+        let total_output = builder.get_output_total()?;
         let mut estimated_needed_output =
-            (total_output + builder.calculate_fee(fee_algorithm).unwrap().to_coin()).unwrap();
+            (total_output + builder.calculate_fee(fee_algorithm)?.to_coin())?;
 
         while let Some(input) = self.select_input(fee_algorithm, estimated_needed_output)? {
-            builder.add_input(&input.ptr, input.value.value);
+            builder.add_input_with_witness(&input.ptr, input.value.value, input.witness_kind);
             selected.push(input);
 
             // update the estimated needed output every time we add an input
             // this is because every time we add an input, we add more to the transaction
             // and the fee increase
             estimated_needed_output =
-                (total_output + builder.calculate_fee(fee_algorithm).unwrap().to_coin()).unwrap();
+                (total_output + builder.calculate_fee(fee_algorithm)?.to_coin())?;
 
             match builder
                 .clone()
@@ -187,35 +195,49 @@ pub trait InputSelectionAlgorithm<Addressing> {
             }
         }
 
-        let (change, loss) = match builder.add_output_policy(fee_algorithm, output_policy) {
-            Err(txbuild::Error::TxNotEnoughTotalInput) => {
-                return Err(Error::NotEnoughInput);
-            }
-            Err(txbuild::Error::TxOutputPolicyNotEnoughCoins(loss)) => (None, Some(loss)),
-            Err(txbuild_err) => {
-                return Err(Error::TxBuildError(txbuild_err));
-            }
-            Ok(change_outputs) => (
-                if change_outputs.is_empty() {
-                    None
-                } else {
-                    Some(output_sum(change_outputs.iter())?)
-                },
-                None,
-            ),
-        };
-
-        let fees = builder.calculate_fee(fee_algorithm).unwrap();
-        let fees = if let Some(loss) = loss {
-            Fee::new((fees.to_coin() + loss)?)
-        } else {
-            fees
-        };
-        let result = InputSelectionResult {
-            estimated_fees: fees,
-            estimated_change: change,
-            selected_inputs: selected,
-        };
-        Ok(result)
+        finalize_selection(builder, fee_algorithm, output_policy, selected)
     }
 }
+
+/// compute the final result of an input selection, once the `selected` inputs
+/// have been added to the `builder`.
+fn finalize_selection<Addressing, F>(
+    mut builder: TxBuilder,
+    fee_algorithm: &F,
+    output_policy: &OutputPolicy,
+    selected: Vec<Input<Addressing>>,
+) -> Result<InputSelectionResult<Addressing>>
+where
+    F: FeeAlgorithm,
+{
+    let (change, loss) = match builder.add_output_policy(fee_algorithm, output_policy) {
+        Err(txbuild::Error::TxNotEnoughTotalInput) => {
+            return Err(Error::NotEnoughInput);
+        }
+        Err(txbuild::Error::TxOutputPolicyNotEnoughCoins(loss)) => (None, Some(loss)),
+        Err(txbuild_err) => {
+            return Err(Error::TxBuildError(txbuild_err));
+        }
+        Ok(change_outputs) => (
+            if change_outputs.is_empty() {
+                None
+            } else {
+                Some(output_sum(change_outputs.iter())?)
+            },
+            None,
+        ),
+    };
+
+    let fees = builder.calculate_fee(fee_algorithm)?;
+    let fees = if let Some(loss) = loss {
+        Fee::new((fees.to_coin() + loss)?)
+    } else {
+        fees
+    };
+    let result = InputSelectionResult {
+        estimated_fees: fees,
+        estimated_change: change,
+        selected_inputs: selected,
+    };
+    Ok(result)
+}
@@ -1,4 +1,5 @@
 use super::*;
+use util::drbg::RandomGenerator;
 
 /// Take the given input collections and select the inputs in the given order
 ///
@@ -65,8 +66,9 @@ impl BasicRandom {
             state: initial_state,
         }
     }
-
-    fn next(&mut self) -> u32 {
+}
+impl RandomGenerator for BasicRandom {
+    fn next_u32(&mut self) -> u32 {
         self.state = self.state.wrapping_mul(1103515245).wrapping_add(12345);
         return self.state;
     }
@@ -78,7 +80,7 @@ pub struct Blackjack<Addressing> {
     inputs: Vec<(bool, Input<Addressing>)>,
     total_input_selected: Coin,
     dust_threshold: Coin,
-    random_generator: BasicRandom,
+    random_generator: Box<dyn RandomGenerator + Send>,
 }
 impl<Addressing> Blackjack<Addressing> {
     #[inline]
@@ -92,7 +94,7 @@ impl<Addressing> Blackjack<Addressing> {
         if inputs.len() == 0 {
             return None;
         }
-        let index = self.random_generator.next() as usize % inputs.len();
+        let index = self.random_generator.next_u32() as usize % inputs.len();
 
         inputs.nth(index)
     }
@@ -103,9 +105,19 @@ impl<Addressing> Blackjack<Addressing> {
             inputs: inputs.into_iter().map(|i| (false, i)).collect(),
             total_input_selected: Coin::zero(),
             dust_threshold: dust_threshold,
-            random_generator: BasicRandom::new(seed as u32),
+            random_generator: Box::new(BasicRandom::new(seed as u32)),
         }
     }
+
+    /// pick the inputs with the given generator, instead of the default
+    /// one seeded from the dust threshold and the number of inputs
+    pub fn with_random_generator<R>(mut self, random_generator: R) -> Self
+    where
+        R: RandomGenerator + Send + 'static,
+    {
+        self.random_generator = Box::new(random_generator);
+        self
+    }
 }
 impl<Addressing: Clone> InputSelectionAlgorithm<Addressing> for Blackjack<Addressing> {
     fn select_input<F>(
@@ -120,7 +132,9 @@ impl<Addressing: Clone> InputSelectionAlgorithm<Addressing> for Blackjack<Addres
 
         const MAX_OVERHEAD_INDEX: usize = 5; // 32bits + 1 bytes of CBOR...
         const MAX_OVERHEAD_TXID: usize = TxId::HASH_SIZE + 2; // 2 bytes of Cbor...
-        const MAX_OVERHEAD_TXIN: usize = MAX_OVERHEAD_INDEX + MAX_OVERHEAD_TXID + 2; // 2 bytes of cbor
+
+        // 2 bytes of cbor
+        const MAX_OVERHEAD_TXIN: usize = MAX_OVERHEAD_INDEX + MAX_OVERHEAD_TXID + 2;
 
         let signature_cost = fee_algorithm
             .estimate_overhead(cbor!(TxInWitness::fake()).unwrap().len())?
@@ -163,6 +177,148 @@ impl<Addressing: Clone> InputSelectionAlgorithm<Addressing> for Blackjack<Addres
     }
 }
 
+/// Random-Improve input selection, as described in CIP-2.
+///
+/// Inputs are first picked at random until the outputs and the fee are
+/// covered. The selection is then improved by picking more random inputs,
+/// as long as each one brings the total selected closer to twice the
+/// total output, without going above three times the total output.
+///
+/// The change ends up about the size of the payment, which avoids
+/// slowly fragmenting the wallet's UTxO into dust.
+pub struct RandomImprove<Addressing> {
+    inputs: Vec<Input<Addressing>>,
+    random_generator: Box<dyn RandomGenerator + Send>,
+}
+impl<Addressing> RandomImprove<Addressing> {
+    pub fn new(seed: u32, inputs: Vec<Input<Addressing>>) -> Self {
+        RandomImprove::with_random_generator(BasicRandom::new(seed), inputs)
+    }
+
+    /// pick the inputs with the given generator (e.g. a `util::drbg::Drbg`
+    /// for a reproducible selection, or a generator seeded from the OS)
+    pub fn with_random_generator<R>(random_generator: R, inputs: Vec<Input<Addressing>>) -> Self
+    where
+        R: RandomGenerator + Send + 'static,
+    {
+        RandomImprove {
+            inputs: inputs,
+            random_generator: Box::new(random_generator),
+        }
+    }
+
+    fn take_random(&mut self) -> Option<Input<Addressing>> {
+        if self.inputs.is_empty() {
+            return None;
+        }
+        let index = self.random_generator.next_u32() as usize % self.inputs.len();
+        Some(self.inputs.swap_remove(index))
+    }
+}
+impl<Addressing> From<Vec<Input<Addressing>>> for RandomImprove<Addressing> {
+    fn from(inputs: Vec<Input<Addressing>>) -> Self {
+        let seed = inputs.len() as u32;
+        RandomImprove::new(seed, inputs)
+    }
+}
+
+fn coin_distance(c1: Coin, c2: Coin) -> u64 {
+    let (c1, c2) = (u64::from(c1), u64::from(c2));
+    if c1 > c2 {
+        c1 - c2
+    } else {
+        c2 - c1
+    }
+}
+
+impl<Addressing> InputSelectionAlgorithm<Addressing> for RandomImprove<Addressing> {
+    fn select_input<F>(
+        &mut self,
+        _fee_algorithm: &F,
+        _estimated_needed_output: Coin,
+    ) -> Result<Option<Input<Addressing>>>
+    where
+        F: FeeAlgorithm,
+    {
+        Ok(self.take_random())
+    }
+
+    fn compute<F>(
+        &mut self,
+        fee_algorithm: &F,
+        outputs: Vec<TxOut>,
+        output_policy: &OutputPolicy,
+    ) -> Result<InputSelectionResult<Addressing>>
+    where
+        F: FeeAlgorithm,
+    {
+        let mut selected = Vec::new();
+        let mut builder = TxBuilder::new();
+
+        if outputs.is_empty() {
+            return Err(Error::NoOutputs);
+        }
+
+        for output in outputs {
+            builder.add_output_value(&output);
+        }
+        let total_output = builder.get_output_total().map_err(Error::TxBuildError)?;
+
+        // random selection: pick inputs until the outputs and the fee are covered
+        loop {
+            match builder
+                .clone()
+                .add_output_policy(fee_algorithm, output_policy)
+            {
+                Err(txbuild::Error::TxNotEnoughTotalInput) => {}
+                Err(txbuild::Error::TxOutputPolicyNotEnoughCoins(_)) | Ok(_) => break,
+                Err(txbuild_err) => return Err(Error::TxBuildError(txbuild_err)),
+            }
+            match self.take_random() {
+                None => return Err(Error::NotEnoughInput),
+                Some(input) => {
+                    builder.add_input_with_witness(
+                        &input.ptr,
+                        input.value.value,
+                        input.witness_kind,
+                    );
+                    selected.push(input);
+                }
+            }
+        }
+
+        // improvement: keep adding random inputs while they bring the total
+        // selected closer to the ideal value, stop at the first that does not
+        let ideal = (total_output + total_output)?;
+        let maximum = (ideal + total_output)?;
+        let mut total_selected = coin::sum_coins(selected.iter().map(|i| i.value.value))?;
+        while let Some(input) = self.take_random() {
+            let improved = (total_selected + input.value.value)?;
+            let mut improved_builder = builder.clone();
+            improved_builder.add_input_with_witness(
+                &input.ptr,
+                input.value.value,
+                input.witness_kind,
+            );
+            let is_improvement = improved <= maximum
+                && coin_distance(improved, ideal) < coin_distance(total_selected, ideal)
+                && improved_builder
+                    .clone()
+                    .add_output_policy(fee_algorithm, output_policy)
+                    .is_ok();
+            if !is_improvement {
+                self.inputs.push(input);
+                break;
+            }
+            builder = improved_builder;
+            total_selected = improved;
+            selected.push(input);
+        }
+
+        finalize_selection(builder, fee_algorithm, output_policy, selected)
+    }
+}
+
 #[cfg(test)]
 mod test {
     use super::*;
@@ -371,6 +527,12 @@ mod test {
             let max_fee = fee_alg.estimate(TX_SIZE_LIMIT).expect("max fee");
             test_fee(value, |i| Blackjack::new(Coin::from(100_000), i), fee_alg, max_fee)
         }
+
+        fn random_improve(value: (Wrapper<ProtocolMagic>, Inputs, Outputs)) -> bool {
+            let fee_alg = LinearFee::default();
+            let max_fee = fee_alg.estimate(TX_SIZE_LIMIT).expect("max fee");
+            test_fee(value, RandomImprove::from, fee_alg, max_fee)
+        }
     }
 }
 
@@ -622,4 +784,121 @@ mod unit_tests {
 
         test_no_enough(Blackjack::new(Coin::from(150_000), inputs), outputs);
     }
+
+    #[test]
+    fn random_improve_change_about_the_payment() {
+        // once the payment and the fee are covered (3 inputs), the selection
+        // is improved with one more input: the change is about the payment
+        // instead of being a dust output
+        let inputs: Vec<_> = (0..10)
+            .map(|_| mk_icarus_style_input(Coin::new(1_000000).unwrap()))
+            .collect();
+        let output1 = mk_icarus_style_txout(Coin::new(2_000000).unwrap());
+
+        let mut random_improve = RandomImprove::new(42, inputs);
+        let result = random_improve
+            .compute(
+                &LinearFee::default(),
+                vec![output1],
+                &OutputPolicy::One(mk_random_icarus_style_address()),
+            )
+            .unwrap();
+
+        assert_eq!(result.selected_inputs.len(), 4);
+        let change = result.estimated_change.expect("a change output");
+        assert!(change > Coin::new(1_500000).unwrap());
+        assert!(change < Coin::new(2_000000).unwrap());
+    }
+
+    #[test]
+    fn random_improve_never_exceeds_three_times_the_payment() {
+        let input1 = mk_icarus_style_input(Coin::new(3_000000).unwrap());
+        let input2 = mk_icarus_style_input(Coin::new(10_000000).unwrap());
+        let output1 = mk_icarus_style_txout(Coin::new(2_000000).unwrap());
+
+        for seed in 0..8 {
+            let inputs = vec![input1.clone(), input2.clone()];
+            let mut random_improve = RandomImprove::new(seed, inputs);
+            let result = random_improve
+                .compute(
+                    &LinearFee::default(),
+                    vec![output1.clone()],
+                    &OutputPolicy::One(mk_random_icarus_style_address()),
+                )
+                .unwrap();
+            // whichever input is picked first covers the payment, and the
+            // other one would go over the maximum of the improvement phase
+            assert_eq!(result.selected_inputs.len(), 1);
+        }
+    }
+
+    #[test]
+    fn random_improve_is_reproducible() {
+        use util::drbg::Drbg;
+
+        let inputs: Vec<_> = (0..20)
+            .map(|i| mk_icarus_style_input(Coin::new(1_000000 + i * 1000).unwrap()))
+            .collect();
+        let output1 = mk_icarus_style_txout(Coin::new(3_000000).unwrap());
+        let change_address = mk_random_icarus_style_address();
+
+        let select = |seed: &[u8]| {
+            RandomImprove::with_random_generator(Drbg::new(seed), inputs.clone())
+                .compute(
+                    &LinearFee::default(),
+                    vec![output1.clone()],
+                    &OutputPolicy::One(change_address.clone()),
+                )
+                .unwrap()
+                .selected_inputs
+                .into_iter()
+                .map(|input| input.ptr)
+                .collect::<Vec<_>>()
+        };
+        assert_eq!(select(b"test seed"), select(b"test seed"));
+    }
+
+    #[test]
+    fn not_enough_ada_random_improve() {
+        let input1 = mk_icarus_style_input(Coin::new(1_000000).unwrap());
+        let input2 = mk_icarus_style_input(Coin::new(1_000000).unwrap());
+        let output1 = mk_icarus_style_txout(Coin::new(2_000000).unwrap());
+
+        test_no_enough(RandomImprove::from(vec![input1, input2]), vec![output1]);
+    }
+
+    quickcheck! {
+        // with enough inputs smaller than the payment available, the
+        // improvement phase brings the total selected to about twice the
+        // payment (without going over three times the payment), so the
+        // change is about the payment instead of being dust
+        fn random_improve_change_is_not_dust(
+            seed: u32,
+            nb_inputs: u8,
+            value: u8,
+            payment: u8
+        ) -> bool {
+            let value = (1 + u64::from(value) % 10) * 1_000000;
+            let payment = value * (1 + u64::from(payment) % 4);
+            let inputs: Vec<_> = (0..20 + nb_inputs % 40)
+                .map(|_| mk_icarus_style_input(Coin::new(value).unwrap()))
+                .collect();
+            let output1 = mk_icarus_style_txout(Coin::new(payment).unwrap());
+
+            let result = RandomImprove::new(seed, inputs)
+                .compute(
+                    &LinearFee::default(),
+                    vec![output1],
+                    &OutputPolicy::One(mk_random_icarus_style_address()),
+                )
+                .unwrap();
+
+            let total_selected = u64::from(
+                sum_coins(result.selected_inputs.iter().map(|i| i.value.value)).unwrap(),
+            );
+            let change = result.estimated_change.map_or(0, u64::from);
+            let fee = u64::from(result.estimated_fees.to_coin());
+            total_selected <= 3 * payment && change + fee >= payment / 2
+        }
+    }
 }
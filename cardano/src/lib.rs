@@ -13,6 +13,38 @@
 //! * Redeem Key
 //! * Wallet abstraction
 //!
+//! # JSON representation
+//!
+//! With the `generic-serialization` feature, the types implement serde's
+//! traits. The human readable representation (e.g. JSON) is the one to use
+//! when exposing these types to the outside (REST APIs, command line
+//! `--output json` modes...) so all the tools agree on the field names and
+//! the encodings:
+//!
+//! * hashes (transaction id, block hash), public keys and signatures are
+//!   hexadecimal strings;
+//! * addresses (`ExtendedAddr`) are base58 strings;
+//! * coin values are numbers of lovelaces;
+//! * `TxoPointer`: `{ "id": "<hex>", "index": <number> }`;
+//! * `TxOut`: `{ "address": "<base58>", "value": <lovelaces> }`;
+//! * `Tx`: `{ "inputs": [<TxoPointer>...], "outputs": [<TxOut>...] }`;
+//! * `TxAux`: `{ "tx": <Tx>, "witness": [<TxInWitness>...] }` where a
+//!   witness is one of `{ "PkWitness": ["<xpub hex>", "<signature hex>"] }`,
+//!   `{ "RedeemWitness": ["<public key hex>", "<signature hex>"] }` or
+//!   `{ "ScriptWitness": [<Script>, <Script>] }`;
+//! * `BlockHeader`: see the `Serialize` implementation of
+//!   `block::BlockHeader`, the header is read back from its `cbor` field;
+//! * `Block`: `{ "header": <BlockHeader>, ..., "cbor": "<hex>" }` with the
+//!   slot leaders of a boundary block or the transactions of a main block,
+//!   see the `Serialize` implementation of `block::Block`, the block is
+//!   read back from its `cbor` field;
+//! * `BlockDate`: `{ "Boundary": <epoch> }` or
+//!   `{ "Normal": { "epoch": <epoch>, "slotid": <slot> } }`;
+//! * `ChainDifficulty`: a number.
+//!
+//! These representations are part of the API: changing them is a breaking
+//! change.
+//!
 #![cfg_attr(feature = "with-bench", feature(test))]
 
 #[cfg(feature = "generic-serialization")]
@@ -52,11 +84,13 @@ pub mod coin;
 pub mod config;
 mod crc32;
 pub mod fee;
+pub mod genesis;
 pub mod hash;
 pub mod hdpayload;
 pub mod hdwallet;
 pub mod input_selection;
 pub mod paperwallet;
+pub mod pretty;
 pub mod redeem;
 pub mod tx;
 pub mod txbuild;
@@ -71,3 +105,6 @@ pub mod wallet;
 pub mod merkle;
 pub mod tags;
 pub mod vss;
+
+#[cfg(test)]
+mod test_utils;
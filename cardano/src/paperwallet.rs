@@ -1,6 +1,31 @@
+//! Paper wallet scrambling
+//!
+//! The version 1 scrambling XORs the input with a key derived from the
+//! password with PBKDF2 (10000 iterations), prepended with the IV. It is
+//! fast to brute force for short passwords, and a wrong password cannot be
+//! detected.
+//!
+//! The version 2 scrambling derives the key with scrypt, a memory-hard
+//! KDF, and wraps the result in a versioned envelope:
+//!
+//! ```text
+//! MAGIC (4 bytes, = 0xfe 'P' 'W' 2)
+//! SCRYPT LOG_N (1 byte) SCRYPT R (1 byte) SCRYPT P (1 byte)
+//! SALT (16 bytes)
+//! SHIELDED INPUT (input length)
+//! CHECK (4 bytes)
+//! ```
+//!
+//! The check allows to detect a wrong password. `unscramble_any` detects
+//! the version of a scrambled input with the magic, to keep unscrambling
+//! the version 1 paper wallets.
+
 use cryptoxide::hmac::Hmac;
 use cryptoxide::pbkdf2::pbkdf2;
 use cryptoxide::sha2::Sha512;
+use cryptoxide::util::fixed_time_eq;
+use std::{error, fmt, result};
+use util::scrypt::scrypt;
 
 const ITERS: u32 = 10000;
 pub const IV_SIZE: usize = 8;
@@ -53,6 +78,160 @@ pub fn unscramble(password: &[u8], input: &[u8]) -> Vec<u8> {
     out
 }
 
+/// the first bytes of a version 2 envelope, the last one is the version
+pub const V2_MAGIC: [u8; 4] = [0xfe, b'P', b'W', 2];
+pub const V2_SALT_SIZE: usize = 16;
+const V2_PARAMS_OFFSET: usize = 4;
+const V2_SALT_OFFSET: usize = V2_PARAMS_OFFSET + 3;
+const V2_HEADER_SIZE: usize = V2_SALT_OFFSET + V2_SALT_SIZE;
+const V2_CHECK_SIZE: usize = 4;
+/// above, deriving the key needs more than 1GB of memory (with `r = 8`)
+const V2_MAX_LOG_N: u8 = 20;
+/// bound of `128 * r * N * p`: scrypt needs `128 * r * N` bytes of memory,
+/// and its running time grows with `128 * r * N * p` (1GB of memory, once)
+const V2_MAX_COST: u64 = 1 << 30;
+
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+pub enum Error {
+    /// the input is too short, or its parameters are invalid
+    InvalidEnvelope,
+    /// the password does not unscramble the input
+    WrongPassword,
+}
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Error::InvalidEnvelope => write!(f, "Invalid scrambled paper wallet"),
+            Error::WrongPassword => write!(f, "Wrong paper wallet password"),
+        }
+    }
+}
+impl error::Error for Error {}
+
+pub type Result<T> = result::Result<T, Error>;
+
+/// The scrypt cost parameters of the version 2 scrambling
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+pub struct KdfParameters {
+    /// `N = 2^log_n`
+    pub log_n: u8,
+    pub r: u8,
+    pub p: u8,
+}
+impl Default for KdfParameters {
+    /// the parameters recommended for interactive use: 16MB of memory
+    fn default() -> Self {
+        KdfParameters {
+            log_n: 14,
+            r: 8,
+            p: 1,
+        }
+    }
+}
+impl KdfParameters {
+    fn is_valid(&self) -> bool {
+        self.log_n > 0
+            && self.log_n <= V2_MAX_LOG_N
+            && self.r > 0
+            && self.p > 0
+            && 128 * (1u64 << self.log_n) * u64::from(self.r) * u64::from(self.p) <= V2_MAX_COST
+    }
+}
+
+fn gen_v2(params: &KdfParameters, salt: &[u8], password: &[u8], buf: &mut [u8]) {
+    scrypt(
+        password,
+        salt,
+        params.log_n,
+        params.r as u32,
+        params.p as u32,
+        buf,
+    );
+}
+
+/// Given a `V2_SALT_SIZE` bytes salt and a password, scramble the input
+/// with a key derived with scrypt, in a version 2 envelope.
+///
+/// # panics
+///
+/// if the salt is not `V2_SALT_SIZE` bytes, or the parameters are invalid
+/// (`log_n`, `r` or `p` is 0, `log_n` is more than 20, or `128 * r * N * p`
+/// is more than 1GB).
+pub fn scramble_v2(params: &KdfParameters, salt: &[u8], password: &[u8], input: &[u8]) -> Vec<u8> {
+    assert!(salt.len() == V2_SALT_SIZE);
+    assert!(params.is_valid(), "invalid KDF parameters {:?}", params);
+
+    let mut key = vec![0u8; input.len() + V2_CHECK_SIZE];
+    gen_v2(params, salt, password, &mut key);
+
+    let mut out = Vec::with_capacity(V2_HEADER_SIZE + key.len());
+    out.extend_from_slice(&V2_MAGIC);
+    out.extend_from_slice(&[params.log_n, params.r, params.p]);
+    out.extend_from_slice(salt);
+    out.extend(input.iter().zip(key.iter()).map(|(i, k)| i ^ k));
+    out.extend_from_slice(&key[input.len()..]);
+    out
+}
+
+fn v2_params(input: &[u8]) -> KdfParameters {
+    KdfParameters {
+        log_n: input[V2_PARAMS_OFFSET],
+        r: input[V2_PARAMS_OFFSET + 1],
+        p: input[V2_PARAMS_OFFSET + 2],
+    }
+}
+
+/// true if the input starts with the version 2 magic
+fn has_v2_magic(input: &[u8]) -> bool {
+    input.len() >= V2_MAGIC.len() && input[..V2_MAGIC.len()] == V2_MAGIC
+}
+
+/// true if the input is a well formed version 2 envelope
+pub fn is_v2(input: &[u8]) -> bool {
+    has_v2_magic(input)
+        && input.len() > V2_HEADER_SIZE + V2_CHECK_SIZE
+        && v2_params(input).is_valid()
+}
+
+/// Reverse the `scramble_v2` operation.
+pub fn unscramble_v2(password: &[u8], input: &[u8]) -> Result<Vec<u8>> {
+    if !is_v2(input) {
+        return Err(Error::InvalidEnvelope);
+    }
+    let params = v2_params(input);
+    let salt = &input[V2_SALT_OFFSET..V2_HEADER_SIZE];
+    let shielded = &input[V2_HEADER_SIZE..input.len() - V2_CHECK_SIZE];
+    let check = &input[input.len() - V2_CHECK_SIZE..];
+
+    let mut key = vec![0u8; shielded.len() + V2_CHECK_SIZE];
+    gen_v2(&params, salt, password, &mut key);
+    if !fixed_time_eq(&key[shielded.len()..], check) {
+        return Err(Error::WrongPassword);
+    }
+    Ok(shielded
+        .iter()
+        .zip(key.iter())
+        .map(|(s, k)| s ^ k)
+        .collect())
+}
+
+/// Unscramble a version 2 envelope (starting with `V2_MAGIC`), or else a
+/// version 1 scrambled input.
+///
+/// A wrong password is detected for a version 2 envelope only. A version 1
+/// input whose random IV starts with the magic (1 in 2^32) is taken for a
+/// version 2 envelope: use `unscramble` for it.
+pub fn unscramble_any(password: &[u8], input: &[u8]) -> Result<Vec<u8>> {
+    if has_v2_magic(input) {
+        return unscramble_v2(password, input);
+    }
+    if input.len() > IV_SIZE {
+        Ok(unscramble(password, input))
+    } else {
+        Err(Error::InvalidEnvelope)
+    }
+}
+
 #[cfg(test)]
 mod tests {
     //use paperwallet::{scramble,unscramble};
@@ -118,4 +297,86 @@ mod tests {
             assert_eq!(&r[..], &tv.input[..]);
         }
     }
+
+    // cheap parameters, the tests run unoptimized
+    const TEST_PARAMS: paperwallet::KdfParameters = paperwallet::KdfParameters {
+        log_n: 4,
+        r: 1,
+        p: 1,
+    };
+
+    #[test]
+    fn paper_scramble_v2() {
+        let salt = [0x2a; paperwallet::V2_SALT_SIZE];
+        let input = [0x5a; 16];
+        let scrambled = paperwallet::scramble_v2(&TEST_PARAMS, &salt, b"Cardano Ada", &input);
+        assert!(paperwallet::is_v2(&scrambled));
+        assert_eq!(&scrambled[..4], &paperwallet::V2_MAGIC[..]);
+        assert_eq!(scrambled.len(), 4 + 3 + salt.len() + input.len() + 4);
+
+        let r = paperwallet::unscramble_v2(b"Cardano Ada", &scrambled).unwrap();
+        assert_eq!(&r[..], &input[..]);
+        assert_eq!(
+            paperwallet::unscramble_v2(b"Cardano", &scrambled),
+            Err(paperwallet::Error::WrongPassword)
+        );
+    }
+
+    #[test]
+    fn paper_unscramble_any() {
+        for tv in GOLDEN_TESTS.iter() {
+            assert!(!paperwallet::is_v2(&tv.shielded_input[..]));
+            let r = paperwallet::unscramble_any(tv.passphrase.as_bytes(), &tv.shielded_input[..]);
+            assert_eq!(&r.unwrap()[..], &tv.input[..]);
+        }
+
+        let salt = [0u8; paperwallet::V2_SALT_SIZE];
+        let scrambled = paperwallet::scramble_v2(&TEST_PARAMS, &salt, b"", &[0x7f; 32]);
+        let r = paperwallet::unscramble_any(b"", &scrambled).unwrap();
+        assert_eq!(&r[..], &[0x7f; 32][..]);
+        assert_eq!(
+            paperwallet::unscramble_any(b"wrong", &scrambled),
+            Err(paperwallet::Error::WrongPassword)
+        );
+
+        assert_eq!(
+            paperwallet::unscramble_any(b"", &[0u8; 8]),
+            Err(paperwallet::Error::InvalidEnvelope)
+        );
+    }
+
+    #[test]
+    fn paper_unscramble_any_v1_with_the_v2_version() {
+        // a single byte equal to the version is not taken for a version 2
+        // envelope
+        let iv = [2, 4, 1, 1, 0, 0, 0, 0];
+        let input = [0x5a; 32];
+        let scrambled = paperwallet::scramble(&iv, b"Cardano Ada", &input);
+        assert!(!paperwallet::is_v2(&scrambled));
+        let r = paperwallet::unscramble_any(b"Cardano Ada", &scrambled).unwrap();
+        assert_eq!(&r[..], &input[..]);
+    }
+
+    #[test]
+    fn kdf_parameters_bounds() {
+        let params = |log_n, r, p| paperwallet::KdfParameters { log_n, r, p };
+        assert!(paperwallet::KdfParameters::default().is_valid());
+        assert!(params(20, 8, 1).is_valid());
+        assert!(params(14, 8, 8).is_valid());
+        assert!(!params(21, 1, 1).is_valid());
+        assert!(!params(20, 8, 2).is_valid());
+        assert!(!params(20, 255, 1).is_valid());
+        assert!(!params(14, 255, 255).is_valid());
+        assert!(!params(14, 0, 1).is_valid());
+        assert!(!params(14, 8, 0).is_valid());
+
+        let mut input = paperwallet::V2_MAGIC.to_vec();
+        input.extend_from_slice(&[14, 255, 255]);
+        input.extend_from_slice(&[0; 40]);
+        assert!(!paperwallet::is_v2(&input));
+        assert_eq!(
+            paperwallet::unscramble_any(b"", &input),
+            Err(paperwallet::Error::InvalidEnvelope)
+        );
+    }
 }
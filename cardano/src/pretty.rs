@@ -0,0 +1,444 @@
+//! Human readable rendering of blocks, headers and transactions.
+//!
+//! The command line front-ends all display the same objects; rendering
+//! them here keeps their output identical. The output is an indented
+//! tree of `name: value` lines:
+//!
+//! ```text
+//! id: 0b9f...
+//! inputs (1):
+//!   0: 5ac2...@0
+//! outputs (1):
+//!   0:
+//!     address: DdzFF...
+//!     value: 1.000000
+//! witnesses (1):
+//!   0:
+//!     type: public key
+//!     ...
+//! ```
+//!
+//! The rendering is controlled by a `Config`: ANSI colors, how deep in the
+//! tree to go, how many elements of a list to show and whether the parts
+//! of a block this library does not decode are dumped as raw CBOR.
+
+use block::{boundary, normal, sign::BlockSignature, types, Block, BlockHeader};
+use cbor_event;
+use std::fmt::{self, Write};
+use tx::{Tx, TxAux, TxInWitness, TxOut};
+use util::hex;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Config {
+    /// highlight the names, hashes and values with ANSI colors
+    pub color: bool,
+    /// maximum depth of the tree to display, the deeper sections are
+    /// collapsed into `...`
+    pub max_depth: Option<usize>,
+    /// maximum number of elements of a list to display
+    pub max_items: Option<usize>,
+    /// dump the undecoded sections as hexadecimal CBOR, instead of their size
+    pub raw_cbor: bool,
+}
+impl Default for Config {
+    fn default() -> Self {
+        Config {
+            color: false,
+            max_depth: None,
+            max_items: None,
+            raw_cbor: false,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Style {
+    Name,
+    Hash,
+    Value,
+    Raw,
+}
+impl Style {
+    fn ansi_code(self) -> &'static str {
+        match self {
+            Style::Name => "\x1b[1m",
+            Style::Hash => "\x1b[33m",
+            Style::Value => "\x1b[32m",
+            Style::Raw => "\x1b[2m",
+        }
+    }
+}
+const ANSI_RESET: &str = "\x1b[0m";
+
+const INDENT: &str = "  ";
+
+/// Write the rendering of `Pretty` objects into `W`
+pub struct Printer<W> {
+    out: W,
+    config: Config,
+    depth: usize,
+}
+impl<W: Write> Printer<W> {
+    pub fn new(out: W, config: Config) -> Self {
+        Printer {
+            out,
+            config,
+            depth: 0,
+        }
+    }
+
+    pub fn config(&self) -> &Config {
+        &self.config
+    }
+
+    pub fn into_inner(self) -> W {
+        self.out
+    }
+
+    fn paint<D: fmt::Display>(&self, style: Style, value: D) -> String {
+        if self.config.color {
+            format!("{}{}{}", style.ansi_code(), value, ANSI_RESET)
+        } else {
+            format!("{}", value)
+        }
+    }
+
+    fn line(&mut self, name: &str, style: Style, value: &str) -> fmt::Result {
+        for _ in 0..self.depth {
+            self.out.write_str(INDENT)?;
+        }
+        let name = self.paint(Style::Name, name);
+        write!(self.out, "{}:", name)?;
+        // the values spanning several lines are indented under their name
+        for (i, line) in value.lines().enumerate() {
+            let line = self.paint(style, line);
+            if i == 0 {
+                write!(self.out, " {}", line)?;
+            } else {
+                self.out.write_char('\n')?;
+                for _ in 0..self.depth + 1 {
+                    self.out.write_str(INDENT)?;
+                }
+                self.out.write_str(&line)?;
+            }
+        }
+        self.out.write_char('\n')
+    }
+
+    /// display a `name: value` line
+    pub fn field<D: fmt::Display>(&mut self, name: &str, value: D) -> fmt::Result {
+        self.line(name, Style::Value, &value.to_string())
+    }
+
+    /// same as `field`, for hashes, keys and signatures
+    pub fn hash<D: fmt::Display>(&mut self, name: &str, value: D) -> fmt::Result {
+        self.line(name, Style::Hash, &value.to_string())
+    }
+
+    /// display a section, its content (written by `f`) being indented
+    /// one level deeper. If the maximum depth is reached the content is
+    /// replaced by `...`.
+    pub fn section<F>(&mut self, name: &str, f: F) -> fmt::Result
+    where
+        F: FnOnce(&mut Self) -> fmt::Result,
+    {
+        if self.config.max_depth.map_or(false, |max| self.depth >= max) {
+            return self.line(name, Style::Raw, "...");
+        }
+        self.line(name, Style::Value, "")?;
+        self.depth += 1;
+        let result = f(self);
+        self.depth -= 1;
+        result
+    }
+
+    /// display `value` in its own section
+    pub fn item<P: Pretty + ?Sized>(&mut self, name: &str, value: &P) -> fmt::Result {
+        self.section(name, |printer| value.pretty(printer))
+    }
+
+    /// display the elements of a list in a section, named by their index.
+    ///
+    /// `f` displays the element with the given name.
+    pub fn list<'a, T: 'a, I, F>(&mut self, name: &str, items: I, mut f: F) -> fmt::Result
+    where
+        I: ExactSizeIterator<Item = &'a T>,
+        F: FnMut(&mut Self, &str, &'a T) -> fmt::Result,
+    {
+        let len = items.len();
+        let shown = self
+            .config
+            .max_items
+            .map_or(len, |max| ::std::cmp::min(max, len));
+        self.section(&format!("{} ({})", name, len), |printer| {
+            for (i, item) in items.take(shown).enumerate() {
+                f(printer, &i.to_string(), item)?;
+            }
+            if shown < len {
+                printer.line("...", Style::Raw, &format!("{} more", len - shown))?;
+            }
+            Ok(())
+        })
+    }
+
+    /// display a part of a block this library does not decode
+    pub fn raw_cbor(&mut self, name: &str, value: &cbor_event::Value) -> fmt::Result {
+        let bytes = match cbor!(value) {
+            Ok(bytes) => bytes,
+            Err(_) => return self.line(name, Style::Raw, "<invalid CBOR>"),
+        };
+        if self.config.raw_cbor {
+            self.line(name, Style::Raw, &hex::encode(&bytes))
+        } else {
+            self.line(
+                name,
+                Style::Raw,
+                &format!("<{} bytes of CBOR>", bytes.len()),
+            )
+        }
+    }
+}
+
+/// An object with a human readable rendering
+pub trait Pretty {
+    fn pretty<W: Write>(&self, printer: &mut Printer<W>) -> fmt::Result;
+}
+
+/// render `value` into a `String`
+pub fn to_string<P: Pretty + ?Sized>(value: &P, config: Config) -> String {
+    let mut printer = Printer::new(String::new(), config);
+    // writing into a String does not fail
+    value.pretty(&mut printer).unwrap();
+    printer.into_inner()
+}
+
+impl Pretty for Block {
+    fn pretty<W: Write>(&self, printer: &mut Printer<W>) -> fmt::Result {
+        match self {
+            Block::BoundaryBlock(block) => block.pretty(printer),
+            Block::MainBlock(block) => block.pretty(printer),
+        }
+    }
+}
+
+impl Pretty for BlockHeader {
+    fn pretty<W: Write>(&self, printer: &mut Printer<W>) -> fmt::Result {
+        match self {
+            BlockHeader::BoundaryBlockHeader(header) => header.pretty(printer),
+            BlockHeader::MainBlockHeader(header) => header.pretty(printer),
+        }
+    }
+}
+
+impl Pretty for boundary::BlockHeader {
+    fn pretty<W: Write>(&self, printer: &mut Printer<W>) -> fmt::Result {
+        printer.field("type", "boundary")?;
+        let hash = BlockHeader::BoundaryBlockHeader(self.clone()).compute_hash();
+        printer.hash("hash", hash)?;
+        printer.field("protocol magic", self.protocol_magic)?;
+        printer.hash("previous block", &self.previous_header)?;
+        printer.field("epoch", self.consensus.epoch)?;
+        printer.field("chain difficulty", self.consensus.chain_difficulty)?;
+        printer.hash("body proof", &self.body_proof)?;
+        printer.raw_cbor("attributes", &self.extra_data.0)
+    }
+}
+
+impl Pretty for boundary::Block {
+    fn pretty<W: Write>(&self, printer: &mut Printer<W>) -> fmt::Result {
+        printer.item("header", &self.header)?;
+        printer.list(
+            "slot leaders",
+            self.body.slot_leaders.iter(),
+            |printer, name, leader| printer.hash(name, leader),
+        )?;
+        printer.raw_cbor("extra", &self.extra)
+    }
+}
+
+impl Pretty for normal::BlockHeader {
+    fn pretty<W: Write>(&self, printer: &mut Printer<W>) -> fmt::Result {
+        printer.field("type", "main")?;
+        let hash = BlockHeader::MainBlockHeader(self.clone()).compute_hash();
+        printer.hash("hash", hash)?;
+        printer.field("protocol magic", self.protocol_magic)?;
+        printer.hash("previous block", &self.previous_header)?;
+        printer.field("date", self.consensus.slot_id)?;
+        printer.field("chain difficulty", self.consensus.chain_difficulty)?;
+        printer.hash("leader", self.consensus.leader_key)?;
+        printer.field(
+            "signature",
+            match self.consensus.block_signature {
+                BlockSignature::Signature(_) => "leader",
+                BlockSignature::ProxyLight(_) => "light delegate",
+                BlockSignature::ProxyHeavy(_) => "heavy delegate",
+            },
+        )?;
+        printer.field("block version", self.extra_data.block_version)?;
+        printer.field(
+            "software version",
+            format!(
+                "{}:{}",
+                self.extra_data.software_version.application_name(),
+                self.extra_data.software_version.application_version
+            ),
+        )?;
+        printer.raw_cbor("attributes", &self.extra_data.attributes.0)?;
+        printer.section("body proof", |printer| {
+            printer.hash("transactions", &self.body_proof.tx)?;
+            printer.field(
+                "ssc",
+                match self.body_proof.mpc {
+                    types::SscProof::Commitments(..) => "commitments",
+                    types::SscProof::Openings(..) => "openings",
+                    types::SscProof::Shares(..) => "shares",
+                    types::SscProof::Certificate(..) => "certificates",
+                },
+            )?;
+            printer.hash("delegation", &self.body_proof.delegation)?;
+            printer.hash("update", &self.body_proof.update)
+        })
+    }
+}
+
+impl Pretty for normal::Block {
+    fn pretty<W: Write>(&self, printer: &mut Printer<W>) -> fmt::Result {
+        printer.item("header", &self.header)?;
+        printer.list(
+            "transactions",
+            self.body.tx.iter(),
+            |printer, name, txaux| printer.item(name, txaux),
+        )?;
+        printer.field(
+            "ssc",
+            match self.body.ssc {
+                normal::SscPayload::CommitmentsPayload(..) => "commitments",
+                normal::SscPayload::OpeningsPayload(..) => "openings",
+                normal::SscPayload::SharesPayload(..) => "shares",
+                normal::SscPayload::CertificatesPayload(..) => "certificates",
+            },
+        )?;
+        printer.field("delegations", self.body.delegation.iter().len())?;
+        printer.field("update", &self.body.update)?;
+        printer.raw_cbor("extra", &self.extra)
+    }
+}
+
+impl Pretty for TxAux {
+    fn pretty<W: Write>(&self, printer: &mut Printer<W>) -> fmt::Result {
+        self.tx.pretty(printer)?;
+        printer.list(
+            "witnesses",
+            self.witness.iter(),
+            |printer, name, witness| printer.item(name, witness),
+        )
+    }
+}
+
+impl Pretty for Tx {
+    fn pretty<W: Write>(&self, printer: &mut Printer<W>) -> fmt::Result {
+        printer.hash("id", self.id())?;
+        printer.list("inputs", self.inputs.iter(), |printer, name, input| {
+            printer.hash(name, input)
+        })?;
+        printer.list("outputs", self.outputs.iter(), |printer, name, output| {
+            printer.item(name, output)
+        })
+    }
+}
+
+impl Pretty for TxOut {
+    fn pretty<W: Write>(&self, printer: &mut Printer<W>) -> fmt::Result {
+        printer.hash("address", &self.address)?;
+        printer.field("value", self.value)
+    }
+}
+
+impl Pretty for TxInWitness {
+    fn pretty<W: Write>(&self, printer: &mut Printer<W>) -> fmt::Result {
+        match self {
+            TxInWitness::PkWitness(public_key, signature) => {
+                printer.field("type", "public key")?;
+                printer.hash("public key", public_key)?;
+                printer.hash("signature", signature)
+            }
+            TxInWitness::ScriptWitness(_, _) => printer.field("type", "script"),
+            TxInWitness::RedeemWitness(public_key, signature) => {
+                printer.field("type", "redeem")?;
+                printer.hash("public key", public_key)?;
+                printer.hash("signature", signature)
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use address::ExtendedAddr;
+    use config::NetworkMagic;
+    use hash::Blake2b256;
+    use hdwallet::{XPrv, XPRV_SIZE};
+    use tx::{TxWitness, TxoPointer};
+
+    fn txaux() -> TxAux {
+        let key = XPrv::normalize_bytes([1; XPRV_SIZE]);
+        let address = ExtendedAddr::new_simple(key.public(), NetworkMagic::NoMagic);
+        let inputs = (0..3)
+            .map(|index| TxoPointer::new(Blake2b256::new(&[1, 2]), index))
+            .collect();
+        let outputs = vec![TxOut::new(address, 1_000_000u32.into())];
+        let tx = Tx::new_with(inputs, outputs);
+        let witness: TxWitness = (0..3).map(|_| TxInWitness::fake()).collect();
+        TxAux::new(tx, witness)
+    }
+
+    #[test]
+    fn plain_transaction() {
+        let txaux = txaux();
+        let rendered = to_string(&txaux, Config::default());
+        assert!(rendered.starts_with(&format!("id: {}\n", txaux.tx.id())));
+        assert!(rendered.contains("inputs (3):\n  0: "));
+        assert!(rendered.contains("outputs (1):\n  0:\n    address: "));
+        assert!(rendered.contains("witnesses (3):\n"));
+        assert!(!rendered.contains("\x1b["));
+    }
+
+    #[test]
+    fn limits() {
+        let txaux = txaux();
+        let config = Config {
+            max_depth: Some(1),
+            max_items: Some(2),
+            ..Config::default()
+        };
+        let rendered = to_string(&txaux, config);
+        assert!(rendered.contains("inputs (3):\n  0: "));
+        assert!(rendered.contains("  ...: 1 more\n"));
+        // the outputs are too deep to be displayed
+        assert!(rendered.contains("  0: ...\n"));
+        assert!(!rendered.contains("address"));
+
+        let config = Config {
+            color: true,
+            ..Config::default()
+        };
+        assert!(to_string(&txaux, config).contains(ANSI_RESET));
+    }
+
+    #[test]
+    fn raw_cbor() {
+        let value = cbor_event::Value::U64(42);
+        let mut printer = Printer::new(String::new(), Config::default());
+        printer.raw_cbor("extra", &value).unwrap();
+        assert_eq!(printer.into_inner(), "extra: <2 bytes of CBOR>\n");
+
+        let config = Config {
+            raw_cbor: true,
+            ..Config::default()
+        };
+        let mut printer = Printer::new(String::new(), config);
+        printer.raw_cbor("extra", &value).unwrap();
+        assert_eq!(printer.into_inner(), "extra: 182a\n");
+    }
+}
@@ -1,12 +1,62 @@
+//! Signing tags
+//!
+//! The data signed by the different keys is prefixed with a tag telling
+//! what is signed, so a signature of one kind of data cannot be replayed
+//! as the signature of another kind.
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
 pub enum SigningTag {
     Tx = 0x01,
     RedeemTx = 0x02,
+    /// the VSS key and the expiry epoch of a VSS certificate
     VssCert = 0x03,
+    /// update proposal
     USProposal = 0x04,
+    /// SSC commitment
     Commitment = 0x05,
+    /// update vote
     USVote = 0x06,
     MainBlock = 0x07,
+    /// main block signed with a lightweight delegation
     MainBlockLight = 0x08,
+    /// main block signed with a heavyweight delegation
     MainBlockHeavy = 0x09,
+    /// proxy secret key (delegation certificate)
     ProxySK = 0x0a,
 }
+impl SigningTag {
+    pub fn from_u8(tag: u8) -> Option<Self> {
+        match tag {
+            0x01 => Some(SigningTag::Tx),
+            0x02 => Some(SigningTag::RedeemTx),
+            0x03 => Some(SigningTag::VssCert),
+            0x04 => Some(SigningTag::USProposal),
+            0x05 => Some(SigningTag::Commitment),
+            0x06 => Some(SigningTag::USVote),
+            0x07 => Some(SigningTag::MainBlock),
+            0x08 => Some(SigningTag::MainBlockLight),
+            0x09 => Some(SigningTag::MainBlockHeavy),
+            0x0a => Some(SigningTag::ProxySK),
+            _ => None,
+        }
+    }
+}
+impl From<SigningTag> for u8 {
+    fn from(tag: SigningTag) -> u8 {
+        tag as u8
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn from_u8() {
+        for tag in 0x01..0x0b {
+            assert_eq!(SigningTag::from_u8(tag).map(u8::from), Some(tag));
+        }
+        assert_eq!(SigningTag::from_u8(0x00), None);
+        assert_eq!(SigningTag::from_u8(0x0b), None);
+    }
+}
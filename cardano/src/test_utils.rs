@@ -0,0 +1,15 @@
+//! Helpers shared by the tests of the crate.
+
+use address::ExtendedAddr;
+use config::NetworkMagic;
+use hdwallet::{XPub, XPUB_SIZE};
+
+/// a public key, the same for the same seed
+pub fn mk_xpub(seed: u8) -> XPub {
+    XPub::from_bytes([seed; XPUB_SIZE])
+}
+
+/// the bootstrap era address of the public key `mk_xpub(seed)`
+pub fn mk_address(seed: u8) -> ExtendedAddr {
+    ExtendedAddr::new_simple(mk_xpub(seed), NetworkMagic::NoMagic)
+}
@@ -12,7 +12,7 @@ use std::{
 };
 
 use crate::{
-    address::{AddrType, Attributes, ExtendedAddr, SpendingData},
+    address::{AddrType, Attributes, ExtendedAddr, Script, SpendingData},
     coin::{self, Coin},
     config::ProtocolMagic,
     hash::Blake2b256,
@@ -29,6 +29,7 @@ use chain_core::property;
 pub type TxId = Blake2b256;
 
 impl property::TransactionId for TxId {}
+impl property::MessageId for TxId {}
 
 pub fn redeem_pubkey_to_txid(
     pubkey: &redeem::PublicKey,
@@ -83,9 +84,84 @@ impl cbor_event::se::Serialize for TxOut {
     }
 }
 
-type TODO = u8;
-type ValidatorScript = TODO;
-type RedeemerScript = TODO;
+/// the script locking the funds, revealed by the witness. Its hash is
+/// in the address (see `SpendingData::ScriptASD`).
+pub type ValidatorScript = Script;
+/// the script provided to unlock the funds
+pub type RedeemerScript = Script;
+
+/// Why a `ScriptWitness` was not accepted by a `ScriptEvaluator`
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ScriptError {
+    /// the evaluator does not execute scripts
+    NotSupported,
+    /// the redeemer does not unlock the validator
+    Rejected,
+}
+impl fmt::Display for ScriptError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            ScriptError::NotSupported => write!(f, "scripts not supported"),
+            ScriptError::Rejected => write!(f, "script rejected"),
+        }
+    }
+}
+impl std::error::Error for ScriptError {}
+
+/// Hook to execute the scripts of a `ScriptWitness`.
+///
+/// This library does not evaluate scripts itself, users that need to can
+/// plug an evaluator in `TxInWitness::verify_tx_with_scripts`.
+pub trait ScriptEvaluator {
+    /// succeed if `redeemer` unlocks `validator` for the given transaction
+    fn evaluate(
+        &self,
+        protocol_magic: ProtocolMagic,
+        validator: &ValidatorScript,
+        redeemer: &RedeemerScript,
+        tx: &Tx,
+    ) -> Result<(), ScriptError>;
+}
+
+/// The default `ScriptEvaluator`: rejects every script witness with
+/// `ScriptError::NotSupported`.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct NoScriptEvaluation;
+impl ScriptEvaluator for NoScriptEvaluation {
+    fn evaluate(
+        &self,
+        _protocol_magic: ProtocolMagic,
+        _validator: &ValidatorScript,
+        _redeemer: &RedeemerScript,
+        _tx: &Tx,
+    ) -> Result<(), ScriptError> {
+        Err(ScriptError::NotSupported)
+    }
+}
+
+/// `ScriptEvaluator` that does not execute the scripts, only checking
+/// the validator and the redeemer have the same version.
+///
+/// It accepts scripts that would fail to run: only use it if the scripts
+/// are known to be valid, e.g. when replaying a chain already validated
+/// by a full node.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct UncheckedScripts;
+impl ScriptEvaluator for UncheckedScripts {
+    fn evaluate(
+        &self,
+        _protocol_magic: ProtocolMagic,
+        validator: &ValidatorScript,
+        redeemer: &RedeemerScript,
+        _tx: &Tx,
+    ) -> Result<(), ScriptError> {
+        if validator.version == redeemer.version {
+            Ok(())
+        } else {
+            Err(ScriptError::Rejected)
+        }
+    }
+}
 
 /// Provide a witness to a specific transaction, generally by revealing
 /// all the hidden information from the tx and cryptographic signatures.
@@ -118,6 +194,24 @@ impl TxInWitness {
         TxInWitness::PkWitness(XPub::from_bytes([0u8; XPUB_SIZE]), fakesig)
     }
 
+    /// same as `fake`, for inputs spending redeem addresses
+    pub fn fake_redeem() -> Self {
+        let fakesig = redeem::Signature::from_bytes([0u8; redeem::SIGNATURE_SIZE]);
+        TxInWitness::RedeemWitness(
+            redeem::PublicKey::from_bytes([0u8; redeem::PUBLICKEY_SIZE]),
+            fakesig,
+        )
+    }
+
+    /// same as `fake`, for inputs spending script addresses, with scripts
+    /// of the given sizes (in bytes)
+    pub fn fake_script(validator_size: usize, redeemer_size: usize) -> Self {
+        TxInWitness::ScriptWitness(
+            Script::new(u16::max_value(), vec![0; validator_size]),
+            Script::new(u16::max_value(), vec![0; redeemer_size]),
+        )
+    }
+
     /// create a TxInWitness from a given private key `XPrv` for the given transaction id `TxId`.
     #[deprecated(note = "use new_extended_pk method instead")]
     pub fn new(protocol_magic: ProtocolMagic, key: &XPrv, txid: &TxId) -> Self {
@@ -165,7 +259,12 @@ impl TxInWitness {
 
                 &ea == address
             }
-            &TxInWitness::ScriptWitness(_, _) => unimplemented!(),
+            &TxInWitness::ScriptWitness(ref validator, _) => {
+                let sd = SpendingData::ScriptASD(validator.clone());
+                let ea = ExtendedAddr::new(address.addr_type, sd, address.attributes.clone());
+
+                &ea == address
+            }
             &TxInWitness::RedeemWitness(ref pk, _) => {
                 let sd = SpendingData::RedeemASD(pk.clone());
                 let ea = ExtendedAddr::new(address.addr_type, sd, address.attributes.clone());
@@ -177,20 +276,32 @@ impl TxInWitness {
 
     /// verify the signature against the given transation `Tx`
     ///
+    /// Script witnesses are rejected as scripts are not supported, see
+    /// `verify_tx_with_scripts`.
     pub fn verify_tx(&self, protocol_magic: ProtocolMagic, tx: &Tx) -> bool {
-        let vec = Self::prepare_byte_to_sign(protocol_magic, self.get_sign_tag(), &tx.id());
-        match self {
-            &TxInWitness::PkWitness(ref pk, ref sig) => pk.verify(&vec, sig),
-            &TxInWitness::ScriptWitness(_, _) => unimplemented!(),
-            &TxInWitness::RedeemWitness(ref pk, ref sig) => pk.verify(sig, &vec),
-        }
+        self.verify_tx_with_scripts(protocol_magic, tx, &NoScriptEvaluation)
     }
 
-    fn get_sign_tag(&self) -> SigningTag {
+    /// same as `verify_tx`, using `evaluator` to check the script witnesses
+    pub fn verify_tx_with_scripts<E: ScriptEvaluator + ?Sized>(
+        &self,
+        protocol_magic: ProtocolMagic,
+        tx: &Tx,
+        evaluator: &E,
+    ) -> bool {
         match self {
-            &TxInWitness::PkWitness(_, _) => SigningTag::Tx,
-            &TxInWitness::ScriptWitness(_, _) => unimplemented!(),
-            &TxInWitness::RedeemWitness(_, _) => SigningTag::RedeemTx,
+            &TxInWitness::PkWitness(ref pk, ref sig) => {
+                let vec = Self::prepare_byte_to_sign(protocol_magic, SigningTag::Tx, &tx.id());
+                pk.verify(&vec, sig)
+            }
+            &TxInWitness::ScriptWitness(ref validator, ref redeemer) => evaluator
+                .evaluate(protocol_magic, validator, redeemer, tx)
+                .is_ok(),
+            &TxInWitness::RedeemWitness(ref pk, ref sig) => {
+                let vec =
+                    Self::prepare_byte_to_sign(protocol_magic, SigningTag::RedeemTx, &tx.id());
+                pk.verify(sig, &vec)
+            }
         }
     }
 
@@ -214,7 +325,14 @@ impl cbor_event::se::Serialize for TxInWitness {
                     .serialize(signature)?;
                 se
             }
-            &TxInWitness::ScriptWitness(_, _) => unimplemented!(),
+            &TxInWitness::ScriptWitness(ref validator, ref redeemer) => {
+                serializer.write_unsigned_integer(1)?;
+                let mut se = Serializer::new_vec();
+                se.write_array(cbor_event::Len::Len(2))?
+                    .serialize(validator)?
+                    .serialize(redeemer)?;
+                se
+            }
             &TxInWitness::RedeemWitness(ref pk, ref signature) => {
                 serializer.write_unsigned_integer(2)?;
                 let mut se = Serializer::new_vec();
@@ -249,6 +367,21 @@ impl cbor_event::de::Deserialize for TxInWitness {
                 let sig = cbor_event::de::Deserialize::deserialize(&mut raw)?;
                 Ok(TxInWitness::PkWitness(pk, sig))
             }
+            1 => {
+                let tag = raw.tag()?;
+                if tag != 24 {
+                    return Err(cbor_event::Error::CustomError(format!(
+                        "Invalid Tag: {} but expected 24",
+                        tag
+                    )));
+                }
+                let bytes = raw.bytes()?;
+                let mut raw = Deserializer::from(std::io::Cursor::new(bytes));
+                raw.tuple(2, "TxInWitness::ScriptWitness")?;
+                let validator = cbor_event::de::Deserialize::deserialize(&mut raw)?;
+                let redeemer = cbor_event::de::Deserialize::deserialize(&mut raw)?;
+                Ok(TxInWitness::ScriptWitness(validator, redeemer))
+            }
             2 => {
                 let tag = raw.tag()?;
                 if tag != 24 {
@@ -546,6 +679,27 @@ impl cbor_event::se::Serialize for TxAux {
     }
 }
 
+/// Verify the witnesses of many transactions (e.g. the transactions of an
+/// epoch, or of a mempool), returns for each transaction whether it has
+/// exactly one witness per input and all its witnesses are valid, see
+/// `TxInWitness::verify_tx_with_scripts`.
+///
+/// The signatures are verified one by one, there is no batch verification.
+pub fn verify_txs<E: ScriptEvaluator + ?Sized>(
+    txs: &[TxAux],
+    protocol_magic: ProtocolMagic,
+    evaluator: &E,
+) -> Vec<bool> {
+    txs.iter()
+        .map(|txaux| {
+            txaux.witness.len() == txaux.tx.inputs.len()
+                && txaux.witness.iter().all(|witness| {
+                    witness.verify_tx_with_scripts(protocol_magic, &txaux.tx, evaluator)
+                })
+        })
+        .collect()
+}
+
 pub fn txaux_serialize<'se, W>(
     tx: &Tx,
     in_witnesses: &Vec<TxInWitness>,
@@ -695,6 +849,14 @@ impl chain_core::property::Transaction for Tx {
         &self.outputs
     }
 }
+impl chain_core::property::Message for TxAux {
+    type Id = TxId;
+
+    fn id(&self) -> Self::Id {
+        self.tx.id()
+    }
+}
+
 impl chain_core::property::Transaction for TxAux {
     type Input = TxoPointer;
     type Output = TxOut;
@@ -828,6 +990,52 @@ mod tests {
         assert!(cbor_event::test_encode_decode(&TxoPointer::new(txid, 666)).unwrap());
     }
 
+    #[test]
+    fn script_witness_encode_decode() {
+        let validator = Script::new(0, vec![1, 2, 3, 4]);
+        let redeemer = Script::new(0, vec![5, 6]);
+        let witness = TxInWitness::ScriptWitness(validator, redeemer);
+
+        assert!(cbor_event::test_encode_decode(&witness).expect("encode/decode ScriptWitness"));
+    }
+
+    #[test]
+    fn script_witness_verify_address() {
+        let validator = Script::new(0, vec![1, 2, 3, 4]);
+        let attrs = address::Attributes::new_bootstrap_era(None, NetworkMagic::NoMagic);
+        let ea = address::ExtendedAddr::new(
+            address::AddrType::ATScript,
+            address::SpendingData::ScriptASD(validator.clone()),
+            attrs,
+        );
+
+        let witness = TxInWitness::ScriptWitness(validator, Script::new(0, vec![5, 6]));
+        assert!(witness.verify_address(&ea));
+        let witness =
+            TxInWitness::ScriptWitness(Script::new(0, vec![7]), Script::new(0, vec![5, 6]));
+        assert!(!witness.verify_address(&ea));
+    }
+
+    #[test]
+    fn script_witness_rejected_by_default() {
+        let protocol_magic = ProtocolMagic::default();
+        let tx: Tx = Deserializer::from(std::io::Cursor::new(TX))
+            .deserialize()
+            .unwrap();
+        let validator = Script::new(0, vec![1]);
+        let redeemer = Script::new(0, vec![2]);
+        assert_eq!(
+            NoScriptEvaluation.evaluate(protocol_magic, &validator, &redeemer, &tx),
+            Err(ScriptError::NotSupported)
+        );
+        let witness = TxInWitness::ScriptWitness(validator, redeemer);
+        assert!(!witness.verify_tx(protocol_magic, &tx));
+
+        assert!(witness.verify_tx_with_scripts(protocol_magic, &tx, &UncheckedScripts));
+        let witness = TxInWitness::ScriptWitness(Script::new(0, vec![1]), Script::new(1, vec![2]));
+        assert!(!witness.verify_tx_with_scripts(protocol_magic, &tx, &UncheckedScripts));
+    }
+
     #[test]
     fn tx_decode() {
         let mut raw = Deserializer::from(std::io::Cursor::new(TX_IN));
@@ -934,6 +1142,63 @@ mod tests {
         assert!(txinwitness.verify(protocol_magic, &ea, &tx));
     }
 
+    #[test]
+    fn verify_txs_results() {
+        let protocol_magic = ProtocolMagic::default();
+        let seed = hdwallet::Seed::from_bytes(SEED);
+        let sk = hdwallet::XPrv::generate_from_seed(&seed);
+        let pk = sk.public();
+        let ea = address::ExtendedAddr::new_simple(pk, protocol_magic.into());
+
+        let mk_tx = |index| {
+            let mut tx = Tx::new();
+            tx.add_input(TxoPointer::new(TxId::new(&[0; 32]), index));
+            tx.add_output(TxOut::new(ea.clone(), Coin::new(42).unwrap()));
+            tx
+        };
+        let (tx1, tx2, tx3, tx4) = (mk_tx(1), mk_tx(2), mk_tx(3), mk_tx(4));
+        let txs = vec![
+            TxAux::new(
+                tx1.clone(),
+                vec![TxInWitness::new_extended_pk(protocol_magic, &sk, &tx1.id())].into(),
+            ),
+            // signing another transaction
+            TxAux::new(
+                tx2.clone(),
+                vec![TxInWitness::new_extended_pk(protocol_magic, &sk, &tx1.id())].into(),
+            ),
+            // more witnesses than inputs
+            TxAux::new(
+                tx3.clone(),
+                vec![
+                    TxInWitness::new_extended_pk(protocol_magic, &sk, &tx3.id()),
+                    TxInWitness::new_extended_pk(protocol_magic, &sk, &tx3.id()),
+                ]
+                .into(),
+            ),
+            // no witness
+            TxAux::new(tx4, vec![].into()),
+        ];
+
+        assert_eq!(
+            verify_txs(&txs, protocol_magic, &NoScriptEvaluation),
+            vec![true, false, false, false]
+        );
+        for (txaux, valid) in txs
+            .iter()
+            .zip(verify_txs(&txs, protocol_magic, &NoScriptEvaluation))
+        {
+            assert_eq!(
+                txaux.witness.len() == txaux.tx.inputs.len()
+                    && txaux
+                        .witness
+                        .iter()
+                        .all(|w| w.verify_tx(protocol_magic, &txaux.tx)),
+                valid
+            );
+        }
+    }
+
     #[test]
     fn txaux_decode() {
         let mut raw = Deserializer::from(std::io::Cursor::new(TX_AUX));
@@ -8,18 +8,24 @@
 //! total flexibility and abstraction/helpers.
 //!
 
-use coin::{Coin, CoinDiff};
+use address::ExtendedAddr;
+use cbor_event::{self, de::Deserializer, se::Serializer};
+use coin::{Coin, CoinDiff, SumCoins};
+use config::ProtocolMagic;
 use fee::{Fee, FeeAlgorithm};
+use std::io::{BufRead, Write};
 use std::iter::Iterator;
-use std::{error, fmt, iter, result};
+use std::{error, fmt, result};
 use tx::{txaux_serialize_size, Tx, TxAux, TxInWitness, TxOut, TxWitness, TxoPointer};
-use txutils::OutputPolicy;
-use {coin, fee};
+use txutils::{output_sum, DustPolicy, OutputPolicy, WitnessKind};
+use {coin, fee, redeem};
 
 /// Transaction Builder composed of inputs, outputs
-#[derive(Clone)]
+#[derive(Debug, Clone)]
 pub struct TxBuilder {
-    inputs: Vec<(TxoPointer, Coin)>,
+    inputs: Vec<(TxoPointer, Coin, WitnessKind)>,
+    /// the inputs only paying the fee, see `add_fee_input`
+    fee_inputs: Vec<(TxoPointer, Coin)>,
     outputs: Vec<TxOut>,
 }
 
@@ -28,11 +34,20 @@ pub enum Error {
     TxInvalidNoInput,
     TxInvalidNoOutput,
     TxNotEnoughTotalInput,
+    /// the inputs not paying the fee (first) do not balance exactly the
+    /// outputs (second), see `TxBuilder::add_fee_change`
+    TxPaymentNotBalanced(Coin, Coin),
     TxOverLimit(usize),
     /// this return as by-product the amount of spare coins left behind
     TxOutputPolicyNotEnoughCoins(Coin),
     TxSignaturesExceeded,
     TxSignaturesMismatch,
+    /// the witness index does not correspond to any input of the transaction
+    TxWitnessInvalidIndex(usize),
+    /// a witness was already added for this input
+    TxWitnessAlreadyPresent(usize),
+    /// the partial transactions do not share the same transaction
+    TxMismatch,
     CoinError(coin::Error),
     FeeError(fee::Error),
 }
@@ -45,6 +60,11 @@ impl fmt::Display for Error {
             Error::TxNotEnoughTotalInput => {
                 write!(f, "Transaction is invalid, already not enough input coins.")
             }
+            Error::TxPaymentNotBalanced(inputs, outputs) => write!(
+                f,
+                "Transaction is invalid, inputs ({}) do not balance the outputs ({})",
+                inputs, outputs
+            ),
             Error::TxOutputPolicyNotEnoughCoins(coins) => write!(
                 f,
                 "Output policy cannot be added, only {} currently leftover",
@@ -60,6 +80,13 @@ impl fmt::Display for Error {
                 f,
                 "Number of signatures does not match the number of witnesses"
             ),
+            Error::TxWitnessInvalidIndex(index) => {
+                write!(f, "Transaction has no input at index {}", index)
+            }
+            Error::TxWitnessAlreadyPresent(index) => {
+                write!(f, "Transaction already has a witness for input {}", index)
+            }
+            Error::TxMismatch => write!(f, "Partial transactions are not for the same transaction"),
             Error::CoinError(_) => write!(f, "Error while performing value operation"),
             Error::FeeError(_) => write!(f, "Error while performing fee operation"),
         }
@@ -96,13 +123,14 @@ impl TxBuilder {
     pub fn new() -> Self {
         TxBuilder {
             inputs: Vec::new(),
+            fee_inputs: Vec::new(),
             outputs: Vec::new(),
         }
     }
 
     /// Return the number of inputs in this builder
     pub fn number_inputs(&self) -> usize {
-        self.inputs.len()
+        self.inputs.len() + self.fee_inputs.len()
     }
 
     /// Add an input in a form of a txo pointer to the current state.
@@ -111,22 +139,53 @@ impl TxBuilder {
     /// associated value with the input, which is not available
     /// from the txo pointer structure.
     pub fn add_input(&mut self, iptr: &TxoPointer, ivalue: Coin) {
-        self.inputs.push((iptr.clone(), ivalue))
+        self.add_input_with_witness(iptr, ivalue, WitnessKind::PubKey)
+    }
+
+    /// Same as `add_input`, for an input signed with the given kind of
+    /// witness: the fee is estimated with a witness of this kind.
+    pub fn add_input_with_witness(&mut self, iptr: &TxoPointer, ivalue: Coin, kind: WitnessKind) {
+        self.inputs.push((iptr.clone(), ivalue, kind));
+        invariants::totals(self, "add_input");
+    }
+
+    /// Same as `add_input`, for an input spending a redeem (AVVM) address.
+    ///
+    /// Its witness will need to be added with `TxFinalized::add_redeem_witness`.
+    pub fn add_redeem_input(&mut self, iptr: &TxoPointer, ivalue: Coin) {
+        self.add_input_with_witness(iptr, ivalue, WitnessKind::Redeem)
+    }
+
+    /// Add an input only used to pay the fee of the transaction (e.g. from
+    /// an operational wallet), the other inputs balancing exactly the
+    /// outputs. What is left of the fee inputs once the fee is paid is
+    /// returned with `add_fee_change`.
+    ///
+    /// The fee inputs come after the other inputs in the transaction.
+    pub fn add_fee_input(&mut self, iptr: &TxoPointer, ivalue: Coin) {
+        self.fee_inputs.push((iptr.clone(), ivalue));
+        invariants::totals(self, "add_fee_input");
+    }
+
+    /// Return true if all the inputs of the builder are redeem inputs
+    pub fn is_redeem(&self) -> bool {
+        !self.inputs.is_empty()
+            && self.fee_inputs.is_empty()
+            && self.inputs.iter().all(|i| i.2 == WitnessKind::Redeem)
     }
 
     /// Add an output (address + coin value) to the current state
     pub fn add_output_value(&mut self, o: &TxOut) {
-        self.outputs.push(o.clone())
+        self.outputs.push(o.clone());
+        invariants::totals(self, "add_output_value");
     }
 
     fn apply_policy_with(&mut self, output_policy: &OutputPolicy, leftover: Coin) -> Vec<TxOut> {
-        match output_policy {
-            OutputPolicy::One(change_addr) => {
-                let txout = TxOut::new(change_addr.clone(), leftover);
-                self.add_output_value(&txout);
-                vec![txout]
-            }
+        let txouts = output_policy.change_outputs(leftover);
+        for txout in txouts.iter() {
+            self.add_output_value(txout);
         }
+        txouts
     }
 
     /// This associate all the leftover values, if any to specific outputs decided by the output policy.
@@ -141,12 +200,128 @@ impl TxBuilder {
     /// If there's no way to "fit" the output policy in the transaction building, as the fee cannot cover
     /// the basic overhead, then TxOutputPoliyNotEnoughCoins is returned with the amount of leftover coins.
     ///
+    /// With `OutputPolicy::MinChange`, a change less than the minimum is handled according to the
+    /// `DustPolicy`, in which case no output is created (and an empty array is returned).
+    ///
     /// Note: that the calculation is not done again if more inputs and outputs are added after this call,
     /// and in most typical cases this should be the last addition to the transaction.
     pub fn add_output_policy<'a, F: FeeAlgorithm>(
         &mut self,
         f: &'a F,
         o: &OutputPolicy,
+    ) -> Result<Vec<TxOut>> {
+        let outs = self.add_output_policy_unchecked(f, o)?;
+        invariants::balanced(self, f, "add_output_policy");
+        Ok(outs)
+    }
+
+    fn add_output_policy_unchecked<'a, F: FeeAlgorithm>(
+        &mut self,
+        f: &'a F,
+        o: &OutputPolicy,
+    ) -> Result<Vec<TxOut>> {
+        match o {
+            OutputPolicy::MinChange {
+                min_change,
+                dust,
+                policy,
+            } => {
+                let mut temp = self.clone();
+                match temp.add_output_policy(f, policy) {
+                    Ok(outs) => {
+                        if outs.is_empty()
+                            || self.outputs.is_empty()
+                            || output_sum(outs.iter())? >= *min_change
+                        {
+                            *self = temp;
+                            return Ok(outs);
+                        }
+                    }
+                    Err(Error::TxOutputPolicyNotEnoughCoins(max)) => {
+                        if self.outputs.is_empty() {
+                            return Err(Error::TxOutputPolicyNotEnoughCoins(max));
+                        }
+                    }
+                    Err(err) => return Err(err),
+                }
+                self.add_dust(f, *dust, policy)?;
+                Ok(Vec::new())
+            }
+            _ => self.add_change_outputs(f, o),
+        }
+    }
+
+    /// Pay the fee with the fee inputs (see `add_fee_input`), returning what
+    /// is left of them to the `change` address.
+    ///
+    /// The other inputs need to balance exactly the outputs already added,
+    /// otherwise `TxPaymentNotBalanced` is returned. As with
+    /// `add_output_policy`, the change output is returned, and none is
+    /// created if the fee inputs are consumed entirely by the fee.
+    pub fn add_fee_change<'a, F: FeeAlgorithm>(
+        &mut self,
+        f: &'a F,
+        change: &ExtendedAddr,
+    ) -> Result<Vec<TxOut>> {
+        let inputs = self.inputs.iter().map(|input| input.1).sum_coins()?;
+        let outputs = self.get_output_total()?;
+        if inputs != outputs {
+            return Err(Error::TxPaymentNotBalanced(inputs, outputs));
+        }
+        let outs = self.add_change_outputs(f, &OutputPolicy::One(change.clone()))?;
+        invariants::balanced(self, f, "add_fee_change");
+        Ok(outs)
+    }
+
+    /// handle the leftover according to the dust policy, considering the
+    /// transaction has at least one output
+    fn add_dust<'a, F: FeeAlgorithm>(
+        &mut self,
+        f: &'a F,
+        dust: DustPolicy,
+        policy: &OutputPolicy,
+    ) -> Result<()> {
+        let leftover = match self.balance(f)? {
+            CoinDiff::Zero => return Ok(()),
+            CoinDiff::Negative(_) => return Err(Error::TxNotEnoughTotalInput),
+            CoinDiff::Positive(leftover) => leftover,
+        };
+        match dust {
+            DustPolicy::DonateToFee => Ok(()),
+            DustPolicy::MergeIntoLastOutput => {
+                let change_addrs = policy.change_addresses();
+                let last = match self
+                    .outputs
+                    .iter()
+                    .rposition(|txout| change_addrs.contains(&&txout.address))
+                {
+                    None => return Ok(()),
+                    Some(last) => last,
+                };
+                let value = self.outputs[last].value;
+                // the bigger value may take more bytes, hence more fee: lower the
+                // merged amount until the fee is covered. Merging nothing covers
+                // it, so this terminates.
+                let mut merged = leftover;
+                loop {
+                    let mut temp = self.clone();
+                    temp.outputs[last].value = (value + merged)?;
+                    match temp.balance(f)? {
+                        CoinDiff::Negative(missing) => merged = merged.saturating_sub(missing),
+                        _ => {
+                            *self = temp;
+                            return Ok(());
+                        }
+                    }
+                }
+            }
+        }
+    }
+
+    fn add_change_outputs<'a, F: FeeAlgorithm>(
+        &mut self,
+        f: &'a F,
+        o: &OutputPolicy,
     ) -> Result<Vec<TxOut>> {
         // first check if there's any output, or not enough coins to cover
         match self.balance(f)? {
@@ -216,31 +391,65 @@ impl TxBuilder {
     ///
     /// For the LinearFee, it is related to the number of bytes that the representant
     /// txaux serialize to, but different algorithms can evaluate different criterions.
+    ///
+    /// Transactions spending only redeem inputs do not pay fee.
     pub fn calculate_fee<'a, F: FeeAlgorithm>(&self, f: &'a F) -> Result<Fee> {
+        if self.is_redeem() {
+            return Ok(Fee::new(Coin::zero()));
+        }
         let tx = self.clone().make_tx_nocheck();
-        let fake_witnesses = iter::repeat(TxInWitness::fake())
-            .take(self.inputs.len())
-            .collect();
-        let fee = f.calculate_for_txaux_component(&tx, &fake_witnesses)?;
+        let fee = f.calculate_for_txaux_component(&tx, &self.fake_witnesses())?;
         Ok(fee)
     }
 
+    /// witnesses of the same size as the ones the inputs will be signed with
+    fn fake_witnesses(&self) -> Vec<TxInWitness> {
+        self.inputs
+            .iter()
+            .map(|i| i.2.fake_witness())
+            .chain(self.fee_inputs.iter().map(|_| TxInWitness::fake()))
+            .collect()
+    }
+
+    /// Size in bytes of the transaction, without its witnesses, in the current
+    /// state of the builder.
+    pub fn current_size(&self) -> usize {
+        let tx = self.clone().make_tx_nocheck();
+        cbor!(&tx).expect("serialize the transaction").len()
+    }
+
+    /// Size in bytes of the transaction once all its inputs are signed, in the
+    /// current state of the builder. This is the size the protocol limits.
+    pub fn estimated_size(&self) -> usize {
+        let tx = self.clone().make_tx_nocheck();
+        txaux_serialize_size(&tx, &self.fake_witnesses())
+    }
+
+    /// Check the transaction, once signed, will not be over the size limit
+    /// `TxFinalized::make_txaux` enforces, returning its estimated size.
+    ///
+    /// Better find out before adding the inputs' witnesses.
+    pub fn check_size(&self) -> Result<usize> {
+        let size = self.estimated_size();
+        if size > TX_SIZE_LIMIT {
+            return Err(Error::TxOverLimit(size));
+        }
+        Ok(size)
+    }
+
     /// get the total of input coins
     pub fn get_input_total(&self) -> Result<Coin> {
-        let total = self
+        Ok(self
             .inputs
             .iter()
-            .fold(Coin::new(0), |acc, ref c| acc.and_then(|v| v + c.1))?;
-        Ok(total)
+            .map(|input| input.1)
+            .chain(self.fee_inputs.iter().map(|input| input.1))
+            .sum_coins()?)
     }
 
     /// get the total of output coins
     pub fn get_output_total(&self) -> Result<Coin> {
-        let total = self
-            .outputs
-            .iter()
-            .fold(Coin::new(0), |acc, ref c| acc.and_then(|v| v + c.value))?;
-        Ok(total)
+        Ok(self.outputs.iter().map(|output| output.value).sum_coins()?)
     }
 
     /// Try to return the differential between the outputs (including fees) and the inputs
@@ -263,18 +472,35 @@ impl TxBuilder {
     }
 
     fn make_tx_nocheck(self) -> Tx {
-        let inputs = self.inputs.iter().map(|(v, _)| v.clone()).collect();
+        let inputs = self
+            .inputs
+            .iter()
+            .map(|(v, _, _)| v.clone())
+            .chain(self.fee_inputs.iter().map(|(v, _)| v.clone()))
+            .collect();
         Tx::new_with(inputs, self.outputs)
     }
 
+    /// Return the kinds of witness expected for the inputs, in the order
+    /// of the inputs of the transaction (the fee inputs last)
+    pub fn witness_kinds(&self) -> Vec<WitnessKind> {
+        self.inputs
+            .iter()
+            .map(|i| i.2)
+            .chain(self.fee_inputs.iter().map(|_| WitnessKind::PubKey))
+            .collect()
+    }
+
     pub fn make_tx(self) -> Result<Tx> {
-        if self.inputs.len() == 0 {
+        if self.number_inputs() == 0 {
             return Err(Error::TxInvalidNoInput);
         }
         if self.outputs.len() == 0 {
             return Err(Error::TxInvalidNoOutput);
         }
-        Ok(self.make_tx_nocheck())
+        let tx = self.make_tx_nocheck();
+        invariants::canonical(&tx, "make_tx");
+        Ok(tx)
     }
 }
 
@@ -283,6 +509,8 @@ impl TxBuilder {
 pub struct TxFinalized {
     tx: Tx,
     witnesses: TxWitness,
+    /// the kinds of witness expected for the inputs, if known
+    witness_kinds: Option<Vec<WitnessKind>>,
 }
 
 impl TxFinalized {
@@ -291,6 +519,18 @@ impl TxFinalized {
         TxFinalized {
             tx: tx,
             witnesses: TxWitness::new(),
+            witness_kinds: None,
+        }
+    }
+
+    /// Same as `new`, knowing the kinds of witness expected for the
+    /// inputs (see `TxBuilder::witness_kinds`): with the
+    /// `strict-invariants` feature, the witnesses are checked against them.
+    pub fn new_with_kinds(tx: Tx, witness_kinds: Vec<WitnessKind>) -> Self {
+        TxFinalized {
+            tx: tx,
+            witnesses: TxWitness::new(),
+            witness_kinds: Some(witness_kinds),
         }
     }
 
@@ -304,26 +544,299 @@ impl TxFinalized {
             return Err(Error::TxSignaturesExceeded);
         }
         self.witnesses.push(witness);
+        invariants::witnesses(self, false, "add_witness");
         Ok(())
     }
 
+    /// Sign the transaction with the given redeem key, and add the
+    /// witness associated with the next input.
+    pub fn add_redeem_witness(
+        &mut self,
+        protocol_magic: ProtocolMagic,
+        key: &redeem::PrivateKey,
+    ) -> Result<()> {
+        let witness = TxInWitness::new_redeem_pk(protocol_magic, key, &self.tx.id());
+        self.add_witness(witness)
+    }
+
     pub fn make_txaux(self) -> Result<TxAux> {
         if self.witnesses.len() != self.tx.inputs.len() {
             return Err(Error::TxSignaturesMismatch);
         }
+        invariants::witnesses(&self, true, "make_txaux");
         let sz = txaux_serialize_size(&self.tx, &(*self.witnesses));
         if sz > TX_SIZE_LIMIT {
             return Err(Error::TxOverLimit(sz));
         }
         let txaux = TxAux::new(self.tx, self.witnesses);
+        invariants::canonical(&txaux, "make_txaux");
         Ok(txaux)
     }
 }
 
+/// A transaction collecting witnesses produced independently, for
+/// example by several parties each holding some of the keys.
+///
+/// It can be serialized to be passed around, then the partial
+/// transactions can be merged until no witness is missing.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct PartialTx {
+    tx: Tx,
+    witnesses: Vec<Option<TxInWitness>>,
+}
+
+impl PartialTx {
+    /// Take a transaction with no witness yet
+    pub fn new(tx: Tx) -> Self {
+        let witnesses = vec![None; tx.inputs.len()];
+        PartialTx {
+            tx: tx,
+            witnesses: witnesses,
+        }
+    }
+
+    pub fn tx(&self) -> &Tx {
+        &self.tx
+    }
+
+    /// Add the witness of the input at the given index
+    pub fn add_witness(&mut self, index: usize, witness: TxInWitness) -> Result<()> {
+        match self.witnesses.get_mut(index) {
+            None => Err(Error::TxWitnessInvalidIndex(index)),
+            Some(Some(_)) => Err(Error::TxWitnessAlreadyPresent(index)),
+            Some(slot) => {
+                *slot = Some(witness);
+                Ok(())
+            }
+        }
+    }
+
+    /// The index of the inputs with no witness yet
+    pub fn missing_witnesses(&self) -> Vec<usize> {
+        self.witnesses
+            .iter()
+            .enumerate()
+            .filter(|(_, w)| w.is_none())
+            .map(|(i, _)| i)
+            .collect()
+    }
+
+    /// Take the witnesses of another partial transaction of the same
+    /// transaction. Witnesses present in both need to be equal, nothing is
+    /// merged otherwise.
+    pub fn merge(&mut self, other: &PartialTx) -> Result<()> {
+        if self.tx != other.tx {
+            return Err(Error::TxMismatch);
+        }
+        for (index, witness) in other.witnesses.iter().enumerate() {
+            match (&self.witnesses[index], witness) {
+                (Some(present), Some(witness)) if present != witness => {
+                    return Err(Error::TxWitnessAlreadyPresent(index));
+                }
+                _ => {}
+            }
+        }
+        for (index, witness) in other.witnesses.iter().enumerate() {
+            if self.witnesses[index].is_none() {
+                self.witnesses[index] = witness.clone();
+            }
+        }
+        Ok(())
+    }
+
+    /// Create the signed transaction, all the witnesses need to be present
+    pub fn finalize(self) -> Result<TxAux> {
+        let mut finalized = TxFinalized::new(self.tx);
+        for witness in self.witnesses {
+            match witness {
+                None => return Err(Error::TxSignaturesMismatch),
+                Some(witness) => finalized.add_witness(witness)?,
+            }
+        }
+        finalized.make_txaux()
+    }
+}
+
+impl cbor_event::se::Serialize for PartialTx {
+    fn serialize<'se, W: Write>(
+        &self,
+        serializer: &'se mut Serializer<W>,
+    ) -> cbor_event::Result<&'se mut Serializer<W>> {
+        let nb_witnesses = self.witnesses.iter().filter(|w| w.is_some()).count();
+        serializer
+            .write_array(cbor_event::Len::Len(2))?
+            .serialize(&self.tx)?
+            .write_map(cbor_event::Len::Len(nb_witnesses as u64))?;
+        for (index, witness) in self.witnesses.iter().enumerate() {
+            if let Some(witness) = witness {
+                serializer
+                    .write_unsigned_integer(index as u64)?
+                    .serialize(witness)?;
+            }
+        }
+        Ok(serializer)
+    }
+}
+impl cbor_event::de::Deserialize for PartialTx {
+    fn deserialize<R: BufRead>(raw: &mut Deserializer<R>) -> cbor_event::Result<Self> {
+        raw.tuple(2, "PartialTx")?;
+        let mut partial = PartialTx::new(raw.deserialize()?);
+        let nb_witnesses = match raw.map()? {
+            cbor_event::Len::Len(len) => len,
+            cbor_event::Len::Indefinite => {
+                return Err(cbor_event::Error::CustomError(format!(
+                    "Invalid PartialTx: indefinite map of witnesses"
+                )));
+            }
+        };
+        for _ in 0..nb_witnesses {
+            let index = raw.unsigned_integer()? as usize;
+            let witness = raw.deserialize()?;
+            partial.add_witness(index, witness).map_err(|err| {
+                cbor_event::Error::CustomError(format!("Invalid PartialTx: {}", err))
+            })?;
+        }
+        Ok(partial)
+    }
+}
+
+/// The invariants of the transactions being built, re-checked at every
+/// step with the `strict-invariants` feature. A broken invariant panics
+/// with a report of the state.
+#[cfg(feature = "strict-invariants")]
+mod invariants {
+    use super::*;
+    use std::fmt::Debug;
+    use std::io::Cursor;
+
+    fn broken<T: Debug>(step: &str, invariant: &str, state: &T) -> ! {
+        panic!(
+            "invariant broken by `{}`: {}\nstate: {:#?}",
+            step, invariant, state
+        )
+    }
+
+    /// the totals of the inputs and of the outputs are valid amounts
+    pub fn totals(builder: &TxBuilder, step: &str) {
+        if let Err(err) = builder.get_input_total() {
+            broken(
+                step,
+                &format!("invalid total of the inputs: {}", err),
+                builder,
+            )
+        }
+        if let Err(err) = builder.get_output_total() {
+            broken(
+                step,
+                &format!("invalid total of the outputs: {}", err),
+                builder,
+            )
+        }
+    }
+
+    /// the inputs pay for the outputs and the fee (inputs = outputs + fee,
+    /// the fee possibly including some dust)
+    pub fn balanced<F: FeeAlgorithm>(builder: &TxBuilder, f: &F, step: &str) {
+        totals(builder, step);
+        match builder.balance(f) {
+            Ok(CoinDiff::Negative(missing)) => broken(
+                step,
+                &format!("the inputs miss {} to pay the outputs and the fee", missing),
+                builder,
+            ),
+            Err(err) => broken(
+                step,
+                &format!("cannot compute the balance: {}", err),
+                builder,
+            ),
+            Ok(_) => {}
+        }
+    }
+
+    /// at most one witness per input (exactly one once complete), each of
+    /// the kind expected for its input when the kinds are known
+    pub fn witnesses(finalized: &TxFinalized, complete: bool, step: &str) {
+        let (inputs, witnesses) = (finalized.tx.inputs.len(), finalized.witnesses.len());
+        if witnesses > inputs || (complete && witnesses != inputs) {
+            broken(
+                step,
+                &format!("{} witnesses for {} inputs", witnesses, inputs),
+                &*finalized.witnesses,
+            )
+        }
+        let kinds = match finalized.witness_kinds {
+            None => return,
+            Some(ref kinds) => kinds,
+        };
+        if kinds.len() != inputs {
+            broken(
+                step,
+                &format!("{} witness kinds for {} inputs", kinds.len(), inputs),
+                kinds,
+            )
+        }
+        for (index, (witness, kind)) in finalized.witnesses.iter().zip(kinds).enumerate() {
+            let expected = match (witness, kind) {
+                (TxInWitness::PkWitness(..), WitnessKind::PubKey) => true,
+                (TxInWitness::RedeemWitness(..), WitnessKind::Redeem) => true,
+                (TxInWitness::ScriptWitness(..), WitnessKind::Script { .. }) => true,
+                _ => false,
+            };
+            if !expected {
+                broken(
+                    step,
+                    &format!(
+                        "the witness of the input {} is not a {:?} witness",
+                        index, kind
+                    ),
+                    witness,
+                )
+            }
+        }
+    }
+
+    /// the encoding decodes back to the same value, and encodes again to
+    /// the same bytes
+    pub fn canonical<T>(value: &T, step: &str)
+    where
+        T: cbor_event::se::Serialize + cbor_event::de::Deserialize + PartialEq + Debug,
+    {
+        let encode = |value: &T| {
+            let mut se = Serializer::new_vec();
+            se.serialize(value).expect("encode in memory");
+            se.finalize()
+        };
+        let bytes = encode(value);
+        let mut raw = Deserializer::from(Cursor::new(&bytes));
+        match raw.deserialize::<T>() {
+            Ok(ref decoded) if decoded == value && encode(decoded) == bytes => {}
+            Ok(_) => broken(step, "non canonical encoding", value),
+            Err(err) => broken(step, &format!("cannot decode the encoding: {}", err), value),
+        }
+    }
+}
+
+#[cfg(not(feature = "strict-invariants"))]
+mod invariants {
+    use super::*;
+
+    #[inline(always)]
+    pub fn totals(_builder: &TxBuilder, _step: &str) {}
+
+    #[inline(always)]
+    pub fn balanced<F: FeeAlgorithm>(_builder: &TxBuilder, _f: &F, _step: &str) {}
+
+    #[inline(always)]
+    pub fn witnesses(_finalized: &TxFinalized, _complete: bool, _step: &str) {}
+
+    #[inline(always)]
+    pub fn canonical<T>(_value: &T, _step: &str) {}
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
-    use address::ExtendedAddr;
+    use config::ProtocolMagic;
     use fee::LinearFee;
     use hash::Blake2b256;
     use tx::{TxId, TxOut};
@@ -426,6 +939,36 @@ mod tests {
         }
     }
 
+    #[test]
+    fn txbuild_fee_inputs() {
+        let inputs = vec![fake_txopointer_val(1_000_000u32.into())];
+        let outputs = vec![TxOut::new(decode_addr(RADDRS[1]), 1_000_000u32.into())];
+        let fee_input = TxoPointer::new(fake_id(), 2);
+        let alg = LinearFee::default();
+        let change = decode_addr(RADDRS[2]);
+
+        let mut builder = build_input_outputs(&inputs[..], &outputs[..]);
+        builder.add_fee_input(&fee_input, 300_000u32.into());
+        let outs = builder.add_fee_change(&alg, &change).unwrap();
+        assert_eq!(outs.len(), 1);
+        assert_eq!(outs[0].address, change);
+        fee_is_minimal(builder.balance(&alg).unwrap());
+        assert_eq!(builder.number_inputs(), 2);
+
+        let tx = builder.make_tx().unwrap();
+        assert_eq!(tx.inputs[1], fee_input);
+        assert_eq!(tx.outputs[0].value, 1_000_000u32.into());
+
+        // the payment does not balance
+        let outputs = vec![TxOut::new(decode_addr(RADDRS[1]), 900_000u32.into())];
+        let mut builder = build_input_outputs(&inputs[..], &outputs[..]);
+        builder.add_fee_input(&fee_input, 300_000u32.into());
+        match builder.add_fee_change(&alg, &change) {
+            Err(Error::TxPaymentNotBalanced(_, _)) => {}
+            r => panic!("unexpected result {:?}", r),
+        }
+    }
+
     #[test]
     fn txbuild_auto_2() {
         let inputs = vec![fake_txopointer_val(1_000_000u32.into())];
@@ -450,4 +993,226 @@ mod tests {
             assert!(build_finalize(builder).is_ok())
         }
     }
+
+    #[test]
+    fn txbuild_split_change() {
+        let inputs = vec![fake_txopointer_val(1_000_000u32.into())];
+        let alg = LinearFee::default();
+        let out_policy = OutputPolicy::Split(vec![decode_addr(RADDRS[0]), decode_addr(RADDRS[2])]);
+        let outputs = vec![TxOut::new(decode_addr(RADDRS[1]), 100_000u32.into())];
+        let mut builder = build_input_outputs(&inputs[..], &outputs[..]);
+        let change = builder.add_output_policy(&alg, &out_policy).unwrap();
+
+        assert_eq!(change.len(), 2);
+        assert_eq!(change[0].address, decode_addr(RADDRS[0]));
+        assert_eq!(change[1].address, decode_addr(RADDRS[2]));
+        let diff = u64::from(change[0].value) - u64::from(change[1].value);
+        assert!(diff <= 1);
+        fee_is_acceptable(builder.balance(&alg).unwrap());
+        assert!(build_finalize(builder).is_ok());
+
+        // no output of zero value
+        let change = out_policy.change_outputs(Coin::unit());
+        assert_eq!(
+            change,
+            vec![TxOut::new(decode_addr(RADDRS[0]), Coin::unit())]
+        );
+        assert!(out_policy.change_outputs(Coin::zero()).is_empty());
+    }
+
+    #[test]
+    fn txbuild_dust_change() {
+        let alg = LinearFee::default();
+        let output = TxOut::new(decode_addr(RADDRS[1]), 500_000u32.into());
+        // the fee does not depend on the value of the inputs
+        let fee = build_input_outputs(&[fake_txopointer_val(1u32.into())], &[output.clone()])
+            .calculate_fee(&alg)
+            .unwrap();
+        let exact = (output.value + fee.to_coin()).unwrap();
+        let policy = |dust| OutputPolicy::MinChange {
+            min_change: 10_000u32.into(),
+            dust: dust,
+            policy: Box::new(OutputPolicy::One(decode_addr(RADDRS[2]))),
+        };
+        let dust_input = vec![fake_txopointer_val((exact + 1_000u32.into()).unwrap())];
+
+        // the dust goes to the fee
+        let mut builder = build_input_outputs(&dust_input, &[output.clone()]);
+        let change = builder.add_output_policy(&alg, &policy(DustPolicy::DonateToFee));
+        assert!(change.unwrap().is_empty());
+        assert_eq!(builder.outputs, vec![output.clone()]);
+        match builder.balance(&alg).unwrap() {
+            CoinDiff::Positive(c) => assert_eq!(c, 1_000u32.into()),
+            _ => panic!("the dust is not paid to the fee"),
+        }
+
+        // the dust is never merged into the payment
+        let mut builder = build_input_outputs(&dust_input, &[output.clone()]);
+        let change = builder.add_output_policy(&alg, &policy(DustPolicy::MergeIntoLastOutput));
+        assert!(change.unwrap().is_empty());
+        assert_eq!(builder.outputs, vec![output.clone()]);
+
+        // the dust goes to the output paying the change address
+        let previous_change = TxOut::new(decode_addr(RADDRS[2]), 1_000u32.into());
+        let outputs = vec![previous_change.clone(), output.clone()];
+        let fee = build_input_outputs(&[fake_txopointer_val(1u32.into())], &outputs)
+            .calculate_fee(&alg)
+            .unwrap();
+        let exact = (output_sum(outputs.iter()).unwrap() + fee.to_coin()).unwrap();
+        let dust_input = vec![fake_txopointer_val((exact + 1_000u32.into()).unwrap())];
+        let mut builder = build_input_outputs(&dust_input, &outputs);
+        let change = builder.add_output_policy(&alg, &policy(DustPolicy::MergeIntoLastOutput));
+        assert!(change.unwrap().is_empty());
+        assert_eq!(builder.outputs.len(), 2);
+        assert!(builder.outputs[0].value > previous_change.value);
+        assert_eq!(builder.outputs[1], output);
+        fee_is_acceptable(builder.balance(&alg).unwrap());
+
+        // above the minimum, the change output is created
+        let input = vec![fake_txopointer_val((exact + 100_000u32.into()).unwrap())];
+        let mut builder = build_input_outputs(&input, &[output.clone()]);
+        let change = builder.add_output_policy(&alg, &policy(DustPolicy::DonateToFee));
+        assert_eq!(change.unwrap().len(), 1);
+        assert_eq!(builder.outputs.len(), 2);
+        fee_is_minimal(builder.balance(&alg).unwrap());
+    }
+
+    #[test]
+    fn txbuild_size() {
+        let alg = LinearFee::default();
+        let inputs = vec![fake_txopointer_val(100_000_000u32.into())];
+        let output = TxOut::new(decode_addr(RADDRS[0]), 1_000u32.into());
+        let mut builder = build_input_outputs(&inputs[..], &[output.clone()]);
+
+        let size = builder.estimated_size();
+        assert!(size > builder.current_size());
+        assert_eq!(builder.check_size().unwrap(), size);
+        // the fee is proportional to the estimated size
+        assert_eq!(
+            builder.calculate_fee(&alg).unwrap(),
+            alg.estimate(size).unwrap()
+        );
+
+        while builder.estimated_size() <= TX_SIZE_LIMIT {
+            for _ in 0..100 {
+                builder.add_output_value(&output);
+            }
+        }
+        match builder.check_size() {
+            Err(Error::TxOverLimit(size)) => assert_eq!(size, builder.estimated_size()),
+            r => panic!("unexpected result {:?}", r),
+        }
+    }
+
+    #[test]
+    fn txbuild_redeem_no_fee() {
+        let alg = LinearFee::default();
+        let key = redeem::PrivateKey::generate(&[1u8; redeem::PRIVATEKEY_SIZE]).unwrap();
+        let mut builder = TxBuilder::new();
+        builder.add_redeem_input(&TxoPointer::new(fake_id(), 0), 1_000_000u32.into());
+        builder.add_output_value(&TxOut::new(decode_addr(RADDRS[1]), 1_000_000u32.into()));
+
+        assert!(builder.is_redeem());
+        assert_eq!(builder.calculate_fee(&alg).unwrap(), Fee::new(Coin::zero()));
+        fee_is_minimal(builder.balance(&alg).unwrap());
+
+        let protocol_magic = ProtocolMagic::default();
+        let kinds = builder.witness_kinds();
+        let mut finalizer = TxFinalized::new_with_kinds(builder.make_tx().unwrap(), kinds);
+        finalizer.add_redeem_witness(protocol_magic, &key).unwrap();
+        let txaux = finalizer.make_txaux().unwrap();
+        match txaux.witness[0] {
+            TxInWitness::RedeemWitness(ref pk, _) => assert_eq!(pk, &key.public()),
+            ref witness => panic!("expected a redeem witness, got {}", witness),
+        }
+        assert!(txaux.witness[0].verify_tx(protocol_magic, &txaux.tx));
+    }
+
+    #[test]
+    fn txbuild_mixed_redeem_fee() {
+        let alg = LinearFee::default();
+        let inputs = vec![
+            fake_txopointer_val(1_000_000u32.into()),
+            (TxoPointer::new(fake_id(), 2), 1_000_000u32.into()),
+        ];
+        let outputs = vec![TxOut::new(decode_addr(RADDRS[1]), 1_000_000u32.into())];
+        let pk_only = build_input_outputs(&inputs[..], &outputs[..]);
+
+        let mut mixed = build_input_outputs(&inputs[..1], &outputs[..]);
+        mixed.add_redeem_input(&inputs[1].0, inputs[1].1);
+
+        // the redeem witness is smaller than the extended public key one
+        let mixed_fee = mixed.calculate_fee(&alg).unwrap();
+        assert!(!mixed.is_redeem());
+        assert!(mixed_fee > Fee::new(Coin::zero()));
+        assert!(mixed_fee < pk_only.calculate_fee(&alg).unwrap());
+    }
+
+    #[test]
+    fn txbuild_script_witness_fee() {
+        let alg = LinearFee::default();
+        let inputs = vec![fake_txopointer_val(1_000_000u32.into())];
+        let outputs = vec![TxOut::new(decode_addr(RADDRS[1]), 1_000_000u32.into())];
+        let pk = build_input_outputs(&inputs[..], &outputs[..]);
+
+        let mut script = build_input_outputs(&[], &outputs[..]);
+        let kind = WitnessKind::Script {
+            validator_size: 1024,
+            redeemer_size: 64,
+        };
+        script.add_input_with_witness(&inputs[0].0, inputs[0].1, kind);
+
+        assert!(script.estimated_size() > pk.estimated_size() + 1024);
+        assert!(script.calculate_fee(&alg).unwrap() > pk.calculate_fee(&alg).unwrap());
+    }
+
+    #[test]
+    #[cfg(feature = "strict-invariants")]
+    #[should_panic(expected = "is not a Redeem witness")]
+    fn witness_of_the_wrong_kind() {
+        let mut builder = TxBuilder::new();
+        builder.add_redeem_input(&TxoPointer::new(fake_id(), 0), 1_000_000u32.into());
+        builder.add_output_value(&TxOut::new(decode_addr(RADDRS[1]), 1_000_000u32.into()));
+
+        let kinds = builder.witness_kinds();
+        let mut finalizer = TxFinalized::new_with_kinds(builder.make_tx().unwrap(), kinds);
+        let _ = finalizer.add_witness(TxInWitness::fake());
+    }
+
+    #[test]
+    fn partial_tx_multisig() {
+        let inputs = vec![
+            (TxoPointer::new(fake_id(), 0), 1_000_000u32.into()),
+            (TxoPointer::new(fake_id(), 1), 1_000_000u32.into()),
+        ];
+        let outputs = vec![TxOut::new(decode_addr(RADDRS[1]), 1_000_000u32.into())];
+        let tx = build_input_outputs(&inputs[..], &outputs[..])
+            .make_tx()
+            .unwrap();
+
+        let mut first = PartialTx::new(tx.clone());
+        let mut second = PartialTx::new(tx);
+        first.add_witness(0, TxInWitness::fake()).unwrap();
+        second.add_witness(1, TxInWitness::fake_redeem()).unwrap();
+        assert!(first.add_witness(2, TxInWitness::fake()).is_err());
+        assert!(first.add_witness(0, TxInWitness::fake()).is_err());
+        assert_eq!(first.missing_witnesses(), vec![1]);
+        assert!(first.clone().finalize().is_err());
+
+        // the partial transactions are passed around serialized
+        let bytes = cbor!(&second).unwrap();
+        let mut raw = Deserializer::from(::std::io::Cursor::new(bytes));
+        let second: PartialTx = raw.deserialize_complete().unwrap();
+
+        // a conflicting witness leaves the partial transaction untouched
+        let mut conflict = second.clone();
+        conflict.witnesses[0] = Some(TxInWitness::fake_redeem());
+        assert!(first.merge(&conflict).is_err());
+        assert_eq!(first.missing_witnesses(), vec![1]);
+
+        first.merge(&second).unwrap();
+        assert!(first.missing_witnesses().is_empty());
+        let txaux = first.finalize().unwrap();
+        assert_eq!(txaux.witness[1], TxInWitness::fake_redeem());
+    }
 }
@@ -1,5 +1,9 @@
 use address::ExtendedAddr;
-use coin::{self, Coin};
+use coin::{self, Coin, CoinDiff, SumCoins};
+use config::ProtocolMagic;
+use fee::{self, Fee, FeeAlgorithm};
+use std::collections::{BTreeMap, BTreeSet};
+use std::{error, fmt, result};
 use tx::*;
 
 /// This is a TxoPointer with extra data associated:
@@ -16,12 +20,117 @@ pub struct TxoPointerInfo<Addressing> {
 
 /// Output Policy chosen.
 ///
-/// For now this is just a placeholder of a single address,
-/// but adding a ratio driven list of addresses seems
-/// a useful flexibility to have
+/// Decides where the change (the leftover of the inputs, once the outputs
+/// and the fee are paid) goes.
 #[derive(Debug, Clone)]
 pub enum OutputPolicy {
+    /// all the change goes to a single address
     One(ExtendedAddr),
+    /// the change is split evenly across the addresses, the remainder of
+    /// the division going to the first one. The list must not be empty.
+    ///
+    /// The addresses getting nothing (the change is less than the number of
+    /// addresses) have no output.
+    Split(Vec<ExtendedAddr>),
+    /// apply `policy`, unless the change is less than `min_change`, in
+    /// which case the change is handled according to `dust` instead of
+    /// creating dust outputs.
+    ///
+    /// The dust handling only applies if the transaction has other
+    /// outputs, otherwise the change is its only output and is kept.
+    MinChange {
+        min_change: Coin,
+        dust: DustPolicy,
+        policy: Box<OutputPolicy>,
+    },
+}
+impl OutputPolicy {
+    /// the outputs paying `change` according to the policy, not
+    /// considering the dust handling. There is no output of zero value.
+    pub fn change_outputs(&self, change: Coin) -> Vec<TxOut> {
+        match self {
+            OutputPolicy::One(_) if change == Coin::zero() => Vec::new(),
+            OutputPolicy::One(change_addr) => vec![TxOut::new(change_addr.clone(), change)],
+            OutputPolicy::Split(change_addrs) => {
+                if change_addrs.is_empty() {
+                    return Vec::new();
+                }
+                let total = u64::from(change);
+                let nb = change_addrs.len() as u64;
+                change_addrs
+                    .iter()
+                    .enumerate()
+                    .map(|(i, change_addr)| {
+                        let value = if i == 0 {
+                            total / nb + total % nb
+                        } else {
+                            total / nb
+                        };
+                        // cannot fail, it is less than `change`
+                        TxOut::new(change_addr.clone(), Coin::new(value).unwrap())
+                    })
+                    .filter(|txout| txout.value != Coin::zero())
+                    .collect()
+            }
+            OutputPolicy::MinChange { policy, .. } => policy.change_outputs(change),
+        }
+    }
+
+    /// the addresses the change is paid to
+    pub fn change_addresses(&self) -> Vec<&ExtendedAddr> {
+        match self {
+            OutputPolicy::One(change_addr) => vec![change_addr],
+            OutputPolicy::Split(change_addrs) => change_addrs.iter().collect(),
+            OutputPolicy::MinChange { policy, .. } => policy.change_addresses(),
+        }
+    }
+}
+
+/// What to do with a change too small to be worth an output
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum DustPolicy {
+    /// leave it to the fee
+    DonateToFee,
+    /// add it to the last output of the transaction paying one of the
+    /// change addresses of the policy (e.g. the change of a previous
+    /// payment), never to an output paying someone else. Without such an
+    /// output it is left to the fee.
+    MergeIntoLastOutput,
+}
+
+/// The kind of witness an input will be signed with. The witnesses do not
+/// have the same size, hence the same cost in fee.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "generic-serialization", derive(Serialize, Deserialize))]
+pub enum WitnessKind {
+    /// an extended public key and its signature (the addresses of the
+    /// wallets)
+    PubKey,
+    /// a redeem (AVVM) public key and its signature
+    Redeem,
+    /// the validator and redeemer scripts, of the given sizes in bytes
+    Script {
+        validator_size: usize,
+        redeemer_size: usize,
+    },
+}
+impl WitnessKind {
+    /// a witness of this kind, for the size estimations
+    pub fn fake_witness(&self) -> TxInWitness {
+        match self {
+            WitnessKind::PubKey => TxInWitness::fake(),
+            WitnessKind::Redeem => TxInWitness::fake_redeem(),
+            WitnessKind::Script {
+                validator_size,
+                redeemer_size,
+            } => TxInWitness::fake_script(*validator_size, *redeemer_size),
+        }
+    }
+}
+impl Default for WitnessKind {
+    fn default() -> Self {
+        WitnessKind::PubKey
+    }
 }
 
 /// This is a Resolved version of a `TxoPointer`.
@@ -32,12 +141,19 @@ pub enum OutputPolicy {
 /// It also contains the `TxOut` the value present at the given
 /// `TxoPointer`'s `TxId` and _index_ in the block chain.
 ///
+/// Build it with `Input::new` (and `with_witness_kind`) rather than with
+/// the fields: more may be added, as `witness_kind` was.
+///
 #[derive(PartialEq, Eq, Debug, Clone)]
 #[cfg_attr(feature = "generic-serialization", derive(Serialize, Deserialize))]
 pub struct Input<Addressing> {
     pub ptr: TxoPointer,
     pub value: TxOut,
     pub addressing: Addressing,
+    /// the kind of witness the input will be signed with, accounted for
+    /// in the fee estimations of the input selection
+    #[cfg_attr(feature = "generic-serialization", serde(default))]
+    pub witness_kind: WitnessKind,
 }
 impl<Addressing> Input<Addressing> {
     pub fn new(ptr: TxoPointer, value: TxOut, addressing: Addressing) -> Self {
@@ -45,14 +161,361 @@ impl<Addressing> Input<Addressing> {
             ptr: ptr,
             value: value,
             addressing: addressing,
+            witness_kind: WitnessKind::PubKey,
         }
     }
 
+    /// set the kind of witness the input will be signed with (`PubKey` by
+    /// default)
+    pub fn with_witness_kind(mut self, witness_kind: WitnessKind) -> Self {
+        self.witness_kind = witness_kind;
+        self
+    }
+
     pub fn value(&self) -> Coin {
         self.value.value
     }
 }
 
 pub fn output_sum<'a, O: 'a + Iterator<Item = &'a TxOut>>(o: O) -> coin::Result<Coin> {
-    o.fold(Coin::new(0), |acc, ref c| acc.and_then(|v| v + c.value))
+    o.map(|output| output.value).sum_coins()
+}
+
+/// The set of unspent outputs a transaction is checked against.
+pub type UtxoSet = BTreeMap<TxoPointer, TxOut>;
+
+/// Violation found by `precheck`.
+#[derive(Debug)]
+pub enum PrecheckError {
+    NoInputs,
+    NoOutputs,
+    /// the given input appears more than once in the transaction
+    DuplicateInput(TxoPointer),
+    /// the given input does not exist in the utxo set, or has already been spent
+    MissingInput(TxoPointer),
+    /// the number of witnesses (found) does not match the number of inputs (expected)
+    WitnessesMismatch {
+        expected: usize,
+        found: usize,
+    },
+    InputsTooBig,
+    OutputsTooBig,
+    /// the inputs do not cover the outputs and the minimal fee
+    NotBalanced {
+        inputs: Coin,
+        outputs: Coin,
+        fee: Fee,
+    },
+    FeeError(fee::Error),
+    /// the witness of the given input is not signed by the owner of the
+    /// spent output, or its signature is invalid
+    InvalidWitness(TxoPointer),
+    /// the script witness of the given input is not accepted
+    ScriptWitness(TxoPointer, ScriptError),
+}
+impl fmt::Display for PrecheckError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            PrecheckError::NoInputs => write!(f, "transaction has no inputs"),
+            PrecheckError::NoOutputs => write!(f, "transaction has no outputs"),
+            PrecheckError::DuplicateInput(ptr) => {
+                write!(f, "input {} is spent more than once", ptr)
+            }
+            PrecheckError::MissingInput(ptr) => {
+                write!(f, "input {} does not exist or is already spent", ptr)
+            }
+            PrecheckError::WitnessesMismatch { expected, found } => write!(
+                f,
+                "transaction has {} witnesses but {} inputs",
+                found, expected
+            ),
+            PrecheckError::InputsTooBig => write!(f, "sum of inputs exceeds limit"),
+            PrecheckError::OutputsTooBig => write!(f, "sum of outputs exceeds limit"),
+            PrecheckError::NotBalanced {
+                inputs,
+                outputs,
+                fee,
+            } => write!(
+                f,
+                "inputs ({}) do not cover outputs ({}) and fee ({})",
+                inputs,
+                outputs,
+                fee.to_coin()
+            ),
+            PrecheckError::FeeError(_) => write!(f, "fee calculation failed"),
+            PrecheckError::InvalidWitness(ptr) => {
+                write!(f, "invalid witness for input {}", ptr)
+            }
+            PrecheckError::ScriptWitness(ptr, _) => {
+                write!(f, "script witness for input {} not accepted", ptr)
+            }
+        }
+    }
+}
+impl error::Error for PrecheckError {
+    fn cause(&self) -> Option<&error::Error> {
+        match self {
+            PrecheckError::FeeError(ref err) => Some(err),
+            PrecheckError::ScriptWitness(_, ref err) => Some(err),
+            _ => None,
+        }
+    }
+}
+impl From<fee::Error> for PrecheckError {
+    fn from(e: fee::Error) -> Self {
+        PrecheckError::FeeError(e)
+    }
+}
+
+/// Check an assembled transaction against the given utxo set before broadcasting it.
+///
+/// This verifies that:
+///
+/// * the transaction has inputs and outputs, and one witness per input;
+/// * no input is spent twice within the transaction;
+/// * every input exists (and hence is unspent) in `utxos`;
+/// * the inputs cover the outputs plus the minimal fee required by `fee_alg`.
+///
+/// Transactions spending only redeem inputs are not required to pay a fee,
+/// in line with the ledger rules.
+///
+/// On success, the fee effectively paid by the transaction is returned.
+/// Witness signatures are not verified here, see `block::Verify` for that.
+pub fn precheck<F: FeeAlgorithm>(
+    txaux: &TxAux,
+    utxos: &UtxoSet,
+    fee_alg: &F,
+) -> result::Result<Fee, PrecheckError> {
+    let tx = &txaux.tx;
+    if tx.inputs.is_empty() {
+        return Err(PrecheckError::NoInputs);
+    }
+    if tx.outputs.is_empty() {
+        return Err(PrecheckError::NoOutputs);
+    }
+    if tx.inputs.len() != txaux.witness.len() {
+        return Err(PrecheckError::WitnessesMismatch {
+            expected: tx.inputs.len(),
+            found: txaux.witness.len(),
+        });
+    }
+
+    let mut seen = BTreeSet::new();
+    let mut inputs = Coin::zero();
+    for ptr in tx.inputs.iter() {
+        if !seen.insert(ptr) {
+            return Err(PrecheckError::DuplicateInput(ptr.clone()));
+        }
+        match utxos.get(ptr) {
+            None => return Err(PrecheckError::MissingInput(ptr.clone())),
+            Some(txout) => {
+                inputs = (inputs + txout.value).map_err(|_| PrecheckError::InputsTooBig)?;
+            }
+        }
+    }
+
+    let outputs = output_sum(tx.outputs.iter()).map_err(|_| PrecheckError::OutputsTooBig)?;
+
+    let only_redeems = txaux.witness.iter().all(|w| match w {
+        TxInWitness::RedeemWitness(_, _) => true,
+        _ => false,
+    });
+    let min_fee = if only_redeems {
+        Fee::new(Coin::zero())
+    } else {
+        fee_alg.calculate_for_txaux(txaux)?
+    };
+
+    let not_balanced = PrecheckError::NotBalanced {
+        inputs,
+        outputs,
+        fee: min_fee,
+    };
+    let outputs_fee = (outputs + min_fee.to_coin()).map_err(|_| PrecheckError::OutputsTooBig)?;
+    match inputs.differential(outputs_fee) {
+        CoinDiff::Negative(_) => Err(not_balanced),
+        CoinDiff::Zero => Ok(min_fee),
+        CoinDiff::Positive(_) => {
+            // cannot underflow, inputs are greater than outputs + fee
+            let paid = (inputs - outputs).map_err(|_| not_balanced)?;
+            Ok(Fee::new(paid))
+        }
+    }
+}
+
+/// Check a transaction signed elsewhere (by another tool, an hardware
+/// wallet...) against the given utxo set before tracking or broadcasting it.
+///
+/// On top of the checks of `precheck`, every witness is verified to be the
+/// one of the address owning the spent output, and its signature to be
+/// valid for the transaction.
+///
+/// Script witnesses are rejected with `ScriptError::NotSupported`, see
+/// `precheck_signed_with_scripts`.
+pub fn precheck_signed<F: FeeAlgorithm>(
+    txaux: &TxAux,
+    utxos: &UtxoSet,
+    protocol_magic: ProtocolMagic,
+    fee_alg: &F,
+) -> result::Result<Fee, PrecheckError> {
+    precheck_signed_with_scripts(txaux, utxos, protocol_magic, fee_alg, &NoScriptEvaluation)
+}
+
+/// same as `precheck_signed`, using `evaluator` to check the script
+/// witnesses
+pub fn precheck_signed_with_scripts<F: FeeAlgorithm, E: ScriptEvaluator + ?Sized>(
+    txaux: &TxAux,
+    utxos: &UtxoSet,
+    protocol_magic: ProtocolMagic,
+    fee_alg: &F,
+    evaluator: &E,
+) -> result::Result<Fee, PrecheckError> {
+    let fee = precheck(txaux, utxos, fee_alg)?;
+    for (ptr, witness) in txaux.tx.inputs.iter().zip(txaux.witness.iter()) {
+        // the input is in the utxo set, checked by `precheck`
+        let spent = &utxos[ptr];
+        if !witness.verify_address(&spent.address) {
+            return Err(PrecheckError::InvalidWitness(ptr.clone()));
+        }
+        if let TxInWitness::ScriptWitness(validator, redeemer) = witness {
+            evaluator
+                .evaluate(protocol_magic, validator, redeemer, &txaux.tx)
+                .map_err(|err| PrecheckError::ScriptWitness(ptr.clone(), err))?;
+        } else if !witness.verify_tx(protocol_magic, &txaux.tx) {
+            return Err(PrecheckError::InvalidWitness(ptr.clone()));
+        }
+    }
+    Ok(fee)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use address::{AddrType, Attributes, Script, SpendingData};
+    use config::NetworkMagic;
+    use fee::LinearFee;
+    use hash::Blake2b256;
+    use hdwallet::{XPrv, XPRV_SIZE};
+    use util::{base58, try_from_slice::TryFromSlice};
+
+    const RADDR: &str = "Ae2tdPwUPEZKmwoy3AU3cXb5Chnasj6mvVNxV1H11997q3VW5ihbSfQwGpm";
+
+    fn decode_addr(addr_str: &str) -> ExtendedAddr {
+        let bytes = base58::decode(addr_str).unwrap();
+        ExtendedAddr::try_from_slice(&bytes).unwrap()
+    }
+
+    fn ptr(index: u32) -> TxoPointer {
+        TxoPointer::new(Blake2b256::new(&[1, 2]), index)
+    }
+
+    fn utxos(values: &[u64]) -> UtxoSet {
+        values
+            .iter()
+            .enumerate()
+            .map(|(i, v)| {
+                (
+                    ptr(i as u32),
+                    TxOut::new(decode_addr(RADDR), Coin::new(*v).unwrap()),
+                )
+            })
+            .collect()
+    }
+
+    fn txaux(inputs: Vec<TxoPointer>, output: u64) -> TxAux {
+        let witness = inputs.iter().map(|_| TxInWitness::fake()).collect();
+        let outputs = vec![TxOut::new(decode_addr(RADDR), Coin::new(output).unwrap())];
+        TxAux::new(Tx::new_with(inputs, outputs), witness)
+    }
+
+    #[test]
+    fn precheck_balanced() {
+        let alg = LinearFee::default();
+        let txaux = txaux(vec![ptr(0), ptr(1)], 500_000);
+        let fee = precheck(&txaux, &utxos(&[400_000, 400_000]), &alg).unwrap();
+        assert_eq!(fee.to_coin(), Coin::new(300_000).unwrap());
+    }
+
+    #[test]
+    fn precheck_duplicate_input() {
+        let alg = LinearFee::default();
+        let txaux = txaux(vec![ptr(0), ptr(0)], 500_000);
+        match precheck(&txaux, &utxos(&[1_000_000]), &alg) {
+            Err(PrecheckError::DuplicateInput(p)) => assert_eq!(p, ptr(0)),
+            r => panic!("unexpected result {:?}", r),
+        }
+    }
+
+    #[test]
+    fn precheck_missing_input() {
+        let alg = LinearFee::default();
+        let txaux = txaux(vec![ptr(0), ptr(3)], 500_000);
+        match precheck(&txaux, &utxos(&[1_000_000]), &alg) {
+            Err(PrecheckError::MissingInput(p)) => assert_eq!(p, ptr(3)),
+            r => panic!("unexpected result {:?}", r),
+        }
+    }
+
+    #[test]
+    fn precheck_not_enough_for_fee() {
+        let alg = LinearFee::default();
+        let txaux = txaux(vec![ptr(0)], 999_000);
+        match precheck(&txaux, &utxos(&[1_000_000]), &alg) {
+            Err(PrecheckError::NotBalanced { .. }) => {}
+            r => panic!("unexpected result {:?}", r),
+        }
+    }
+
+    #[test]
+    fn precheck_signed_witnesses() {
+        let alg = LinearFee::default();
+        let pm = ProtocolMagic::default();
+        let key = XPrv::normalize_bytes([1; XPRV_SIZE]);
+        let owner = ExtendedAddr::new_simple(key.public(), NetworkMagic::NoMagic);
+        let mut utxos = UtxoSet::new();
+        utxos.insert(ptr(0), TxOut::new(owner, Coin::new(1_000_000).unwrap()));
+
+        let outputs = vec![TxOut::new(decode_addr(RADDR), Coin::new(500_000).unwrap())];
+        let tx = Tx::new_with(vec![ptr(0)], outputs);
+        let witness = vec![TxInWitness::new(pm, &key, &tx.id())].into();
+        let signed = TxAux::new(tx.clone(), witness);
+        assert!(precheck_signed(&signed, &utxos, pm, &alg).is_ok());
+
+        // signed by a key not owning the spent output
+        let other = XPrv::normalize_bytes([2; XPRV_SIZE]);
+        let witness = vec![TxInWitness::new(pm, &other, &tx.id())].into();
+        let txaux = TxAux::new(tx, witness);
+        match precheck_signed(&txaux, &utxos, pm, &alg) {
+            Err(PrecheckError::InvalidWitness(p)) => assert_eq!(p, ptr(0)),
+            r => panic!("unexpected result {:?}", r),
+        }
+    }
+
+    #[test]
+    fn precheck_signed_scripts() {
+        let alg = LinearFee::default();
+        let pm = ProtocolMagic::default();
+        let validator = Script::new(0, vec![1, 2, 3]);
+        let owner = ExtendedAddr::new(
+            AddrType::ATScript,
+            SpendingData::ScriptASD(validator.clone()),
+            Attributes::new_bootstrap_era(None, NetworkMagic::NoMagic),
+        );
+        let mut utxos = UtxoSet::new();
+        utxos.insert(ptr(0), TxOut::new(owner, Coin::new(1_000_000).unwrap()));
+
+        let outputs = vec![TxOut::new(decode_addr(RADDR), Coin::new(500_000).unwrap())];
+        let tx = Tx::new_with(vec![ptr(0)], outputs);
+        let redeemer = Script::new(0, vec![4, 5]);
+        let witness = vec![TxInWitness::ScriptWitness(validator, redeemer)].into();
+        let txaux = TxAux::new(tx, witness);
+
+        match precheck_signed(&txaux, &utxos, pm, &alg) {
+            Err(PrecheckError::ScriptWitness(p, ScriptError::NotSupported)) => {
+                assert_eq!(p, ptr(0))
+            }
+            r => panic!("unexpected result {:?}", r),
+        }
+        assert!(precheck_signed_with_scripts(&txaux, &utxos, pm, &alg, &UncheckedScripts).is_ok());
+    }
 }
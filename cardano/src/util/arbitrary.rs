@@ -76,6 +76,7 @@ impl Arbitrary for Wrapper<(hdwallet::XPrv, address::ExtendedAddr)> {
             derivation_path: None,
             stake_distribution: address::StakeDistribution::BootstrapEraDistr,
             network_magic: *network_magic,
+            remains: ::std::collections::BTreeMap::new(),
         };
         let addr_type = address::AddrType::ATPubKey;
         let addr = address::HashedSpendingData::new(
@@ -136,13 +137,6 @@ impl<A: Arbitrary> Arbitrary for Wrapper<(hdwallet::XPrv, txutils::Input<A>)> {
         let ptr: Wrapper<tx::TxoPointer> = Arbitrary::arbitrary(g);
         let addressing: A = Arbitrary::arbitrary(g);
         let (xprv, txout) = value.unwrap();
-        Wrapper((
-            xprv,
-            txutils::Input {
-                ptr: ptr.unwrap(),
-                value: txout,
-                addressing: addressing,
-            },
-        ))
+        Wrapper((xprv, txutils::Input::new(ptr.unwrap(), txout, addressing)))
     }
 }
@@ -0,0 +1,128 @@
+//! Deterministic random generators.
+//!
+//! The library never draws randomness from the operating system itself:
+//! the algorithms needing random numbers (e.g. the input selections
+//! breaking ties, the entropy of new mnemonics) take a `RandomGenerator`.
+//! The applications give them a generator seeded from the OS, the tests a
+//! `Drbg` seeded with a constant so the transactions built are byte for
+//! byte reproducible.
+//!
+//! `Drbg` is the HMAC-SHA256 deterministic random bit generator of NIST
+//! SP 800-90A, as used by RFC 6979 to derive the nonces of signatures: the
+//! same seed always gives the same sequence of bytes.
+
+use cryptoxide::hmac::Hmac;
+use cryptoxide::mac::Mac;
+use cryptoxide::sha2::Sha256;
+
+/// A source of random numbers
+pub trait RandomGenerator {
+    fn next_u32(&mut self) -> u32;
+
+    fn fill_bytes(&mut self, dst: &mut [u8]) {
+        for chunk in dst.chunks_mut(4) {
+            let v = self.next_u32();
+            for (i, byte) in chunk.iter_mut().enumerate() {
+                *byte = (v >> (8 * i)) as u8;
+            }
+        }
+    }
+}
+
+impl<'a, R: RandomGenerator + ?Sized> RandomGenerator for &'a mut R {
+    fn next_u32(&mut self) -> u32 {
+        (**self).next_u32()
+    }
+
+    fn fill_bytes(&mut self, dst: &mut [u8]) {
+        (**self).fill_bytes(dst)
+    }
+}
+
+const HASH_SIZE: usize = 32;
+
+/// HMAC-SHA256 deterministic random bit generator
+#[derive(Clone)]
+pub struct Drbg {
+    k: [u8; HASH_SIZE],
+    v: [u8; HASH_SIZE],
+}
+impl Drbg {
+    /// instantiate the generator with the given seed (the entropy input
+    /// and the personalization string, concatenated)
+    pub fn new(seed: &[u8]) -> Self {
+        let mut drbg = Drbg {
+            k: [0; HASH_SIZE],
+            v: [1; HASH_SIZE],
+        };
+        drbg.update(seed);
+        drbg
+    }
+
+    fn hmac(&self, parts: &[&[u8]], out: &mut [u8; HASH_SIZE]) {
+        let mut mac = Hmac::new(Sha256::new(), &self.k);
+        for part in parts {
+            mac.input(part);
+        }
+        mac.raw_result(out);
+    }
+
+    fn update(&mut self, data: &[u8]) {
+        let mut k = [0; HASH_SIZE];
+        let mut v = [0; HASH_SIZE];
+        self.hmac(&[&self.v[..], &[0x00], data], &mut k);
+        self.k = k;
+        self.hmac(&[&self.v[..]], &mut v);
+        self.v = v;
+        if data.is_empty() {
+            return;
+        }
+        self.hmac(&[&self.v[..], &[0x01], data], &mut k);
+        self.k = k;
+        self.hmac(&[&self.v[..]], &mut v);
+        self.v = v;
+    }
+}
+impl RandomGenerator for Drbg {
+    fn next_u32(&mut self) -> u32 {
+        let mut bytes = [0; 4];
+        self.fill_bytes(&mut bytes);
+        bytes
+            .iter()
+            .fold(0, |acc, byte| (acc << 8) | u32::from(*byte))
+    }
+
+    fn fill_bytes(&mut self, dst: &mut [u8]) {
+        for chunk in dst.chunks_mut(HASH_SIZE) {
+            let mut v = [0; HASH_SIZE];
+            self.hmac(&[&self.v[..]], &mut v);
+            self.v = v;
+            chunk.copy_from_slice(&self.v[..chunk.len()]);
+        }
+        self.update(&[]);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use util::hex;
+
+    #[test]
+    fn drbg_is_deterministic() {
+        let mut drbg = Drbg::new(b"seed");
+        let mut bytes = [0; 40];
+        drbg.fill_bytes(&mut bytes);
+        assert_eq!(hex::encode(&bytes), EXPECTED_BYTES);
+        assert_eq!(drbg.next_u32(), EXPECTED_U32);
+
+        let mut other = Drbg::new(b"other seed");
+        let mut other_bytes = [0; 40];
+        other.fill_bytes(&mut other_bytes);
+        assert_ne!(bytes[..], other_bytes[..]);
+    }
+
+    const EXPECTED_BYTES: &str =
+        "945418b8333283ae441104ff0af8ab77c755914dbcd4971f9db434098d72cc5fbcb6778fbaa207c9";
+    const EXPECTED_U32: u32 = 1662711044;
+}
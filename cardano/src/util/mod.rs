@@ -1,7 +1,9 @@
 pub mod base58;
 pub mod bits;
 pub mod diff_maps;
+pub mod drbg;
 pub mod hex;
+pub mod scrypt;
 pub mod securemem;
 pub mod try_from_slice;
 
@@ -0,0 +1,142 @@
+//! scrypt key derivation function (RFC 7914)
+//!
+//! A memory-hard KDF: deriving a key needs `128 * r * N` bytes of memory,
+//! which makes brute forcing short passwords on dedicated hardware
+//! expensive, unlike PBKDF2.
+//!
+//! # Example
+//!
+//! ```
+//! use cardano::util::scrypt::scrypt;
+//!
+//! let mut key = [0u8; 32];
+//! scrypt(b"password", b"salt", 10, 8, 1, &mut key);
+//! ```
+
+use cryptoxide::hmac::Hmac;
+use cryptoxide::pbkdf2::pbkdf2;
+use cryptoxide::sha2::Sha256;
+
+/// number of 32 bits words in a salsa20 block
+const BLOCK_WORDS: usize = 16;
+
+fn quarter_round(x: &mut [u32; BLOCK_WORDS], a: usize, b: usize, c: usize, d: usize) {
+    x[b] ^= x[a].wrapping_add(x[d]).rotate_left(7);
+    x[c] ^= x[b].wrapping_add(x[a]).rotate_left(9);
+    x[d] ^= x[c].wrapping_add(x[b]).rotate_left(13);
+    x[a] ^= x[d].wrapping_add(x[c]).rotate_left(18);
+}
+
+fn salsa20_8(block: &mut [u32]) {
+    let mut x = [0u32; BLOCK_WORDS];
+    x.copy_from_slice(block);
+    for _ in 0..4 {
+        quarter_round(&mut x, 0, 4, 8, 12);
+        quarter_round(&mut x, 5, 9, 13, 1);
+        quarter_round(&mut x, 10, 14, 2, 6);
+        quarter_round(&mut x, 15, 3, 7, 11);
+
+        quarter_round(&mut x, 0, 1, 2, 3);
+        quarter_round(&mut x, 5, 6, 7, 4);
+        quarter_round(&mut x, 10, 11, 8, 9);
+        quarter_round(&mut x, 15, 12, 13, 14);
+    }
+    for (b, x) in block.iter_mut().zip(x.iter()) {
+        *b = b.wrapping_add(*x);
+    }
+}
+
+// BlockMix of the `2 * r` salsa20 blocks of `input` into `output`
+fn block_mix(input: &[u32], output: &mut [u32]) {
+    let blocks = input.len() / BLOCK_WORDS;
+    let mut x = [0u32; BLOCK_WORDS];
+    x.copy_from_slice(&input[(blocks - 1) * BLOCK_WORDS..]);
+    for i in 0..blocks {
+        for (x, b) in x.iter_mut().zip(input[i * BLOCK_WORDS..].iter()) {
+            *x ^= *b;
+        }
+        salsa20_8(&mut x);
+        // the even blocks go in the first half, the odd ones in the second
+        let dst = (i / 2 + (i % 2) * (blocks / 2)) * BLOCK_WORDS;
+        output[dst..dst + BLOCK_WORDS].copy_from_slice(&x);
+    }
+}
+
+fn ro_mix(block: &mut [u8], n: usize) {
+    let words = block.len() / 4;
+    let mut x: Vec<u32> = block
+        .chunks(4)
+        .map(|b| {
+            (b[0] as u32) | ((b[1] as u32) << 8) | ((b[2] as u32) << 16) | ((b[3] as u32) << 24)
+        })
+        .collect();
+    let mut y = vec![0u32; words];
+    let mut v = Vec::with_capacity(n * words);
+
+    for _ in 0..n {
+        v.extend_from_slice(&x);
+        block_mix(&x, &mut y);
+        ::std::mem::swap(&mut x, &mut y);
+    }
+    for _ in 0..n {
+        let j = (x[words - BLOCK_WORDS] as usize) & (n - 1);
+        for (x, v) in x.iter_mut().zip(v[j * words..].iter()) {
+            *x ^= *v;
+        }
+        block_mix(&x, &mut y);
+        ::std::mem::swap(&mut x, &mut y);
+    }
+
+    for (b, w) in block.chunks_mut(4).zip(x.iter()) {
+        b[0] = *w as u8;
+        b[1] = (*w >> 8) as u8;
+        b[2] = (*w >> 16) as u8;
+        b[3] = (*w >> 24) as u8;
+    }
+}
+
+/// Derive `output.len()` bytes from the password and the salt, with the
+/// cost parameters `N = 2^log_n`, `r` and `p`.
+///
+/// # panics
+///
+/// if `log_n` is 0 or not less than 32, or `r` or `p` are 0.
+pub fn scrypt(password: &[u8], salt: &[u8], log_n: u8, r: u32, p: u32, output: &mut [u8]) {
+    assert!(log_n > 0 && log_n < 32, "invalid scrypt log_n {}", log_n);
+    assert!(r > 0 && p > 0, "invalid scrypt parameters r={} p={}", r, p);
+    let n = 1usize << log_n;
+    let block_size = 128 * r as usize;
+
+    let mut blocks = vec![0u8; block_size * p as usize];
+    let mut mac = Hmac::new(Sha256::new(), password);
+    pbkdf2(&mut mac, salt, 1, &mut blocks);
+    for block in blocks.chunks_mut(block_size) {
+        ro_mix(block, n);
+    }
+    let mut mac = Hmac::new(Sha256::new(), password);
+    pbkdf2(&mut mac, &blocks, 1, output);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use util::hex;
+
+    #[test]
+    fn rfc7914_vectors() {
+        let mut key = [0u8; 64];
+        scrypt(b"", b"", 4, 1, 1, &mut key);
+        assert_eq!(
+            hex::encode(&key),
+            "77d6576238657b203b19ca42c18a0497f16b4844e3074ae8dfdffa3fede21442\
+             fcd0069ded0948f8326a753a0fc81f17e8d3e0fb2e0d3628cf35e20c38d18906"
+        );
+
+        scrypt(b"password", b"NaCl", 10, 8, 16, &mut key);
+        assert_eq!(
+            hex::encode(&key),
+            "fdbabe1c9d3472007856e7190d01e9fe7c6ad7cbc8237830e77376634b373162\
+             2eaf30d92e22a3886ff109279d9830dac727afb94a83ee6d8360cbdfa2cc0640"
+        );
+    }
+}
@@ -1,3 +1,10 @@
+//! Keys and signatures of the VSS (publicly verifiable secret sharing)
+//! scheme of the shared seed computation
+//!
+//! The VSS certificates of the stakeholders (see
+//! `block::normal::VssCertificate`) bind their VSS public key to their
+//! signing key, until an expiry epoch.
+
 use cbor_event::{self, de::Deserializer, se::Serializer};
 use std::{
     fmt,
@@ -8,11 +15,22 @@ use util::hex;
 
 const SIGNATURE_SIZE: usize = 64;
 
+/// size of the public key: a compressed secp256k1 point
+pub const PUBLICKEY_SIZE: usize = 33;
+
+/// the public key is serialized as the CBOR bytes of its own CBOR bytes
+/// serialization
+const PUBLICKEY_PREFIX: [u8; 2] = [0x58, PUBLICKEY_SIZE as u8];
+
 // XXX Error and Result copied with slight modifications from redeem.rs
 #[derive(Debug, PartialEq, Eq, PartialOrd, Ord, Clone, Copy)]
 #[cfg_attr(feature = "generic-serialization", derive(Serialize, Deserialize))]
 pub enum Error {
     InvalidSignatureSize(usize),
+    InvalidPublicKeySize(usize),
+    /// the first byte of the public key is not the one of a compressed
+    /// point (`0x02` or `0x03`)
+    InvalidPublicKey(u8),
 }
 impl fmt::Display for Error {
     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
@@ -22,27 +40,82 @@ impl fmt::Display for Error {
                 "invalid Signature size, expected {} but received {} bytes.",
                 SIGNATURE_SIZE, sz
             ),
+            &Error::InvalidPublicKeySize(sz) => write!(
+                f,
+                "invalid VSS public key size, expected {} but received {} bytes.",
+                PUBLICKEY_SIZE, sz
+            ),
+            &Error::InvalidPublicKey(b) => write!(
+                f,
+                "invalid VSS public key, expected a compressed point but the first byte is 0x{:02x}",
+                b
+            ),
         }
     }
 }
 pub type Result<T> = result::Result<T, Error>;
 impl ::std::error::Error for Error {}
 
-// TODO: decode to 35 bytes public-key http://hackage.haskell.org/package/pvss/docs/Crypto-SCRAPE.html#t:Point
-#[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord)]
-pub struct PublicKey(pub Vec<u8>);
+/// A VSS public key: a compressed point of the secp256k1 curve, see
+/// http://hackage.haskell.org/package/pvss/docs/Crypto-SCRAPE.html#t:Point
+#[derive(Clone, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct PublicKey(Vec<u8>);
+impl PublicKey {
+    pub fn from_slice(bytes: &[u8]) -> Result<Self> {
+        if bytes.len() != PUBLICKEY_SIZE {
+            return Err(Error::InvalidPublicKeySize(bytes.len()));
+        }
+        if bytes[0] != 0x02 && bytes[0] != 0x03 {
+            return Err(Error::InvalidPublicKey(bytes[0]));
+        }
+        Ok(PublicKey(bytes.to_vec()))
+    }
+}
+impl fmt::Debug for PublicKey {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}", hex::encode(self.as_ref()))
+    }
+}
+impl fmt::Display for PublicKey {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}", hex::encode(self.as_ref()))
+    }
+}
+impl AsRef<[u8]> for PublicKey {
+    fn as_ref(&self) -> &[u8] {
+        &self.0
+    }
+}
 impl cbor_event::se::Serialize for PublicKey {
     fn serialize<'se, W: Write>(
         &self,
         serializer: &'se mut Serializer<W>,
     ) -> cbor_event::Result<&'se mut Serializer<W>> {
-        serializer.write_bytes(&self.0)
+        let mut bytes = Vec::with_capacity(PUBLICKEY_PREFIX.len() + PUBLICKEY_SIZE);
+        bytes.extend_from_slice(&PUBLICKEY_PREFIX);
+        bytes.extend_from_slice(self.as_ref());
+        serializer.write_bytes(&bytes)
     }
 }
 impl cbor_event::de::Deserialize for PublicKey {
     fn deserialize<R: BufRead>(reader: &mut Deserializer<R>) -> cbor_event::Result<Self> {
         let bytes = reader.bytes()?;
-        Ok(PublicKey(bytes))
+        // only the canonical encoding of the inner bytes is accepted, to
+        // serialize it back identically (it is part of the signed data)
+        if bytes.len() != PUBLICKEY_PREFIX.len() + PUBLICKEY_SIZE {
+            return Err(cbor_event::Error::NotEnough(
+                bytes.len(),
+                PUBLICKEY_PREFIX.len() + PUBLICKEY_SIZE,
+            ));
+        }
+        if bytes[0..2] != PUBLICKEY_PREFIX {
+            return Err(cbor_event::Error::CustomError(format!(
+                "Invalid VSS public key encoding: {}",
+                hex::encode(&bytes[0..2])
+            )));
+        }
+        PublicKey::from_slice(&bytes[2..])
+            .map_err(|err| cbor_event::Error::CustomError(format!("{}", err)))
     }
 }
 
@@ -102,7 +175,41 @@ impl cbor_event::de::Deserialize for Signature {
             Ok(sig) => Ok(sig),
             Err(Error::InvalidSignatureSize(sz)) => {
                 Err(cbor_event::Error::NotEnough(SIGNATURE_SIZE, sz))
-            } // Err(err) => Err(cbor_event::Error::CustomError(format!("unexpected error: {}", err))),
+            }
+            Err(err) => Err(cbor_event::Error::CustomError(format!(
+                "unexpected error: {}",
+                err
+            ))),
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn public_key_cbor() {
+        let key = PublicKey::from_slice(&[0x03; PUBLICKEY_SIZE]).unwrap();
+        let mut se = Serializer::new_vec();
+        se.serialize(&key).unwrap();
+        let bytes = se.finalize();
+        assert_eq!(&bytes[0..4], &[0x58, 0x23, 0x58, 0x21]);
+
+        let mut raw = Deserializer::from(::std::io::Cursor::new(bytes));
+        let decoded: PublicKey = raw.deserialize().unwrap();
+        assert_eq!(decoded, key);
+    }
+
+    #[test]
+    fn public_key_invalid() {
+        assert_eq!(
+            PublicKey::from_slice(&[0x02; 32]),
+            Err(Error::InvalidPublicKeySize(32))
+        );
+        assert_eq!(
+            PublicKey::from_slice(&[0x04; PUBLICKEY_SIZE]),
+            Err(Error::InvalidPublicKey(0x04))
+        );
+    }
+}
@@ -1,15 +1,24 @@
-use address::ExtendedAddr;
+use address::{Addr, ExtendedAddr};
 use bip::bip39;
 use bip::bip44::{BIP44_COIN_TYPE, BIP44_PURPOSE, BIP44_SOFT_UPPER_BOUND};
+use block::Block;
+use coin::{self, Coin};
 use config::{NetworkMagic, ProtocolMagic};
 /// BIP44 derivation scheme and address model
 ///
 use hdwallet::{DerivationIndex, DerivationScheme, Result, XPrv, XPub, XPRV_SIZE};
-use std::{collections::BTreeMap, ops::Deref};
-use tx::{TxId, TxInWitness};
+use std::{
+    borrow::Borrow,
+    collections::{BTreeMap, BTreeSet},
+    ops::Deref,
+};
+use tx::{TxAux, TxId, TxInWitness, TxoPointer};
+use txutils::{Input, OutputPolicy};
 
+use super::error::{self, WalletError};
 use super::keygen;
 use super::scheme;
+use super::state::{LookupCursor, SequentialCursor};
 
 pub use bip::bip44::{self, AddrType, Addressing, Change, Error, Index};
 
@@ -83,6 +92,37 @@ impl Wallet {
         Wallet::from_bip39_seed(&seed, derivation_scheme)
     }
 
+    /// helper to create (or recover) a wallet from BIP39 mnemonics and the
+    /// optional BIP39 passphrase (sometimes called the "25th word").
+    ///
+    /// Without passphrase, this is the same wallet as the one created with
+    /// `from_bip39_mnemonics` and an empty password. As for the mnemonics,
+    /// the passphrase is expected to be normalized (UTF-8 NFKD) so the
+    /// wallet can be recovered from other BIP39 implementations.
+    pub fn from_bip39_mnemonics_with_passphrase(
+        mnemonics_phrase: &bip39::MnemonicString,
+        passphrase: Option<&str>,
+        derivation_scheme: DerivationScheme,
+    ) -> Self {
+        let passphrase = passphrase.unwrap_or("");
+        Wallet::from_bip39_mnemonics(mnemonics_phrase, passphrase.as_bytes(), derivation_scheme)
+    }
+
+    /// Create a watch-only compatible copy of a Ledger hardware wallet from
+    /// its BIP39 mnemonics and password, with the same addresses.
+    ///
+    /// The root key is generated the way the Ledger's Cardano application
+    /// does (see `XPrv::generate_from_ledger_bip39`).
+    pub fn from_ledger_mnemonics(
+        mnemonics_phrase: &bip39::MnemonicString,
+        password: &[u8],
+    ) -> Self {
+        let seed = bip39::Seed::from_mnemonic_string(mnemonics_phrase, password);
+        let xprv = XPrv::generate_from_ledger_bip39(&seed);
+
+        Wallet::from_root_key(xprv, DerivationScheme::V2)
+    }
+
     /// Create a new wallet from a root entropy
     ///
     /// This is the recommended method to create a wallet from initial generated value.
@@ -100,9 +140,73 @@ impl Wallet {
         Wallet::from_root_key(xprv, derivation_scheme)
     }
 
+    /// Recover an Icarus (or Yoroi) wallet from its mnemonic phrase.
+    ///
+    /// Those wallets use 15 mnemonic words, sequential indexing and the V2
+    /// derivation scheme. The root key is generated from the entropy of the
+    /// mnemonics (as in `from_entropy`) without password.
+    pub fn from_icarus_mnemonics(mnemonics: &bip39::Mnemonics) -> bip39::Result<Self> {
+        let words = mnemonics.get_type();
+        if words != bip39::Type::Type15Words {
+            return Err(bip39::Error::WrongNumberOfWords(words.mnemonic_count()));
+        }
+        let entropy = bip39::Entropy::from_mnemonics(mnemonics)?;
+        Ok(Wallet::from_entropy(&entropy, b"", DerivationScheme::V2))
+    }
+
     pub fn derivation_scheme(&self) -> DerivationScheme {
         self.derivation_scheme
     }
+
+    /// same as `scheme::Wallet::create_account` but fails if the account
+    /// number is not within the bounds of the BIP44 accounts (the account
+    /// derivation is a hard derivation, the given `id` is the number of
+    /// the account, i.e. before adding `BIP44_SOFT_UPPER_BOUND`).
+    pub fn try_create_account(&mut self, alias: &str, id: u32) -> error::Result<Account<XPrv>> {
+        bip44::Account::new(id)?;
+        Ok(scheme::Wallet::create_account(self, alias, id))
+    }
+
+    /// derive the address the change of a new transaction of the given
+    /// account goes to: the next unused address of the account's internal
+    /// chain according to `cursor` (see `Discovered::accounts`).
+    ///
+    /// The cursor is moved past the returned address, so the following
+    /// transactions do not reuse it.
+    pub fn next_change_address(
+        &self,
+        account: u32,
+        cursor: &mut SequentialCursor,
+        network_magic: NetworkMagic,
+    ) -> error::Result<ChangeAddress> {
+        let addressing = Addressing::new(account, AddrType::Internal, cursor.internal)?;
+        let key = self
+            .cached_root_key
+            .account(self.derivation_scheme, account)
+            .change(self.derivation_scheme, AddrType::Internal)
+            .index(self.derivation_scheme, cursor.internal)
+            .public();
+        cursor.internal += 1;
+        Ok(ChangeAddress {
+            addressing,
+            address: ExtendedAddr::new_simple(key.0, network_magic),
+        })
+    }
+}
+
+/// An address of the internal chain of an account, derived to receive the
+/// change of a transaction (see `Wallet::next_change_address`).
+#[derive(Debug, Clone)]
+pub struct ChangeAddress {
+    /// the derivation of the address, to remember it is used
+    pub addressing: Addressing,
+    pub address: ExtendedAddr,
+}
+impl ChangeAddress {
+    /// the output policy paying all the change to this address
+    pub fn output_policy(&self) -> OutputPolicy {
+        OutputPolicy::One(self.address.clone())
+    }
 }
 impl Deref for Wallet {
     type Target = RootLevel<XPrv>;
@@ -220,6 +324,34 @@ impl Account<XPrv> {
     }
 }
 impl Account<XPub> {
+    /// same as `scheme::Account::generate_addresses` but fails on the
+    /// out of bound indices instead of panicking.
+    pub fn try_generate_addresses<'a, I>(
+        &'a self,
+        addresses: I,
+        network_magic: NetworkMagic,
+    ) -> error::Result<Vec<ExtendedAddr>>
+    where
+        I: Iterator<Item = &'a (bip44::AddrType, u32)>,
+    {
+        let (hint_low, hint_max) = addresses.size_hint();
+        let mut vec = Vec::with_capacity(hint_max.unwrap_or(hint_low));
+
+        for addressing in addresses {
+            if addressing.1 >= BIP44_SOFT_UPPER_BOUND {
+                return Err(bip44::Error::IndexOutOfBound(addressing.1).into());
+            }
+            let key = self
+                .cached_root_key
+                .change(self.derivation_scheme, addressing.0)?
+                .index(self.derivation_scheme, addressing.1)?;
+            let addr = ExtendedAddr::new_simple(key.0, network_magic);
+            vec.push(addr);
+        }
+
+        Ok(vec)
+    }
+
     /// create an [`AddressGenerator`](./struct.AddressGenerator.html) iterator.
     ///
     /// an address iterator starts from the given index, and stop when
@@ -289,21 +421,8 @@ impl scheme::Account for Account<XPub> {
     where
         I: Iterator<Item = &'a Self::Addressing>,
     {
-        let (hint_low, hint_max) = addresses.size_hint();
-        let mut vec = Vec::with_capacity(hint_max.unwrap_or(hint_low));
-
-        for addressing in addresses {
-            let key = self
-                .cached_root_key
-                .change(self.derivation_scheme, addressing.0)
-                .expect("cannot fail")
-                .index(self.derivation_scheme, addressing.1)
-                .expect("cannot fail");
-            let addr = ExtendedAddr::new_simple(key.0, network_magic);
-            vec.push(addr);
-        }
-
-        vec
+        self.try_generate_addresses(addresses, network_magic)
+            .expect("cannot fail")
     }
 }
 impl scheme::Account for Account<XPrv> {
@@ -521,3 +640,402 @@ impl<T> Deref for IndexLevel<T> {
         &self.0
     }
 }
+
+/// Number of consecutive unused addresses after which the lookup of an
+/// account's addresses stops, as recommended by BIP44.
+pub const DEFAULT_GAP_LIMIT: u32 = 20;
+
+/// The accounts, addresses and UTxOs of a wallet found by `discover`
+#[derive(Debug, Clone, Default)]
+pub struct Discovered {
+    /// the accounts with at least one used address, and the index
+    /// following their last used external and internal addresses
+    pub accounts: BTreeMap<u32, SequentialCursor>,
+    /// the used addresses, in the order they were found in the chain
+    pub addresses: Vec<(Addressing, ExtendedAddr)>,
+    /// the unspent outputs of the wallet
+    pub utxos: BTreeMap<TxoPointer, Input<Addressing>>,
+}
+impl Discovered {
+    /// the lookup state to save in a wallet state `Checkpoint`
+    pub fn lookup_cursor(&self) -> LookupCursor {
+        LookupCursor::SequentialIndex(self.accounts.clone())
+    }
+
+    /// the value of the unspent outputs of the wallet
+    pub fn total(&self) -> coin::Result<Coin> {
+        coin::sum_coins(self.utxos.values().map(|input| input.value.value))
+    }
+}
+
+/// Scan the given blocks for the addresses of the wallet.
+///
+/// See [`AddressDiscovery`](./struct.AddressDiscovery.html) for how the
+/// addresses are looked up.
+pub fn discover<I>(
+    wallet: &Wallet,
+    network_magic: NetworkMagic,
+    chain: I,
+    gap_limit: u32,
+) -> Discovered
+where
+    I: IntoIterator,
+    I::Item: Borrow<Block>,
+{
+    let mut discovery = AddressDiscovery::new(wallet, network_magic, gap_limit);
+    for block in chain {
+        discovery.apply_block(block.borrow());
+    }
+    discovery.finish()
+}
+
+struct AccountLookup {
+    key: AccountLevel<XPub>,
+    /// index following the last used addresses
+    used: SequentialCursor,
+    /// index following the last addresses added to the lookup table
+    generated: SequentialCursor,
+}
+
+/// BIP44 address discovery.
+///
+/// The addresses of every account are looked up in a window of
+/// `gap_limit` addresses following the last used external (resp.
+/// internal) address. The window moves forward every time one of its
+/// addresses is found in the chain. Likewise, the accounts are looked up
+/// one after the other: the next account is looked up once an address of
+/// the last one is found used.
+///
+/// The transactions need to be applied in the order of the chain.
+pub struct AddressDiscovery<'a> {
+    wallet: &'a Wallet,
+    network_magic: NetworkMagic,
+    gap_limit: u32,
+    accounts: BTreeMap<u32, AccountLookup>,
+    addresses: BTreeMap<Addr, Addressing>,
+    used_addresses: BTreeSet<Addr>,
+    discovered: Discovered,
+}
+impl<'a> AddressDiscovery<'a> {
+    pub fn new(wallet: &'a Wallet, network_magic: NetworkMagic, gap_limit: u32) -> Self {
+        Self::try_new(wallet, network_magic, gap_limit).expect("the gap limit cannot be zero")
+    }
+
+    /// same as `new` but fails with `WalletError::ZeroGapLimit` instead
+    /// of panicking if `gap_limit` is zero.
+    pub fn try_new(
+        wallet: &'a Wallet,
+        network_magic: NetworkMagic,
+        gap_limit: u32,
+    ) -> error::Result<Self> {
+        if gap_limit == 0 {
+            return Err(WalletError::ZeroGapLimit);
+        }
+        let mut discovery = AddressDiscovery {
+            wallet,
+            network_magic,
+            gap_limit,
+            accounts: BTreeMap::new(),
+            addresses: BTreeMap::new(),
+            used_addresses: BTreeSet::new(),
+            discovered: Discovered::default(),
+        };
+        discovery.add_account(0);
+        Ok(discovery)
+    }
+
+    pub fn apply_block(&mut self, block: &Block) {
+        if let Block::MainBlock(blk) = block {
+            for txaux in blk.body.tx.iter() {
+                self.apply_tx(txaux);
+            }
+        }
+    }
+
+    pub fn apply_tx(&mut self, txaux: &TxAux) {
+        for txin in txaux.tx.inputs.iter() {
+            self.discovered.utxos.remove(txin);
+        }
+
+        let id = txaux.tx.id();
+        for (index, txout) in txaux.tx.outputs.iter().enumerate() {
+            if let Some(addressing) = self.lookup(&txout.address) {
+                let ptr = TxoPointer::new(id, index as u32);
+                self.discovered
+                    .utxos
+                    .insert(ptr.clone(), Input::new(ptr, txout.clone(), addressing));
+            }
+        }
+    }
+
+    pub fn finish(mut self) -> Discovered {
+        self.discovered.accounts = self
+            .accounts
+            .iter()
+            .filter(|(_, account)| account.used != SequentialCursor::default())
+            .map(|(id, account)| (*id, account.used))
+            .collect();
+        self.discovered
+    }
+
+    fn lookup(&mut self, address: &ExtendedAddr) -> Option<Addressing> {
+        let addr = address.to_address();
+        let addressing = *self.addresses.get(&addr)?;
+        if self.used_addresses.insert(addr) {
+            self.discovered
+                .addresses
+                .push((addressing, address.clone()));
+        }
+
+        let id = addressing.account.get_account_number();
+        let index = addressing.index.get_scheme_value() + 1;
+        let addr_type = addressing.address_type();
+        let up_to = {
+            let account = self.accounts.get_mut(&id).unwrap();
+            let used = match addr_type {
+                AddrType::External => &mut account.used.external,
+                AddrType::Internal => &mut account.used.internal,
+            };
+            *used = ::std::cmp::max(*used, index);
+            *used + self.gap_limit
+        };
+        self.generate(id, addr_type, up_to);
+
+        // the account is used, look for the next one
+        let next_id = id + 1;
+        if !self.accounts.contains_key(&next_id) && next_id < BIP44_SOFT_UPPER_BOUND {
+            self.add_account(next_id);
+        }
+
+        Some(addressing)
+    }
+
+    fn add_account(&mut self, id: u32) {
+        let key = self
+            .wallet
+            .account(self.wallet.derivation_scheme, id)
+            .public();
+        self.accounts.insert(
+            id,
+            AccountLookup {
+                key,
+                used: SequentialCursor::default(),
+                generated: SequentialCursor::default(),
+            },
+        );
+        let gap_limit = self.gap_limit;
+        self.generate(id, AddrType::External, gap_limit);
+        self.generate(id, AddrType::Internal, gap_limit);
+    }
+
+    /// add the addresses of the account up to (excluded) `up_to` to the
+    /// lookup table
+    fn generate(&mut self, id: u32, addr_type: AddrType, up_to: u32) {
+        let derivation_scheme = self.wallet.derivation_scheme;
+        let network_magic = self.network_magic;
+        let up_to = ::std::cmp::min(up_to, BIP44_SOFT_UPPER_BOUND);
+        let account = self.accounts.get_mut(&id).unwrap();
+        let generated = match addr_type {
+            AddrType::External => &mut account.generated.external,
+            AddrType::Internal => &mut account.generated.internal,
+        };
+        if *generated >= up_to {
+            return;
+        }
+
+        let change = account
+            .key
+            .change(derivation_scheme, addr_type)
+            .expect("soft derivation cannot fail");
+        for index in *generated..up_to {
+            let key = change
+                .index(derivation_scheme, index)
+                .expect("soft derivation cannot fail");
+            let address = ExtendedAddr::new_simple(key.0, network_magic);
+            let addressing =
+                Addressing::new(id, addr_type, index).expect("index within the soft bounds");
+            self.addresses.insert(address.to_address(), addressing);
+        }
+        *generated = up_to;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use bip::bip39::dictionary::ENGLISH;
+    use tx::{Tx, TxOut, TxWitness};
+    use util::hex;
+
+    #[test]
+    fn icarus_wallet_root_key() {
+        let mnemonics = "eight country switch draw meat scout mystery blade tip drift useless good keep usage title";
+        let mnemonics = bip39::Mnemonics::from_string(&ENGLISH, mnemonics).unwrap();
+        let wallet = Wallet::from_icarus_mnemonics(&mnemonics).unwrap();
+
+        let mut root_key = [0; XPRV_SIZE];
+        root_key.copy_from_slice(&hex::decode("c065afd2832cd8b087c4d9ab7011f481ee1e0721e78ea5dd609f3ab3f156d245d176bd8fd4ec60b4731c3918a2a72a0226c0cd119ec35b47e4d55884667f552a23f7fdcd4a10c6cd2c7393ac61d877873e248f417634aa3d812af327ffe9d620").unwrap());
+        let expected = Wallet::from_root_key(
+            XPrv::from_bytes_verified(root_key).unwrap(),
+            DerivationScheme::V2,
+        );
+        assert!(*wallet == *expected);
+        assert_eq!(wallet.derivation_scheme(), DerivationScheme::V2);
+
+        let mnemonics = "abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon about";
+        let mnemonics = bip39::Mnemonics::from_string(&ENGLISH, mnemonics).unwrap();
+        assert!(Wallet::from_icarus_mnemonics(&mnemonics).is_err());
+    }
+
+    fn mk_wallet() -> Wallet {
+        let entropy = bip39::Entropy::from_slice(&[0; 16]).unwrap();
+        Wallet::from_entropy(&entropy, b"", DerivationScheme::V2)
+    }
+
+    #[test]
+    fn bip39_passphrase() {
+        let mnemonics = "abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon about";
+        let mnemonics = bip39::MnemonicString::new(&ENGLISH, mnemonics.to_owned()).unwrap();
+
+        let without =
+            Wallet::from_bip39_mnemonics_with_passphrase(&mnemonics, None, DerivationScheme::V2);
+        let empty = Wallet::from_bip39_mnemonics(&mnemonics, b"", DerivationScheme::V2);
+        assert!(*without == *empty);
+
+        let with = Wallet::from_bip39_mnemonics_with_passphrase(
+            &mnemonics,
+            Some("TREZOR"),
+            DerivationScheme::V2,
+        );
+        let seed = bip39::Seed::from_mnemonic_string(&mnemonics, b"TREZOR");
+        let expected = Wallet::from_bip39_seed(&seed, DerivationScheme::V2);
+        assert!(*with == *expected);
+        assert!(*with != *without);
+    }
+
+    #[test]
+    fn next_change_address() {
+        let wallet = mk_wallet();
+        let mut cursor = SequentialCursor {
+            external: 4,
+            internal: 2,
+        };
+        let change = wallet
+            .next_change_address(1, &mut cursor, NetworkMagic::NoMagic)
+            .unwrap();
+        assert_eq!(change.addressing.to_string(), "1.1.2");
+        assert_eq!(
+            change.address,
+            mk_address(&wallet, 1, AddrType::Internal, 2)
+        );
+        assert_eq!(
+            cursor,
+            SequentialCursor {
+                external: 4,
+                internal: 3
+            }
+        );
+
+        let next = wallet
+            .next_change_address(1, &mut cursor, NetworkMagic::NoMagic)
+            .unwrap();
+        assert_eq!(next.address, mk_address(&wallet, 1, AddrType::Internal, 3));
+    }
+
+    #[test]
+    fn try_create_account_out_of_bound() {
+        let mut wallet = mk_wallet();
+        assert!(wallet.try_create_account("account 0", 0).is_ok());
+        match wallet.try_create_account("hard", BIP44_SOFT_UPPER_BOUND) {
+            Err(WalletError::Bip44(Error::AccountOutOfBound(id))) => {
+                assert_eq!(id, BIP44_SOFT_UPPER_BOUND)
+            }
+            Err(err) => panic!("unexpected error: {}", err),
+            Ok(_) => panic!("account {} created", BIP44_SOFT_UPPER_BOUND),
+        }
+        assert_eq!(scheme::Wallet::list_accounts(&wallet).len(), 1);
+    }
+
+    #[test]
+    fn discovery_zero_gap_limit() {
+        let wallet = mk_wallet();
+        match AddressDiscovery::try_new(&wallet, NetworkMagic::NoMagic, 0) {
+            Err(WalletError::ZeroGapLimit) => {}
+            Err(err) => panic!("unexpected error: {}", err),
+            Ok(_) => panic!("discovery started with a zero gap limit"),
+        }
+    }
+
+    fn mk_address(wallet: &Wallet, account: u32, addr_type: AddrType, index: u32) -> ExtendedAddr {
+        let key = wallet
+            .account(wallet.derivation_scheme(), account)
+            .change(wallet.derivation_scheme(), addr_type)
+            .index(wallet.derivation_scheme(), index)
+            .public();
+        ExtendedAddr::new_simple(key.0, NetworkMagic::NoMagic)
+    }
+
+    fn mk_txaux(seed: u8, inputs: Vec<TxoPointer>, outputs: Vec<ExtendedAddr>) -> TxAux {
+        let outputs = outputs
+            .into_iter()
+            .map(|address| TxOut::new(address, Coin::new(1_000000).unwrap()))
+            .collect();
+        let mut inputs = inputs;
+        inputs.push(TxoPointer::new(TxId::new(&[seed]), 0));
+        TxAux::new(Tx::new_with(inputs, outputs), TxWitness::new())
+    }
+
+    #[test]
+    fn discover_addresses_within_gap() {
+        let wallet = mk_wallet();
+        let ext = |account, index| mk_address(&wallet, account, AddrType::External, index);
+        let int = |account, index| mk_address(&wallet, account, AddrType::Internal, index);
+
+        let mut discovery = AddressDiscovery::new(&wallet, NetworkMagic::NoMagic, 5);
+        // index 7 is out of the initial window [0, 5)
+        let tx1 = mk_txaux(1, vec![], vec![ext(0, 7), ext(0, 3), int(0, 2)]);
+        discovery.apply_tx(&tx1);
+        // window is now [0, 9), and account 1 is looked up
+        // account 3 is not looked up until account 2 is used
+        let tx2 = mk_txaux(2, vec![], vec![ext(0, 7), ext(1, 0), ext(3, 0)]);
+        discovery.apply_tx(&tx2);
+        // spend the output to int(0, 2)
+        let tx3 = mk_txaux(3, vec![TxoPointer::new(tx1.tx.id(), 2)], vec![]);
+        discovery.apply_tx(&tx3);
+
+        let discovered = discovery.finish();
+        let accounts: Vec<_> = discovered.accounts.into_iter().collect();
+        assert_eq!(
+            accounts,
+            vec![
+                (
+                    0,
+                    SequentialCursor {
+                        external: 8,
+                        internal: 3
+                    }
+                ),
+                (
+                    1,
+                    SequentialCursor {
+                        external: 1,
+                        internal: 0
+                    }
+                ),
+            ]
+        );
+        let addresses: Vec<_> = discovered
+            .addresses
+            .iter()
+            .map(|(addressing, _)| addressing.to_string())
+            .collect();
+        assert_eq!(addresses, vec!["0.0.3", "0.1.2", "0.0.7", "1.0.0"]);
+        let utxos: Vec<_> = discovered.utxos.keys().cloned().collect();
+        assert_eq!(
+            utxos.len(),
+            3,
+            "ext(0, 3), ext(0, 7) and ext(1, 0) are unspent"
+        );
+        assert!(!utxos.contains(&TxoPointer::new(tx1.tx.id(), 2)));
+    }
+}
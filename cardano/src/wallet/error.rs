@@ -0,0 +1,85 @@
+//! errors of the wallet operations
+//!
+//! The `try_` variants of the wallet functions (e.g.
+//! `bip44::Wallet::try_create_account`) return a `WalletError` where the
+//! original functions panic on invalid parameters, so the bindings (C,
+//! WebAssembly...) can report the error to the caller instead of aborting.
+
+use bip::{bip39, bip44};
+use coin;
+use hdpayload;
+use hdwallet;
+use std::{error, fmt, result};
+
+use super::rindex;
+
+#[derive(Debug)]
+pub enum WalletError {
+    /// invalid BIP44 addressing (account or index out of bound...)
+    Bip44(bip44::Error),
+    Bip39(bip39::Error),
+    RIndex(rindex::Error),
+    Derivation(hdwallet::Error),
+    Payload(hdpayload::Error),
+    Coin(coin::Error),
+    /// the gap limit of the address discovery cannot be zero
+    ZeroGapLimit,
+}
+impl fmt::Display for WalletError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            WalletError::Bip44(_) => write!(f, "Invalid BIP44 addressing"),
+            WalletError::Bip39(_) => write!(f, "Wallet's Mnemonic Error"),
+            WalletError::RIndex(_) => write!(f, "Random index wallet error"),
+            WalletError::Derivation(_) => write!(f, "Invalid key derivation"),
+            WalletError::Payload(_) => write!(f, "Error while decoding an address' payload"),
+            WalletError::Coin(_) => write!(f, "Error on coin operations"),
+            WalletError::ZeroGapLimit => write!(f, "The gap limit cannot be zero"),
+        }
+    }
+}
+impl error::Error for WalletError {
+    fn cause(&self) -> Option<&error::Error> {
+        match self {
+            WalletError::Bip44(ref err) => Some(err),
+            WalletError::Bip39(ref err) => Some(err),
+            WalletError::RIndex(ref err) => Some(err),
+            WalletError::Derivation(ref err) => Some(err),
+            WalletError::Payload(ref err) => Some(err),
+            WalletError::Coin(ref err) => Some(err),
+            WalletError::ZeroGapLimit => None,
+        }
+    }
+}
+impl From<bip44::Error> for WalletError {
+    fn from(e: bip44::Error) -> Self {
+        WalletError::Bip44(e)
+    }
+}
+impl From<bip39::Error> for WalletError {
+    fn from(e: bip39::Error) -> Self {
+        WalletError::Bip39(e)
+    }
+}
+impl From<rindex::Error> for WalletError {
+    fn from(e: rindex::Error) -> Self {
+        WalletError::RIndex(e)
+    }
+}
+impl From<hdwallet::Error> for WalletError {
+    fn from(e: hdwallet::Error) -> Self {
+        WalletError::Derivation(e)
+    }
+}
+impl From<hdpayload::Error> for WalletError {
+    fn from(e: hdpayload::Error) -> Self {
+        WalletError::Payload(e)
+    }
+}
+impl From<coin::Error> for WalletError {
+    fn from(e: coin::Error) -> Self {
+        WalletError::Coin(e)
+    }
+}
+
+pub type Result<T> = result::Result<T, WalletError>;
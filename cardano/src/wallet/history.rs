@@ -0,0 +1,231 @@
+//! The statement of a set of addresses: the chronological ledger of the
+//! funds they receive and spend, with the running balance.
+//!
+//! As for the `watch` module, no key is needed, only the addresses: the
+//! blocks are applied in the order of the chain, from a point where the
+//! addresses had no funds (e.g. the genesis, or the creation of the
+//! wallet).
+
+use address::ExtendedAddr;
+use block::{Block, BlockDate, HeaderHash};
+use coin::{self, Coin, SumCoins};
+use std::borrow::Borrow;
+use std::collections::BTreeMap;
+use tx::{TxAux, TxId, TxOut, TxoPointer};
+use wallet::watch::{AddressWatch, WatchEvent};
+
+/// The effect of a transaction on the funds of the addresses
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct HistoryEntry {
+    pub date: BlockDate,
+    pub block: HeaderHash,
+    pub txid: TxId,
+    /// sum of the outputs paying to the addresses
+    pub credit: Coin,
+    /// sum of the outputs of the addresses spent by the transaction
+    pub debit: Coin,
+    /// the fee of the transaction, if it was paid by the addresses (all
+    /// the inputs of the transaction are theirs)
+    pub fee: Option<Coin>,
+    /// the balance of the addresses after the transaction
+    pub balance: Coin,
+}
+impl HistoryEntry {
+    /// the transaction moves funds between the addresses only (e.g. the
+    /// change of a payment made to the same wallet)
+    pub fn is_internal(&self) -> bool {
+        self.fee
+            .map_or(false, |fee| (self.credit + fee).ok() == Some(self.debit))
+    }
+}
+
+/// The statement of the addresses, built as the blocks are applied.
+#[derive(Debug, Clone)]
+pub struct History {
+    watch: AddressWatch,
+    balance: Coin,
+    entries: Vec<HistoryEntry>,
+}
+impl History {
+    pub fn new<'a, I>(addresses: I) -> Self
+    where
+        I: IntoIterator<Item = &'a ExtendedAddr>,
+    {
+        // the statement is only ever extended, no block is undone
+        let mut watch = AddressWatch::new().with_undo_depth(0);
+        for address in addresses {
+            watch.watch(address);
+        }
+        History {
+            watch,
+            balance: Coin::zero(),
+            entries: Vec::new(),
+        }
+    }
+
+    /// the entries of the statement, oldest first
+    pub fn entries(&self) -> &[HistoryEntry] {
+        &self.entries
+    }
+
+    pub fn into_entries(self) -> Vec<HistoryEntry> {
+        self.entries
+    }
+
+    /// the current balance of the addresses
+    pub fn balance(&self) -> Coin {
+        self.balance
+    }
+
+    /// the unspent outputs of the addresses
+    pub fn utxos(&self) -> &BTreeMap<TxoPointer, TxOut> {
+        self.watch.utxos()
+    }
+
+    /// apply the transactions of the block, returns the number of entries
+    /// added to the statement
+    pub fn apply_block(&mut self, block: &Block) -> coin::Result<usize> {
+        let blk = match block {
+            Block::BoundaryBlock(_) => return Ok(0),
+            Block::MainBlock(blk) => blk,
+        };
+        let date = block.header().blockdate();
+        let hash = block.header().compute_hash();
+        let before = self.entries.len();
+        for txaux in blk.body.tx.iter() {
+            self.apply_tx(date, &hash, txaux)?;
+        }
+        Ok(self.entries.len() - before)
+    }
+
+    fn apply_tx(&mut self, date: BlockDate, block: &HeaderHash, txaux: &TxAux) -> coin::Result<()> {
+        let txid = txaux.tx.id();
+
+        let events = self.watch.apply_tx(txaux);
+        if events.is_empty() {
+            return Ok(());
+        }
+
+        let mut spent = 0;
+        let (mut debit, mut credit) = (Coin::zero(), Coin::zero());
+        for event in events {
+            match event {
+                WatchEvent::Spent { value, .. } => {
+                    spent += 1;
+                    debit = (debit + value)?;
+                }
+                WatchEvent::Received { value, .. } => credit = (credit + value)?,
+            }
+        }
+        let all_inputs_spent = spent == txaux.tx.inputs.len();
+
+        let fee = if all_inputs_spent {
+            let outputs = txaux.tx.outputs.iter().map(|o| o.value).sum_coins()?;
+            Some(debit.checked_sub(outputs)?)
+        } else {
+            None
+        };
+        self.balance = (self.balance + credit)?.checked_sub(debit)?;
+        self.entries.push(HistoryEntry {
+            date,
+            block: block.clone(),
+            txid,
+            credit,
+            debit,
+            fee,
+            balance: self.balance,
+        });
+        Ok(())
+    }
+}
+
+/// the statement of the addresses over the given blocks, in the order of
+/// the chain.
+pub fn history<'a, A, I>(addresses: A, blocks: I) -> coin::Result<Vec<HistoryEntry>>
+where
+    A: IntoIterator<Item = &'a ExtendedAddr>,
+    I: IntoIterator,
+    I::Item: Borrow<Block>,
+{
+    let mut history = History::new(addresses);
+    for block in blocks {
+        history.apply_block(block.borrow())?;
+    }
+    Ok(history.into_entries())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use block::EpochSlotId;
+    use test_utils::mk_address;
+    use tx::{Tx, TxWitness};
+
+    fn mk_txaux(inputs: Vec<TxoPointer>, outputs: Vec<(ExtendedAddr, u64)>) -> TxAux {
+        let outputs = outputs
+            .into_iter()
+            .map(|(address, value)| TxOut::new(address, Coin::new(value).unwrap()))
+            .collect();
+        TxAux::new(Tx::new_with(inputs, outputs), TxWitness::new())
+    }
+
+    fn coin(value: u64) -> Coin {
+        Coin::new(value).unwrap()
+    }
+
+    #[test]
+    fn credits_debits_and_fees() {
+        let (ours, change, other) = (mk_address(1), mk_address(2), mk_address(3));
+        let mut history = History::new(&[ours.clone(), change.clone()]);
+        let date = BlockDate::Normal(EpochSlotId {
+            epoch: 1,
+            slotid: 2,
+        });
+        let block = HeaderHash::new(&[1]);
+
+        // received from someone else
+        let tx1 = mk_txaux(
+            vec![TxoPointer::new(TxId::new(&[0]), 0)],
+            vec![(other.clone(), 5), (ours.clone(), 10)],
+        );
+        history.apply_tx(date, &block, &tx1).unwrap();
+        // not involving the addresses
+        let tx2 = mk_txaux(
+            vec![TxoPointer::new(tx1.tx.id(), 0)],
+            vec![(other.clone(), 4)],
+        );
+        history.apply_tx(date, &block, &tx2).unwrap();
+        // paying someone else, with change
+        let tx3 = mk_txaux(
+            vec![TxoPointer::new(tx1.tx.id(), 1)],
+            vec![(other.clone(), 6), (change.clone(), 3)],
+        );
+        history.apply_tx(date, &block, &tx3).unwrap();
+
+        {
+            let entries = history.entries();
+            assert_eq!(entries.len(), 2);
+            assert_eq!(entries[0].txid, tx1.tx.id());
+            assert_eq!(entries[0].credit, coin(10));
+            assert_eq!(entries[0].debit, coin(0));
+            assert_eq!(entries[0].fee, None);
+            assert_eq!(entries[0].balance, coin(10));
+
+            assert_eq!(entries[1].txid, tx3.tx.id());
+            assert_eq!(entries[1].credit, coin(3));
+            assert_eq!(entries[1].debit, coin(10));
+            assert_eq!(entries[1].fee, Some(coin(1)));
+            assert_eq!(entries[1].balance, coin(3));
+            assert!(!entries[1].is_internal());
+        }
+
+        // moving the change to another address of the wallet
+        let tx4 = mk_txaux(vec![TxoPointer::new(tx3.tx.id(), 1)], vec![(ours, 2)]);
+        history.apply_tx(date, &block, &tx4).unwrap();
+        let entry = history.entries().last().unwrap();
+        assert_eq!(entry.fee, Some(coin(1)));
+        assert!(entry.is_internal());
+        assert_eq!(history.balance(), coin(2));
+        assert_eq!(history.utxos().len(), 1);
+    }
+}
@@ -0,0 +1,189 @@
+//! signing of arbitrary messages with the key of an address.
+//!
+//! To prove the ownership of an address (to an exchange, for an airdrop...)
+//! its owner signs a message with the private key of the address. What is
+//! actually signed is an envelope binding the message to the address, so the
+//! signature cannot be presented for another address, nor be mistaken for
+//! the signature of a transaction:
+//!
+//! ```text
+//! [ "Cardano Signed Message", address, message ]
+//! ```
+//!
+//! (CBOR array of a text, the address and a bytes string).
+//!
+
+use address::{ExtendedAddr, SpendingData};
+use cbor_event::{self, de::Deserializer, se::Serializer};
+use hdwallet::{Signature, XPrv, XPub};
+use std::io::{BufRead, Write};
+use std::{error, fmt};
+
+/// prefix of the signed envelope, so it cannot collide with the data
+/// signed by the protocol (which always starts with a signing tag)
+pub const MESSAGE_PREFIX: &'static str = "Cardano Signed Message";
+
+#[derive(Debug, PartialEq, Eq)]
+pub enum Error {
+    /// the public key is not the one of the address
+    AddressMismatch,
+    /// the signature does not match the message and address
+    InvalidSignature,
+}
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Error::AddressMismatch => write!(f, "the key does not belong to the address"),
+            Error::InvalidSignature => write!(f, "invalid message signature"),
+        }
+    }
+}
+impl error::Error for Error {}
+
+/// a message signed with the private key of an address
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SignedMessage {
+    pub address: ExtendedAddr,
+    pub public_key: XPub,
+    pub message: Vec<u8>,
+    pub signature: Signature<SignedMessage>,
+}
+impl SignedMessage {
+    /// sign `message` with `key`, the private key of `address`
+    pub fn new(key: &XPrv, address: ExtendedAddr, message: Vec<u8>) -> Result<Self, Error> {
+        let public_key = key.public();
+        if !belongs_to(&public_key, &address) {
+            return Err(Error::AddressMismatch);
+        }
+        let signature = key.sign(&envelope(&address, &message));
+        Ok(SignedMessage {
+            address,
+            public_key,
+            message,
+            signature,
+        })
+    }
+
+    /// check the public key is the one of the address, and that it signed
+    /// the message for this address
+    pub fn verify(&self) -> Result<(), Error> {
+        if !belongs_to(&self.public_key, &self.address) {
+            return Err(Error::AddressMismatch);
+        }
+        if !self
+            .public_key
+            .verify(&envelope(&self.address, &self.message), &self.signature)
+        {
+            return Err(Error::InvalidSignature);
+        }
+        Ok(())
+    }
+
+    pub fn to_bytes(&self) -> cbor_event::Result<Vec<u8>> {
+        cbor!(self)
+    }
+
+    pub fn from_bytes(bytes: &[u8]) -> cbor_event::Result<Self> {
+        let mut raw = Deserializer::from(::std::io::Cursor::new(bytes));
+        raw.deserialize_complete()
+    }
+}
+
+fn belongs_to(public_key: &XPub, address: &ExtendedAddr) -> bool {
+    let sd = SpendingData::PubKeyASD(public_key.clone());
+    &ExtendedAddr::new(address.addr_type, sd, address.attributes.clone()) == address
+}
+
+fn envelope(address: &ExtendedAddr, message: &[u8]) -> Vec<u8> {
+    let mut se = Serializer::new_vec();
+    se.write_array(cbor_event::Len::Len(3))
+        .expect("write the envelope")
+        .write_text(MESSAGE_PREFIX)
+        .expect("write the message prefix")
+        .serialize(address)
+        .expect("serialize the address")
+        .write_bytes(message)
+        .expect("write the message");
+    se.finalize()
+}
+
+impl cbor_event::se::Serialize for SignedMessage {
+    fn serialize<'se, W: Write>(
+        &self,
+        serializer: &'se mut Serializer<W>,
+    ) -> cbor_event::Result<&'se mut Serializer<W>> {
+        serializer
+            .write_array(cbor_event::Len::Len(4))?
+            .serialize(&self.address)?
+            .serialize(&self.public_key)?
+            .write_bytes(&self.message)?
+            .serialize(&self.signature)
+    }
+}
+impl cbor_event::de::Deserialize for SignedMessage {
+    fn deserialize<R: BufRead>(raw: &mut Deserializer<R>) -> cbor_event::Result<Self> {
+        raw.tuple(4, "SignedMessage")?;
+        let address = raw.deserialize()?;
+        let public_key = raw.deserialize()?;
+        let message = raw.bytes()?;
+        let signature = raw.deserialize()?;
+        Ok(SignedMessage {
+            address,
+            public_key,
+            message,
+            signature,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use config::NetworkMagic;
+    use hdwallet::XPRV_SIZE;
+
+    fn key(seed: u8) -> XPrv {
+        XPrv::normalize_bytes([seed; XPRV_SIZE])
+    }
+
+    fn address(key: &XPrv) -> ExtendedAddr {
+        ExtendedAddr::new_simple(key.public(), NetworkMagic::NoMagic)
+    }
+
+    #[test]
+    fn sign_and_verify() {
+        let key = key(1);
+        let message = b"I own this address".to_vec();
+        let signed = SignedMessage::new(&key, address(&key), message).unwrap();
+        assert_eq!(signed.verify(), Ok(()));
+
+        let bytes = signed.to_bytes().unwrap();
+        assert_eq!(SignedMessage::from_bytes(&bytes).unwrap(), signed);
+    }
+
+    #[test]
+    fn signature_is_bound_to_message_and_address() {
+        let key1 = key(1);
+        let key2 = key(2);
+        let signed = SignedMessage::new(&key1, address(&key1), b"hello".to_vec()).unwrap();
+
+        let mut other_message = signed.clone();
+        other_message.message = b"goodbye".to_vec();
+        assert_eq!(other_message.verify(), Err(Error::InvalidSignature));
+
+        let mut other_address = signed.clone();
+        other_address.address = address(&key2);
+        assert_eq!(other_address.verify(), Err(Error::AddressMismatch));
+
+        // the same key, on an address of another network
+        let mut other_network = signed.clone();
+        other_network.address =
+            ExtendedAddr::new_simple(key1.public(), NetworkMagic::Magic(1097911063));
+        assert_eq!(other_network.verify(), Err(Error::InvalidSignature));
+
+        assert_eq!(
+            SignedMessage::new(&key1, address(&key2), b"hello".to_vec()),
+            Err(Error::AddressMismatch)
+        );
+    }
+}
@@ -1,4 +1,11 @@
 pub mod bip44;
+pub mod error;
+pub mod history;
 pub mod keygen;
+pub mod message;
 pub mod rindex;
 pub mod scheme;
+pub mod state;
+pub mod watch;
+
+pub use self::error::WalletError;
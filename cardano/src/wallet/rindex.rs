@@ -13,7 +13,7 @@ use input_selection;
 ///
 use std::{error, fmt, iter, ops::Deref};
 use tx::{self, Tx, TxAux, TxId, TxInWitness};
-use txutils::{self, OutputPolicy};
+use txutils;
 
 use super::scheme;
 
@@ -83,53 +83,66 @@ impl Wallet {
     /// the Wallet root public key.
     ///
     /// This function returns the addressing if the address belongs
-    /// to this wallet, otherwise it returns `None`
+    /// to this wallet, otherwise it returns `None` (including when the
+    /// payload of the address is invalid, see `try_check_address`).
     pub fn check_address(&self, address: &ExtendedAddr) -> Option<Addressing> {
-        let hdkey = hdpayload::HDKey::new(&self.root_key.public());
+        self.try_check_address(address).unwrap_or(None)
+    }
 
+    /// same as `check_address` but reports why an address with a
+    /// derivation path cannot be one of the wallet's addresses (the
+    /// payload is not a valid addressing, the address cannot be
+    /// reconstructed...).
+    pub fn try_check_address(
+        &self,
+        address: &ExtendedAddr,
+    ) -> super::error::Result<Option<Addressing>> {
         // This wallet has has only one account
         let account: &RootKey = scheme::Wallet::list_accounts(self);
-        if let &Some(ref hdpa) = &address.attributes.derivation_path {
-            if let Ok(path) = hdkey.decrypt_path(hdpa) {
-                let addressing = Addressing(path.as_ref()[0], path.as_ref()[1]);
-
+        let generator = account.address_generator();
+        match generator.try_get_addressing(address)? {
+            None => Ok(None),
+            Some(addressing) => {
                 // regenerate the address to prevent HDAddressPayload reuse
                 //
                 // i.e. it is possible to a mean player to reuse existing
                 // payload in their own addresses to make recipient believe
                 // they have received funds. This check prevents that to happen.
-                let addresses = scheme::Account::generate_addresses(
-                    account,
-                    [addressing].iter(),
-                    address.attributes.network_magic,
-                );
-
-                debug_assert!(
-                    addresses.len() == 1,
-                    "we expect to generate only one address here..."
-                );
-
-                if address == &addresses[0] {
-                    return Some(addressing);
-                }
+                generator.compare_address(address, &addressing)?;
+                Ok(Some(addressing))
             }
         }
-
-        None
     }
 
+    /// move all the `inputs` to the `output_policy`, the fee are computed
+    /// with the fee policy of the mainnet (see `move_transaction_with_fee_alg`)
     pub fn move_transaction(
         &self,
         protocol_magic: ProtocolMagic,
         inputs: &Vec<txutils::TxoPointerInfo<Addressing>>,
         output_policy: &txutils::OutputPolicy,
+    ) -> input_selection::Result<(TxAux, fee::Fee)> {
+        self.move_transaction_with_fee_alg(
+            protocol_magic,
+            &fee::LinearFee::default(),
+            inputs,
+            output_policy,
+        )
+    }
+
+    /// same as `move_transaction`, the fee are computed with `alg`, the fee
+    /// policy of the network (see `GenesisData::fee_policy`)
+    pub fn move_transaction_with_fee_alg<F: FeeAlgorithm>(
+        &self,
+        protocol_magic: ProtocolMagic,
+        alg: &F,
+        inputs: &Vec<txutils::TxoPointerInfo<Addressing>>,
+        output_policy: &txutils::OutputPolicy,
     ) -> input_selection::Result<(TxAux, fee::Fee)> {
         if inputs.len() == 0 {
             return Err(input_selection::Error::NoInputs);
         }
 
-        let alg = fee::LinearFee::default();
-
         let total_input: Coin = {
             let mut total = Coin::zero();
             for ref i in inputs.iter() {
@@ -158,14 +171,11 @@ impl Wallet {
 
         loop {
             let mut tx = tx_base.clone();
-            match output_policy {
-                OutputPolicy::One(change_addr) => {
-                    let txout = tx::TxOut::new(change_addr.clone(), out_total);
-                    tx.add_output(txout);
-                }
-            };
+            for txout in output_policy.change_outputs(out_total) {
+                tx.add_output(txout);
+            }
 
-            let current_diff = (total_input - tx.get_output_total()?).unwrap_or(Coin::zero());
+            let current_diff = total_input.saturating_sub(tx.get_output_total()?);
             let txaux_fee: fee::Fee = alg.calculate_for_txaux_component(&tx, &fake_witnesses)?;
 
             if current_diff == txaux_fee.to_coin() {
@@ -591,6 +601,7 @@ mod test {
     use super::*;
     use crate::config::ProtocolMagic;
     use crate::tx::TxoPointer;
+    use crate::txutils::OutputPolicy;
     use crate::wallet::rindex;
     use crate::wallet::scheme::Wallet;
 
@@ -666,4 +677,23 @@ mod test {
             assert!(witness.verify_tx(*PROTOCOL_MAGIC, &txaux.tx));
         }
     }
+
+    #[test]
+    fn test_move_rindex_wallet_with_fee_alg() {
+        let wallet = rindex::Wallet::from_daedalus_mnemonics(
+            DerivationScheme::V1,
+            &bip39::dictionary::ENGLISH,
+            MNEMONICS,
+        )
+        .unwrap();
+        let policy = OutputPolicy::One(OUTPUT.clone());
+        let (_, default_fee) = wallet
+            .move_transaction(*PROTOCOL_MAGIC, &INPUTS, &policy)
+            .unwrap();
+        let alg = fee::LinearFee::new(fee::Milli::integral(200_000), fee::Milli::new(43, 946));
+        let (_, fee) = wallet
+            .move_transaction_with_fee_alg(*PROTOCOL_MAGIC, &alg, &INPUTS, &policy)
+            .unwrap();
+        assert!(fee.to_coin() > default_fee.to_coin());
+    }
 }
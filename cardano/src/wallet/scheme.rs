@@ -75,6 +75,9 @@ pub trait Wallet {
     /// it select the needed inputs, compute the fee and possible change
     /// signes every TxIn as needed.
     ///
+    /// The fee are computed with the fee policy of the mainnet, see
+    /// `new_transaction_with_fee_alg` for the other networks.
+    ///
     fn new_transaction<'a, I>(
         &self,
         protocol_magic: ProtocolMagic,
@@ -87,29 +90,54 @@ pub trait Wallet {
         I: 'a + Iterator<Item = &'a Input<Self::Addressing>> + ExactSizeIterator,
         Self::Addressing: 'a,
     {
-        let fee_alg = fee::LinearFee::default();
+        self.new_transaction_with_fee_alg(
+            protocol_magic,
+            &fee::LinearFee::default(),
+            selection_policy,
+            inputs,
+            outputs,
+            output_policy,
+        )
+    }
 
+    /// same as `new_transaction`, the fee are computed with `fee_alg`, the
+    /// fee policy of the network (see `GenesisData::fee_policy`).
+    ///
+    fn new_transaction_with_fee_alg<'a, I, F>(
+        &self,
+        protocol_magic: ProtocolMagic,
+        fee_alg: &F,
+        selection_policy: SelectionPolicy,
+        inputs: I,
+        outputs: Vec<TxOut>,
+        output_policy: &OutputPolicy,
+    ) -> input_selection::Result<(tx::TxAux, fee::Fee)>
+    where
+        I: 'a + Iterator<Item = &'a Input<Self::Addressing>> + ExactSizeIterator,
+        Self::Addressing: 'a,
+        F: FeeAlgorithm,
+    {
         let selection_result = match selection_policy {
             SelectionPolicy::FirstMatchFirst => {
                 let inputs: Vec<Input<Self::Addressing>> = inputs.cloned().collect();
                 let mut alg = input_selection::HeadFirst::from(inputs);
-                alg.compute(&fee_alg, outputs.clone(), output_policy)?
+                alg.compute(fee_alg, outputs.clone(), output_policy)?
             }
             SelectionPolicy::LargestFirst => {
                 let inputs: Vec<Input<Self::Addressing>> = inputs.cloned().collect();
                 let mut alg = input_selection::LargestFirst::from(inputs);
-                alg.compute(&fee_alg, outputs.clone(), output_policy)?
+                alg.compute(fee_alg, outputs.clone(), output_policy)?
             }
             SelectionPolicy::Blackjack(dust) => {
                 let inputs: Vec<Input<Self::Addressing>> = inputs.cloned().collect();
                 let mut alg = input_selection::Blackjack::new(dust, inputs);
-                alg.compute(&fee_alg, outputs.clone(), output_policy)?
+                alg.compute(fee_alg, outputs.clone(), output_policy)?
             }
         };
 
         let mut txbuilder = TxBuilder::new();
         for input in selection_result.selected_inputs.iter() {
-            txbuilder.add_input(&input.ptr, input.value.value)
+            txbuilder.add_input_with_witness(&input.ptr, input.value.value, input.witness_kind)
         }
         for output in outputs.iter() {
             txbuilder.add_output_value(output);
@@ -118,17 +146,18 @@ pub trait Wallet {
         // here we try to add the output policy, if it didn't work because
         // the amount of coin leftover is not enough to add the policy, then
         // we ignore the error
-        match txbuilder.add_output_policy(&fee_alg, output_policy) {
+        match txbuilder.add_output_policy(fee_alg, output_policy) {
             Err(txbuild::Error::TxOutputPolicyNotEnoughCoins(_)) => {}
             Err(e) => return Err(input_selection::Error::TxBuildError(e)),
             Ok(_) => {}
         };
 
+        let witness_kinds = txbuilder.witness_kinds();
         let tx = txbuilder
             .make_tx()
             .map_err(input_selection::Error::TxBuildError)?;
         let txid = tx.id();
-        let mut txfinalized = TxFinalized::new(tx);
+        let mut txfinalized = TxFinalized::new_with_kinds(tx, witness_kinds);
 
         let witnesses = self.sign_tx(
             protocol_magic,
@@ -0,0 +1,331 @@
+//! wallet state snapshots.
+//!
+//! Restoring a wallet means replaying every block of the chain. A
+//! [`Checkpoint`](./struct.Checkpoint.html) captures everything needed to
+//! resume from a given block instead: the position in the chain, the UTxOs
+//! owned by the wallet and where the address lookup is at.
+//!
+
+use block::{BlockDate, EpochSlotId, HeaderHash};
+use cbor_event::{self, de::Deserializer, se::Serializer};
+use std::collections::BTreeMap;
+use std::io::{BufRead, Write};
+use txutils::UtxoSet;
+
+/// position of the wallet state in the chain
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct StatePtr {
+    /// date of the latest block applied to the state, `None` if the
+    /// state is still at the genesis
+    pub latest_addr: Option<BlockDate>,
+    /// hash of the latest block applied to the state (or of the genesis)
+    pub latest_known_hash: HeaderHash,
+}
+impl StatePtr {
+    pub fn new_before_genesis(genesis: HeaderHash) -> Self {
+        StatePtr {
+            latest_addr: None,
+            latest_known_hash: genesis,
+        }
+    }
+
+    pub fn new(date: BlockDate, hash: HeaderHash) -> Self {
+        StatePtr {
+            latest_addr: Some(date),
+            latest_known_hash: hash,
+        }
+    }
+}
+
+/// where the sequential index lookup of an account is at
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct SequentialCursor {
+    /// next address index to look for on the external chain
+    pub external: u32,
+    /// next address index to look for on the internal (change) chain
+    pub internal: u32,
+}
+
+/// state of the lookup structure used to recognize the wallet's addresses
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum LookupCursor {
+    /// random index (daedalus) wallets recognize their addresses from
+    /// the address payload, there is nothing to remember
+    RandomIndex,
+    /// sequential index (bip44) wallets: the cursor of each known account
+    SequentialIndex(BTreeMap<u32, SequentialCursor>),
+}
+
+/// snapshot of a wallet state, to persist and restore instead of
+/// replaying the chain from the start.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Checkpoint {
+    pub ptr: StatePtr,
+    pub utxos: UtxoSet,
+    pub cursor: LookupCursor,
+}
+impl Checkpoint {
+    pub fn new(ptr: StatePtr, utxos: UtxoSet, cursor: LookupCursor) -> Self {
+        Checkpoint { ptr, utxos, cursor }
+    }
+
+    /// serialize the checkpoint, ready to be persisted
+    pub fn to_bytes(&self) -> cbor_event::Result<Vec<u8>> {
+        cbor!(self)
+    }
+
+    /// restore a checkpoint previously serialized with `to_bytes`
+    pub fn from_bytes(bytes: &[u8]) -> cbor_event::Result<Self> {
+        let mut raw = Deserializer::from(::std::io::Cursor::new(bytes));
+        raw.deserialize_complete()
+    }
+
+    /// split the checkpoint into the pieces of the wallet state
+    pub fn into_parts(self) -> (StatePtr, UtxoSet, LookupCursor) {
+        (self.ptr, self.utxos, self.cursor)
+    }
+}
+
+/// decide when a new checkpoint is due, every `interval` blocks
+#[derive(Debug, Clone)]
+pub struct CheckpointSchedule {
+    interval: u64,
+    since_last: u64,
+}
+impl CheckpointSchedule {
+    pub fn new(interval: u64) -> Self {
+        CheckpointSchedule {
+            interval: interval,
+            since_last: 0,
+        }
+    }
+
+    /// to call after every block applied to the wallet state, returns
+    /// `true` when a checkpoint needs to be produced
+    pub fn block_applied(&mut self) -> bool {
+        self.since_last += 1;
+        if self.since_last >= self.interval {
+            self.since_last = 0;
+            true
+        } else {
+            false
+        }
+    }
+}
+
+const CHECKPOINT_VERSION: u64 = 1;
+
+const BLOCK_DATE_BOUNDARY: u64 = 0;
+const BLOCK_DATE_NORMAL: u64 = 1;
+
+const CURSOR_RANDOM_INDEX: u64 = 0;
+const CURSOR_SEQUENTIAL_INDEX: u64 = 1;
+
+impl cbor_event::se::Serialize for StatePtr {
+    fn serialize<'se, W: Write>(
+        &self,
+        serializer: &'se mut Serializer<W>,
+    ) -> cbor_event::Result<&'se mut Serializer<W>> {
+        serializer
+            .write_array(cbor_event::Len::Len(2))?
+            .serialize(&self.latest_known_hash)?;
+        match self.latest_addr {
+            None => serializer.write_array(cbor_event::Len::Len(0)),
+            Some(BlockDate::Boundary(epoch)) => serializer
+                .write_array(cbor_event::Len::Len(2))?
+                .write_unsigned_integer(BLOCK_DATE_BOUNDARY)?
+                .write_unsigned_integer(epoch),
+            Some(BlockDate::Normal(ref slot)) => serializer
+                .write_array(cbor_event::Len::Len(2))?
+                .write_unsigned_integer(BLOCK_DATE_NORMAL)?
+                .serialize(slot),
+        }
+    }
+}
+impl cbor_event::de::Deserialize for StatePtr {
+    fn deserialize<R: BufRead>(raw: &mut Deserializer<R>) -> cbor_event::Result<Self> {
+        raw.tuple(2, "StatePtr")?;
+        let latest_known_hash = raw.deserialize()?;
+        let latest_addr = match raw.array()? {
+            cbor_event::Len::Len(0) => None,
+            cbor_event::Len::Len(2) => match raw.unsigned_integer()? {
+                BLOCK_DATE_BOUNDARY => Some(BlockDate::Boundary(raw.unsigned_integer()?)),
+                BLOCK_DATE_NORMAL => Some(BlockDate::Normal(raw.deserialize::<EpochSlotId>()?)),
+                idx => {
+                    return Err(cbor_event::Error::CustomError(format!(
+                        "Unsupported BlockDate: {}",
+                        idx
+                    )));
+                }
+            },
+            len => {
+                return Err(cbor_event::Error::CustomError(format!(
+                    "Invalid StatePtr: received date of {:?} elements",
+                    len
+                )));
+            }
+        };
+        Ok(StatePtr {
+            latest_addr,
+            latest_known_hash,
+        })
+    }
+}
+
+impl cbor_event::se::Serialize for LookupCursor {
+    fn serialize<'se, W: Write>(
+        &self,
+        serializer: &'se mut Serializer<W>,
+    ) -> cbor_event::Result<&'se mut Serializer<W>> {
+        match self {
+            LookupCursor::RandomIndex => serializer
+                .write_array(cbor_event::Len::Len(1))?
+                .write_unsigned_integer(CURSOR_RANDOM_INDEX),
+            LookupCursor::SequentialIndex(accounts) => {
+                serializer
+                    .write_array(cbor_event::Len::Len(2))?
+                    .write_unsigned_integer(CURSOR_SEQUENTIAL_INDEX)?
+                    .write_map(cbor_event::Len::Len(accounts.len() as u64))?;
+                for (account, cursor) in accounts.iter() {
+                    serializer
+                        .write_unsigned_integer(*account as u64)?
+                        .write_array(cbor_event::Len::Len(2))?
+                        .write_unsigned_integer(cursor.external as u64)?
+                        .write_unsigned_integer(cursor.internal as u64)?;
+                }
+                Ok(serializer)
+            }
+        }
+    }
+}
+impl cbor_event::de::Deserialize for LookupCursor {
+    fn deserialize<R: BufRead>(raw: &mut Deserializer<R>) -> cbor_event::Result<Self> {
+        let len = raw.array()?;
+        match (len, raw.unsigned_integer()?) {
+            (cbor_event::Len::Len(1), CURSOR_RANDOM_INDEX) => Ok(LookupCursor::RandomIndex),
+            (cbor_event::Len::Len(2), CURSOR_SEQUENTIAL_INDEX) => {
+                let num_accounts = match raw.map()? {
+                    cbor_event::Len::Len(len) => len,
+                    cbor_event::Len::Indefinite => {
+                        return Err(cbor_event::Error::CustomError(format!(
+                            "Invalid LookupCursor: indefinite map of accounts"
+                        )));
+                    }
+                };
+                let mut accounts = BTreeMap::new();
+                for _ in 0..num_accounts {
+                    let account = raw.deserialize()?;
+                    raw.tuple(2, "SequentialCursor")?;
+                    let external = raw.deserialize()?;
+                    let internal = raw.deserialize()?;
+                    accounts.insert(account, SequentialCursor { external, internal });
+                }
+                Ok(LookupCursor::SequentialIndex(accounts))
+            }
+            (len, idx) => Err(cbor_event::Error::CustomError(format!(
+                "Unsupported LookupCursor: {} (with {:?} elements)",
+                idx, len
+            ))),
+        }
+    }
+}
+
+impl cbor_event::se::Serialize for Checkpoint {
+    fn serialize<'se, W: Write>(
+        &self,
+        serializer: &'se mut Serializer<W>,
+    ) -> cbor_event::Result<&'se mut Serializer<W>> {
+        serializer
+            .write_array(cbor_event::Len::Len(4))?
+            .write_unsigned_integer(CHECKPOINT_VERSION)?
+            .serialize(&self.ptr)?;
+        cbor_event::se::serialize_fixed_map(self.utxos.iter(), serializer)?;
+        serializer.serialize(&self.cursor)
+    }
+}
+impl cbor_event::de::Deserialize for Checkpoint {
+    fn deserialize<R: BufRead>(raw: &mut Deserializer<R>) -> cbor_event::Result<Self> {
+        raw.tuple(4, "Checkpoint")?;
+        let version = raw.unsigned_integer()?;
+        if version != CHECKPOINT_VERSION {
+            return Err(cbor_event::Error::CustomError(format!(
+                "Unsupported Checkpoint version: {}",
+                version
+            )));
+        }
+        let ptr = raw.deserialize()?;
+        let utxos = raw.deserialize()?;
+        let cursor = raw.deserialize()?;
+        Ok(Checkpoint { ptr, utxos, cursor })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use address::ExtendedAddr;
+    use coin::Coin;
+    use config::NetworkMagic;
+    use hdwallet::{XPub, XPUB_SIZE};
+    use tx::{TxId, TxOut, TxoPointer};
+
+    fn checkpoint(latest_addr: Option<BlockDate>, cursor: LookupCursor) -> Checkpoint {
+        let hash = HeaderHash::new(&[1, 2, 3]);
+        let mut utxos = UtxoSet::new();
+        for i in 0..3 {
+            let xpub = XPub::from_bytes([i; XPUB_SIZE]);
+            let address = ExtendedAddr::new_simple(xpub, NetworkMagic::NoMagic);
+            utxos.insert(
+                TxoPointer::new(TxId::new(&[i]), i as u32),
+                TxOut::new(address, Coin::new(1_000000 * i as u64).unwrap()),
+            );
+        }
+        let ptr = StatePtr {
+            latest_addr,
+            latest_known_hash: hash,
+        };
+        Checkpoint::new(ptr, utxos, cursor)
+    }
+
+    fn roundtrip(checkpoint: Checkpoint) {
+        let bytes = checkpoint.to_bytes().unwrap();
+        assert_eq!(Checkpoint::from_bytes(&bytes).unwrap(), checkpoint);
+    }
+
+    #[test]
+    fn checkpoint_random_index_roundtrip() {
+        roundtrip(checkpoint(None, LookupCursor::RandomIndex));
+        roundtrip(checkpoint(
+            Some(BlockDate::Boundary(3)),
+            LookupCursor::RandomIndex,
+        ));
+    }
+
+    #[test]
+    fn checkpoint_sequential_index_roundtrip() {
+        let mut accounts = BTreeMap::new();
+        accounts.insert(
+            0,
+            SequentialCursor {
+                external: 12,
+                internal: 3,
+            },
+        );
+        accounts.insert(0x80000001, SequentialCursor::default());
+        let date = BlockDate::Normal(EpochSlotId {
+            epoch: 42,
+            slotid: 1234,
+        });
+        roundtrip(checkpoint(
+            Some(date),
+            LookupCursor::SequentialIndex(accounts),
+        ));
+    }
+
+    #[test]
+    fn checkpoint_schedule() {
+        let mut schedule = CheckpointSchedule::new(3);
+        let due: Vec<bool> = (0..7).map(|_| schedule.block_applied()).collect();
+        assert_eq!(due, vec![false, false, true, false, false, true, false]);
+    }
+}
@@ -0,0 +1,291 @@
+//! Watch a set of addresses for the funds they receive and spend.
+//!
+//! This does not need any key: it is meant for the services notifying
+//! the owners of the addresses (payment detection of a merchant...), as
+//! new blocks are applied.
+
+use address::{Addr, ExtendedAddr};
+use block::{Block, HeaderHash};
+use coin::Coin;
+use config::MAINNET_EPOCH_STABILITY_DEPTH;
+use std::collections::{BTreeMap, BTreeSet, VecDeque};
+use tx::{TxAux, TxId, TxOut, TxoPointer};
+
+/// A change of the funds of a watched address
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum WatchEvent {
+    /// the output `ptr` pays `value` to the watched `address`
+    Received {
+        address: ExtendedAddr,
+        ptr: TxoPointer,
+        value: Coin,
+    },
+    /// the output `ptr` of the watched `address` is spent by the
+    /// transaction `by`
+    Spent {
+        address: ExtendedAddr,
+        ptr: TxoPointer,
+        value: Coin,
+        by: TxId,
+    },
+}
+impl WatchEvent {
+    pub fn address(&self) -> &ExtendedAddr {
+        match self {
+            WatchEvent::Received { address, .. } => address,
+            WatchEvent::Spent { address, .. } => address,
+        }
+    }
+}
+
+/// the number of the last applied blocks that can be undone by default:
+/// deeper blocks are stable
+pub const DEFAULT_UNDO_DEPTH: usize = MAINNET_EPOCH_STABILITY_DEPTH;
+
+/// The watched addresses and their unspent outputs.
+///
+/// The blocks need to be applied in the order of the chain, the outputs
+/// received before the watch started are not known to be spent. On a
+/// rollback, the blocks are undone from the tip with `undo_block`.
+#[derive(Debug, Clone)]
+pub struct AddressWatch {
+    addresses: BTreeSet<Addr>,
+    utxos: BTreeMap<TxoPointer, TxOut>,
+    /// the events of the last applied blocks, the tip at the back
+    undo: VecDeque<(HeaderHash, Vec<WatchEvent>)>,
+    undo_depth: usize,
+}
+impl Default for AddressWatch {
+    fn default() -> Self {
+        AddressWatch {
+            addresses: BTreeSet::new(),
+            utxos: BTreeMap::new(),
+            undo: VecDeque::new(),
+            undo_depth: DEFAULT_UNDO_DEPTH,
+        }
+    }
+}
+impl AddressWatch {
+    pub fn new() -> Self {
+        AddressWatch::default()
+    }
+
+    /// keep the events of the last `depth` applied blocks to undo them
+    pub fn with_undo_depth(mut self, depth: usize) -> Self {
+        self.undo_depth = depth;
+        while self.undo.len() > depth {
+            self.undo.pop_front();
+        }
+        self
+    }
+
+    /// start watching the given address
+    pub fn watch(&mut self, address: &ExtendedAddr) {
+        self.addresses.insert(address.to_address());
+    }
+
+    /// stop watching the given address, forgetting its unspent outputs
+    pub fn unwatch(&mut self, address: &ExtendedAddr) {
+        let addr = address.to_address();
+        self.addresses.remove(&addr);
+        self.utxos
+            .retain(|_, output| output.address.to_address() != addr);
+    }
+
+    pub fn is_watched(&self, address: &ExtendedAddr) -> bool {
+        self.addresses.contains(&address.to_address())
+    }
+
+    /// the unspent outputs of the watched addresses
+    pub fn utxos(&self) -> &BTreeMap<TxoPointer, TxOut> {
+        &self.utxos
+    }
+
+    /// the changes of the funds of the watched addresses in the block
+    pub fn apply_block(&mut self, block: &Block) -> Vec<WatchEvent> {
+        let hash = block.header().compute_hash();
+        match block {
+            Block::MainBlock(blk) => self.apply_txs(hash, blk.body.tx.iter()),
+            Block::BoundaryBlock(_) => self.apply_txs(hash, None),
+        }
+    }
+
+    fn apply_txs<'a, I>(&mut self, hash: HeaderHash, txs: I) -> Vec<WatchEvent>
+    where
+        I: IntoIterator<Item = &'a TxAux>,
+    {
+        let mut events = Vec::new();
+        for txaux in txs {
+            events.extend(self.apply_tx(txaux));
+        }
+        if self.undo_depth > 0 {
+            if self.undo.len() == self.undo_depth {
+                self.undo.pop_front();
+            }
+            self.undo.push_back((hash, events.clone()));
+        }
+        events
+    }
+
+    /// undo the last applied block, `hash`, reverting the changes of the
+    /// funds of the watched addresses: the events of the block are
+    /// returned, last first.
+    ///
+    /// `None` if `hash` is not the last applied block, or if it is deeper
+    /// than the undo depth.
+    pub fn undo_block(&mut self, hash: &HeaderHash) -> Option<Vec<WatchEvent>> {
+        match self.undo.back() {
+            Some((tip, _)) if tip == hash => {}
+            _ => return None,
+        }
+        let (_, mut events) = self.undo.pop_back().unwrap();
+        events.reverse();
+        for event in events.iter() {
+            match event {
+                WatchEvent::Received { ptr, .. } => {
+                    self.utxos.remove(ptr);
+                }
+                WatchEvent::Spent {
+                    address,
+                    ptr,
+                    value,
+                    ..
+                } => {
+                    if self.is_watched(address) {
+                        self.utxos
+                            .insert(ptr.clone(), TxOut::new(address.clone(), *value));
+                    }
+                }
+            }
+        }
+        Some(events)
+    }
+
+    /// the changes of the funds of the watched addresses in the
+    /// transaction. Unlike the blocks, the transactions applied on their
+    /// own cannot be undone.
+    pub fn apply_tx(&mut self, txaux: &TxAux) -> Vec<WatchEvent> {
+        let mut events = Vec::new();
+        let id = txaux.tx.id();
+
+        for txin in txaux.tx.inputs.iter() {
+            if let Some(output) = self.utxos.remove(txin) {
+                events.push(WatchEvent::Spent {
+                    address: output.address,
+                    ptr: txin.clone(),
+                    value: output.value,
+                    by: id,
+                });
+            }
+        }
+
+        for (index, output) in txaux.tx.outputs.iter().enumerate() {
+            if self.addresses.contains(&output.address.to_address()) {
+                let ptr = TxoPointer::new(id, index as u32);
+                self.utxos.insert(ptr.clone(), output.clone());
+                events.push(WatchEvent::Received {
+                    address: output.address.clone(),
+                    ptr,
+                    value: output.value,
+                });
+            }
+        }
+
+        events
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use test_utils::mk_address;
+    use tx::{Tx, TxWitness};
+
+    fn mk_txaux(seed: u8, inputs: Vec<TxoPointer>, outputs: Vec<ExtendedAddr>) -> TxAux {
+        let outputs = outputs
+            .into_iter()
+            .map(|address| TxOut::new(address, Coin::new(1_000000).unwrap()))
+            .collect();
+        let mut inputs = inputs;
+        inputs.push(TxoPointer::new(TxId::new(&[seed]), 0));
+        TxAux::new(Tx::new_with(inputs, outputs), TxWitness::new())
+    }
+
+    #[test]
+    fn received_and_spent() {
+        let (watched, other) = (mk_address(1), mk_address(2));
+        let mut watch = AddressWatch::new();
+        watch.watch(&watched);
+
+        let tx1 = mk_txaux(1, vec![], vec![other.clone(), watched.clone()]);
+        let events = watch.apply_tx(&tx1);
+        let ptr = TxoPointer::new(tx1.tx.id(), 1);
+        assert_eq!(
+            events,
+            vec![WatchEvent::Received {
+                address: watched.clone(),
+                ptr: ptr.clone(),
+                value: Coin::new(1_000000).unwrap(),
+            }]
+        );
+        assert_eq!(watch.utxos().len(), 1);
+
+        // spending an output of another address is not reported
+        let tx2 = mk_txaux(
+            2,
+            vec![TxoPointer::new(tx1.tx.id(), 0)],
+            vec![other.clone()],
+        );
+        assert!(watch.apply_tx(&tx2).is_empty());
+
+        let tx3 = mk_txaux(3, vec![ptr.clone()], vec![other]);
+        let events = watch.apply_tx(&tx3);
+        assert_eq!(
+            events,
+            vec![WatchEvent::Spent {
+                address: watched,
+                ptr,
+                value: Coin::new(1_000000).unwrap(),
+                by: tx3.tx.id(),
+            }]
+        );
+        assert!(watch.utxos().is_empty());
+    }
+
+    #[test]
+    fn undo_blocks() {
+        let (watched, other) = (mk_address(1), mk_address(2));
+        let mut watch = AddressWatch::new().with_undo_depth(2);
+        watch.watch(&watched);
+
+        let tx1 = mk_txaux(1, vec![], vec![watched.clone()]);
+        let ptr = TxoPointer::new(tx1.tx.id(), 0);
+        let tx2 = mk_txaux(2, vec![ptr.clone()], vec![other.clone()]);
+        let tx3 = mk_txaux(3, vec![], vec![watched.clone()]);
+        let (h1, h2, h3) = (
+            HeaderHash::new(b"1"),
+            HeaderHash::new(b"2"),
+            HeaderHash::new(b"3"),
+        );
+        let received = watch.apply_txs(h1.clone(), vec![&tx1]);
+        let spent = watch.apply_txs(h2.clone(), vec![&tx2]);
+        assert!(watch.utxos().is_empty());
+
+        // only the last applied block can be undone
+        assert_eq!(watch.undo_block(&h1), None);
+        assert_eq!(watch.undo_block(&h2), Some(spent));
+        assert_eq!(watch.utxos().keys().collect::<Vec<_>>(), vec![&ptr]);
+        assert_eq!(watch.undo_block(&h1), Some(received));
+        assert!(watch.utxos().is_empty());
+        assert_eq!(watch.undo_block(&h1), None);
+
+        // the blocks deeper than the undo depth are forgotten
+        watch.apply_txs(h1.clone(), vec![&tx1]);
+        watch.apply_txs(h2.clone(), vec![&tx2]);
+        watch.apply_txs(h3.clone(), vec![&tx3]);
+        assert!(watch.undo_block(&h3).is_some());
+        assert!(watch.undo_block(&h2).is_some());
+        assert_eq!(watch.undo_block(&h1), None);
+        assert_eq!(watch.utxos().keys().collect::<Vec<_>>(), vec![&ptr]);
+    }
+}
@@ -12,3 +12,4 @@ cfg_if! {
 pub mod mempack;
 pub mod packer;
 pub mod property;
+pub mod store;
@@ -0,0 +1,72 @@
+//! Block storage abstraction
+//!
+//! The interface the tools following a chain need from the storage of its
+//! blocks: get and put the blocks by identifier, the tip of the local
+//! chain, and the walk back from a block to the genesis.
+
+use crate::property::Block;
+
+/// Storage of the blocks of a chain.
+pub trait BlockStore {
+    /// The type of the blocks stored.
+    type Block: Block;
+
+    /// The error of the storage operations.
+    type Error: std::error::Error;
+
+    /// Get the block with the given identifier, `None` if it is not
+    /// stored.
+    fn get_block(
+        &self,
+        id: &<Self::Block as Block>::Id,
+    ) -> Result<Option<Self::Block>, Self::Error>;
+
+    /// Store the block. Storing a block already stored is not an error.
+    fn put_block(&mut self, block: &Self::Block) -> Result<(), Self::Error>;
+
+    /// Tell if the block with the given identifier is stored.
+    fn block_exists(&self, id: &<Self::Block as Block>::Id) -> Result<bool, Self::Error> {
+        self.get_block(id).map(|block| block.is_some())
+    }
+
+    /// The identifier of the tip of the local chain, `None` if there is
+    /// no local chain yet.
+    fn tip(&self) -> Result<Option<<Self::Block as Block>::Id>, Self::Error>;
+
+    /// Iterate over the block with the given identifier and its ancestors,
+    /// from the most recent to the oldest stored one.
+    fn ancestors(&self, id: <Self::Block as Block>::Id) -> Ancestors<Self>
+    where
+        Self: Sized,
+    {
+        Ancestors {
+            store: self,
+            next: Some(id),
+        }
+    }
+}
+
+/// Iterator over a block and its ancestors, see `BlockStore::ancestors`.
+///
+/// The iteration stops after the first block whose parent is not stored
+/// (e.g. the genesis block, whose parent identifier is `BlockId::zero`).
+pub struct Ancestors<'a, S: BlockStore> {
+    store: &'a S,
+    next: Option<<S::Block as Block>::Id>,
+}
+
+impl<'a, S: BlockStore> Iterator for Ancestors<'a, S> {
+    type Item = Result<S::Block, S::Error>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let id = self.next.take()?;
+        match self.store.get_block(&id) {
+            Ok(Some(block)) => {
+                self.next = Some(block.parent_id());
+                Some(Ok(block))
+            }
+            Ok(None) => None,
+            Err(err) => Some(Err(err)),
+        }
+    }
+}
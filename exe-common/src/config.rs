@@ -1,11 +1,13 @@
 pub mod net {
     use cardano::block::{EpochId, HeaderHash};
-    use cardano::config::ProtocolMagic;
+    use cardano::config::{EpochParameters, ProtocolMagic};
+    use network_ntt::transport::Address;
     use serde;
     use serde_yaml;
     use std::{
         fmt,
         fs::{self, File},
+        net::ToSocketAddrs,
         ops::{Deref, DerefMut},
         path::Path,
         str::FromStr,
@@ -45,6 +47,14 @@ pub mod net {
     ///
     /// let native_peer = Peer::new("mainnet.iohk.io".to_string());
     /// assert!(native_peer.is_native());
+    ///
+    /// // UNIX domain sockets are only available on unix platforms
+    /// #[cfg(unix)]
+    /// {
+    ///     let ntt_peer = Peer::new("x-cardano-ntt://unix:/run/cardano/node.sock".to_string());
+    ///     assert!(ntt_peer.is_ntt());
+    ///     assert!(ntt_peer.get_ntt_address().is_some());
+    /// }
     /// ```
     ///
     #[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord)]
@@ -66,6 +76,27 @@ pub mod net {
             }
         }
 
+        /// construct the `Peer` of the given protocol, whatever the form of
+        /// the address
+        pub fn with_protocol(protocol: PeerProtocol, addr: String) -> Self {
+            match protocol {
+                PeerProtocol::Native => Peer::native(addr),
+                PeerProtocol::Http => Peer::http(addr),
+                PeerProtocol::Ntt if addr.starts_with(NTT_PREFIX) => {
+                    Peer::ntt(addr[NTT_PREFIX.len()..].to_string())
+                }
+                PeerProtocol::Ntt => Peer::ntt(addr),
+            }
+        }
+
+        pub fn protocol(&self) -> PeerProtocol {
+            match self {
+                &Peer::Native(_) => PeerProtocol::Native,
+                &Peer::Http(_) => PeerProtocol::Http,
+                &Peer::Ntt(_) => PeerProtocol::Ntt,
+            }
+        }
+
         /// force constructing a native `Peer`.
         pub fn native(addr: String) -> Self {
             Peer::Native(addr)
@@ -100,6 +131,13 @@ pub mod net {
             }
         }
 
+        /// the parsed address of the ntt peer (see `parse_ntt_address`).
+        ///
+        /// `None` if it is not a ntt peer or the address is not valid.
+        pub fn get_ntt_address(&self) -> Option<Address> {
+            self.get_ntt().and_then(parse_ntt_address)
+        }
+
         /// get the address, indifferent to whether the `Peer` is a native or
         /// a http `Peer`.
         pub fn get_address(&self) -> &str {
@@ -122,6 +160,19 @@ pub mod net {
             self.get_ntt().is_some()
         }
     }
+    /// parse the address of a ntt peer: `unix:/path/to/socket` for a UNIX
+    /// domain socket, or else `host:port`, resolved to its first socket
+    /// address.
+    pub fn parse_ntt_address(addr: &str) -> Option<Address> {
+        if let Ok(address) = addr.parse() {
+            return Some(address);
+        }
+        addr.to_socket_addrs()
+            .ok()
+            .and_then(|mut addrs| addrs.next())
+            .map(Address::from)
+    }
+
     impl fmt::Display for Peer {
         fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
             match self {
@@ -136,9 +187,92 @@ pub mod net {
         where
             S: serde::Serializer,
         {
-            self.get_address().serialize(serializer)
+            match self {
+                // keep the prefix, for `Peer::new` to parse it back
+                &Peer::Ntt(ref addr) => format!("{}{}", NTT_PREFIX, addr).serialize(serializer),
+                _ => self.get_address().serialize(serializer),
+            }
+        }
+    }
+
+    /// The protocols to talk to a peer
+    #[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Serialize, Deserialize)]
+    #[serde(rename_all = "kebab-case")]
+    pub enum PeerProtocol {
+        Native,
+        Http,
+        Ntt,
+    }
+
+    /// What a peer can be used for
+    #[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Serialize, Deserialize)]
+    #[serde(rename_all = "kebab-case")]
+    pub enum Capability {
+        GetTip,
+        GetBlocks,
+        SendTransaction,
+        Subscribe,
+    }
+
+    /// The options of a peer, see `NamedPeer`
+    #[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+    pub struct PeerOptions {
+        /// a disabled peer is kept in the configuration but not used
+        #[serde(default = "default_enabled", skip_serializing_if = "is_true")]
+        pub enabled: bool,
+        /// the peers are used by increasing priority
+        #[serde(default, skip_serializing_if = "is_zero")]
+        pub priority: u32,
+        /// maximum number of requests per second, see
+        /// `network::peer::RateLimited`
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        pub rate_limit: Option<u32>,
+        /// what the peer is used for, everything it supports if empty
+        #[serde(default, skip_serializing_if = "Vec::is_empty")]
+        pub capabilities: Vec<Capability>,
+    }
+    impl Default for PeerOptions {
+        fn default() -> Self {
+            PeerOptions {
+                enabled: true,
+                priority: 0,
+                rate_limit: None,
+                capabilities: Vec::new(),
+            }
+        }
+    }
+    impl PeerOptions {
+        pub fn has_capability(&self, capability: Capability) -> bool {
+            self.capabilities.is_empty() || self.capabilities.contains(&capability)
         }
     }
+
+    fn default_enabled() -> bool {
+        true
+    }
+    fn is_true(b: &bool) -> bool {
+        *b
+    }
+    fn is_zero(n: &u32) -> bool {
+        *n == 0
+    }
+
+    /// the detailed form of a peer in the configuration file
+    #[derive(Serialize, Deserialize)]
+    struct PeerSpec {
+        address: String,
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        protocol: Option<PeerProtocol>,
+        #[serde(flatten)]
+        options: PeerOptions,
+    }
+
+    #[derive(Deserialize)]
+    #[serde(untagged)]
+    enum PeerEntry {
+        Address(Peer),
+        Spec(PeerSpec),
+    }
     impl<'de> serde::Deserialize<'de> for Peer {
         fn deserialize<D>(deserializer: D) -> Result<Self, D::Error>
         where
@@ -149,11 +283,28 @@ pub mod net {
         }
     }
 
+    /// A `Peer` with its name and options.
+    ///
+    /// In the configuration file, a peer is given either by its address
+    /// only, or with its options:
+    ///
+    /// ```yaml
+    /// - hermes: http://hermes.dev.iohkdev.io/mainnet
+    /// - local-node:
+    ///     address: unix:/run/cardano/node.sock
+    ///     protocol: ntt
+    ///     priority: 1
+    ///     rate_limit: 10
+    ///     capabilities: [get-tip, get-blocks]
+    /// ```
     #[derive(Debug, Clone)]
-    pub struct NamedPeer(String, Peer);
+    pub struct NamedPeer(String, Peer, PeerOptions);
     impl NamedPeer {
         pub fn new(name: String, peer: Peer) -> Self {
-            NamedPeer(name, peer)
+            NamedPeer(name, peer, PeerOptions::default())
+        }
+        pub fn with_options(name: String, peer: Peer, options: PeerOptions) -> Self {
+            NamedPeer(name, peer, options)
         }
         pub fn name(&self) -> &str {
             self.0.as_str()
@@ -161,6 +312,9 @@ pub mod net {
         pub fn peer(&self) -> &Peer {
             &self.1
         }
+        pub fn options(&self) -> &PeerOptions {
+            &self.2
+        }
     }
     impl Deref for NamedPeer {
         type Target = Peer;
@@ -180,7 +334,16 @@ pub mod net {
         {
             use serde::ser::SerializeMap;
             let mut map_serializer = serializer.serialize_map(Some(1))?;
-            map_serializer.serialize_entry(self.name(), self.peer())?;
+            if self.options() == &PeerOptions::default() {
+                map_serializer.serialize_entry(self.name(), self.peer())?;
+            } else {
+                let spec = PeerSpec {
+                    address: self.peer().get_address().to_string(),
+                    protocol: Some(self.peer().protocol()),
+                    options: self.options().clone(),
+                };
+                map_serializer.serialize_entry(self.name(), &spec)?;
+            }
             map_serializer.end()
         }
     }
@@ -204,7 +367,16 @@ pub mod net {
                     V::Error: serde::de::Error,
                 {
                     if let Some((k, v)) = visitor.next_entry()? {
-                        Ok(NamedPeer::new(k, v))
+                        Ok(match v {
+                            PeerEntry::Address(peer) => NamedPeer::new(k, peer),
+                            PeerEntry::Spec(spec) => {
+                                let peer = match spec.protocol {
+                                    None => Peer::new(spec.address),
+                                    Some(protocol) => Peer::with_protocol(protocol, spec.address),
+                                };
+                                NamedPeer::with_options(k, peer, spec.options)
+                            }
+                        })
                     } else {
                         Err(serde::de::Error::invalid_length(
                             0,
@@ -233,6 +405,31 @@ pub mod net {
             self.0.push(NamedPeer::new(name, peer))
         }
 
+        /// add a new peer with the given options in the `Peers` set
+        pub fn push_with_options(&mut self, name: String, peer: Peer, options: PeerOptions) {
+            self.0.push(NamedPeer::with_options(name, peer, options))
+        }
+
+        /// the enabled peers, by increasing priority (in the order of the
+        /// configuration for the same priority)
+        pub fn by_priority(&self) -> Vec<&NamedPeer> {
+            let mut peers: Vec<_> = self.iter().filter(|np| np.options().enabled).collect();
+            peers.sort_by_key(|np| np.options().priority);
+            peers
+        }
+
+        /// the enabled peers usable for all the given capabilities, by
+        /// increasing priority
+        pub fn with_capabilities(&self, capabilities: &[Capability]) -> Vec<&NamedPeer> {
+            let mut peers = self.by_priority();
+            peers.retain(|np| {
+                capabilities
+                    .iter()
+                    .all(|capability| np.options().has_capability(*capability))
+            });
+            peers
+        }
+
         pub fn natives<'a>(&'a self) -> Vec<&'a str> {
             self.iter()
                 .filter_map(|np| np.peer().get_native())
@@ -351,6 +548,11 @@ pub mod net {
             let mut file = File::open(path).unwrap();
             serde_yaml::from_reader(&mut file).unwrap()
         }
+
+        /// the length of the epochs of the network
+        pub fn epoch_parameters(&self) -> EpochParameters {
+            EpochParameters::new(self.epoch_stability_depth)
+        }
         pub fn to_file<P: AsRef<Path>>(&self, p: P) {
             let dir = p.as_ref().parent().unwrap().to_path_buf();
             fs::DirBuilder::new()
@@ -362,4 +564,107 @@ pub mod net {
             file.render_permanent(&p.as_ref().to_path_buf()).unwrap();
         }
     }
+
+    #[cfg(test)]
+    mod tests {
+        use super::*;
+
+        fn peers(yaml: &str) -> Peers {
+            serde_yaml::from_str(yaml).unwrap()
+        }
+
+        #[test]
+        fn peers_given_by_their_address() {
+            // the form of the configurations written before the options
+            let yaml = "---
+- iohk-hosts: relays.cardano-mainnet.iohk.io:3000
+- hermes: http://hermes.dev.iohkdev.io/mainnet
+";
+            let peers = peers(yaml);
+            assert_eq!(peers.len(), 2);
+            assert_eq!(peers[0].name(), "iohk-hosts");
+            assert!(peers[0].is_native());
+            assert_eq!(peers[1].name(), "hermes");
+            assert!(peers[1].is_http());
+            assert!(peers
+                .iter()
+                .all(|np| np.options() == &PeerOptions::default()));
+
+            // and written back the same way
+            let written = serde_yaml::to_string(&peers).unwrap();
+            assert!(!written.contains("address"));
+            let read: Peers = serde_yaml::from_str(&written).unwrap();
+            for (read, peer) in read.iter().zip(peers.iter()) {
+                assert_eq!(read.name(), peer.name());
+                assert_eq!(read.peer(), peer.peer());
+            }
+        }
+
+        #[test]
+        fn config_round_trip() {
+            for config in vec![Config::mainnet(), Config::staging(), Config::testnet()] {
+                let yaml = serde_yaml::to_string(&config).unwrap();
+                let read: Config = serde_yaml::from_str(&yaml).unwrap();
+                assert_eq!(read.genesis, config.genesis);
+                assert_eq!(read.protocol_magic, config.protocol_magic);
+                assert_eq!(read.peers.len(), config.peers.len());
+                for (read, peer) in read.peers.iter().zip(config.peers.iter()) {
+                    assert_eq!(read.name(), peer.name());
+                    assert_eq!(read.peer(), peer.peer());
+                    assert_eq!(read.options(), peer.options());
+                }
+            }
+        }
+
+        #[test]
+        fn peers_with_options() {
+            let peers = peers(
+                "---
+- hermes: http://hermes.dev.iohkdev.io/mainnet
+- relay:
+    address: relays.cardano-mainnet.iohk.io:3000
+    priority: 2
+    rate_limit: 10
+- local-node:
+    address: 127.0.0.1:3000
+    protocol: http
+    priority: 1
+    capabilities: [get-tip, get-blocks]
+- disabled:
+    address: 127.0.0.1:3001
+    enabled: false
+",
+            );
+            assert_eq!(peers.len(), 4);
+            assert!(peers[1].is_native());
+            assert_eq!(peers[1].options().rate_limit, Some(10));
+            // the address alone would be a native peer
+            assert!(peers[2].is_http());
+            assert!(!peers[3].options().enabled);
+
+            let names = |peers: Vec<&NamedPeer>| -> Vec<String> {
+                peers.iter().map(|np| np.name().to_owned()).collect()
+            };
+            assert_eq!(
+                names(peers.by_priority()),
+                vec!["hermes", "local-node", "relay"]
+            );
+            assert_eq!(
+                names(peers.with_capabilities(&[Capability::GetBlocks])),
+                vec!["hermes", "local-node", "relay"]
+            );
+            assert_eq!(
+                names(peers.with_capabilities(&[Capability::SendTransaction])),
+                vec!["hermes", "relay"]
+            );
+
+            let read: Peers =
+                serde_yaml::from_str(&serde_yaml::to_string(&peers).unwrap()).unwrap();
+            for (read, peer) in read.iter().zip(peers.iter()) {
+                assert_eq!(read.name(), peer.name());
+                assert_eq!(read.peer(), peer.peer());
+                assert_eq!(read.options(), peer.options());
+            }
+        }
+    }
 }
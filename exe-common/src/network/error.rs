@@ -2,13 +2,16 @@ use cardano::block::HeaderHash;
 use cardano_storage as storage;
 use cbor_event;
 use hyper;
+use network_ntt::client as ntt_client;
 use protocol::{self, ntt};
 use std::{error, fmt, io};
+use sync::validator;
 
 #[derive(Debug)]
 pub enum Error {
     IoError(io::Error),
     NttError(ntt::Error),
+    NttClientError(ntt_client::Error),
     ProtocolError(protocol::Error),
     CborError(cbor_event::Error),
     HyperError(hyper::Error),
@@ -17,8 +20,30 @@ pub enum Error {
     NoSuchBlock(HeaderHash),
     StorageError(storage::Error),
     BlockError(cardano::block::Error),
+    BlockRejected(HeaderHash, validator::Error),
     InvalidPeerAddress(String),
 }
+impl Error {
+    /// whether the operation may succeed if tried again later (the peer
+    /// was unreachable or the connection was lost), as opposed to errors
+    /// the peer will keep returning.
+    pub fn is_transient(&self) -> bool {
+        match self {
+            Error::IoError(err) => match err.kind() {
+                io::ErrorKind::ConnectionRefused
+                | io::ErrorKind::ConnectionReset
+                | io::ErrorKind::ConnectionAborted
+                | io::ErrorKind::BrokenPipe
+                | io::ErrorKind::TimedOut
+                | io::ErrorKind::Interrupted => true,
+                _ => false,
+            },
+            Error::NttClientError(err) => err.is_transient(),
+            Error::ConnectionTimedOut => true,
+            _ => false,
+        }
+    }
+}
 impl From<io::Error> for Error {
     fn from(e: io::Error) -> Self {
         Error::IoError(e)
@@ -39,6 +64,11 @@ impl From<ntt::Error> for Error {
         Error::NttError(e)
     }
 }
+impl From<ntt_client::Error> for Error {
+    fn from(e: ntt_client::Error) -> Self {
+        Error::NttClientError(e)
+    }
+}
 impl From<cbor_event::Error> for Error {
     fn from(e: cbor_event::Error) -> Self {
         Error::CborError(e)
@@ -59,6 +89,7 @@ impl fmt::Display for Error {
         match self {
             Error::IoError(_) => write!(f, "I/O Error"),
             Error::NttError(_) => write!(f, "Low level protocol error"),
+            Error::NttClientError(_) => write!(f, "Network client error"),
             Error::ProtocolError(_) => write!(f, "Blockchain protocol error"),
             Error::CborError(_) => write!(f, "Data encoding error"),
             Error::HyperError(_) => write!(f, "Error in HTTP engine"),
@@ -67,6 +98,7 @@ impl fmt::Display for Error {
             Error::NoSuchBlock(hash) => write!(f, "Requested block {} does not exist", hash),
             Error::StorageError(_) => write!(f, "Storage error"),
             Error::BlockError(_) => write!(f, "Block error"),
+            Error::BlockRejected(hash, _) => write!(f, "Block {} rejected", hash),
             Error::InvalidPeerAddress(addr) => write!(f, "Invalid peer address {}", addr),
         }
     }
@@ -76,6 +108,7 @@ impl error::Error for Error {
         match self {
             Error::IoError(ref err) => Some(err),
             Error::NttError(ref err) => Some(err),
+            Error::NttClientError(ref err) => Some(err),
             Error::ProtocolError(ref err) => Some(err),
             Error::CborError(ref err) => Some(err),
             Error::HyperError(ref err) => Some(err),
@@ -84,6 +117,7 @@ impl error::Error for Error {
             Error::NoSuchBlock(_) => None,
             Error::StorageError(ref err) => Some(err),
             Error::BlockError(ref err) => Some(err),
+            Error::BlockRejected(_, ref err) => Some(err),
             Error::InvalidPeerAddress(_) => None,
         }
     }
@@ -1,6 +1,7 @@
 use cardano::hash::HASH_SIZE_256;
 use cardano::{
     block::{block, Block, BlockDate, BlockHeader, HeaderHash, RawBlock},
+    config::EpochParameters,
     tx::TxAux,
 };
 use std::io::Write;
@@ -22,14 +23,18 @@ static NETWORK_REFRESH_FREQUENCY: Duration = Duration::from_secs(60 * 10);
 pub struct HermesEndPoint {
     pub url: String,
     pub blockchain: String,
+    epoch_parameters: EpochParameters,
     core: Core,
 }
 
 impl HermesEndPoint {
-    pub fn new(url: String, blockchain: String) -> Self {
+    /// `epoch_parameters` are the length of the epochs of the network
+    /// (see `config::net::Config::epoch_parameters`)
+    pub fn new(url: String, blockchain: String, epoch_parameters: EpochParameters) -> Self {
         HermesEndPoint {
             url,
             blockchain,
+            epoch_parameters,
             core: Core::new().unwrap(),
         }
     }
@@ -129,7 +134,7 @@ impl Api for HermesEndPoint {
         loop {
             // FIXME: hack
             if let BlockDate::Normal(d) = from.date {
-                if d.slotid == 21599 && !inclusive {
+                if d.slotid as usize + 1 == self.epoch_parameters.slots_per_epoch && !inclusive {
                     from = BlockRef {
                         hash: HeaderHash::from([0; HASH_SIZE_256]), // FIXME: use None?
                         parent: from.hash.clone(),
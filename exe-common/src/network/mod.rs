@@ -9,5 +9,5 @@ pub mod result;
 pub use self::api::*;
 pub use self::error::Error;
 pub use self::hermes::HermesEndPoint;
-pub use self::peer::Peer;
+pub use self::peer::{Peer, RateLimited};
 pub use self::result::Result;
@@ -7,8 +7,10 @@ use network::{Error, Result};
 //to_socket_addr
 use network_core::client::block::BlockService;
 use network_ntt::client as ntt;
-use std::net::SocketAddr;
+use network_ntt::transport::Address;
 use std::ops::Deref;
+use std::thread;
+use std::time::Duration;
 
 use cardano::{
     block::{Block, BlockHeader, HeaderHash, RawBlock},
@@ -16,16 +18,39 @@ use cardano::{
     tx::{TxAux, TxId},
 };
 
+/// number of times to try connecting to the peer while the failures are
+/// transient (see `ntt::Error::is_transient`).
+const CONNECT_ATTEMPTS: usize = 3;
+static CONNECT_RETRY_DELAY: Duration = Duration::from_secs(2);
+
 pub struct NetworkCore {
     handle: ntt::ClientHandle<Block, TxId>,
     pub rt: Runtime,
 }
 
 impl NetworkCore {
-    pub fn new(sockaddr: SocketAddr, magic: ProtocolMagic) -> Result<Self> {
-        trace!("New network core: {}", sockaddr);
-        let connecting = ntt::connect(sockaddr, ntt::ProtocolMagic::from(*magic.deref()));
-        match connecting.wait() {
+    pub fn new(address: Address, magic: ProtocolMagic) -> Result<Self> {
+        trace!("New network core: {}", address);
+        let mut attempt = 1;
+        let connected = loop {
+            let connecting = ntt::connect_to(
+                &address,
+                ntt::ProtocolMagic::from(*magic.deref()),
+                ntt::AllowedMagics::any(),
+            );
+            match connecting.wait() {
+                Err(ref err) if err.is_transient() && attempt < CONNECT_ATTEMPTS => {
+                    warn!(
+                        "cannot connect to {} ({}), retrying ({}/{})",
+                        address, err, attempt, CONNECT_ATTEMPTS
+                    );
+                    attempt += 1;
+                    thread::sleep(CONNECT_RETRY_DELAY);
+                }
+                result => break result,
+            }
+        };
+        match connected {
             Ok((connection, handle)) => {
                 // FIXME: use default executor, or take
                 // executor argument before merge.
@@ -41,7 +66,7 @@ impl NetworkCore {
                 );
                 Ok(NetworkCore { handle, rt })
             }
-            Err(_err) => unimplemented!(),
+            Err(err) => Err(Error::from(err)),
         }
     }
 }
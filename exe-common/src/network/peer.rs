@@ -1,4 +1,4 @@
-use cardano::config::ProtocolMagic;
+use cardano::config::{EpochParameters, ProtocolMagic};
 use cardano::{
     block::{Block, BlockHeader, HeaderHash, RawBlock},
     tx::TxAux,
@@ -6,7 +6,8 @@ use cardano::{
 use config;
 use network::api::{BlockRef, *};
 use network::{hermes, native, ntt, Error, Result};
-use std::net::ToSocketAddrs;
+use std::thread;
+use std::time::{Duration, Instant};
 
 /// network object to handle a peer connection and redirect to constructing
 /// the appropriate network protocol object (native, http...)
@@ -21,6 +22,7 @@ impl Peer {
         name: String,
         cfg: config::net::Peer,
         protocol_magic: ProtocolMagic,
+        epoch_parameters: EpochParameters,
     ) -> Result<Self> {
         match cfg {
             config::net::Peer::Native(addr) => Ok(Peer::Native(native::PeerPool::new(
@@ -28,18 +30,15 @@ impl Peer {
                 addr,
                 protocol_magic,
             )?)),
-            config::net::Peer::Http(addr) => {
-                Ok(Peer::Http(hermes::HermesEndPoint::new(addr, network)))
-            }
-            config::net::Peer::Ntt(addr) => {
-                let mut addrs_iter = addr
-                    .to_socket_addrs()
-                    .or_else(|_| Err(Error::InvalidPeerAddress(addr.to_string())))?;
-                match addrs_iter.next() {
-                    Some(addr) => ntt::NetworkCore::new(addr, protocol_magic).map(Peer::Ntt),
-                    None => Err(Error::InvalidPeerAddress(addr.to_string())),
-                }
-            }
+            config::net::Peer::Http(addr) => Ok(Peer::Http(hermes::HermesEndPoint::new(
+                addr,
+                network,
+                epoch_parameters,
+            ))),
+            config::net::Peer::Ntt(addr) => match config::net::parse_ntt_address(&addr) {
+                Some(address) => ntt::NetworkCore::new(address, protocol_magic).map(Peer::Ntt),
+                None => Err(Error::InvalidPeerAddress(addr.to_string())),
+            },
         }
     }
 }
@@ -93,3 +92,166 @@ impl Api for Peer {
         }
     }
 }
+
+/// A connection making at most a given number of requests per second
+/// (the `rate_limit` of the options of the peer, see
+/// `config::net::PeerOptions`): the requests are delayed as needed.
+pub struct RateLimited<A> {
+    inner: A,
+    interval: Duration,
+    last: Option<Instant>,
+}
+impl<A> RateLimited<A> {
+    /// at most `rate_limit` requests per second, no limit if `None` or 0
+    pub fn new(inner: A, rate_limit: Option<u32>) -> Self {
+        let interval = match rate_limit {
+            Some(rate) if rate > 0 => Duration::from_secs(1) / rate,
+            _ => Duration::from_secs(0),
+        };
+        RateLimited {
+            inner,
+            interval,
+            last: None,
+        }
+    }
+
+    pub fn get_ref(&self) -> &A {
+        &self.inner
+    }
+
+    pub fn into_inner(self) -> A {
+        self.inner
+    }
+
+    /// how long a request made at `now` has to wait, given the last one
+    fn delay(&self, now: Instant) -> Duration {
+        match self.last {
+            Some(last) => {
+                let elapsed = now.duration_since(last);
+                if elapsed < self.interval {
+                    self.interval - elapsed
+                } else {
+                    Duration::from_secs(0)
+                }
+            }
+            None => Duration::from_secs(0),
+        }
+    }
+
+    fn wait(&mut self) {
+        let delay = self.delay(Instant::now());
+        if delay > Duration::from_secs(0) {
+            thread::sleep(delay);
+        }
+        self.last = Some(Instant::now());
+    }
+}
+impl<A: Api> Api for RateLimited<A> {
+    fn get_tip(&mut self) -> Result<BlockHeader> {
+        self.wait();
+        self.inner.get_tip()
+    }
+
+    fn wait_for_new_tip(&mut self, prev_tip: &HeaderHash) -> Result<BlockHeader> {
+        self.wait();
+        self.inner.wait_for_new_tip(prev_tip)
+    }
+
+    fn get_block(&mut self, hash: &HeaderHash) -> Result<RawBlock> {
+        self.wait();
+        self.inner.get_block(hash)
+    }
+
+    fn get_blocks<F>(
+        &mut self,
+        from: &BlockRef,
+        inclusive: bool,
+        to: &BlockRef,
+        got_block: &mut F,
+    ) -> Result<()>
+    where
+        F: FnMut(&HeaderHash, &Block, &RawBlock) -> (),
+    {
+        self.wait();
+        self.inner.get_blocks(from, inclusive, to, got_block)
+    }
+
+    fn send_transaction(&mut self, txaux: TxAux) -> Result<bool> {
+        self.wait();
+        self.inner.send_transaction(txaux)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// counts the blocks requested, all empty
+    struct Counter(usize);
+    impl Api for Counter {
+        fn get_tip(&mut self) -> Result<BlockHeader> {
+            unimplemented!()
+        }
+        fn wait_for_new_tip(&mut self, _: &HeaderHash) -> Result<BlockHeader> {
+            unimplemented!()
+        }
+        fn get_block(&mut self, _: &HeaderHash) -> Result<RawBlock> {
+            self.0 += 1;
+            Ok(RawBlock::from_dat(Vec::new()))
+        }
+        fn get_blocks<F>(&mut self, _: &BlockRef, _: bool, _: &BlockRef, _: &mut F) -> Result<()>
+        where
+            F: FnMut(&HeaderHash, &Block, &RawBlock) -> (),
+        {
+            unimplemented!()
+        }
+        fn send_transaction(&mut self, _: TxAux) -> Result<bool> {
+            unimplemented!()
+        }
+    }
+
+    #[test]
+    fn rate_limit() {
+        assert_eq!(
+            RateLimited::new(Counter(0), Some(10)).interval,
+            Duration::from_millis(100)
+        );
+        assert_eq!(
+            RateLimited::new(Counter(0), None).interval,
+            Duration::from_secs(0)
+        );
+        assert_eq!(
+            RateLimited::new(Counter(0), Some(0)).interval,
+            Duration::from_secs(0)
+        );
+    }
+
+    #[test]
+    fn rate_limit_delay() {
+        let mut api = RateLimited::new(Counter(0), Some(10));
+        let start = Instant::now();
+        // the first request is not delayed
+        assert_eq!(api.delay(start), Duration::from_secs(0));
+
+        api.last = Some(start);
+        assert_eq!(api.delay(start), Duration::from_millis(100));
+        assert_eq!(
+            api.delay(start + Duration::from_millis(30)),
+            Duration::from_millis(70)
+        );
+        assert_eq!(
+            api.delay(start + Duration::from_millis(150)),
+            Duration::from_secs(0)
+        );
+    }
+
+    #[test]
+    fn rate_limit_requests() {
+        let mut api = RateLimited::new(Counter(0), None);
+        for _ in 0..3 {
+            api.get_block(&HeaderHash::new(&[])).unwrap();
+        }
+        assert_eq!(api.get_ref().0, 3);
+        assert!(api.last.is_some());
+    }
+}
@@ -0,0 +1,384 @@
+//! Chain selection between the tips announced by several remotes.
+//!
+//! The candidates are compared by their `ChainDifficulty`, the best one
+//! is selected only if it improves on the local chain. Then the candidate's
+//! chain is walked backward until a block we already hold is found (the
+//! divergence point), making sure it is part of our local chain, and
+//! producing a `Plan` describing which local blocks need to be rolled back
+//! before applying the candidate's blocks.
+
+use cardano::block::{BlockDate, BlockHeader, ChainDifficulty, HeaderHash};
+use cardano_storage::{self as storage, types::header_to_blockhash, Storage};
+use cbor_event;
+use network::{self, api::Api, api::BlockRef};
+use std::{error, fmt, result};
+
+/// A tip announced by a remote peer.
+#[derive(Debug, Clone)]
+pub struct Candidate {
+    pub peer: String,
+    pub tip: BlockHeader,
+}
+
+impl Candidate {
+    pub fn new(peer: String, tip: BlockHeader) -> Self {
+        Candidate { peer, tip }
+    }
+
+    pub fn difficulty(&self) -> ChainDifficulty {
+        self.tip.difficulty()
+    }
+}
+
+/// The steps to switch the local chain to a candidate's chain.
+#[derive(Debug, Clone)]
+pub struct Plan {
+    /// the peer the blocks need to be fetched from
+    pub peer: String,
+    /// the candidate's tip
+    pub tip: BlockRef,
+    pub difficulty: ChainDifficulty,
+    /// the most recent block common to the local chain and the candidate's
+    /// chain, this is where to fetch (exclusive) the candidate's blocks from.
+    pub fork_point: BlockRef,
+    /// the local blocks to roll back, from the local tip down to the
+    /// fork point (excluded).
+    pub rollback: Vec<HeaderHash>,
+}
+
+impl Plan {
+    /// the candidate's chain is a simple extension of the local chain
+    pub fn is_fast_forward(&self) -> bool {
+        self.rollback.is_empty()
+    }
+}
+
+#[derive(Debug)]
+pub enum Error {
+    /// none of the candidates has a better chain than the local one
+    NoBetterCandidate,
+    /// the divergence point is more than the allowed number of blocks
+    /// behind the local tip
+    ForkTooDeep(u64),
+    /// the divergence point is stored locally but is not an ancestor of the local tip
+    NotOnLocalChain(HeaderHash),
+    NetworkError(network::Error),
+    StorageError(storage::Error),
+    CborError(cbor_event::Error),
+}
+impl From<network::Error> for Error {
+    fn from(e: network::Error) -> Self {
+        Error::NetworkError(e)
+    }
+}
+impl From<storage::Error> for Error {
+    fn from(e: storage::Error) -> Self {
+        Error::StorageError(e)
+    }
+}
+impl From<cbor_event::Error> for Error {
+    fn from(e: cbor_event::Error) -> Self {
+        Error::CborError(e)
+    }
+}
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Error::NoBetterCandidate => write!(f, "No remote has a better chain than ours"),
+            Error::ForkTooDeep(max) => write!(
+                f,
+                "Remote chain diverges more than {} blocks before our tip",
+                max
+            ),
+            Error::NotOnLocalChain(hash) => write!(
+                f,
+                "Divergence point {} is not part of the local chain",
+                hash
+            ),
+            Error::NetworkError(_) => write!(f, "Network error"),
+            Error::StorageError(_) => write!(f, "Storage error"),
+            Error::CborError(_) => write!(f, "Data encoding error"),
+        }
+    }
+}
+impl error::Error for Error {
+    fn cause(&self) -> Option<&error::Error> {
+        match self {
+            Error::NoBetterCandidate => None,
+            Error::ForkTooDeep(_) => None,
+            Error::NotOnLocalChain(_) => None,
+            Error::NetworkError(ref err) => Some(err),
+            Error::StorageError(ref err) => Some(err),
+            Error::CborError(ref err) => Some(err),
+        }
+    }
+}
+
+pub type Result<T> = result::Result<T, Error>;
+
+fn block_ref(header: &BlockHeader) -> BlockRef {
+    BlockRef {
+        hash: header.compute_hash(),
+        parent: header.get_previous_header(),
+        date: header.get_blockdate(),
+    }
+}
+
+/// Select the candidate with the highest difficulty, provided it is
+/// strictly higher than the `local` difficulty.
+///
+/// On equal difficulty, the first candidate in the list is preferred.
+pub fn select_best<'a>(
+    local: ChainDifficulty,
+    candidates: &'a [Candidate],
+) -> Option<&'a Candidate> {
+    candidates
+        .iter()
+        .fold(None, |best: Option<&'a Candidate>, candidate| {
+            let threshold = best.map(|b| b.difficulty()).unwrap_or(local);
+            if candidate.difficulty() > threshold {
+                Some(candidate)
+            } else {
+                best
+            }
+        })
+}
+
+/// Compute the plan to switch from the local chain ending at `local_tip`
+/// to the candidate's chain.
+///
+/// The candidate's chain is walked backward through `net` (which is expected
+/// to be connected to the candidate's peer) until a block present in
+/// `storage` is found. That block needs to be an ancestor of `local_tip`,
+/// and at most `max_rollback` blocks deep.
+pub fn plan<A: Api>(
+    net: &mut A,
+    storage: &Storage,
+    local_tip: &BlockHeader,
+    candidate: &Candidate,
+    max_rollback: u64,
+) -> Result<Plan> {
+    let local_difficulty: u64 = local_tip.difficulty().into();
+    let min_difficulty = local_difficulty.saturating_sub(max_rollback);
+
+    // find the divergence point, walking back the candidate's chain
+    let mut fork = candidate.tip.clone();
+    loop {
+        let hash = fork.compute_hash();
+        if storage.block_exists(&header_to_blockhash(&hash))? {
+            break;
+        }
+        if u64::from(fork.difficulty()) < min_difficulty {
+            return Err(Error::ForkTooDeep(max_rollback));
+        }
+        fork = net
+            .get_block(&fork.get_previous_header())?
+            .decode()?
+            .get_header();
+    }
+    let fork_point = block_ref(&fork);
+    let fork_difficulty = fork.difficulty();
+
+    // check the divergence point is on our chain, collecting the
+    // blocks to roll back on the way
+    let mut rollback = Vec::new();
+    let mut current = local_tip.clone();
+    loop {
+        let hash = current.compute_hash();
+        if hash == fork_point.hash {
+            break;
+        }
+        if current.difficulty() < fork_difficulty {
+            return Err(Error::NotOnLocalChain(fork_point.hash));
+        }
+        if rollback.len() as u64 >= max_rollback {
+            return Err(Error::ForkTooDeep(max_rollback));
+        }
+        rollback.push(hash);
+        if let BlockDate::Boundary(0) = current.get_blockdate() {
+            // reached the beginning of the chain without meeting the fork point
+            return Err(Error::NotOnLocalChain(fork_point.hash));
+        }
+        let parent = header_to_blockhash(&current.get_previous_header());
+        current = storage.read_block(&parent)?.decode()?.get_header();
+    }
+
+    Ok(Plan {
+        peer: candidate.peer.clone(),
+        tip: block_ref(&candidate.tip),
+        difficulty: candidate.difficulty(),
+        fork_point,
+        rollback,
+    })
+}
+
+/// Select the best candidate and compute the plan to switch to its chain.
+///
+/// `connect` gives access to the network API of the peer announcing
+/// the selected candidate.
+pub fn select_and_plan<A, F>(
+    storage: &Storage,
+    local_tip: &BlockHeader,
+    candidates: &[Candidate],
+    max_rollback: u64,
+    connect: F,
+) -> Result<Plan>
+where
+    A: Api,
+    F: FnOnce(&Candidate) -> network::Result<A>,
+{
+    let candidate = match select_best(local_tip.difficulty(), candidates) {
+        None => return Err(Error::NoBetterCandidate),
+        Some(candidate) => candidate,
+    };
+    let mut net = connect(candidate)?;
+    plan(&mut net, storage, local_tip, candidate, max_rollback)
+}
+
+#[cfg(test)]
+mod test {
+    use super::super::test_utils::*;
+    use super::*;
+    use cardano::block::fake::FakeChain;
+    use cardano::block::{Block, EpochSlotId};
+    use cardano::config::ProtocolMagic;
+
+    fn candidate(peer: &str, block: &Block) -> Candidate {
+        Candidate::new(peer.to_owned(), block.get_header())
+    }
+
+    /// the local chain: an epoch boundary block and 3 main blocks, and a
+    /// fork of 3 main blocks from the first main block
+    fn chains() -> (Vec<Block>, Vec<Block>) {
+        let mut chain = FakeChain::new(ProtocolMagic::from(1), HeaderHash::new(b"genesis"));
+        let mut local = vec![chain.next_boundary(), chain.next_main()];
+        let mut fork = chain.clone();
+        local.push(chain.next_main());
+        local.push(chain.next_main());
+        let fork = vec![
+            fork.main_at(EpochSlotId {
+                epoch: 0,
+                slotid: 5,
+            }),
+            fork.next_main(),
+            fork.next_main(),
+        ];
+        (local, fork)
+    }
+
+    #[test]
+    fn select_best_candidate() {
+        let (local, fork) = chains();
+        let candidates = vec![
+            candidate("a", &fork[0]),
+            candidate("b", &fork[2]),
+            candidate("c", &local[3]),
+        ];
+        let best = select_best(local[1].header().difficulty(), &candidates).unwrap();
+        assert_eq!(best.peer, "b");
+
+        // on equal difficulty, the first candidate wins
+        let candidates = vec![candidate("a", &fork[1]), candidate("b", &local[3])];
+        let best = select_best(local[1].header().difficulty(), &candidates).unwrap();
+        assert_eq!(best.peer, "a");
+
+        // no candidate improves on the local chain
+        assert!(select_best(local[3].header().difficulty(), &candidates).is_none());
+        assert!(select_best(local[3].header().difficulty(), &[]).is_none());
+    }
+
+    #[test]
+    fn plan_fast_forward() {
+        let (local, _) = chains();
+        let storage = temp_storage("fast-forward");
+        store(&storage, &local[..2]);
+        let mut net = MockApi::new(&local);
+
+        let plan = plan(
+            &mut net,
+            &storage,
+            &local[1].get_header(),
+            &candidate("a", &local[3]),
+            10,
+        )
+        .unwrap();
+        assert!(plan.is_fast_forward());
+        assert_eq!(plan.fork_point.hash, local[1].header().compute_hash());
+        assert_eq!(plan.tip.hash, local[3].header().compute_hash());
+        assert_eq!(plan.difficulty, local[3].header().difficulty());
+    }
+
+    #[test]
+    fn plan_rollback() {
+        let (local, fork) = chains();
+        let storage = temp_storage("rollback");
+        store(&storage, &local);
+        let mut net = MockApi::new(&[&local[..], &fork[..]].concat());
+
+        let plan = plan(
+            &mut net,
+            &storage,
+            &local[3].get_header(),
+            &candidate("a", &fork[2]),
+            10,
+        )
+        .unwrap();
+        assert!(!plan.is_fast_forward());
+        assert_eq!(plan.fork_point.hash, local[1].header().compute_hash());
+        assert_eq!(
+            plan.rollback,
+            vec![
+                local[3].header().compute_hash(),
+                local[2].header().compute_hash()
+            ]
+        );
+
+        match super::plan(
+            &mut net,
+            &storage,
+            &local[3].get_header(),
+            &candidate("a", &fork[2]),
+            1,
+        ) {
+            Err(Error::ForkTooDeep(1)) => {}
+            r => panic!("unexpected result: {:?}", r),
+        }
+    }
+
+    #[test]
+    fn plan_not_on_local_chain() {
+        let (local, fork) = chains();
+        let storage = temp_storage("not-on-local-chain");
+        store(&storage, &local);
+        // the fork's first block is known, but is not part of the local chain
+        store(&storage, &fork[..1]);
+        let mut net = MockApi::new(&fork);
+
+        match plan(
+            &mut net,
+            &storage,
+            &local[3].get_header(),
+            &candidate("a", &fork[2]),
+            10,
+        ) {
+            Err(Error::NotOnLocalChain(hash)) => {
+                assert_eq!(hash, fork[0].header().compute_hash())
+            }
+            r => panic!("unexpected result: {:?}", r),
+        }
+    }
+
+    #[test]
+    fn select_and_plan_no_better_candidate() {
+        let (local, _) = chains();
+        let storage = temp_storage("no-better-candidate");
+        let candidates = vec![candidate("a", &local[2])];
+        let r = select_and_plan(&storage, &local[3].get_header(), &candidates, 10, |_| {
+            Ok(MockApi::new(&[]))
+        });
+        match r {
+            Err(Error::NoBetterCandidate) => {}
+            r => panic!("unexpected result: {:?}", r),
+        }
+    }
+}
@@ -7,12 +7,27 @@ use cardano_storage::{
     pack, tag, types, Error, Storage,
 };
 use config::net;
-use network::{api::Api, api::BlockRef, Peer, Result};
+use network::{self, api::Api, api::BlockRef, Peer, RateLimited, Result};
 use std::mem;
 use std::sync::{Arc, RwLock};
+use std::thread;
 use std::time::{Duration, SystemTime};
 use storage_units::packfile;
 
+pub mod chain_select;
+mod status;
+#[cfg(test)]
+mod test_utils;
+pub mod validator;
+
+pub use self::status::{SyncPhase, SyncProgress, SyncStatus, SyncStatusHandle};
+pub use self::validator::BlockValidator;
+
+/// number of times to try an exchange with the peer while the failures
+/// are transient (see `network::Error::is_transient`).
+const SYNC_ATTEMPTS: usize = 5;
+const SYNC_RETRY_DELAY: Duration = Duration::from_secs(5);
+
 fn duration_print(d: Duration) -> String {
     format!("{}.{:03} seconds", d.as_secs(), d.subsec_millis())
 }
@@ -24,18 +39,21 @@ struct EpochWriterState {
     blobs_to_delete: Vec<HeaderHash>,
 }
 
-fn net_sync_to<A: Api>(
+fn net_sync_to<A: Api, V: BlockValidator>(
     net: &mut A,
     net_cfg: &net::Config,
     genesis_data: &GenesisData,
     storage: Arc<RwLock<Storage>>,
     tip_header: &BlockHeader,
+    status: &SyncStatusHandle,
+    validator: &mut V,
 ) -> Result<()> {
     let tip = BlockRef {
         hash: tip_header.compute_hash(),
         parent: tip_header.get_previous_header(),
         date: tip_header.get_blockdate(),
     };
+    status.set_remote_tip(tip.clone());
     let storage_config = storage.read().unwrap().config.clone();
 
     debug!("Configured genesis   : {}", net_cfg.genesis);
@@ -76,6 +94,7 @@ fn net_sync_to<A: Api>(
     // stable epoch before our_tip.
 
     info!("Fetching from        : {} ({})", our_tip.hash, our_tip.date);
+    status.set_local_tip(our_tip.hash.clone(), our_tip.date);
 
     // Determine whether the previous epoch is stable yet. Note: This
     // assumes that k is smaller than the number of blocks in an
@@ -116,6 +135,7 @@ fn net_sync_to<A: Api>(
         // If tip.slotid < w, the previous epoch won't have been
         // created yet either, so do that now.
         if epoch_id > net_cfg.epoch_start {
+            status.set_phase(SyncPhase::PackingEpoch(epoch_id - 1));
             maybe_create_epoch(
                 &mut storage.write().unwrap(),
                 genesis_data,
@@ -150,6 +170,8 @@ fn net_sync_to<A: Api>(
         && first_unstable_epoch > net_cfg.epoch_start
         && !epoch_exists(&storage_config, first_unstable_epoch - 1).unwrap()
     {
+        status.set_phase(SyncPhase::PackingEpoch(first_unstable_epoch - 1));
+
         // Iterate to the last block in the previous epoch.
         let mut cur_hash = our_tip.hash.clone();
         loop {
@@ -186,11 +208,23 @@ fn net_sync_to<A: Api>(
         },
     )?;
 
+    status.set_phase(SyncPhase::FetchingBlocks);
+
+    let mut rejected = Ok(());
     net.get_blocks(
         &our_tip,
         our_tip_is_genesis,
         &tip,
         &mut |block_hash, block, block_raw| {
+            // once a block is rejected, ignore the ones following it
+            if rejected.is_err() {
+                return;
+            }
+            if let Err(err) = validator.validate(&chain_state.last_block, block_hash, block) {
+                rejected = Err(network::Error::BlockRejected(block_hash.clone(), err));
+                return;
+            }
+
             let date: BlockDate = block.header().blockdate();
 
             let epoch_transition = chain_state
@@ -227,6 +261,7 @@ fn net_sync_to<A: Api>(
             chain_state
                 .verify_block(block_hash, block)
                 .expect(&format!("Block {} ({}) failed to verify", block_hash, date));
+            status.block_received(block_hash.clone(), date);
 
             if date.get_epochid() >= first_unstable_epoch {
                 // This block is not part of a stable epoch yet and could
@@ -257,6 +292,7 @@ fn net_sync_to<A: Api>(
             }
         },
     )?;
+    rejected?;
 
     // Update the tip tag to point to the most recent block.
     tag::write(
@@ -288,22 +324,130 @@ pub fn net_sync<A: Api>(
     storage: Arc<RwLock<Storage>>,
     sync_once: bool,
 ) -> Result<()> {
+    net_sync_with_status(
+        net,
+        net_cfg,
+        genesis_data,
+        storage,
+        sync_once,
+        &SyncStatusHandle::new(),
+    )
+}
+
+/// Same as `net_sync`, reporting the progress of the synchronization
+/// in `status`.
+pub fn net_sync_with_status<A: Api>(
+    net: &mut A,
+    net_cfg: &net::Config,
+    genesis_data: &GenesisData,
+    storage: Arc<RwLock<Storage>>,
+    sync_once: bool,
+    status: &SyncStatusHandle,
+) -> Result<()> {
+    net_sync_with_validator(
+        net,
+        net_cfg,
+        genesis_data,
+        storage,
+        sync_once,
+        status,
+        &mut validator::HashLinkage,
+    )
+}
+
+/// Same as `net_sync_with_status`, giving every block fetched to
+/// `validator` before writing it to `storage`. The synchronization stops
+/// with `Error::BlockRejected` at the first block the validator rejects.
+pub fn net_sync_with_validator<A: Api, V: BlockValidator>(
+    net: &mut A,
+    net_cfg: &net::Config,
+    genesis_data: &GenesisData,
+    storage: Arc<RwLock<Storage>>,
+    sync_once: bool,
+    status: &SyncStatusHandle,
+    validator: &mut V,
+) -> Result<()> {
+    let result = do_net_sync(
+        net,
+        net_cfg,
+        genesis_data,
+        storage,
+        sync_once,
+        status,
+        validator,
+    );
+    match &result {
+        Ok(()) => status.set_phase(SyncPhase::Done),
+        Err(err) => status.set_error(err),
+    }
+    result
+}
+
+fn do_net_sync<A: Api, V: BlockValidator>(
+    net: &mut A,
+    net_cfg: &net::Config,
+    genesis_data: &GenesisData,
+    storage: Arc<RwLock<Storage>>,
+    sync_once: bool,
+    status: &SyncStatusHandle,
+    validator: &mut V,
+) -> Result<()> {
+    status.set_epoch_parameters(genesis_data.epoch_parameters());
+
     // recover and print the TIP of the network
-    let mut tip_header = net.get_tip()?;
+    status.set_phase(SyncPhase::FetchingTip);
+    let mut tip_header = retrying(SYNC_RETRY_DELAY, || net.get_tip())?;
 
     loop {
-        net_sync_to(net, net_cfg, genesis_data, storage.clone(), &tip_header)?;
+        // the synchronization resumes from the HEAD tag, so a failed one
+        // can be started again
+        retrying(SYNC_RETRY_DELAY, || {
+            net_sync_to(
+                net,
+                net_cfg,
+                genesis_data,
+                storage.clone(),
+                &tip_header,
+                status,
+                validator,
+            )
+        })?;
 
         if sync_once {
             break;
         }
 
-        tip_header = net.wait_for_new_tip(&tip_header.compute_hash())?;
+        status.set_phase(SyncPhase::WaitingForTip);
+        let tip_hash = tip_header.compute_hash();
+        tip_header = retrying(SYNC_RETRY_DELAY, || net.wait_for_new_tip(&tip_hash))?;
     }
 
     Ok(())
 }
 
+/// run `f` until it succeeds or fails with an error which is not
+/// transient, at most `SYNC_ATTEMPTS` times, waiting `delay` between the
+/// attempts.
+fn retrying<T, F>(delay: Duration, mut f: F) -> Result<T>
+where
+    F: FnMut() -> Result<T>,
+{
+    let mut attempt = 1;
+    loop {
+        match f() {
+            Err(ref err) if err.is_transient() && attempt < SYNC_ATTEMPTS => {
+                warn!(
+                    "exchange with the peer failed ({}), retrying ({}/{})",
+                    err, attempt, SYNC_ATTEMPTS
+                );
+                attempt += 1;
+                thread::sleep(delay);
+            }
+            result => return result,
+        }
+    }
+}
+
 // Create an epoch from a complete set of previously fetched blocks on
 // disk.
 fn maybe_create_epoch(
@@ -430,16 +574,21 @@ fn finish_epoch(
     Ok(())
 }
 
-pub fn get_peer(blockchain: &str, cfg: &net::Config, native: bool) -> Peer {
-    for peer in cfg.peers.iter() {
+/// the first peer of the configuration to sync from, by priority, with
+/// its rate limit
+pub fn get_peer(blockchain: &str, cfg: &net::Config, native: bool) -> RateLimited<Peer> {
+    let capabilities = [net::Capability::GetTip, net::Capability::GetBlocks];
+    for peer in cfg.peers.with_capabilities(&capabilities) {
         if (native && peer.is_native()) || (!native && peer.is_http()) {
-            return Peer::new(
+            let connection = Peer::new(
                 String::from(blockchain),
                 peer.name().to_owned(),
                 peer.peer().clone(),
                 cfg.protocol_magic,
+                cfg.epoch_parameters(),
             )
             .unwrap();
+            return RateLimited::new(connection, peer.options().rate_limit);
         }
     }
 
@@ -457,3 +606,266 @@ pub fn get_chain_state_at_end_of(
         &chain_state::get_last_block_of_epoch(storage, epoch_id)?,
     )?)
 }
+
+/// Download the blocks of a stable epoch again and pack them, replacing
+/// the epoch in `storage` (e.g. after `cardano_storage::check_epochs`
+/// reported it missing or corrupted).
+///
+/// The epochs before `epoch_id` must be valid, and the first block of the
+/// next epoch must already be in `storage` (packed or not): the blocks are
+/// fetched up to it.
+pub fn resync_epoch<A: Api>(
+    net: &mut A,
+    net_cfg: &net::Config,
+    genesis_data: &GenesisData,
+    storage: Arc<RwLock<Storage>>,
+    epoch_id: EpochId,
+) -> Result<()> {
+    let storage_config = storage.read().unwrap().config.clone();
+
+    // start from the last block of the previous epoch, or the genesis
+    let from_genesis = epoch_id == net_cfg.epoch_start;
+    let from = if from_genesis {
+        BlockRef {
+            hash: net_cfg.genesis.clone(),
+            parent: net_cfg.genesis_prev.clone(),
+            date: BlockDate::Boundary(net_cfg.epoch_start),
+        }
+    } else {
+        let storage = storage.read().unwrap();
+        let hash = chain_state::get_last_block_of_epoch(&storage, epoch_id - 1)?;
+        let block = storage.read_block(&hash.clone().into())?.decode()?;
+        let header = block.header();
+        BlockRef {
+            hash,
+            parent: header.previous_header(),
+            date: header.blockdate(),
+        }
+    };
+    let to = get_first_block_of_epoch(&storage.read().unwrap(), epoch_id + 1)?;
+
+    info!(
+        "Resyncing epoch {} from {} to {}",
+        epoch_id, from.hash, to.hash
+    );
+
+    let mut chain_state = chain_state::restore_chain_state(
+        &storage.read().unwrap(),
+        genesis_data,
+        if from_genesis {
+            &from.parent
+        } else {
+            &from.hash
+        },
+    )?;
+
+    let mut epoch_writer_state = EpochWriterState {
+        epoch_id,
+        writer: pack::packwriter_init(&storage_config)?,
+        write_start_time: SystemTime::now(),
+        blobs_to_delete: vec![],
+    };
+
+    let mut result = Ok(());
+    net.get_blocks(
+        &from,
+        from_genesis,
+        &to,
+        &mut |block_hash, block, block_raw| {
+            // `to` is the first block of the next epoch, it is not part of the pack
+            if result.is_err() || block.header().blockdate().get_epochid() != epoch_id {
+                return;
+            }
+            result = chain_state
+                .verify_block(block_hash, block)
+                .map_err(network::Error::from)
+                .and_then(|()| {
+                    let hash = types::header_to_blockhash(&block_hash);
+                    Ok(epoch_writer_state
+                        .writer
+                        .append(&hash, block_raw.as_ref())?)
+                });
+        },
+    )?;
+    result?;
+
+    // the peer did not send the blocks of the epoch
+    if chain_state.last_date.map(|date| date.get_epochid()) != Some(epoch_id) {
+        return Err(Error::EpochExpectingBoundary.into());
+    }
+
+    finish_epoch(
+        &mut storage.write().unwrap(),
+        genesis_data,
+        epoch_writer_state,
+        &chain_state,
+    )
+}
+
+// Find the first block of `epoch_id`, either in its epoch pack or by
+// walking back the blocks fetched since the last packed epoch.
+fn get_first_block_of_epoch(storage: &Storage, epoch_id: EpochId) -> Result<BlockRef> {
+    let block = match epoch::epoch_open_pack_reader(&storage.config, epoch_id)? {
+        Some(mut reader) => match pack::packreader_block_next(&mut reader)? {
+            Some(block_raw) => block_raw.decode()?,
+            None => return Err(Error::EpochExpectingBoundary.into()),
+        },
+        None => {
+            let mut block = storage.get_block_from_tag(&tag::HEAD)?;
+            loop {
+                let (date, prev_hash) = {
+                    let hdr = block.header();
+                    (hdr.blockdate(), hdr.previous_header())
+                };
+                if date.get_epochid() < epoch_id {
+                    return Err(Error::EpochExpectingBoundary.into());
+                }
+                if date.get_epochid() == epoch_id && date.is_boundary() {
+                    break;
+                }
+                block = storage.read_block(&prev_hash.into())?.decode()?;
+            }
+            block
+        }
+    };
+    let header = block.header();
+    Ok(BlockRef {
+        hash: header.compute_hash(),
+        parent: header.previous_header(),
+        date: header.blockdate(),
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::test_utils::*;
+    use super::*;
+    use cardano::block::fake::FakeChain;
+    use cardano::config::ProtocolMagic;
+    use cardano::fee::LinearFee;
+    use std::collections::BTreeMap;
+    use std::time::UNIX_EPOCH;
+
+    /// a genesis with 10 slots per epoch
+    fn genesis_data(protocol_magic: ProtocolMagic) -> GenesisData {
+        GenesisData {
+            genesis_prev: HeaderHash::new(b"genesis"),
+            epoch_stability_depth: 1,
+            start_time: UNIX_EPOCH,
+            slot_duration: Duration::from_secs(20),
+            protocol_magic,
+            fee_policy: LinearFee::default(),
+            avvm_distr: BTreeMap::new(),
+            non_avvm_balances: BTreeMap::new(),
+            boot_stakeholders: BTreeMap::new(),
+        }
+    }
+
+    fn net_config(genesis: &Block) -> net::Config {
+        net::Config {
+            genesis: genesis.header().compute_hash(),
+            genesis_prev: HeaderHash::new(b"genesis"),
+            epoch_stability_depth: 1,
+            protocol_magic: ProtocolMagic::from(1),
+            epoch_start: 0,
+            peers: net::Peers::new(),
+        }
+    }
+
+    /// 3 epochs of a boundary block and 3 main blocks
+    fn epochs() -> Vec<Vec<Block>> {
+        let mut chain = FakeChain::new(ProtocolMagic::from(1), HeaderHash::new(b"genesis"))
+            .with_slot_leaders(10);
+        (0..3)
+            .map(|_| {
+                let mut blocks = vec![chain.next_boundary()];
+                blocks.extend((0..3).map(|_| chain.next_main()));
+                blocks
+            })
+            .collect()
+    }
+
+    /// a storage with the epochs after the first one as blobs, HEAD
+    /// pointing at the last block
+    fn storage(name: &str, epochs: &[Vec<Block>]) -> Arc<RwLock<Storage>> {
+        let storage = temp_storage(name);
+        let blocks = epochs[1..].concat();
+        store(&storage, &blocks);
+        let head = blocks.last().unwrap().header().compute_hash();
+        tag::write_hash(&storage, &tag::HEAD, &head);
+        Arc::new(RwLock::new(storage))
+    }
+
+    #[test]
+    fn retry_the_transient_errors() {
+        let delay = Duration::from_millis(1);
+
+        let mut calls = 0;
+        let result = retrying(delay, || {
+            calls += 1;
+            if calls < 3 {
+                Err(network::Error::ConnectionTimedOut)
+            } else {
+                Ok(calls)
+            }
+        });
+        assert_eq!(result.unwrap(), 3);
+
+        let mut calls = 0;
+        let result: Result<()> = retrying(delay, || {
+            calls += 1;
+            Err(network::Error::ConnectionTimedOut)
+        });
+        assert!(result.is_err());
+        assert_eq!(calls, SYNC_ATTEMPTS);
+
+        let mut calls = 0;
+        let result: Result<()> = retrying(delay, || {
+            calls += 1;
+            Err(network::Error::NoSuchBlock(HeaderHash::new(b"missing")))
+        });
+        assert!(result.is_err());
+        assert_eq!(calls, 1);
+    }
+
+    #[test]
+    fn resync_epochs() {
+        let epochs = epochs();
+        let storage = storage("resync", &epochs);
+        let mut net = MockApi::new(&epochs.concat());
+        let genesis_data = genesis_data(ProtocolMagic::from(1));
+        let net_cfg = net_config(&epochs[0][0]);
+
+        resync_epoch(&mut net, &net_cfg, &genesis_data, storage.clone(), 0).unwrap();
+        resync_epoch(&mut net, &net_cfg, &genesis_data, storage.clone(), 1).unwrap();
+
+        let storage = storage.read().unwrap();
+        for (epoch_id, blocks) in epochs[..2].iter().enumerate() {
+            let last = blocks.last().unwrap().header().compute_hash();
+            let chain_state =
+                get_chain_state_at_end_of(&storage, epoch_id as EpochId, &genesis_data).unwrap();
+            assert_eq!(chain_state.last_block, last);
+        }
+        assert!(!epoch_exists(&storage.config, 2).unwrap());
+    }
+
+    #[test]
+    fn resync_epoch_errors() {
+        let epochs = epochs();
+        let storage = storage("resync-errors", &epochs);
+        let mut net = MockApi::new(&epochs.concat());
+        let net_cfg = net_config(&epochs[0][0]);
+
+        // the previous epoch is not packed
+        let genesis = genesis_data(ProtocolMagic::from(1));
+        assert!(resync_epoch(&mut net, &net_cfg, &genesis, storage.clone(), 1).is_err());
+
+        // the blocks do not verify
+        let genesis = genesis_data(ProtocolMagic::from(2));
+        match resync_epoch(&mut net, &net_cfg, &genesis, storage.clone(), 0) {
+            Err(network::Error::BlockError(_)) => {}
+            result => panic!("unexpected result {:?}", result),
+        }
+        assert!(!epoch_exists(&storage.read().unwrap().config, 0).unwrap());
+    }
+}
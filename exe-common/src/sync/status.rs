@@ -0,0 +1,288 @@
+//! Introspection of a running synchronization.
+//!
+//! `net_sync_with_status` keeps a `SyncStatus` up to date while it runs,
+//! the embedding application reads it through a `SyncStatusHandle`
+//! (e.g. to report it on a health endpoint). `SyncStatus::progress`
+//! summarizes it for the front-ends reporting the progress in a machine
+//! readable way rather than with a progress bar.
+
+use cardano::block::{BlockDate, EpochId, HeaderHash};
+use cardano::config::EpochParameters;
+use network::api::BlockRef;
+use serde_json;
+use std::fmt;
+use std::sync::{Arc, RwLock};
+use std::time::{Duration, Instant};
+
+/// what the synchronization is currently doing
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SyncPhase {
+    /// the synchronization has not started yet
+    Idle,
+    /// querying the network for its tip
+    FetchingTip,
+    /// packing an epoch that became stable
+    PackingEpoch(EpochId),
+    /// fetching the blocks up to the network's tip
+    FetchingBlocks,
+    /// up to date, waiting for the network to announce a new tip
+    WaitingForTip,
+    /// the synchronization stopped on an error (see `last_error`)
+    Failed,
+    /// the synchronization completed (only when syncing once)
+    Done,
+}
+impl fmt::Display for SyncPhase {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            SyncPhase::Idle => write!(f, "idle"),
+            SyncPhase::FetchingTip => write!(f, "fetching tip"),
+            SyncPhase::PackingEpoch(epoch) => write!(f, "packing epoch {}", epoch),
+            SyncPhase::FetchingBlocks => write!(f, "fetching blocks"),
+            SyncPhase::WaitingForTip => write!(f, "waiting for a new tip"),
+            SyncPhase::Failed => write!(f, "failed"),
+            SyncPhase::Done => write!(f, "done"),
+        }
+    }
+}
+
+/// snapshot of the state of the synchronization
+#[derive(Debug, Clone)]
+pub struct SyncStatus {
+    pub phase: SyncPhase,
+    /// name of the peer we synchronize from, as set by the caller
+    pub peer: Option<String>,
+    /// the tip announced by the network
+    pub remote_tip: Option<BlockRef>,
+    /// the latest block applied to the local chain
+    pub local_tip: Option<(HeaderHash, BlockDate)>,
+    /// number of blocks received since the synchronization started
+    pub blocks_received: u64,
+    /// average number of blocks received per second since the network
+    /// tip was last fetched
+    pub blocks_per_second: f64,
+    pub last_error: Option<String>,
+    /// the length of the epochs of the network, set from the genesis
+    /// when the synchronization starts
+    pub epoch_parameters: EpochParameters,
+}
+impl Default for SyncStatus {
+    fn default() -> Self {
+        SyncStatus {
+            phase: SyncPhase::Idle,
+            peer: None,
+            remote_tip: None,
+            local_tip: None,
+            blocks_received: 0,
+            blocks_per_second: 0.0,
+            last_error: None,
+            epoch_parameters: EpochParameters::default(),
+        }
+    }
+}
+impl fmt::Display for SyncStatus {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        writeln!(f, "phase: {}", self.phase)?;
+        if let Some(peer) = &self.peer {
+            writeln!(f, "peer: {}", peer)?;
+        }
+        if let Some(tip) = &self.remote_tip {
+            writeln!(f, "remote tip: {} ({})", tip.hash, tip.date)?;
+        }
+        if let Some((hash, date)) = &self.local_tip {
+            writeln!(f, "local tip: {} ({})", hash, date)?;
+        }
+        writeln!(
+            f,
+            "blocks received: {} ({:.1} blocks/s)",
+            self.blocks_received, self.blocks_per_second
+        )?;
+        if let Some(err) = &self.last_error {
+            writeln!(f, "last error: {}", err)?;
+        }
+        Ok(())
+    }
+}
+
+impl SyncStatus {
+    /// summary of the progress towards the network's tip
+    pub fn progress(&self) -> SyncProgress {
+        let epoch = match self.phase {
+            SyncPhase::PackingEpoch(epoch) => Some(epoch),
+            _ => self.local_tip.as_ref().map(|(_, date)| date.get_epochid()),
+        };
+        let slots = match (&self.local_tip, &self.remote_tip) {
+            (Some((_, local)), Some(remote)) => Some((
+                local.slot_number_with(&self.epoch_parameters),
+                remote.date.slot_number_with(&self.epoch_parameters),
+            )),
+            _ => None,
+        };
+        let percent = slots.map(|(local, remote)| {
+            if local >= remote {
+                100.0
+            } else {
+                local as f64 * 100.0 / remote as f64
+            }
+        });
+        // assume one block per slot
+        let eta = slots.and_then(|(local, remote)| {
+            if self.blocks_per_second > 0.0 {
+                let remaining = remote.saturating_sub(local) as f64;
+                Some((remaining / self.blocks_per_second).round() as u64)
+            } else {
+                None
+            }
+        });
+        SyncProgress {
+            phase: self.phase.to_string(),
+            epoch,
+            percent,
+            eta_seconds: eta,
+        }
+    }
+}
+
+/// progress event of the synchronization
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct SyncProgress {
+    pub phase: String,
+    /// the epoch being fetched or packed
+    pub epoch: Option<EpochId>,
+    /// how far the local tip is from the network's tip
+    pub percent: Option<f64>,
+    /// estimated number of seconds before reaching the network's tip
+    pub eta_seconds: Option<u64>,
+}
+impl SyncProgress {
+    /// the event as a single line of JSON (without the line feed), as
+    /// written to the newline-delimited JSON progress streams
+    pub fn to_json(&self) -> String {
+        serde_json::to_string(self).expect("serialize progress event")
+    }
+}
+
+#[derive(Debug)]
+struct Inner {
+    status: SyncStatus,
+    rate_start: Instant,
+    rate_blocks: u64,
+}
+
+/// shared access to the `SyncStatus` of a synchronization, cheap to clone
+#[derive(Debug, Clone)]
+pub struct SyncStatusHandle(Arc<RwLock<Inner>>);
+impl Default for SyncStatusHandle {
+    fn default() -> Self {
+        SyncStatusHandle(Arc::new(RwLock::new(Inner {
+            status: SyncStatus::default(),
+            rate_start: Instant::now(),
+            rate_blocks: 0,
+        })))
+    }
+}
+impl SyncStatusHandle {
+    pub fn new() -> Self {
+        SyncStatusHandle::default()
+    }
+
+    /// get a snapshot of the current status
+    pub fn get(&self) -> SyncStatus {
+        self.0.read().unwrap().status.clone()
+    }
+
+    pub fn set_peer(&self, peer: String) {
+        self.0.write().unwrap().status.peer = Some(peer);
+    }
+
+    pub(super) fn set_epoch_parameters(&self, epoch_parameters: EpochParameters) {
+        self.0.write().unwrap().status.epoch_parameters = epoch_parameters;
+    }
+
+    pub(super) fn set_phase(&self, phase: SyncPhase) {
+        self.0.write().unwrap().status.phase = phase;
+    }
+
+    pub(super) fn set_remote_tip(&self, tip: BlockRef) {
+        let mut inner = self.0.write().unwrap();
+        inner.status.remote_tip = Some(tip);
+        inner.rate_start = Instant::now();
+        inner.rate_blocks = 0;
+    }
+
+    pub(super) fn set_local_tip(&self, hash: HeaderHash, date: BlockDate) {
+        self.0.write().unwrap().status.local_tip = Some((hash, date));
+    }
+
+    pub(super) fn block_received(&self, hash: HeaderHash, date: BlockDate) {
+        let mut inner = self.0.write().unwrap();
+        inner.rate_blocks += 1;
+        let elapsed = duration_as_secs(inner.rate_start.elapsed());
+        if elapsed > 0.0 {
+            inner.status.blocks_per_second = inner.rate_blocks as f64 / elapsed;
+        }
+        inner.status.blocks_received += 1;
+        inner.status.local_tip = Some((hash, date));
+    }
+
+    pub(super) fn set_error<E: fmt::Display>(&self, error: &E) {
+        let mut inner = self.0.write().unwrap();
+        inner.status.phase = SyncPhase::Failed;
+        inner.status.last_error = Some(format!("{}", error));
+    }
+}
+
+fn duration_as_secs(d: Duration) -> f64 {
+    d.as_secs() as f64 + d.subsec_nanos() as f64 / 1_000_000_000.0
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use cardano::block::EpochSlotId;
+
+    fn date(epoch: EpochId, slotid: u16) -> BlockDate {
+        BlockDate::Normal(EpochSlotId { epoch, slotid })
+    }
+
+    fn status(local: BlockDate, remote: BlockDate) -> SyncStatus {
+        SyncStatus {
+            phase: SyncPhase::FetchingBlocks,
+            remote_tip: Some(BlockRef {
+                hash: HeaderHash::new(b"remote"),
+                parent: HeaderHash::new(b"parent"),
+                date: remote,
+            }),
+            local_tip: Some((HeaderHash::new(b"local"), local)),
+            blocks_per_second: 2.0,
+            // 10 slots per epoch
+            epoch_parameters: EpochParameters::new(1),
+            ..SyncStatus::default()
+        }
+    }
+
+    #[test]
+    fn progress_with_the_epochs_of_the_network() {
+        let progress = status(date(1, 5), date(2, 0)).progress();
+        assert_eq!(progress.epoch, Some(1));
+        assert_eq!(progress.percent, Some(75.0));
+        assert_eq!(progress.eta_seconds, Some(3));
+
+        let progress = status(BlockDate::Boundary(2), date(2, 0)).progress();
+        assert_eq!(progress.percent, Some(100.0));
+        assert_eq!(progress.eta_seconds, Some(0));
+    }
+
+    #[test]
+    fn progress_without_tips() {
+        let progress = SyncStatus::default().progress();
+        assert_eq!(progress.phase, "idle");
+        assert_eq!(progress.epoch, None);
+        assert_eq!(progress.percent, None);
+        assert_eq!(progress.eta_seconds, None);
+
+        let mut packing = status(date(1, 5), date(2, 0));
+        packing.phase = SyncPhase::PackingEpoch(0);
+        assert_eq!(packing.progress().epoch, Some(0));
+    }
+}
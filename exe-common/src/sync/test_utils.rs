@@ -0,0 +1,87 @@
+//! Helpers for the tests: a peer serving fake blocks (see
+//! `cardano::block::fake`) and storages in temporary directories.
+
+use cardano::block::{fake, Block, BlockHeader, HeaderHash, RawBlock};
+use cardano::tx::TxAux;
+use cardano_storage::{blob, types::header_to_blockhash, Storage, StorageConfig};
+use network::{self, api::Api, api::BlockRef};
+use rand;
+use std::collections::HashMap;
+use std::env;
+
+/// a peer serving the blocks it was given
+pub struct MockApi(HashMap<HeaderHash, RawBlock>);
+
+impl MockApi {
+    pub fn new(blocks: &[Block]) -> Self {
+        MockApi(
+            blocks
+                .iter()
+                .map(|block| (block.header().compute_hash(), fake::raw(block)))
+                .collect(),
+        )
+    }
+}
+
+impl Api for MockApi {
+    fn get_tip(&mut self) -> network::Result<BlockHeader> {
+        unimplemented!()
+    }
+    fn wait_for_new_tip(&mut self, _prev_tip: &HeaderHash) -> network::Result<BlockHeader> {
+        unimplemented!()
+    }
+    fn get_block(&mut self, hash: &HeaderHash) -> network::Result<RawBlock> {
+        self.0
+            .get(hash)
+            .cloned()
+            .ok_or_else(|| network::Error::NoSuchBlock(hash.clone()))
+    }
+    fn get_blocks<F>(
+        &mut self,
+        from: &BlockRef,
+        inclusive: bool,
+        to: &BlockRef,
+        got_block: &mut F,
+    ) -> network::Result<()>
+    where
+        F: FnMut(&HeaderHash, &Block, &RawBlock) -> (),
+    {
+        // walk back from `to` to `from`
+        let mut blocks = vec![];
+        let mut hash = to.hash.clone();
+        loop {
+            let raw = self.get_block(&hash)?;
+            let block = raw.decode()?;
+            let parent = block.header().previous_header();
+            let is_from = hash == from.hash;
+            if !is_from || inclusive {
+                blocks.push((hash, block, raw));
+            }
+            if is_from {
+                break;
+            }
+            hash = parent;
+        }
+        for (hash, block, raw) in blocks.iter().rev() {
+            got_block(hash, block, raw);
+        }
+        Ok(())
+    }
+    fn send_transaction(&mut self, _txaux: TxAux) -> network::Result<bool> {
+        unimplemented!()
+    }
+}
+
+/// a new storage, in its own temporary directory
+pub fn temp_storage(name: &str) -> Storage {
+    let path = env::temp_dir().join(format!("exe-common-{}-{}", name, rand::random::<u32>()));
+    Storage::init(&StorageConfig::new(&path)).unwrap()
+}
+
+/// write the blocks as blobs
+pub fn store(storage: &Storage, blocks: &[Block]) {
+    for block in blocks {
+        let hash = header_to_blockhash(&block.header().compute_hash());
+        blob::write(storage, &hash, fake::raw(block).as_ref()).unwrap();
+    }
+}
@@ -0,0 +1,127 @@
+//! Validation of the blocks fetched during the synchronization.
+//!
+//! Every block received from the network is given to a `BlockValidator`
+//! before it is written to the storage, on top of the verification of the
+//! chain state. The default, `HashLinkage`, only checks the block is the one
+//! requested and follows the previous block; `FullVerification` also runs
+//! `cardano::block::verify_block`. Applications embedding the sync (hermes,
+//! the wallets...) can add their own policy (checkpoints, rejecting the
+//! blocks after a given date...) by implementing the trait, or with a
+//! closure.
+
+use cardano::block::{self, Block, HeaderHash};
+use std::{error, fmt};
+
+#[derive(Debug)]
+pub enum Error {
+    /// the hash of the block is not the one announced
+    WrongBlockHash(HeaderHash, HeaderHash), // actual, expected
+    /// the block does not follow the previous block of the chain
+    WrongPreviousBlock(HeaderHash, HeaderHash), // actual, expected
+    /// the block failed to verify
+    InvalidBlock(block::Error),
+    /// the block was rejected by the policy of the application
+    Rejected(String),
+}
+impl From<block::Error> for Error {
+    fn from(e: block::Error) -> Self {
+        Error::InvalidBlock(e)
+    }
+}
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Error::WrongBlockHash(actual, expected) => write!(
+                f,
+                "Block hash {} does not match the expected hash {}",
+                actual, expected
+            ),
+            Error::WrongPreviousBlock(actual, expected) => write!(
+                f,
+                "Block follows {} instead of the previous block {}",
+                actual, expected
+            ),
+            Error::InvalidBlock(_) => write!(f, "Invalid block"),
+            Error::Rejected(reason) => write!(f, "Block rejected: {}", reason),
+        }
+    }
+}
+impl error::Error for Error {
+    fn cause(&self) -> Option<&error::Error> {
+        match self {
+            Error::InvalidBlock(ref err) => Some(err),
+            _ => None,
+        }
+    }
+}
+
+pub type Result<T> = ::std::result::Result<T, Error>;
+
+/// Called for every block fetched by the synchronization, before it is
+/// written to the storage. Returning an error stops the synchronization.
+pub trait BlockValidator {
+    /// `parent` is the hash of the last block of the local chain, the
+    /// block `block_hash` is expected to follow.
+    fn validate(
+        &mut self,
+        parent: &HeaderHash,
+        block_hash: &HeaderHash,
+        block: &Block,
+    ) -> Result<()>;
+}
+
+impl<F> BlockValidator for F
+where
+    F: FnMut(&HeaderHash, &HeaderHash, &Block) -> Result<()>,
+{
+    fn validate(
+        &mut self,
+        parent: &HeaderHash,
+        block_hash: &HeaderHash,
+        block: &Block,
+    ) -> Result<()> {
+        self(parent, block_hash, block)
+    }
+}
+
+/// Only check the block has the expected hash and follows `parent`.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct HashLinkage;
+
+impl BlockValidator for HashLinkage {
+    fn validate(
+        &mut self,
+        parent: &HeaderHash,
+        block_hash: &HeaderHash,
+        block: &Block,
+    ) -> Result<()> {
+        let header = block.header();
+        let hash = header.compute_hash();
+        if &hash != block_hash {
+            return Err(Error::WrongBlockHash(hash, block_hash.clone()));
+        }
+        let previous = header.previous_header();
+        if &previous != parent {
+            return Err(Error::WrongPreviousBlock(previous, parent.clone()));
+        }
+        Ok(())
+    }
+}
+
+/// `HashLinkage`, and the verification of the content of the block (proofs,
+/// signatures, transactions witnesses...).
+#[derive(Debug, Clone, Copy, Default)]
+pub struct FullVerification;
+
+impl BlockValidator for FullVerification {
+    fn validate(
+        &mut self,
+        parent: &HeaderHash,
+        block_hash: &HeaderHash,
+        block: &Block,
+    ) -> Result<()> {
+        HashLinkage.validate(parent, block_hash, block)?;
+        block::verify_block(block_hash, block)?;
+        Ok(())
+    }
+}
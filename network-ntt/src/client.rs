@@ -1,4 +1,5 @@
 use super::gossip::NodeId;
+use super::transport::{self, Address, Socket};
 
 use chain_core::property::{Block, HasHeader, Header};
 
@@ -7,12 +8,13 @@ use network_core::{
     error as core_error,
     subscription::BlockEvent,
 };
-pub use protocol::protocol::ProtocolMagic;
+pub use protocol::protocol::{AllowedMagics, ProtocolMagic};
 use protocol::{
-    network_transport::LightWeightConnectionId,
+    network_transport::{self as nt, LightWeightConnectionId, ResponseCode},
     protocol::{CloseLightConnection, GetBlockHeaders, GetBlocks, NewLightConnection},
     ConnectingError, Inbound, InboundError, InboundStream, Message, OutboundError, OutboundSink,
-    ProtocolBlock, ProtocolBlockId, ProtocolHeader, ProtocolTransactionId, Response,
+    ProtocolBlock, ProtocolBlockId, ProtocolHeader, ProtocolTransactionId, Response, ResumePolicy,
+    Resumed, Session, SessionError,
 };
 
 use futures::{
@@ -44,6 +46,23 @@ pub fn connect<B, Tx>(
     sockaddr: SocketAddr,
     magic: ProtocolMagic,
 ) -> impl Future<Item = (Connection<TcpStream, B, Tx>, ClientHandle<B, Tx>), Error = Error>
+where
+    B: ProtocolBlock,
+    Tx: ProtocolTransactionId,
+    <B as Block>::Id: ProtocolBlockId,
+    <B as HasHeader>::Header: ProtocolHeader,
+{
+    connect_with_allowed_magics(sockaddr, magic, AllowedMagics::any())
+}
+
+/// Same as `connect`, but the connection fails with
+/// `Error::ProtocolMagicRejected` if the remote's protocol magic
+/// is not in `allowed`.
+pub fn connect_with_allowed_magics<B, Tx>(
+    sockaddr: SocketAddr,
+    magic: ProtocolMagic,
+    allowed: AllowedMagics,
+) -> impl Future<Item = (Connection<TcpStream, B, Tx>, ClientHandle<B, Tx>), Error = Error>
 where
     B: ProtocolBlock,
     Tx: ProtocolTransactionId,
@@ -52,17 +71,91 @@ where
 {
     TcpStream::connect(&sockaddr)
         .map_err(Error::Connect)
-        .and_then(move |stream| {
-            protocol::Connection::connect(stream, magic)
-                .map_err(Error::Handshake)
-                .and_then(move |connection| {
-                    let (cmd_sink, cmd_source) = mpsc::unbounded();
-                    let handle = ClientHandle {
-                        channel: cmd_sink,
-                        phantom: PhantomData,
-                    };
-                    future::ok((Connection::new(connection, cmd_source), handle))
-                })
+        .and_then(move |stream| handshake(stream, magic, allowed))
+}
+
+/// Same as `connect_with_allowed_magics`, on any of the transports (see
+/// the `transport` module).
+pub fn connect_to<B, Tx>(
+    address: &Address,
+    magic: ProtocolMagic,
+    allowed: AllowedMagics,
+) -> impl Future<Item = (Connection<Socket, B, Tx>, ClientHandle<B, Tx>), Error = Error>
+where
+    B: ProtocolBlock,
+    Tx: ProtocolTransactionId,
+    <B as Block>::Id: ProtocolBlockId,
+    <B as HasHeader>::Header: ProtocolHeader,
+{
+    transport::connect(address)
+        .map_err(Error::Connect)
+        .and_then(move |stream| handshake(stream, magic, allowed))
+}
+
+/// Establish the protocol connection on an already connected stream
+/// (e.g. one end of an in-process `transport::memory` connection).
+pub fn handshake<T, B, Tx>(
+    stream: T,
+    magic: ProtocolMagic,
+    allowed: AllowedMagics,
+) -> impl Future<Item = (Connection<T, B, Tx>, ClientHandle<B, Tx>), Error = Error>
+where
+    T: AsyncRead + AsyncWrite,
+    B: ProtocolBlock,
+    Tx: ProtocolTransactionId,
+    <B as Block>::Id: ProtocolBlockId,
+    <B as HasHeader>::Header: ProtocolHeader,
+{
+    protocol::Connection::connect_with_allowed_magics(stream, magic, allowed)
+        .map_err(Error::Handshake)
+        .and_then(move |connection| {
+            let (cmd_sink, cmd_source) = mpsc::unbounded();
+            let handle = ClientHandle {
+                channel: cmd_sink,
+                phantom: PhantomData,
+            };
+            future::ok((Connection::new(connection, cmd_source), handle))
+        })
+}
+
+/// Resume a lost connection (see `Connection::into_lost`) on a newly
+/// connected stream: the handshake is done again, then the subscription
+/// and the `GetBlocks` requests in progress are replayed according to the
+/// `ResumePolicy` of the connection, the blocks being requested again
+/// from the last one received. The `ClientHandle` of the lost connection
+/// keeps working with the new one.
+///
+/// The requests not resumed end with an error, as do the requests
+/// waiting for a single reply (e.g. the tip). On failure the lost
+/// connection is given back, to try again on another stream.
+pub fn resume<T, B, Tx>(
+    stream: T,
+    magic: ProtocolMagic,
+    allowed: AllowedMagics,
+    lost: LostConnection<B, Tx>,
+) -> impl Future<Item = Connection<T, B, Tx>, Error = (Error, LostConnection<B, Tx>)>
+where
+    T: AsyncRead + AsyncWrite,
+    B: ProtocolBlock,
+    Tx: ProtocolTransactionId,
+    <B as Block>::Id: ProtocolBlockId,
+    <B as HasHeader>::Header: ProtocolHeader,
+{
+    protocol::Connection::connect_with_allowed_magics(stream, magic, allowed)
+        .then(move |res| match res {
+            Ok(connection) => Ok((connection, lost)),
+            Err(err) => Err((Error::Handshake(err), lost)),
+        })
+        .and_then(|(connection, mut lost)| {
+            let replay = match lost.session.connection_lost() {
+                Ok(replay) => replay,
+                Err(err) => return future::Either::A(future::err((Error::Session(err), lost))),
+            };
+            let (sink, inbound) = connection.split();
+            future::Either::B(replay.replay(sink).then(move |res| match res {
+                Ok((resumed, sink)) => Ok(lost.resumed(&resumed, inbound, sink)),
+                Err(err) => Err((Error::Outbound(err), lost)),
+            }))
         })
 }
 
@@ -252,6 +345,83 @@ pub enum Error {
     Handshake(ConnectingError),
     Inbound(InboundError),
     Outbound(OutboundError),
+    /// the connection could not be resumed
+    Session(SessionError),
+}
+
+/// Whether it is worth trying again after an `Error`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ErrorKind {
+    /// the connection failed or was lost (refused, reset, timed out...),
+    /// connecting again later may succeed.
+    Transient,
+    /// the remote peer is not compatible with us or violated the protocol,
+    /// connecting again will fail the same way.
+    Fatal,
+}
+
+impl Error {
+    pub fn kind(&self) -> ErrorKind {
+        match self {
+            Error::Connect(e) => io_error_kind(e),
+            Error::Handshake(e) => connecting_error_kind(e),
+            Error::Inbound(e) => inbound_error_kind(e),
+            Error::Outbound(e) => outbound_error_kind(e),
+            Error::Session(_) => ErrorKind::Fatal,
+        }
+    }
+
+    pub fn is_transient(&self) -> bool {
+        self.kind() == ErrorKind::Transient
+    }
+
+    /// The remote's protocol magic, if the connection was rejected
+    /// because of it.
+    pub fn rejected_protocol_magic(&self) -> Option<ProtocolMagic> {
+        match self {
+            Error::Handshake(ConnectingError::ProtocolMagicRejected(magic)) => Some(*magic),
+            _ => None,
+        }
+    }
+}
+
+fn io_error_kind(e: &io::Error) -> ErrorKind {
+    use std::io::ErrorKind::*;
+
+    match e.kind() {
+        ConnectionRefused | ConnectionReset | ConnectionAborted | NotConnected
+        | AddrNotAvailable | BrokenPipe | TimedOut | Interrupted | WouldBlock | UnexpectedEof => {
+            ErrorKind::Transient
+        }
+        _ => ErrorKind::Fatal,
+    }
+}
+
+fn connecting_error_kind(e: &ConnectingError) -> ErrorKind {
+    match e {
+        ConnectingError::IoError(e) => io_error_kind(e),
+        ConnectingError::NtError(nt::ConnectingError::IoError(e)) => io_error_kind(e),
+        ConnectingError::NtError(nt::ConnectingError::ConnectionFailed(
+            ResponseCode::CrossedRequest,
+        )) => ErrorKind::Transient,
+        ConnectingError::ConnectionClosed => ErrorKind::Transient,
+        _ => ErrorKind::Fatal,
+    }
+}
+
+fn inbound_error_kind(e: &InboundError) -> ErrorKind {
+    match e {
+        InboundError::IoError(e) => io_error_kind(e),
+        InboundError::ConnectionTerminated => ErrorKind::Transient,
+        _ => ErrorKind::Fatal,
+    }
+}
+
+fn outbound_error_kind(e: &OutboundError) -> ErrorKind {
+    match e {
+        OutboundError::IoError(e) => io_error_kind(e),
+        OutboundError::Unknown => ErrorKind::Fatal,
+    }
 }
 
 impl fmt::Display for Error {
@@ -261,6 +431,7 @@ impl fmt::Display for Error {
             Error::Handshake(_) => write!(f, "failed to set up the protocol connection"),
             Error::Inbound(_) => write!(f, "network input error"),
             Error::Outbound(_) => write!(f, "network output error"),
+            Error::Session(_) => write!(f, "failed to resume the connection"),
         }
     }
 }
@@ -272,6 +443,7 @@ impl error::Error for Error {
             Error::Handshake(e) => Some(e),
             Error::Inbound(e) => Some(e),
             Error::Outbound(e) => Some(e),
+            Error::Session(e) => Some(e),
         }
     }
 }
@@ -301,6 +473,56 @@ where
     commands: mpsc::UnboundedReceiver<Command<B>>,
     unary_requests: HashMap<LightWeightConnectionId, UnaryRequest<B>>,
     stream_requests: HashMap<LightWeightConnectionId, StreamRequest<B>>,
+    session: Session<<B as Block>::Id>,
+}
+
+/// What is left of a `Connection` once its transport connection is lost,
+/// to `resume` it on a new one.
+pub struct LostConnection<B: Block + HasHeader, Tx> {
+    session: Session<B::Id>,
+    commands: mpsc::UnboundedReceiver<Command<B>>,
+    stream_requests: HashMap<LightWeightConnectionId, StreamRequest<B>>,
+    phantom: PhantomData<Tx>,
+}
+
+impl<B, Tx> LostConnection<B, Tx>
+where
+    B: ProtocolBlock,
+    Tx: ProtocolTransactionId,
+    <B as Block>::Id: ProtocolBlockId,
+    <B as HasHeader>::Header: ProtocolHeader,
+{
+    fn resumed<T>(
+        mut self,
+        resumed: &Resumed<B::Id>,
+        inbound: InboundStream<T, B, Tx>,
+        sink: OutboundSink<T, B, Tx>,
+    ) -> Connection<T, B, Tx>
+    where
+        T: AsyncRead + AsyncWrite,
+    {
+        self.session.resumed(resumed);
+        let mut stream_requests = HashMap::new();
+        for request in resumed.get_blocks.iter() {
+            if let Some(req) = self.stream_requests.remove(&request.previous) {
+                stream_requests.insert(request.lwcid, req);
+            }
+        }
+        for (_, StreamRequest::Blocks(chan, ..)) in self.stream_requests {
+            let _ = chan.unbounded_send(Err(core_error::Error::new(
+                core_error::Code::Canceled,
+                "connection lost",
+            )));
+        }
+        Connection {
+            inbound: Some(inbound),
+            out_state: OutboundState::Ready(sink),
+            commands: self.commands,
+            unary_requests: HashMap::new(),
+            stream_requests,
+            session: self.session,
+        }
+    }
 }
 
 impl<T, B, Tx> Connection<T, B, Tx>
@@ -322,6 +544,25 @@ where
             commands,
             unary_requests: HashMap::new(),
             stream_requests: HashMap::new(),
+            session: Session::new(ResumePolicy::default()),
+        }
+    }
+
+    /// What to restore when the connection is resumed (see `resume`),
+    /// `ResumePolicy::default()` if not set.
+    pub fn with_resume_policy(mut self, policy: ResumePolicy) -> Self {
+        self.session = Session::new(policy);
+        self
+    }
+
+    /// The state to resume the connection with, once it failed (see
+    /// `resume`).
+    pub fn into_lost(self) -> LostConnection<B, Tx> {
+        LostConnection {
+            session: self.session,
+            commands: self.commands,
+            stream_requests: self.stream_requests,
+            phantom: PhantomData,
         }
     }
 }
@@ -454,6 +695,9 @@ where
                     Occupied(entry) => match entry.get() {
                         StreamRequest::Blocks(chan, ..) => {
                             let res = convert_response(response, |p| p);
+                            if let Ok(block) = &res {
+                                self.session.block_received(lwcid, block.id());
+                            }
                             chan.unbounded_send(res).unwrap();
                         }
                         _ /* StreamRequest::Blah(chan) */ => {
@@ -466,6 +710,7 @@ where
                 // TODO: to be implemented
             }
             Inbound::CloseConnection(lwcid) => {
+                self.session.get_blocks_done(lwcid);
                 match self.stream_requests.remove(&lwcid) {
                     None => {
                         // TODO: log the bogus close message
@@ -481,6 +726,10 @@ where
 
     fn process_command(&mut self, cmd: Command<B>) {
         let lwcid = self.out_state.start_send(&cmd);
+        if let Command::Stream(StreamRequest::Blocks(_, from, to)) = &cmd {
+            self.session
+                .get_blocks_sent(lwcid, from.clone(), to.clone());
+        }
         match cmd {
             Command::Unary(req) => {
                 self.unary_requests.insert(lwcid, req);
@@ -599,3 +848,72 @@ where
         Ok(Async::Ready(()))
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::{Error as IoError, ErrorKind as IoErrorKind};
+
+    #[test]
+    fn error_kinds() {
+        let io_error = |kind| IoError::new(kind, "test");
+
+        assert_eq!(
+            Error::Connect(io_error(IoErrorKind::ConnectionRefused)).kind(),
+            ErrorKind::Transient
+        );
+        assert_eq!(
+            Error::Connect(io_error(IoErrorKind::PermissionDenied)).kind(),
+            ErrorKind::Fatal
+        );
+
+        assert_eq!(
+            Error::Handshake(ConnectingError::ConnectionClosed).kind(),
+            ErrorKind::Transient
+        );
+        assert_eq!(
+            Error::Handshake(ConnectingError::NtError(
+                nt::ConnectingError::ConnectionFailed(ResponseCode::CrossedRequest)
+            ))
+            .kind(),
+            ErrorKind::Transient
+        );
+        assert_eq!(
+            Error::Handshake(ConnectingError::NtError(
+                nt::ConnectingError::ConnectionFailed(ResponseCode::UnsupportedVersion)
+            ))
+            .kind(),
+            ErrorKind::Fatal
+        );
+        let rejected = Error::Handshake(ConnectingError::ProtocolMagicRejected(
+            ProtocolMagic::from(42),
+        ));
+        assert_eq!(rejected.kind(), ErrorKind::Fatal);
+        assert_eq!(
+            rejected.rejected_protocol_magic(),
+            Some(ProtocolMagic::from(42))
+        );
+
+        assert_eq!(
+            Error::Inbound(InboundError::ConnectionTerminated).kind(),
+            ErrorKind::Transient
+        );
+        assert_eq!(
+            Error::Inbound(InboundError::IoError(io_error(
+                IoErrorKind::ConnectionReset
+            )))
+            .kind(),
+            ErrorKind::Transient
+        );
+
+        assert_eq!(
+            Error::Outbound(OutboundError::IoError(io_error(IoErrorKind::BrokenPipe))).kind(),
+            ErrorKind::Transient
+        );
+        assert_eq!(
+            Error::Outbound(OutboundError::Unknown).kind(),
+            ErrorKind::Fatal
+        );
+        assert!(!Error::Outbound(OutboundError::Unknown).is_transient());
+    }
+}
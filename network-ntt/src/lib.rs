@@ -12,3 +12,4 @@ extern crate futures;
 pub mod client;
 pub mod gossip;
 pub mod server;
+pub mod transport;
@@ -30,25 +30,72 @@
 //! As NT state is shared all the services could provide a uniform
 //! access to the chain state. And one could build very flexible
 //! topology.
+//!
+//! The peers subscribing to the node are announced the new tips given
+//! to the server's `Subscribers` (see the `subscription` module).
 mod error;
+pub mod subscription;
 
 use error::{Error, ErrorKind};
+use subscription::Subscribers;
+
+use super::transport::{self, Address};
 
 use network_core::server::{block::BlockService, content::ContentService, Node};
 use protocol::{
-    protocol::ProtocolMagic, Inbound, Message, ProtocolBlock, ProtocolBlockId, ProtocolHeader,
-    ProtocolTransactionId,
+    protocol::{AcceptingError, AllowedMagics, ConnectingError, ProtocolMagic},
+    Inbound, Message, ProtocolBlock, ProtocolBlockId, ProtocolHeader, ProtocolTransactionId,
 };
 
-use futures::{future, prelude::*, stream::Stream, sync::mpsc};
+use futures::{
+    future,
+    prelude::*,
+    stream::{self, Stream},
+    sync::mpsc,
+};
 use tokio::net::{TcpListener, TcpStream};
 
 use std::net::SocketAddr;
 
 /// Internal structure of network transport node.
 #[derive(Clone)]
-pub struct Server<N> {
+pub struct Server<N: Node> {
     node: N,
+    allowed_magics: AllowedMagics,
+    subscribers: Subscribers<<<N as Node>::BlockService as BlockService>::Header>,
+}
+
+impl<N: Node> Server<N> {
+    pub fn new(node: N) -> Self {
+        Server {
+            node,
+            allowed_magics: AllowedMagics::any(),
+            subscribers: Subscribers::default(),
+        }
+    }
+
+    /// Announce the new tips to the given subscribers, e.g. to share
+    /// them between the servers listening on different addresses.
+    pub fn with_subscribers(
+        mut self,
+        subscribers: Subscribers<<<N as Node>::BlockService as BlockService>::Header>,
+    ) -> Self {
+        self.subscribers = subscribers;
+        self
+    }
+
+    /// The peers subscribed to this server: when the node learns a new
+    /// tip, announce it with `Subscribers::announce`.
+    pub fn subscribers(&self) -> &Subscribers<<<N as Node>::BlockService as BlockService>::Header> {
+        &self.subscribers
+    }
+
+    /// Only peer with the nodes whose handshake has one of the given
+    /// protocol magics, on accepted and initiated connections.
+    pub fn with_allowed_magics(mut self, allowed: AllowedMagics) -> Self {
+        self.allowed_magics = allowed;
+        self
+    }
 }
 
 /// Sets up a listening TCP socket bound to the given address.
@@ -61,23 +108,35 @@ pub fn listen(
     Ok(stream)
 }
 
+/// Sets up a listener on any of the transports (see the `transport`
+/// module). The accepted `Socket`s are given to `accept`.
+pub fn listen_to(address: &Address) -> Result<transport::Listener, tokio::io::Error> {
+    transport::listen(address)
+}
+
 /// Run a server that will listen on a specific sockets
 /// and accept all incomming connections.
 /// Server maintains all of the incomming connection and
 /// `run_connection` is spawned on each of those connections.
-pub fn accept<N: 'static>(
-    stream: TcpStream,
+pub fn accept<N: 'static, T>(
+    stream: T,
     node: Server<N>,
 ) -> impl future::Future<Item = impl futures::future::Future<Item = (), Error = Error>, Error = Error>
 where
+    T: tokio::io::AsyncRead + tokio::io::AsyncWrite,
     N: Node + Clone,
     <<N as Node>::BlockService as BlockService>::Block: ProtocolBlock,
     <<N as Node>::BlockService as BlockService>::BlockId: ProtocolBlockId,
     <<N as Node>::BlockService as BlockService>::Header: ProtocolHeader,
     <<N as Node>::ContentService as ContentService>::MessageId: ProtocolTransactionId,
 {
-    protocol::Connection::accept(stream)
-        .map_err(move |err| Error::new(ErrorKind::Handshake, err))
+    protocol::Connection::accept_with_allowed_magics(stream, node.allowed_magics.clone())
+        .map_err(move |err| match err {
+            AcceptingError::ProtocolMagicRejected(_) => {
+                Error::new(ErrorKind::ProtocolMagicRejected, err)
+            }
+            err => Error::new(ErrorKind::Handshake, err),
+        })
         .and_then(move |connection| {
             let node = node.clone();
             Ok(run_connection(node, connection))
@@ -101,14 +160,50 @@ where
 {
     TcpStream::connect(&sockaddr)
         .map_err(move |err| Error::new(ErrorKind::Connect, err))
-        .and_then(move |stream| {
-            protocol::Connection::connect(stream, magic)
-                .map_err(move |err| Error::new(ErrorKind::Handshake, err))
-                .and_then(move |connection| {
-                    let node = node.clone();
-                    Ok(run_connection(node, connection))
-                })
+        .and_then(move |stream| handshake(stream, node, magic))
+}
+
+/// Same as `connect`, on any of the transports (see the `transport`
+/// module).
+pub fn connect_to<N: 'static>(
+    address: &Address,
+    node: Server<N>,
+    magic: ProtocolMagic,
+) -> impl future::Future<Item = impl futures::future::Future<Item = (), Error = Error>, Error = Error>
+where
+    N: Node + Clone,
+    <<N as Node>::BlockService as BlockService>::Block: ProtocolBlock,
+    <<N as Node>::BlockService as BlockService>::BlockId: ProtocolBlockId,
+    <<N as Node>::BlockService as BlockService>::Header: ProtocolHeader,
+    <<N as Node>::ContentService as ContentService>::MessageId: ProtocolTransactionId,
+{
+    transport::connect(address)
+        .map_err(move |err| Error::new(ErrorKind::Connect, err))
+        .and_then(move |stream| handshake(stream, node, magic))
+}
+
+fn handshake<N: 'static, T>(
+    stream: T,
+    node: Server<N>,
+    magic: ProtocolMagic,
+) -> impl future::Future<Item = impl futures::future::Future<Item = (), Error = Error>, Error = Error>
+where
+    T: tokio::io::AsyncRead + tokio::io::AsyncWrite,
+    N: Node + Clone,
+    <<N as Node>::BlockService as BlockService>::Block: ProtocolBlock,
+    <<N as Node>::BlockService as BlockService>::BlockId: ProtocolBlockId,
+    <<N as Node>::BlockService as BlockService>::Header: ProtocolHeader,
+    <<N as Node>::ContentService as ContentService>::MessageId: ProtocolTransactionId,
+{
+    let allowed = node.allowed_magics.clone();
+    protocol::Connection::connect_with_allowed_magics(stream, magic, allowed)
+        .map_err(move |err| match err {
+            ConnectingError::ProtocolMagicRejected(_) => {
+                Error::new(ErrorKind::ProtocolMagicRejected, err)
+            }
+            err => Error::new(ErrorKind::Handshake, err),
         })
+        .and_then(move |connection| Ok(run_connection(node, connection)))
 }
 
 /// Method defining communication over the heavyweight connection,
@@ -135,6 +230,20 @@ where
 
     let (sink, stream) = connection.split();
     let (sink_tx, sink_rx) = mpsc::unbounded();
+    let (subscribe_tx, subscribe_rx) = mpsc::unbounded();
+
+    // The headers announced to the peer, on the light connection it
+    // subscribed with. The light connection is closed if the peer is
+    // evicted for not reading them fast enough.
+    let announcements = subscription::MergeAll::new(subscribe_rx.map(
+        |(lwcid, subscription): (_, subscription::Subscription<_>)| {
+            subscription
+                .map(move |header| {
+                    Message::BlockHeaders(lwcid, Response::Ok(BlockHeaders(vec![header])))
+                })
+                .chain(stream::once(Ok(Message::CloseConnection(lwcid))))
+        },
+    ));
 
     // Processing of the incomming messages.
     let stream = stream
@@ -152,13 +261,9 @@ where
                         .unwrap();
                     future::Either::A(future::ok(()))
                 }
-                Inbound::Subscribe(_lwcid, _keep_alive) => {
-                    // TODO: implement subscription mechanism.
-                    //
-                    //state.subscriptions.write().unwrap().insert(
-                    //    SubscriptionId(state.connection.clone(), lwcid),
-                    //    sink_tx.clone(),
-                    //);
+                Inbound::Subscribe(lwcid, _keep_alive) => {
+                    let subscription = server.subscribers.subscribe();
+                    subscribe_tx.unbounded_send((lwcid, subscription)).unwrap();
                     future::Either::A(future::ok(()))
                 }
                 Inbound::GetBlockHeaders(lwcid, get_block_header) => {
@@ -248,6 +353,7 @@ where
         .map_err(|_err| ())
         .and_then(move |(_lwcid, sink)| {
             sink_rx
+                .select(announcements)
                 .fold(sink, |sink, outbound| match outbound {
                     Message::AckNodeId(_lwcid, node_id) => {
                         future::Either::A(sink.ack_node_id(node_id).map_err(|_err| ()))
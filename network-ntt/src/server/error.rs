@@ -10,6 +10,8 @@ pub struct Error {
 #[derive(Copy, Clone, Debug, Eq, PartialEq)]
 pub enum ErrorKind {
     Handshake,
+    /// the remote's protocol magic is not allowed
+    ProtocolMagicRejected,
     Connect,
     Protocol,
 }
@@ -40,6 +42,7 @@ impl fmt::Display for Error {
     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
         match self.kind {
             ErrorKind::Handshake => write!(f, "error during handshake"),
+            ErrorKind::ProtocolMagicRejected => write!(f, "peer from another network"),
             ErrorKind::Connect => write!(f, "error during connection"),
             ErrorKind::Protocol => write!(f, "protocol error"),
         }
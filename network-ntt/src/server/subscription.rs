@@ -0,0 +1,265 @@
+//! Announcement of the new tips to the subscribed peers.
+//!
+//! The peers connected to the server subscribe to be told about the
+//! blocks it learns of, instead of polling for its tip. Every subscriber
+//! has a bounded queue of headers to send: a peer reading too slowly to
+//! keep up with the announcements is evicted (its subscription is closed)
+//! rather than letting its queue grow without limit.
+
+use futures::{prelude::*, sync::mpsc};
+
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex};
+
+/// Default number of announcements waiting to be sent to a subscriber
+/// before it is evicted
+pub const DEFAULT_QUEUE_SIZE: usize = 16;
+
+/// The set of the subscribed peers, shared by all the connections of the
+/// server (and the servers it is given to, see `Server::with_subscribers`).
+pub struct Subscribers<H> {
+    inner: Arc<Mutex<Inner<H>>>,
+}
+
+struct Inner<H> {
+    queue_size: usize,
+    next_id: u64,
+    queues: HashMap<u64, mpsc::Sender<H>>,
+}
+
+/// Outcome of an announcement
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub struct Announcement {
+    /// number of subscribers the header was queued for
+    pub delivered: usize,
+    /// number of subscribers evicted because their queue was full
+    pub evicted: usize,
+}
+
+impl<H> Clone for Subscribers<H> {
+    fn clone(&self) -> Self {
+        Subscribers {
+            inner: self.inner.clone(),
+        }
+    }
+}
+
+impl<H> Default for Subscribers<H> {
+    fn default() -> Self {
+        Subscribers::new(DEFAULT_QUEUE_SIZE)
+    }
+}
+
+impl<H> Subscribers<H> {
+    /// `queue_size` is the number of announcements waiting to be sent
+    /// to a subscriber before it is evicted.
+    pub fn new(queue_size: usize) -> Self {
+        Subscribers {
+            inner: Arc::new(Mutex::new(Inner {
+                queue_size,
+                next_id: 0,
+                queues: HashMap::new(),
+            })),
+        }
+    }
+
+    /// number of subscribed peers
+    pub fn len(&self) -> usize {
+        self.inner.lock().unwrap().queues.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    pub(crate) fn subscribe(&self) -> Subscription<H> {
+        let mut inner = self.inner.lock().unwrap();
+        let id = inner.next_id;
+        inner.next_id += 1;
+        let (sender, receiver) = mpsc::channel(inner.queue_size);
+        inner.queues.insert(id, sender);
+        Subscription {
+            id,
+            receiver,
+            subscribers: self.clone(),
+        }
+    }
+}
+
+impl<H: Clone> Subscribers<H> {
+    /// Announce `header`, the new tip, to all the subscribers.
+    ///
+    /// This does not wait for the header to be sent. The subscribers
+    /// whose queue is full are evicted.
+    pub fn announce(&self, header: &H) -> Announcement {
+        let mut announcement = Announcement::default();
+        let mut inner = self.inner.lock().unwrap();
+        inner
+            .queues
+            .retain(|_, queue| match queue.try_send(header.clone()) {
+                Ok(()) => {
+                    announcement.delivered += 1;
+                    true
+                }
+                Err(ref err) if err.is_full() => {
+                    announcement.evicted += 1;
+                    false
+                }
+                // the connection is gone
+                Err(_) => false,
+            });
+        announcement
+    }
+}
+
+/// The stream of the headers to announce to a subscriber.
+///
+/// The stream ends if the subscriber is evicted, dropping it unsubscribes.
+pub(crate) struct Subscription<H> {
+    id: u64,
+    receiver: mpsc::Receiver<H>,
+    subscribers: Subscribers<H>,
+}
+
+impl<H> Stream for Subscription<H> {
+    type Item = H;
+    type Error = ();
+
+    fn poll(&mut self) -> Poll<Option<H>, ()> {
+        self.receiver.poll()
+    }
+}
+
+impl<H> Drop for Subscription<H> {
+    fn drop(&mut self) {
+        if let Ok(mut inner) = self.subscribers.inner.lock() {
+            inner.queues.remove(&self.id);
+        }
+    }
+}
+
+/// The items of all the streams given by a stream, as they come.
+///
+/// Unlike `Stream::flatten`, which reads a stream to its end before the
+/// next one, all the streams received so far are polled: a subscription
+/// (which ends only with its connection) does not starve the next ones.
+pub(crate) struct MergeAll<S: Stream> {
+    streams: Option<S>,
+    active: Vec<S::Item>,
+}
+
+impl<S: Stream> MergeAll<S> {
+    pub(crate) fn new(streams: S) -> Self {
+        MergeAll {
+            streams: Some(streams),
+            active: Vec::new(),
+        }
+    }
+}
+
+impl<S> Stream for MergeAll<S>
+where
+    S: Stream,
+    S::Item: Stream<Error = S::Error>,
+{
+    type Item = <S::Item as Stream>::Item;
+    type Error = S::Error;
+
+    fn poll(&mut self) -> Poll<Option<Self::Item>, S::Error> {
+        let mut ended = false;
+        if let Some(streams) = &mut self.streams {
+            loop {
+                match streams.poll()? {
+                    Async::Ready(Some(stream)) => self.active.push(stream),
+                    Async::Ready(None) => {
+                        ended = true;
+                        break;
+                    }
+                    Async::NotReady => break,
+                }
+            }
+        }
+        if ended {
+            self.streams = None;
+        }
+
+        let mut i = 0;
+        while i < self.active.len() {
+            match self.active[i].poll()? {
+                Async::Ready(Some(item)) => {
+                    // poll the others first next time
+                    let stream = self.active.remove(i);
+                    self.active.push(stream);
+                    return Ok(Async::Ready(Some(item)));
+                }
+                Async::Ready(None) => {
+                    self.active.remove(i);
+                }
+                Async::NotReady => i += 1,
+            }
+        }
+
+        if self.streams.is_none() && self.active.is_empty() {
+            Ok(Async::Ready(None))
+        } else {
+            Ok(Async::NotReady)
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use futures::stream;
+
+    #[test]
+    fn merge_all_streams() {
+        let streams = stream::iter_ok::<_, ()>(vec![
+            stream::iter_ok(vec![1, 2]),
+            stream::iter_ok(vec![3]),
+            stream::iter_ok(vec![]),
+        ]);
+        let items = MergeAll::new(streams).collect().wait().unwrap();
+        assert_eq!(items, vec![1, 3, 2]);
+    }
+
+    #[test]
+    fn announce_to_all_subscriptions() {
+        let subscribers = Subscribers::new(4);
+        let subscriptions = vec![subscribers.subscribe(), subscribers.subscribe()];
+        assert_eq!(
+            subscribers.announce(&1),
+            Announcement {
+                delivered: 2,
+                evicted: 0
+            }
+        );
+        subscribers.announce(&2);
+
+        // the first subscription never ends, the second one is not
+        // waiting for it
+        let merged = MergeAll::new(stream::iter_ok(subscriptions));
+        let headers = merged.take(4).collect().wait().unwrap();
+        assert_eq!(headers, vec![1, 1, 2, 2]);
+        assert_eq!(subscribers.len(), 0);
+    }
+
+    #[test]
+    fn evict_slow_subscribers() {
+        let subscribers = Subscribers::new(1);
+        let subscription = subscribers.subscribe();
+        // a queue of `n` holds `n + 1` headers (one per sender)
+        subscribers.announce(&1);
+        subscribers.announce(&2);
+        assert_eq!(
+            subscribers.announce(&3),
+            Announcement {
+                delivered: 0,
+                evicted: 1
+            }
+        );
+        assert!(subscribers.is_empty());
+        let headers = subscription.collect().wait().unwrap();
+        assert_eq!(headers, vec![1, 2]);
+    }
+}
@@ -0,0 +1,332 @@
+//! The transports the connections of the node can use.
+//!
+//! A node listens and connects on TCP sockets, or on UNIX domain sockets
+//! for the local IPC between the processes of the same host (e.g. hermes
+//! and the cardano-cli daemons). The `memory` transport connects the two
+//! ends of a connection within the same process, without any socket: it
+//! is meant for the tests.
+//!
+//! The addresses are written `host:port` (or `tcp:host:port`) for TCP and
+//! `unix:/path/to/socket` for UNIX domain sockets.
+pub mod memory;
+
+use futures::prelude::*;
+use tokio::io::{AsyncRead, AsyncWrite};
+use tokio::net::tcp::{self, TcpListener, TcpStream};
+#[cfg(unix)]
+use tokio::net::unix::{self, UnixListener, UnixStream};
+
+#[cfg(unix)]
+use std::path::PathBuf;
+use std::{
+    error, fmt,
+    io::{self, Read, Write},
+    net::{AddrParseError, SocketAddr},
+    str::FromStr,
+};
+
+const TCP_PREFIX: &str = "tcp:";
+const UNIX_PREFIX: &str = "unix:";
+
+/// The address a node listens on, or connects to.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub enum Address {
+    Tcp(SocketAddr),
+    /// the path of a UNIX domain socket
+    #[cfg(unix)]
+    Unix(PathBuf),
+}
+
+impl From<SocketAddr> for Address {
+    fn from(addr: SocketAddr) -> Self {
+        Address::Tcp(addr)
+    }
+}
+
+impl fmt::Display for Address {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Address::Tcp(addr) => write!(f, "{}", addr),
+            #[cfg(unix)]
+            Address::Unix(path) => write!(f, "{}{}", UNIX_PREFIX, path.display()),
+        }
+    }
+}
+
+#[derive(Debug)]
+pub enum AddressError {
+    /// not a valid `ip:port` socket address
+    InvalidSocketAddr(AddrParseError),
+    /// the `unix:` address has no path
+    EmptyPath,
+    /// UNIX domain sockets are not available on this platform
+    UnixNotSupported,
+}
+
+impl fmt::Display for AddressError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            AddressError::InvalidSocketAddr(_) => write!(f, "invalid socket address"),
+            AddressError::EmptyPath => write!(f, "missing path of the UNIX domain socket"),
+            AddressError::UnixNotSupported => {
+                write!(f, "UNIX domain sockets are not supported on this platform")
+            }
+        }
+    }
+}
+
+impl error::Error for AddressError {
+    fn source(&self) -> Option<&(dyn error::Error + 'static)> {
+        match self {
+            AddressError::InvalidSocketAddr(e) => Some(e),
+            _ => None,
+        }
+    }
+}
+
+impl FromStr for Address {
+    type Err = AddressError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        if s.starts_with(UNIX_PREFIX) {
+            let path = &s[UNIX_PREFIX.len()..];
+            if path.is_empty() {
+                return Err(AddressError::EmptyPath);
+            }
+            unix_address(path)
+        } else {
+            let addr = if s.starts_with(TCP_PREFIX) {
+                &s[TCP_PREFIX.len()..]
+            } else {
+                s
+            };
+            addr.parse()
+                .map(Address::Tcp)
+                .map_err(AddressError::InvalidSocketAddr)
+        }
+    }
+}
+
+#[cfg(unix)]
+fn unix_address(path: &str) -> Result<Address, AddressError> {
+    Ok(Address::Unix(PathBuf::from(path)))
+}
+
+#[cfg(not(unix))]
+fn unix_address(_path: &str) -> Result<Address, AddressError> {
+    Err(AddressError::UnixNotSupported)
+}
+
+/// A connected stream, over any of the transports.
+#[derive(Debug)]
+pub enum Socket {
+    Tcp(TcpStream),
+    #[cfg(unix)]
+    Unix(UnixStream),
+    Memory(memory::MemoryStream),
+}
+
+impl Read for Socket {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        match self {
+            Socket::Tcp(stream) => stream.read(buf),
+            #[cfg(unix)]
+            Socket::Unix(stream) => stream.read(buf),
+            Socket::Memory(stream) => stream.read(buf),
+        }
+    }
+}
+
+impl Write for Socket {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        match self {
+            Socket::Tcp(stream) => stream.write(buf),
+            #[cfg(unix)]
+            Socket::Unix(stream) => stream.write(buf),
+            Socket::Memory(stream) => stream.write(buf),
+        }
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        match self {
+            Socket::Tcp(stream) => stream.flush(),
+            #[cfg(unix)]
+            Socket::Unix(stream) => stream.flush(),
+            Socket::Memory(stream) => stream.flush(),
+        }
+    }
+}
+
+impl AsyncRead for Socket {}
+
+impl AsyncWrite for Socket {
+    fn shutdown(&mut self) -> Poll<(), io::Error> {
+        match self {
+            Socket::Tcp(stream) => AsyncWrite::shutdown(stream),
+            #[cfg(unix)]
+            Socket::Unix(stream) => AsyncWrite::shutdown(stream),
+            Socket::Memory(stream) => AsyncWrite::shutdown(stream),
+        }
+    }
+}
+
+/// Future of a `Socket` connected to an `Address`, see `connect`.
+pub enum Connecting {
+    Tcp(tcp::ConnectFuture),
+    #[cfg(unix)]
+    Unix(unix::ConnectFuture),
+}
+
+impl Future for Connecting {
+    type Item = Socket;
+    type Error = io::Error;
+
+    fn poll(&mut self) -> Poll<Socket, io::Error> {
+        match self {
+            Connecting::Tcp(connecting) => connecting.poll().map(|r| r.map(Socket::Tcp)),
+            #[cfg(unix)]
+            Connecting::Unix(connecting) => connecting.poll().map(|r| r.map(Socket::Unix)),
+        }
+    }
+}
+
+/// Connect to the given address.
+pub fn connect(address: &Address) -> Connecting {
+    match address {
+        Address::Tcp(addr) => Connecting::Tcp(TcpStream::connect(addr)),
+        #[cfg(unix)]
+        Address::Unix(path) => Connecting::Unix(UnixStream::connect(path)),
+    }
+}
+
+/// The incoming connections on a listening address, see `listen`.
+pub enum Listener {
+    Tcp(tcp::Incoming),
+    #[cfg(unix)]
+    Unix(unix::Incoming),
+    Memory(memory::MemoryListener),
+}
+
+impl Stream for Listener {
+    type Item = Socket;
+    type Error = io::Error;
+
+    fn poll(&mut self) -> Poll<Option<Socket>, io::Error> {
+        match self {
+            Listener::Tcp(incoming) => incoming.poll().map(|r| r.map(|s| s.map(Socket::Tcp))),
+            #[cfg(unix)]
+            Listener::Unix(incoming) => incoming.poll().map(|r| r.map(|s| s.map(Socket::Unix))),
+            Listener::Memory(listener) => listener.poll().map(|r| r.map(|s| s.map(Socket::Memory))),
+        }
+    }
+}
+
+impl From<memory::MemoryListener> for Listener {
+    fn from(listener: memory::MemoryListener) -> Self {
+        Listener::Memory(listener)
+    }
+}
+
+/// Listen on the given address.
+///
+/// Binding a UNIX domain socket fails if its path already exists: the
+/// socket file left by a previous process needs to be removed first.
+pub fn listen(address: &Address) -> io::Result<Listener> {
+    match address {
+        Address::Tcp(addr) => {
+            let listener = TcpListener::bind(addr)?;
+            Ok(Listener::Tcp(listener.incoming()))
+        }
+        #[cfg(unix)]
+        Address::Unix(path) => {
+            let listener = UnixListener::bind(path)?;
+            Ok(Listener::Unix(listener.incoming()))
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parse_tcp_addresses() {
+        let addr: SocketAddr = "127.0.0.1:3000".parse().unwrap();
+        assert_eq!(
+            "127.0.0.1:3000".parse::<Address>().unwrap(),
+            Address::Tcp(addr)
+        );
+        assert_eq!(
+            "tcp:127.0.0.1:3000".parse::<Address>().unwrap(),
+            Address::Tcp(addr)
+        );
+        assert_eq!(Address::Tcp(addr).to_string(), "127.0.0.1:3000");
+
+        match "localhost".parse::<Address>() {
+            Err(AddressError::InvalidSocketAddr(_)) => {}
+            r => panic!("unexpected result {:?}", r),
+        }
+    }
+
+    #[test]
+    fn parse_unix_addresses() {
+        match "unix:".parse::<Address>() {
+            Err(AddressError::EmptyPath) => {}
+            r => panic!("unexpected result {:?}", r),
+        }
+
+        let parsed = "unix:/run/node.sock".parse::<Address>();
+        #[cfg(unix)]
+        {
+            let address = parsed.unwrap();
+            assert_eq!(address, Address::Unix(PathBuf::from("/run/node.sock")));
+            assert_eq!(address.to_string(), "unix:/run/node.sock");
+        }
+        #[cfg(not(unix))]
+        match parsed {
+            Err(AddressError::UnixNotSupported) => {}
+            r => panic!("unexpected result {:?}", r),
+        }
+    }
+
+    #[test]
+    fn memory_listener() {
+        let (connector, listener) = memory::listener();
+        let client = connector.connect().unwrap();
+        drop(connector);
+
+        let (socket, _) = Listener::from(listener).into_future().wait().ok().unwrap();
+        let server = match socket {
+            Some(socket @ Socket::Memory(_)) => socket,
+            s => panic!("unexpected socket {:?}", s),
+        };
+        tokio::io::write_all(client, b"hello").wait().unwrap();
+        let (_, received) = tokio::io::read_exact(server, [0u8; 5]).wait().unwrap();
+        assert_eq!(&received, b"hello");
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn unix_listen_connect() {
+        use std::{env, fs, process};
+        use tokio::runtime::current_thread::Runtime;
+
+        let path = env::temp_dir().join(format!("network-ntt-{}.sock", process::id()));
+        let _ = fs::remove_file(&path);
+        let address = Address::Unix(path.clone());
+
+        let mut runtime = Runtime::new().unwrap();
+        let server = listen(&address)
+            .unwrap()
+            .into_future()
+            .map_err(|(e, _)| e)
+            .and_then(|(socket, _)| tokio::io::read_exact(socket.unwrap(), [0u8; 5]));
+        let client = connect(&address).and_then(|socket| tokio::io::write_all(socket, b"hello"));
+        let ((_, received), _) = runtime.block_on(server.join(client)).unwrap();
+        assert_eq!(&received, b"hello");
+
+        // the socket file is left, binding again fails
+        assert!(listen(&address).is_err());
+        fs::remove_file(&path).unwrap();
+    }
+}
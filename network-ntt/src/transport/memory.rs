@@ -0,0 +1,218 @@
+//! In-process transport
+//!
+//! The two ends of a `MemoryStream` exchange the written bytes over
+//! channels, within the same process. `listener` gives a `MemoryListener`
+//! receiving the streams connected with its `MemoryConnector`, to run a
+//! server and its clients in the same test.
+
+use futures::{prelude::*, sync::mpsc};
+use tokio::io::{AsyncRead, AsyncWrite};
+
+use std::{
+    cmp,
+    io::{self, Read, Write},
+};
+
+/// One end of an in-process connection.
+///
+/// The writes never block. Reading needs to be done from a task (as for
+/// any tokio stream): it returns `WouldBlock` until the other end writes.
+#[derive(Debug)]
+pub struct MemoryStream {
+    incoming: mpsc::UnboundedReceiver<Vec<u8>>,
+    outgoing: Option<mpsc::UnboundedSender<Vec<u8>>>,
+    buf: Vec<u8>,
+    pos: usize,
+}
+
+/// Create the two connected ends of an in-process connection.
+pub fn pair() -> (MemoryStream, MemoryStream) {
+    let (tx1, rx1) = mpsc::unbounded();
+    let (tx2, rx2) = mpsc::unbounded();
+    (MemoryStream::new(rx1, tx2), MemoryStream::new(rx2, tx1))
+}
+
+impl MemoryStream {
+    fn new(
+        incoming: mpsc::UnboundedReceiver<Vec<u8>>,
+        outgoing: mpsc::UnboundedSender<Vec<u8>>,
+    ) -> Self {
+        MemoryStream {
+            incoming,
+            outgoing: Some(outgoing),
+            buf: Vec::new(),
+            pos: 0,
+        }
+    }
+}
+
+impl Read for MemoryStream {
+    fn read(&mut self, out: &mut [u8]) -> io::Result<usize> {
+        while self.pos == self.buf.len() {
+            match self.incoming.poll() {
+                Ok(Async::Ready(Some(buf))) => {
+                    self.buf = buf;
+                    self.pos = 0;
+                }
+                // the other end is closed
+                Ok(Async::Ready(None)) | Err(()) => return Ok(0),
+                Ok(Async::NotReady) => return Err(io::ErrorKind::WouldBlock.into()),
+            }
+        }
+        let len = cmp::min(out.len(), self.buf.len() - self.pos);
+        out[..len].copy_from_slice(&self.buf[self.pos..self.pos + len]);
+        self.pos += len;
+        Ok(len)
+    }
+}
+
+impl Write for MemoryStream {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        match &self.outgoing {
+            None => Err(io::ErrorKind::BrokenPipe.into()),
+            Some(outgoing) => outgoing
+                .unbounded_send(buf.to_vec())
+                .map(|()| buf.len())
+                .map_err(|_| io::ErrorKind::BrokenPipe.into()),
+        }
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        Ok(())
+    }
+}
+
+impl AsyncRead for MemoryStream {}
+
+impl AsyncWrite for MemoryStream {
+    /// the other end reads the end of the stream, once it has read what
+    /// was written before
+    fn shutdown(&mut self) -> Poll<(), io::Error> {
+        self.outgoing = None;
+        Ok(Async::Ready(()))
+    }
+}
+
+/// Connects `MemoryStream`s to the associated `MemoryListener`.
+#[derive(Debug, Clone)]
+pub struct MemoryConnector(mpsc::UnboundedSender<MemoryStream>);
+
+impl MemoryConnector {
+    /// Connect to the listener, returns the client end of the connection.
+    pub fn connect(&self) -> io::Result<MemoryStream> {
+        let (client, server) = pair();
+        self.0
+            .unbounded_send(server)
+            .map(|()| client)
+            .map_err(|_| io::ErrorKind::ConnectionRefused.into())
+    }
+}
+
+/// The stream of the server ends of the connections made with the
+/// associated `MemoryConnector`s. It ends once all the connectors are
+/// dropped.
+#[derive(Debug)]
+pub struct MemoryListener(mpsc::UnboundedReceiver<MemoryStream>);
+
+impl Stream for MemoryListener {
+    type Item = MemoryStream;
+    type Error = io::Error;
+
+    fn poll(&mut self) -> Poll<Option<MemoryStream>, io::Error> {
+        self.0
+            .poll()
+            .map_err(|()| io::Error::new(io::ErrorKind::Other, "memory listener error"))
+    }
+}
+
+/// Create an in-process listener, and the connector to connect to it.
+pub fn listener() -> (MemoryConnector, MemoryListener) {
+    let (tx, rx) = mpsc::unbounded();
+    (MemoryConnector(tx), MemoryListener(rx))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use futures::future;
+
+    #[test]
+    fn exchange_both_ways() {
+        let (client, server) = pair();
+        let (client, _) = tokio::io::write_all(client, b"ping").wait().unwrap();
+        let (server, received) = tokio::io::read_exact(server, [0u8; 4]).wait().unwrap();
+        assert_eq!(&received, b"ping");
+
+        let (_, _) = tokio::io::write_all(server, b"pong").wait().unwrap();
+        let (_, received) = tokio::io::read_exact(client, [0u8; 4]).wait().unwrap();
+        assert_eq!(&received, b"pong");
+    }
+
+    #[test]
+    fn read_would_block_until_written() {
+        let (mut client, mut server) = pair();
+        let read = future::lazy(|| {
+            let mut buf = [0u8; 4];
+            future::ok::<_, ()>(server.read(&mut buf).map_err(|e| e.kind()))
+        })
+        .wait()
+        .unwrap();
+        assert_eq!(read, Err(io::ErrorKind::WouldBlock));
+
+        client.write_all(b"data").unwrap();
+        let (_, received) = tokio::io::read_exact(server, [0u8; 4]).wait().unwrap();
+        assert_eq!(&received, b"data");
+    }
+
+    #[test]
+    fn shutdown_ends_the_stream() {
+        let (mut client, server) = pair();
+        client.write_all(b"last").unwrap();
+        client.shutdown().unwrap();
+        assert_eq!(
+            client.write(b"more").map_err(|e| e.kind()),
+            Err(io::ErrorKind::BrokenPipe)
+        );
+
+        let (_, received) = tokio::io::read_to_end(server, Vec::new()).wait().unwrap();
+        assert_eq!(received, b"last".to_vec());
+    }
+
+    #[test]
+    fn write_to_a_dropped_end() {
+        let (mut client, server) = pair();
+        drop(server);
+        assert_eq!(
+            client.write(b"data").map_err(|e| e.kind()),
+            Err(io::ErrorKind::BrokenPipe)
+        );
+    }
+
+    #[test]
+    fn listener_accepts_the_connections() {
+        let (connector, listener) = listener();
+        let client1 = connector.connect().unwrap();
+        let client2 = connector.clone().connect().unwrap();
+        drop(connector);
+
+        // the listener ends once the connectors are dropped
+        let servers = listener.collect().wait().unwrap();
+        assert_eq!(servers.len(), 2);
+
+        for (client, server) in vec![client1, client2].into_iter().zip(servers) {
+            tokio::io::write_all(client, b"hello").wait().unwrap();
+            let (_, received) = tokio::io::read_exact(server, [0u8; 5]).wait().unwrap();
+            assert_eq!(&received, b"hello");
+        }
+    }
+
+    #[test]
+    fn connect_to_a_dropped_listener() {
+        let (connector, listener) = listener();
+        drop(listener);
+        assert_eq!(
+            connector.connect().map(|_| ()).map_err(|e| e.kind()),
+            Err(io::ErrorKind::ConnectionRefused)
+        );
+    }
+}
@@ -18,8 +18,9 @@ pub mod network_transport;
 pub mod protocol;
 
 pub use self::protocol::{
-    Accepting, AcceptingError, CloseLightConnection, Connecting, ConnectingError, Connection,
-    Inbound, InboundError, InboundStream, Message, MessageType, NewLightConnection, Outbound,
-    OutboundError, OutboundSink, ProtocolBlock, ProtocolBlockDate, ProtocolBlockId, ProtocolHeader,
-    ProtocolTransactionId, Response,
+    Accepting, AcceptingError, AllowedMagics, CloseLightConnection, Connecting, ConnectingError,
+    Connection, Inbound, InboundError, InboundStream, Message, MessageType, NewLightConnection,
+    Outbound, OutboundError, OutboundSink, ProtocolBlock, ProtocolBlockDate, ProtocolBlockId,
+    ProtocolHeader, ProtocolTransactionId, Response, Resume, ResumePolicy, Resumed,
+    ResumedGetBlocks, Session, SessionError,
 };
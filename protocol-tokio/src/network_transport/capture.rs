@@ -0,0 +1,378 @@
+//! Capture and replay of the network transport traffic.
+//!
+//! `Capture` wraps the transport given to `Connection::connect` or
+//! `Connection::accept` and records, with the time it happened, every
+//! chunk of bytes received from or sent to the peer. `Replay` plays the
+//! peer's side of a capture, so the exchange can be fed back through the
+//! handshake and the `Event` codec offline.
+//!
+//! Both also implement the blocking `Read` and `Write`: the blocking
+//! `protocol` crate uses them for its own connections.
+//!
+//! The capture file starts with an 8 bytes magic, followed by the records:
+//!
+//! ```text
+//! direction: u8 (0: inbound, 1: outbound)
+//! seconds since the UNIX epoch: u64 (big endian)
+//! nanoseconds: u32 (big endian)
+//! length: u32 (big endian)
+//! data: [u8; length]
+//! ```
+
+use std::collections::VecDeque;
+use std::io::{self, Read, Write};
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+use bytes::{Buf, BufMut, IntoBuf};
+use futures::{Async, Poll};
+use tokio_io::{AsyncRead, AsyncWrite};
+
+pub const MAGIC: [u8; 8] = *b"NTTCAP01";
+
+const RECORD_HEADER_SIZE: usize = 17;
+const DIRECTION_INBOUND: u8 = 0;
+const DIRECTION_OUTBOUND: u8 = 1;
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum Direction {
+    /// bytes received from the peer
+    Inbound,
+    /// bytes sent to the peer
+    Outbound,
+}
+
+/// a chunk of bytes exchanged with the peer
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Record {
+    pub direction: Direction,
+    /// time elapsed since the UNIX epoch
+    pub timestamp: Duration,
+    pub data: Vec<u8>,
+}
+impl Record {
+    pub fn new(direction: Direction, data: Vec<u8>) -> Self {
+        let timestamp = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .unwrap_or(Duration::from_secs(0));
+        Record {
+            direction,
+            timestamp,
+            data,
+        }
+    }
+
+    fn write<W: Write>(&self, writer: &mut W) -> io::Result<()> {
+        let mut header = Vec::with_capacity(RECORD_HEADER_SIZE);
+        header.put_u8(match self.direction {
+            Direction::Inbound => DIRECTION_INBOUND,
+            Direction::Outbound => DIRECTION_OUTBOUND,
+        });
+        header.put_u64_be(self.timestamp.as_secs());
+        header.put_u32_be(self.timestamp.subsec_nanos());
+        header.put_u32_be(self.data.len() as u32);
+        writer.write_all(&header)?;
+        writer.write_all(&self.data)
+    }
+
+    /// read the next record, `None` if the end of the capture is reached
+    fn read<R: Read>(reader: &mut R) -> io::Result<Option<Self>> {
+        let mut header = [0u8; RECORD_HEADER_SIZE];
+        // a clean end of file is only acceptable between two records
+        if reader.read(&mut header[..1])? == 0 {
+            return Ok(None);
+        }
+        reader.read_exact(&mut header[1..])?;
+
+        let mut header = (&header[..]).into_buf();
+        let direction = match header.get_u8() {
+            DIRECTION_INBOUND => Direction::Inbound,
+            DIRECTION_OUTBOUND => Direction::Outbound,
+            d => {
+                return Err(io::Error::new(
+                    io::ErrorKind::InvalidData,
+                    format!("invalid capture record direction {}", d),
+                ));
+            }
+        };
+        let secs = header.get_u64_be();
+        let nanos = header.get_u32_be();
+        let mut data = vec![0; header.get_u32_be() as usize];
+        reader.read_exact(&mut data)?;
+        Ok(Some(Record {
+            direction,
+            timestamp: Duration::new(secs, nanos),
+            data,
+        }))
+    }
+}
+
+/// write the records of a capture
+#[derive(Debug)]
+pub struct Writer<W> {
+    writer: W,
+}
+impl<W: Write> Writer<W> {
+    pub fn new(mut writer: W) -> io::Result<Self> {
+        writer.write_all(&MAGIC)?;
+        Ok(Writer { writer })
+    }
+
+    /// record `data`, exchanged with the peer just now
+    pub fn record(&mut self, direction: Direction, data: &[u8]) -> io::Result<()> {
+        self.write_record(&Record::new(direction, data.to_vec()))
+    }
+
+    pub fn write_record(&mut self, record: &Record) -> io::Result<()> {
+        record.write(&mut self.writer)
+    }
+
+    pub fn get_ref(&self) -> &W {
+        &self.writer
+    }
+
+    pub fn into_inner(self) -> W {
+        self.writer
+    }
+}
+
+/// iterate over the records of a capture
+#[derive(Debug)]
+pub struct Reader<R> {
+    reader: R,
+}
+impl<R: Read> Reader<R> {
+    pub fn new(mut reader: R) -> io::Result<Self> {
+        let mut magic = [0u8; 8];
+        reader.read_exact(&mut magic)?;
+        if magic != MAGIC {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidData,
+                "not a network transport capture",
+            ));
+        }
+        Ok(Reader { reader })
+    }
+}
+impl<R: Read> Iterator for Reader<R> {
+    type Item = io::Result<Record>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        match Record::read(&mut self.reader) {
+            Ok(None) => None,
+            Ok(Some(record)) => Some(Ok(record)),
+            Err(err) => Some(Err(err)),
+        }
+    }
+}
+
+/// A transport recording everything going through it in a capture.
+///
+/// The capture is written synchronously, a file or an in memory buffer
+/// are fine but a slow writer will slow the connection down.
+#[derive(Debug)]
+pub struct Capture<T, W> {
+    inner: T,
+    writer: Writer<W>,
+}
+impl<T, W: Write> Capture<T, W> {
+    pub fn new(inner: T, writer: Writer<W>) -> Self {
+        Capture { inner, writer }
+    }
+
+    pub fn get_ref(&self) -> &T {
+        &self.inner
+    }
+
+    pub fn writer(&self) -> &Writer<W> {
+        &self.writer
+    }
+
+    pub fn into_inner(self) -> (T, Writer<W>) {
+        (self.inner, self.writer)
+    }
+}
+impl<T: Read, W: Write> Read for Capture<T, W> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        let len = self.inner.read(buf)?;
+        if len > 0 {
+            self.writer.record(Direction::Inbound, &buf[..len])?;
+        }
+        Ok(len)
+    }
+}
+impl<T: Write, W: Write> Write for Capture<T, W> {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        let len = self.inner.write(buf)?;
+        if len > 0 {
+            self.writer.record(Direction::Outbound, &buf[..len])?;
+        }
+        Ok(len)
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        self.inner.flush()?;
+        self.writer.writer.flush()
+    }
+}
+impl<T: AsyncRead, W: Write> AsyncRead for Capture<T, W> {}
+impl<T: AsyncWrite, W: Write> AsyncWrite for Capture<T, W> {
+    fn shutdown(&mut self) -> Poll<(), io::Error> {
+        self.inner.shutdown()
+    }
+}
+
+/// A transport playing the peer's side of a capture.
+///
+/// Reading returns the inbound bytes of the capture, in order, then the
+/// end of the stream. What is written is kept so it can be compared to
+/// the outbound bytes of the capture.
+#[derive(Debug)]
+pub struct Replay {
+    inbound: VecDeque<u8>,
+    outbound: Vec<u8>,
+    sent: Vec<u8>,
+}
+impl Replay {
+    pub fn new<I: IntoIterator<Item = Record>>(records: I) -> Self {
+        let mut replay = Replay {
+            inbound: VecDeque::new(),
+            outbound: Vec::new(),
+            sent: Vec::new(),
+        };
+        for record in records {
+            match record.direction {
+                Direction::Inbound => replay.inbound.extend(record.data),
+                Direction::Outbound => replay.outbound.extend(record.data),
+            }
+        }
+        replay
+    }
+
+    pub fn from_reader<R: Read>(reader: Reader<R>) -> io::Result<Self> {
+        let records = reader.collect::<io::Result<Vec<_>>>()?;
+        Ok(Replay::new(records))
+    }
+
+    /// the bytes written to the transport so far
+    pub fn sent(&self) -> &[u8] {
+        &self.sent
+    }
+
+    /// the outbound bytes of the capture
+    pub fn expected(&self) -> &[u8] {
+        &self.outbound
+    }
+
+    /// offset of the first byte sent that differs from the capture, if any
+    pub fn diverged(&self) -> Option<usize> {
+        self.sent
+            .iter()
+            .zip(self.outbound.iter())
+            .position(|(sent, expected)| sent != expected)
+            .or_else(|| {
+                if self.sent.len() > self.outbound.len() {
+                    Some(self.outbound.len())
+                } else {
+                    None
+                }
+            })
+    }
+}
+impl Read for Replay {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        let len = ::std::cmp::min(buf.len(), self.inbound.len());
+        for (byte, inbound) in buf.iter_mut().zip(self.inbound.drain(..len)) {
+            *byte = inbound;
+        }
+        Ok(len)
+    }
+}
+impl Write for Replay {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        self.sent.extend_from_slice(buf);
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        Ok(())
+    }
+}
+impl AsyncRead for Replay {}
+impl AsyncWrite for Replay {
+    fn shutdown(&mut self) -> Poll<(), io::Error> {
+        Ok(Async::Ready(()))
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::super::event::EventCodec;
+    use super::super::{Connection, ControlHeader, Event, LightWeightConnectionId};
+    use super::*;
+    use bytes::BytesMut;
+    use futures::{Future, Sink};
+    use tokio_codec::Decoder;
+
+    fn handshake_success() -> Record {
+        Record::new(Direction::Inbound, vec![0, 0, 0, 0])
+    }
+
+    #[test]
+    fn records_roundtrip() {
+        let records = vec![
+            Record::new(Direction::Outbound, vec![1, 2, 3]),
+            Record::new(Direction::Inbound, vec![]),
+            Record::new(Direction::Inbound, vec![4; 1024]),
+        ];
+        let mut writer = Writer::new(Vec::new()).unwrap();
+        for record in records.iter() {
+            writer.write_record(record).unwrap();
+        }
+        let bytes = writer.into_inner();
+
+        let read = Reader::new(&bytes[..])
+            .unwrap()
+            .collect::<io::Result<Vec<_>>>()
+            .unwrap();
+        assert_eq!(read, records);
+
+        // truncated in the middle of a record
+        let truncated = Reader::new(&bytes[..bytes.len() - 1]).unwrap();
+        assert!(truncated.collect::<io::Result<Vec<_>>>().is_err());
+    }
+
+    #[test]
+    fn capture_and_replay_events() {
+        let lwcid = LightWeightConnectionId::first_non_reserved();
+        let event = Event::Control(ControlHeader::CreateNewConnection, lwcid);
+
+        // capture a connection sending an event to a peer accepting the handshake
+        let peer = Replay::new(vec![handshake_success()]);
+        let transport = Capture::new(peer, Writer::new(Vec::new()).unwrap());
+        let connection = Connection::connect(transport).wait().unwrap();
+        let connection = connection.send(event.clone()).wait().unwrap();
+        let (transport, writer) = connection.0.into_inner().into_inner();
+        assert_eq!(transport.diverged(), None);
+        let capture = writer.into_inner();
+
+        // replay it, the connection sends the same bytes
+        let replay = Replay::from_reader(Reader::new(&capture[..]).unwrap()).unwrap();
+        let connection = Connection::connect(replay).wait().unwrap();
+        let connection = connection.send(event.clone()).wait().unwrap();
+        let replay = connection.0.into_inner();
+        assert_eq!(replay.diverged(), None);
+        assert_eq!(replay.sent(), replay.expected());
+
+        // and the events sent can be decoded back from the capture
+        let outbound: Vec<u8> = Reader::new(&capture[..])
+            .unwrap()
+            .map(|record| record.unwrap())
+            .filter(|record| record.direction == Direction::Outbound)
+            .flat_map(|record| record.data)
+            .collect();
+        // skip the 16 bytes of the handshake
+        let mut bytes = BytesMut::from(&outbound[16..]);
+        assert_eq!(EventCodec.decode(&mut bytes).unwrap(), Some(event));
+        assert!(bytes.is_empty());
+    }
+}
@@ -1,6 +1,7 @@
-use std::{error, fmt, io, ops::Deref};
+use std::{error, fmt, io, io::Cursor, ops::Deref};
 
 use bytes::{Buf, BufMut, Bytes, BytesMut, IntoBuf};
+use cbor_event::{self, de::Deserializer, Deserialize};
 use tokio_codec as codec;
 
 #[derive(Copy, Clone, Debug, PartialEq, PartialOrd, Eq, Ord, Hash)]
@@ -50,6 +51,139 @@ impl From<ControlHeader> for u32 {
     }
 }
 
+/// Maximum length of the data of an `Event::Data`.
+///
+/// The length is read from the stream before the data: without a limit, a
+/// peer could make us buffer up to 4GiB. The largest messages are the
+/// blocks, bounded by the maximum block size of the protocol (2MiB).
+pub const MAX_DATA_LENGTH: u32 = 8 * 1024 * 1024;
+
+/// Maximum nesting of the arrays, maps and tags in the CBOR data of an
+/// `Event::Data`, see `check_cbor_limits`.
+pub const MAX_CBOR_DEPTH: usize = 64;
+
+/// Maximum number of items of an array or of entries of a map in the CBOR
+/// data of an `Event::Data`: as many as one byte each can fit in the data.
+pub const MAX_CBOR_LENGTH: u64 = MAX_DATA_LENGTH as u64;
+
+/// The CBOR data of an `Event::Data` is above the limits or not well formed
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CborLimitError {
+    /// nested deeper than `MAX_CBOR_DEPTH`
+    TooDeep,
+    /// an array or a map of more than `MAX_CBOR_LENGTH` items
+    TooLong(u64),
+    /// the data ends in the middle of an item
+    Truncated,
+    /// an initial byte with a reserved additional information, or an
+    /// unexpected break
+    Malformed(u8),
+}
+impl fmt::Display for CborLimitError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            CborLimitError::TooDeep => write!(f, "CBOR nested deeper than {}", MAX_CBOR_DEPTH),
+            CborLimitError::TooLong(len) => write!(
+                f,
+                "CBOR collection of {} items, above the maximum of {}",
+                len, MAX_CBOR_LENGTH
+            ),
+            CborLimitError::Truncated => write!(f, "truncated CBOR"),
+            CborLimitError::Malformed(byte) => write!(f, "malformed CBOR (byte 0x{:02x})", byte),
+        }
+    }
+}
+
+impl error::Error for CborLimitError {}
+
+/// Check the CBOR items of `bytes` against `MAX_CBOR_DEPTH` and
+/// `MAX_CBOR_LENGTH`, without recursion, before decoding them (the
+/// deserializer of `cbor_event` recurses on the nested items).
+pub fn check_cbor_limits(bytes: &[u8]) -> Result<(), CborLimitError> {
+    // the number of items left in each of the enclosing collections, `None`
+    // for the indefinite ones (ended by a break)
+    let mut open: Vec<Option<u64>> = Vec::new();
+    let mut pos = 0;
+    while pos < bytes.len() {
+        let initial = bytes[pos];
+        pos += 1;
+        let major = initial >> 5;
+        let info = initial & 0x1f;
+        let argument = match info {
+            0..=23 => Some(u64::from(info)),
+            24..=27 => {
+                let size = 1 << (info - 24);
+                if bytes.len() - pos < size {
+                    return Err(CborLimitError::Truncated);
+                }
+                let value = bytes[pos..pos + size]
+                    .iter()
+                    .fold(0, |acc, byte| (acc << 8) | u64::from(*byte));
+                pos += size;
+                Some(value)
+            }
+            31 if major >= 2 && major != 6 => None,
+            _ => return Err(CborLimitError::Malformed(initial)),
+        };
+
+        // the collection opened by the item, if any
+        let opened = match (major, argument) {
+            // a break, ending the innermost indefinite collection
+            (7, None) => match open.pop() {
+                Some(None) => None,
+                _ => return Err(CborLimitError::Malformed(initial)),
+            },
+            // the bytes of a string
+            (2, Some(len)) | (3, Some(len)) => {
+                if ((bytes.len() - pos) as u64) < len {
+                    return Err(CborLimitError::Truncated);
+                }
+                pos += len as usize;
+                None
+            }
+            (4, Some(len)) | (5, Some(len)) if len > MAX_CBOR_LENGTH => {
+                return Err(CborLimitError::TooLong(len));
+            }
+            (4, Some(len)) => Some(Some(len)),
+            (5, Some(len)) => Some(Some(len * 2)),
+            // the content of the tag
+            (6, Some(_)) => Some(Some(1)),
+            // the chunks of a string, the items of an array or map
+            (2, None) | (3, None) | (4, None) | (5, None) => Some(None),
+            _ => None,
+        };
+        if let Some(items) = opened {
+            if items != Some(0) {
+                if open.len() == MAX_CBOR_DEPTH {
+                    return Err(CborLimitError::TooDeep);
+                }
+                open.push(items);
+                continue;
+            }
+        }
+        // the item is complete, as is the collection ended by a break
+        while let Some(Some(left)) = open.last_mut() {
+            *left -= 1;
+            if *left > 0 {
+                break;
+            }
+            open.pop();
+        }
+    }
+    if open.is_empty() {
+        Ok(())
+    } else {
+        Err(CborLimitError::Truncated)
+    }
+}
+
+/// Decode the CBOR data of an `Event::Data`, once checked against the
+/// limits (see `check_cbor_limits`).
+pub fn decode_cbor<T: Deserialize>(bytes: &[u8]) -> cbor_event::Result<T> {
+    check_cbor_limits(bytes).map_err(|err| cbor_event::Error::CustomError(err.to_string()))?;
+    Deserializer::from(Cursor::new(bytes)).deserialize()
+}
+
 /// represent control commands or data exchanged between connections
 #[derive(Clone, Debug, PartialEq, Eq)]
 pub enum Event {
@@ -94,6 +228,9 @@ pub enum DecodeEventError {
     ///
     /// includes value in range `[0..1024[`
     InvalidLightWeightConnectionId(u32),
+
+    /// The length of the data is above `MAX_DATA_LENGTH`
+    DataTooLarge(u32),
 }
 
 impl From<io::Error> for DecodeEventError {
@@ -110,6 +247,11 @@ impl fmt::Display for DecodeEventError {
             DecodeEventError::InvalidLightWeightConnectionId(n) => {
                 write!(f, "invalid lightweight connection id {}", n)
             }
+            DecodeEventError::DataTooLarge(n) => write!(
+                f,
+                "data of {} bytes, above the maximum of {} bytes",
+                n, MAX_DATA_LENGTH
+            ),
         }
     }
 }
@@ -120,6 +262,7 @@ impl error::Error for DecodeEventError {
             DecodeEventError::IoError(e) => Some(e),
             DecodeEventError::InvalidControlHeader(_) => None,
             DecodeEventError::InvalidLightWeightConnectionId(_) => None,
+            DecodeEventError::DataTooLarge(_) => None,
         }
     }
 }
@@ -210,6 +353,11 @@ impl codec::Decoder for EventCodec {
             ControlHeaderOrLightWeightConnectionId::UnknownControlHeader(ch) => {
                 Err(DecodeEventError::InvalidControlHeader(ch))
             }
+            ControlHeaderOrLightWeightConnectionId::LightWeightConnectionId(_)
+                if l > MAX_DATA_LENGTH =>
+            {
+                Err(DecodeEventError::DataTooLarge(l))
+            }
             ControlHeaderOrLightWeightConnectionId::LightWeightConnectionId(lwcid) => {
                 // the length of the data
                 let len = l as usize;
@@ -312,4 +460,68 @@ mod test {
             parsed == event
         }
     }
+
+    #[test]
+    fn event_decode_data_too_large() {
+        let mut stream = BytesMut::with_capacity(8);
+        stream.put_u32_be(*LightWeightConnectionId::first_non_reserved());
+        stream.put_u32_be(MAX_DATA_LENGTH + 1);
+
+        match EventCodec.decode(&mut stream) {
+            Err(DecodeEventError::DataTooLarge(len)) => assert_eq!(len, MAX_DATA_LENGTH + 1),
+            result => panic!("expected DataTooLarge, got {:?}", result),
+        }
+    }
+
+    #[test]
+    fn cbor_limits() {
+        let check = check_cbor_limits;
+        assert_eq!(check(&[0x82, 0x01, 0x02]), Ok(()));
+        assert_eq!(check(&[0x82, 0x80, 0x01]), Ok(()));
+        assert_eq!(check(&[0xa1, 0x01, 0x02]), Ok(()));
+        assert_eq!(check(&[0x82, 0x9f, 0x01, 0xff, 0x02]), Ok(()));
+        assert_eq!(check(&[0x82, 0xd8, 0x18, 0x41, 0x01, 0x02]), Ok(()));
+        assert_eq!(check(&[0x5f, 0x41, 0x01, 0x41, 0x02, 0xff]), Ok(()));
+        assert_eq!(check(&[0xf9, 0x00, 0x00, 0xf4, 0xf5, 0xf6]), Ok(()));
+
+        assert_eq!(check(&[0x82, 0x01]), Err(CborLimitError::Truncated));
+        assert_eq!(
+            check(&[0x82, 0x9f, 0x01, 0xff]),
+            Err(CborLimitError::Truncated)
+        );
+        assert_eq!(check(&[0x43, 0x01, 0x02]), Err(CborLimitError::Truncated));
+        assert_eq!(check(&[0x19, 0x01]), Err(CborLimitError::Truncated));
+        assert_eq!(
+            check(&[0x82, 0x01, 0xff]),
+            Err(CborLimitError::Malformed(0xff))
+        );
+        assert_eq!(check(&[0x1c]), Err(CborLimitError::Malformed(0x1c)));
+        assert_eq!(
+            check(&[0x9b, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff]),
+            Err(CborLimitError::TooLong(u64::max_value()))
+        );
+
+        let nested = |depth| {
+            let mut bytes = vec![0x81; depth];
+            bytes.push(0x01);
+            bytes
+        };
+        assert_eq!(check(&nested(MAX_CBOR_DEPTH)), Ok(()));
+        assert_eq!(
+            check(&nested(MAX_CBOR_DEPTH + 1)),
+            Err(CborLimitError::TooDeep)
+        );
+        // would overflow the stack of a recursive decoder
+        assert_eq!(check(&vec![0x9f; 1_000_000]), Err(CborLimitError::TooDeep));
+    }
+
+    #[test]
+    fn decode_cbor_within_limits() {
+        let values: Vec<u64> = decode_cbor(&[0x82, 0x01, 0x02]).unwrap();
+        assert_eq!(values, vec![1, 2]);
+
+        let mut nested = vec![0x81; MAX_CBOR_DEPTH + 1];
+        nested.push(0x01);
+        assert!(decode_cbor::<Vec<u64>>(&nested).is_err());
+    }
 }
@@ -1,4 +1,5 @@
 mod accepting;
+pub mod capture;
 mod closing;
 mod connecting;
 mod event;
@@ -12,7 +13,10 @@ use tokio_io::{AsyncRead, AsyncWrite};
 pub use self::accepting::{Accepting, AcceptingError};
 pub use self::closing::{Closing, ClosingError};
 pub use self::connecting::{Connecting, ConnectingError};
-pub use self::event::{ControlHeader, DecodeEventError, Event, LightWeightConnectionId};
+pub use self::event::{
+    check_cbor_limits, decode_cbor, CborLimitError, ControlHeader, DecodeEventError, Event,
+    LightWeightConnectionId, MAX_CBOR_DEPTH, MAX_CBOR_LENGTH, MAX_DATA_LENGTH,
+};
 pub use self::response_code::ResponseCode;
 
 /// Network Transport connection where we can accept Event
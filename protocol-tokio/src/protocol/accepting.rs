@@ -6,13 +6,12 @@ use futures::{
 };
 use tokio_io::{AsyncRead, AsyncWrite};
 
-use cbor_event::de::Deserializer;
 use chain_core::property;
-use std::{self, fmt, io::Cursor, vec};
+use std::{self, fmt, vec};
 
 use super::{
     chain_bounds::{ProtocolBlock, ProtocolBlockId, ProtocolHeader, ProtocolTransactionId},
-    nt, Connection, Handshake, Message, NodeId,
+    nt, AllowedMagics, Connection, Handshake, Message, NodeId, ProtocolMagic,
 };
 
 enum AcceptingState<T, B: property::Block, Tx: property::TransactionId> {
@@ -34,14 +33,20 @@ enum Transition<T, B: property::Block, Tx: property::TransactionId> {
 
 pub struct Accepting<T, B: property::Block, Tx: property::TransactionId> {
     state: AcceptingState<T, B, Tx>,
+    allowed_magics: AllowedMagics,
 }
 
 impl<T: AsyncRead + AsyncWrite, B: property::Block, Tx: property::TransactionId>
     Accepting<T, B, Tx>
 {
     pub fn new(inner: T) -> Self {
+        Self::with_allowed_magics(inner, AllowedMagics::any())
+    }
+
+    pub fn with_allowed_magics(inner: T, allowed: AllowedMagics) -> Self {
         Accepting {
             state: AcceptingState::NtAccepting(nt::Connection::accept(inner)),
+            allowed_magics: allowed,
         }
     }
 }
@@ -90,10 +95,8 @@ where
                         None => return Err(AcceptingError::ConnectionClosed),
                         Some(e) => {
                             if let Ok((lwcid, bytes)) = e.expect_data() {
-                                let bytes: Vec<_> = bytes.into_iter().collect();
-                                let mut de = Deserializer::from(Cursor::new(&bytes));
-                                let peer_handshake: Handshake =
-                                    de.deserialize().map_err(AcceptingError::InvalidHandshake)?;
+                                let peer_handshake: Handshake = nt::decode_cbor(&bytes)
+                                    .map_err(AcceptingError::InvalidHandshake)?;
                                 (lwcid, peer_handshake)
                             } else {
                                 return Err(AcceptingError::ExpectedHandshake);
@@ -101,6 +104,18 @@ where
                         }
                     };
                     debug!("peer sent handshake {:?} {:#?}", lwcid, peer_handshake);
+                    if !self
+                        .allowed_magics
+                        .is_allowed(&peer_handshake.protocol_magic)
+                    {
+                        warn!(
+                            "peer handshake with unexpected protocol magic {}",
+                            peer_handshake.protocol_magic
+                        );
+                        return Err(AcceptingError::ProtocolMagicRejected(
+                            peer_handshake.protocol_magic,
+                        ));
+                    }
                     Transition::ReceivedHandshake(connection)
                 }
                 AcceptingState::ExpectNodeId(ref mut connection) => {
@@ -171,6 +186,8 @@ pub enum AcceptingError {
     ExpectedNewLightWeightConnectionId,
     ExpectedHandshake,
     InvalidHandshake(cbor_event::Error),
+    /// the peer's protocol magic is not one of the allowed ones
+    ProtocolMagicRejected(ProtocolMagic),
     ExpectedNodeId,
     AlreadyConnected,
 }
@@ -212,6 +229,9 @@ impl fmt::Display for AcceptingError {
             }
             AcceptingError::ExpectedHandshake => write!(f, "expected handshake"),
             AcceptingError::InvalidHandshake(_) => write!(f, "invalid handshake"),
+            AcceptingError::ProtocolMagicRejected(magic) => {
+                write!(f, "peer's protocol magic {} is not allowed", magic)
+            }
             AcceptingError::ExpectedNodeId => write!(f, "expected node id"),
             AcceptingError::AlreadyConnected => write!(f, "already connected"),
         }
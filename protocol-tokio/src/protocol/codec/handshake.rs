@@ -49,6 +49,36 @@ impl From<u32> for ProtocolMagic {
     }
 }
 
+/// The protocol magics accepted in the handshake of a peer.
+///
+/// Nodes of different networks (mainnet, testnet...) use different protocol
+/// magics, restricting them prevents peering across networks by mistake.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct AllowedMagics(Option<Vec<ProtocolMagic>>);
+impl AllowedMagics {
+    /// accept any protocol magic
+    pub fn any() -> Self {
+        AllowedMagics(None)
+    }
+
+    /// accept only the given protocol magics
+    pub fn only<I: IntoIterator<Item = ProtocolMagic>>(magics: I) -> Self {
+        AllowedMagics(Some(magics.into_iter().collect()))
+    }
+
+    pub fn is_allowed(&self, magic: &ProtocolMagic) -> bool {
+        match &self.0 {
+            None => true,
+            Some(magics) => magics.contains(magic),
+        }
+    }
+}
+impl Default for AllowedMagics {
+    fn default() -> Self {
+        AllowedMagics::any()
+    }
+}
+
 #[derive(Debug, PartialEq, Eq, PartialOrd, Ord, Clone)]
 pub struct HandlerSpec(u16);
 impl HandlerSpec {
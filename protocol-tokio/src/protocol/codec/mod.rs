@@ -2,7 +2,7 @@ mod handshake;
 mod message;
 mod node_id;
 
-pub use self::handshake::{HandlerSpec, HandlerSpecs, Handshake, ProtocolMagic};
+pub use self::handshake::{AllowedMagics, HandlerSpec, HandlerSpecs, Handshake, ProtocolMagic};
 pub use self::message::{
     BlockHeaders, GetBlockHeaders, GetBlocks, KeepAlive, Message, MessageCode, MessageType,
     Response,
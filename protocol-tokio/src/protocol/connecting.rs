@@ -1,4 +1,4 @@
-use std::{io::Cursor, vec};
+use std::vec;
 
 use chain_core::property;
 
@@ -10,11 +10,11 @@ use futures::{
 };
 use tokio_io::{AsyncRead, AsyncWrite};
 
-use cbor_event::{self, de::Deserializer};
+use cbor_event;
 
 use super::{
     chain_bounds::{ProtocolBlock, ProtocolBlockId, ProtocolHeader, ProtocolTransactionId},
-    nt, Connection, Handshake, Message, NodeId, ProtocolMagic,
+    nt, AllowedMagics, Connection, Handshake, Message, NodeId, ProtocolMagic,
 };
 
 use std::fmt;
@@ -40,14 +40,20 @@ enum Transition<T, B: property::Block, Tx: property::TransactionId> {
 
 pub struct Connecting<T, B: property::Block, Tx: property::TransactionId> {
     state: ConnectingState<T, B, Tx>,
+    allowed_magics: AllowedMagics,
 }
 
 impl<T: AsyncRead + AsyncWrite, B: property::Block, Tx: property::TransactionId>
     Connecting<T, B, Tx>
 {
     pub fn new(inner: T, magic: ProtocolMagic) -> Self {
+        Self::with_allowed_magics(inner, magic, AllowedMagics::any())
+    }
+
+    pub fn with_allowed_magics(inner: T, magic: ProtocolMagic, allowed: AllowedMagics) -> Self {
         Connecting {
             state: ConnectingState::NtConnecting(nt::Connection::connect(inner), magic),
+            allowed_magics: allowed,
         }
     }
 }
@@ -101,10 +107,7 @@ where
                         None => return Err(ConnectingError::ConnectionClosed),
                         Some(e) => {
                             if let Ok((lwcid, bytes)) = e.expect_data() {
-                                let bytes: Vec<_> = bytes.into_iter().collect();
-                                let mut de = Deserializer::from(Cursor::new(&bytes));
-                                let peer_handshake: Handshake = de
-                                    .deserialize()
+                                let peer_handshake: Handshake = nt::decode_cbor(&bytes)
                                     .map_err(ConnectingError::InvalidHandshake)?;
                                 (lwcid, peer_handshake)
                             } else {
@@ -113,6 +116,18 @@ where
                         }
                     };
                     debug!("peer sent handshake {:?} {:#?}", lwcid, peer_handshake);
+                    if !self
+                        .allowed_magics
+                        .is_allowed(&peer_handshake.protocol_magic)
+                    {
+                        warn!(
+                            "peer handshake with unexpected protocol magic {}",
+                            peer_handshake.protocol_magic
+                        );
+                        return Err(ConnectingError::ProtocolMagicRejected(
+                            peer_handshake.protocol_magic,
+                        ));
+                    }
                     Transition::ReceivedHandshake(connection)
                 }
                 ConnectingState::ExpectNodeId(ref mut connection) => {
@@ -178,6 +193,8 @@ pub enum ConnectingError {
     ExpectedNewLightWeightConnectionId,
     ExpectedHandshake,
     InvalidHandshake(cbor_event::Error),
+    /// the peer's protocol magic is not one of the allowed ones
+    ProtocolMagicRejected(ProtocolMagic),
     ExpectedNodeId,
     AlreadyConnected,
 }
@@ -220,6 +237,9 @@ impl fmt::Display for ConnectingError {
             }
             ConnectingError::ExpectedHandshake => write!(f, "expected handshake"),
             ConnectingError::InvalidHandshake(_) => write!(f, "invalid handshake"),
+            ConnectingError::ProtocolMagicRejected(magic) => {
+                write!(f, "peer's protocol magic {} is not allowed", magic)
+            }
             ConnectingError::ExpectedNodeId => write!(f, "expected node id"),
             ConnectingError::AlreadyConnected => write!(f, "already connected"),
         }
@@ -4,6 +4,7 @@ mod codec;
 mod connecting;
 mod inbound_stream;
 mod outbound_sink;
+mod session;
 
 use chain_core::property;
 
@@ -20,14 +21,15 @@ use tokio_io::{AsyncRead, AsyncWrite};
 pub use self::accepting::{Accepting, AcceptingError};
 pub use self::chain_bounds::*;
 pub use self::codec::{
-    BlockHeaders, GetBlockHeaders, GetBlocks, HandlerSpec, HandlerSpecs, Handshake, KeepAlive,
-    Message, MessageType, NodeId, ProtocolMagic, Response,
+    AllowedMagics, BlockHeaders, GetBlockHeaders, GetBlocks, HandlerSpec, HandlerSpecs, Handshake,
+    KeepAlive, Message, MessageType, NodeId, ProtocolMagic, Response,
 };
 pub use self::connecting::{Connecting, ConnectingError};
 pub use self::inbound_stream::{Inbound, InboundError, InboundStream};
 pub use self::outbound_sink::{
     CloseLightConnection, NewLightConnection, Outbound, OutboundError, OutboundSink,
 };
+pub use self::session::{Resume, ResumePolicy, Resumed, ResumedGetBlocks, Session, SessionError};
 
 use std::marker::PhantomData;
 
@@ -109,11 +111,27 @@ where
         Connecting::new(inner, magic)
     }
 
+    /// same as `connect`, but the connection is rejected if the remote
+    /// handshake's protocol magic is not in `allowed`.
+    pub fn connect_with_allowed_magics(
+        inner: T,
+        magic: ProtocolMagic,
+        allowed: AllowedMagics,
+    ) -> Connecting<T, B, Tx> {
+        Connecting::with_allowed_magics(inner, magic, allowed)
+    }
+
     /// this function is to use when receiving inbound connection
     pub fn accept(inner: T) -> Accepting<T, B, Tx> {
         Accepting::new(inner)
     }
 
+    /// same as `accept`, but the connection is rejected if the remote
+    /// handshake's protocol magic is not in `allowed`.
+    pub fn accept_with_allowed_magics(inner: T, allowed: AllowedMagics) -> Accepting<T, B, Tx> {
+        Accepting::with_allowed_magics(inner, allowed)
+    }
+
     pub fn split(self) -> (OutboundSink<T, B, Tx>, InboundStream<T, B, Tx>) {
         let state = self.state;
         let (sink, stream) = self.connection.split();
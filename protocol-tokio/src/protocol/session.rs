@@ -0,0 +1,381 @@
+//! session resumption after the transport connection is lost
+//!
+//! When the TCP connection drops, the light connections and the `NodeId`
+//! mappings of the `ConnectionState` are lost with it. The `Session`
+//! keeps track of what the client asked for (the subscription, the
+//! `GetBlocks` requests and the last block received for each of them) so
+//! it can be replayed on a new connection: the handshake is replayed by
+//! establishing the new connection with `Connection::connect`, then
+//! `Resume::replay` re-opens the subscription and resumes the pending
+//! `GetBlocks` requests from the last received block, and
+//! `Session::resumed` records them.
+//!
+//! The client of the `network-ntt` crate keeps a `Session` for its
+//! connection, see `network_ntt::client::resume`.
+
+use chain_core::property;
+
+use futures::{future, stream, Future, Sink, Stream};
+use tokio_io::AsyncWrite;
+
+use std::{collections::BTreeMap, error, fmt};
+
+use super::{
+    chain_bounds::{ProtocolBlock, ProtocolBlockId, ProtocolHeader, ProtocolTransactionId},
+    nt, GetBlocks, KeepAlive, Message, OutboundError, OutboundSink,
+};
+
+/// What is restored on a new connection, and how many times.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ResumePolicy {
+    /// re-open the subscription connection, if the session had one
+    pub resubscribe: bool,
+    /// send again the `GetBlocks` requests not completed yet, starting
+    /// from the last block received
+    pub resume_get_blocks: bool,
+    /// give up after this number of reconnections in a row without
+    /// receiving any block (`None` to never give up)
+    pub max_reconnections: Option<u32>,
+}
+impl Default for ResumePolicy {
+    fn default() -> Self {
+        ResumePolicy {
+            resubscribe: true,
+            resume_get_blocks: true,
+            max_reconnections: Some(5),
+        }
+    }
+}
+
+#[derive(Debug)]
+pub enum SessionError {
+    /// the connection was lost too many times in a row (see
+    /// `ResumePolicy::max_reconnections`)
+    TooManyReconnections(u32),
+}
+impl fmt::Display for SessionError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            SessionError::TooManyReconnections(n) => {
+                write!(f, "connection lost {} times without progress", n)
+            }
+        }
+    }
+}
+impl error::Error for SessionError {}
+
+#[derive(Debug, Clone)]
+struct PendingBlocks<Id> {
+    from: Id,
+    to: Id,
+    last_received: Option<Id>,
+}
+
+/// The state of a client session that outlives its transport connection.
+///
+/// The client reports what it sends and receives (`subscribed`,
+/// `get_blocks_sent`, `block_received`...), calls `connection_lost` to
+/// know what needs to be replayed on the new connection, then `resumed`
+/// with what was replayed.
+#[derive(Debug)]
+pub struct Session<Id> {
+    policy: ResumePolicy,
+    subscription: Option<KeepAlive>,
+    get_blocks: BTreeMap<nt::LightWeightConnectionId, PendingBlocks<Id>>,
+    reconnections: u32,
+}
+
+impl<Id: Clone + PartialEq> Session<Id> {
+    pub fn new(policy: ResumePolicy) -> Self {
+        Session {
+            policy,
+            subscription: None,
+            get_blocks: BTreeMap::new(),
+            reconnections: 0,
+        }
+    }
+
+    pub fn policy(&self) -> &ResumePolicy {
+        &self.policy
+    }
+
+    /// the subscription connection was opened
+    pub fn subscribed(&mut self, keep_alive: KeepAlive) {
+        self.subscription = Some(keep_alive);
+    }
+
+    /// the `GetBlocks` request `from`..`to` was sent on the light
+    /// connection `lwcid`
+    pub fn get_blocks_sent(&mut self, lwcid: nt::LightWeightConnectionId, from: Id, to: Id) {
+        self.get_blocks.insert(
+            lwcid,
+            PendingBlocks {
+                from,
+                to,
+                last_received: None,
+            },
+        );
+    }
+
+    /// the block `id` was received on the light connection `lwcid`. The
+    /// request is completed once its last block is received.
+    pub fn block_received(&mut self, lwcid: nt::LightWeightConnectionId, id: Id) {
+        self.reconnections = 0;
+        let completed = match self.get_blocks.get_mut(&lwcid) {
+            None => return,
+            Some(pending) => {
+                let completed = pending.to == id;
+                pending.last_received = Some(id);
+                completed
+            }
+        };
+        if completed {
+            self.get_blocks.remove(&lwcid);
+        }
+    }
+
+    /// the request sent on `lwcid` is over (the peer replied with an
+    /// error, or the light connection was closed)
+    pub fn get_blocks_done(&mut self, lwcid: nt::LightWeightConnectionId) {
+        self.get_blocks.remove(&lwcid);
+    }
+
+    /// number of `GetBlocks` requests not completed yet
+    pub fn pending_get_blocks(&self) -> usize {
+        self.get_blocks.len()
+    }
+
+    /// the transport connection was lost: what needs to be replayed on
+    /// the new connection, according to the policy.
+    ///
+    /// The pending requests and the subscription are kept (unless the
+    /// policy does not resume them) until `resumed` reports them sent
+    /// again: if the replay fails, they are replayed on the next
+    /// connection.
+    pub fn connection_lost(&mut self) -> Result<Resume<Id>, SessionError> {
+        self.reconnections += 1;
+        if let Some(max) = self.policy.max_reconnections {
+            if self.reconnections > max {
+                return Err(SessionError::TooManyReconnections(self.reconnections));
+            }
+        }
+
+        if !self.policy.resubscribe {
+            self.subscription = None;
+        }
+        if !self.policy.resume_get_blocks {
+            self.get_blocks.clear();
+        }
+        let keep_alive = self.subscription;
+        let get_blocks = self
+            .get_blocks
+            .iter()
+            .map(|(lwcid, pending)| {
+                let from = pending.last_received.as_ref().unwrap_or(&pending.from);
+                (*lwcid, from.clone(), pending.to.clone())
+            })
+            .collect();
+        Ok(Resume {
+            keep_alive,
+            get_blocks,
+        })
+    }
+
+    /// the subscription and the requests were replayed on the new
+    /// connection (see `Resume::replay`)
+    pub fn resumed(&mut self, resumed: &Resumed<Id>) {
+        for request in resumed.get_blocks.iter() {
+            self.get_blocks.remove(&request.previous);
+            self.get_blocks_sent(request.lwcid, request.from.clone(), request.to.clone());
+        }
+    }
+}
+
+/// What to replay on a new connection (see `Session::connection_lost`).
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Resume<Id> {
+    /// the keep alive of the subscription to re-open, if any
+    pub keep_alive: Option<KeepAlive>,
+    /// the `(lwcid, from, to)` of the `GetBlocks` requests to send
+    /// again, `lwcid` being the light connection they were sent on. `from`
+    /// is the last block received, the peer sends it again.
+    pub get_blocks: Vec<(nt::LightWeightConnectionId, Id, Id)>,
+}
+
+/// The subscription and requests sent by `Resume::replay`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Resumed<Id> {
+    pub subscription: Option<nt::LightWeightConnectionId>,
+    pub get_blocks: Vec<ResumedGetBlocks<Id>>,
+}
+
+/// A `GetBlocks` request sent again on the new connection
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ResumedGetBlocks<Id> {
+    /// the light connection the request was sent on before, on the lost
+    /// connection
+    pub previous: nt::LightWeightConnectionId,
+    /// the light connection of the new connection the request was sent on
+    pub lwcid: nt::LightWeightConnectionId,
+    pub from: Id,
+    pub to: Id,
+}
+
+impl<Id: ProtocolBlockId> Resume<Id> {
+    /// re-open the subscription and send the `GetBlocks` requests, each on
+    /// a new light connection (closed once the request is sent, as
+    /// `OutboundSink` users do), on the given newly established connection.
+    pub fn replay<T, B, Tx>(
+        self,
+        sink: OutboundSink<T, B, Tx>,
+    ) -> impl Future<Item = (Resumed<Id>, OutboundSink<T, B, Tx>), Error = OutboundError>
+    where
+        T: AsyncWrite,
+        B: ProtocolBlock + property::Block<Id = Id>,
+        Tx: ProtocolTransactionId,
+        <B as property::HasHeader>::Header: ProtocolHeader,
+    {
+        let Resume {
+            keep_alive,
+            get_blocks,
+        } = self;
+        let subscribed = match keep_alive {
+            None => future::Either::A(future::ok((None, sink))),
+            Some(keep_alive) => future::Either::B(
+                sink.subscribe(keep_alive)
+                    .map(|(lwcid, sink)| (Some(lwcid), sink)),
+            ),
+        };
+        subscribed.and_then(move |(subscription, sink)| {
+            stream::iter_ok::<_, OutboundError>(get_blocks)
+                .fold(
+                    (sink, Vec::new()),
+                    |(sink, mut sent), (previous, from, to)| {
+                        sink.new_light_connection().and_then(move |(lwcid, sink)| {
+                            let request = GetBlocks::new(from.clone(), to.clone());
+                            sent.push(ResumedGetBlocks {
+                                previous,
+                                lwcid,
+                                from,
+                                to,
+                            });
+                            sink.send(Message::GetBlocks(lwcid, request))
+                                .and_then(move |sink| sink.close_light_connection(lwcid))
+                                .map(move |sink| (sink, sent))
+                        })
+                    },
+                )
+                .map(move |(sink, get_blocks)| {
+                    (
+                        Resumed {
+                            subscription,
+                            get_blocks,
+                        },
+                        sink,
+                    )
+                })
+        })
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn lwcid(n: u32) -> nt::LightWeightConnectionId {
+        let mut id = nt::LightWeightConnectionId::first_non_reserved();
+        for _ in 0..n {
+            id.next();
+        }
+        id
+    }
+
+    #[test]
+    fn resume_from_last_received_block() {
+        let mut session = Session::new(ResumePolicy::default());
+        session.subscribed(true);
+        session.get_blocks_sent(lwcid(1), 10u64, 20);
+        session.get_blocks_sent(lwcid(2), 30u64, 40);
+        session.get_blocks_sent(lwcid(3), 50u64, 60);
+
+        session.block_received(lwcid(1), 10);
+        session.block_received(lwcid(1), 11);
+        // the second request is completed
+        session.block_received(lwcid(2), 40);
+        assert_eq!(session.pending_get_blocks(), 2);
+
+        let resume = session.connection_lost().unwrap();
+        assert_eq!(
+            resume,
+            Resume {
+                keep_alive: Some(true),
+                get_blocks: vec![(lwcid(1), 11, 20), (lwcid(3), 50, 60)],
+            }
+        );
+        // until they are sent again
+        assert_eq!(session.pending_get_blocks(), 2);
+
+        // the replayed requests are pending again, on their new light
+        // connections
+        session.resumed(&Resumed {
+            subscription: Some(lwcid(4)),
+            get_blocks: vec![
+                ResumedGetBlocks {
+                    previous: lwcid(1),
+                    lwcid: lwcid(5),
+                    from: 11,
+                    to: 20,
+                },
+                ResumedGetBlocks {
+                    previous: lwcid(3),
+                    lwcid: lwcid(6),
+                    from: 50,
+                    to: 60,
+                },
+            ],
+        });
+        assert_eq!(session.pending_get_blocks(), 2);
+        session.block_received(lwcid(6), 60);
+        assert_eq!(session.pending_get_blocks(), 1);
+
+        // the subscription is still replayed if the new connection is
+        // lost too
+        let resume = session.connection_lost().unwrap();
+        assert_eq!(
+            resume,
+            Resume {
+                keep_alive: Some(true),
+                get_blocks: vec![(lwcid(5), 11, 20)],
+            }
+        );
+    }
+
+    #[test]
+    fn give_up_without_progress() {
+        let policy = ResumePolicy {
+            resubscribe: false,
+            resume_get_blocks: true,
+            max_reconnections: Some(2),
+        };
+        let mut session = Session::new(policy);
+        session.subscribed(false);
+        session.get_blocks_sent(lwcid(1), 1u64, 5);
+
+        let resume = session.connection_lost().unwrap();
+        assert_eq!(resume.keep_alive, None);
+        assert_eq!(resume.get_blocks, vec![(lwcid(1), 1, 5)]);
+
+        session.get_blocks_sent(lwcid(2), 1, 5);
+        assert!(session.connection_lost().is_ok());
+
+        // receiving a block resets the count
+        session.get_blocks_sent(lwcid(3), 1, 5);
+        session.block_received(lwcid(3), 1);
+        assert!(session.connection_lost().is_ok());
+        assert!(session.connection_lost().is_ok());
+        match session.connection_lost() {
+            Err(SessionError::TooManyReconnections(3)) => {}
+            r => panic!("unexpected result {:?}", r),
+        }
+    }
+}
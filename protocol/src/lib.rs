@@ -1,4 +1,5 @@
 extern crate cardano;
+extern crate protocol_tokio;
 #[macro_use]
 extern crate log;
 #[macro_use]
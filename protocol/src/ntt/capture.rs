@@ -0,0 +1,36 @@
+//! Capture and replay of the network transport traffic.
+//!
+//! The implementation is shared with the asynchronous protocol, see
+//! `protocol_tokio::network_transport::capture`. To capture the traffic of
+//! a connection, give the `Capture` to `Connection::handshake` instead of
+//! the stream itself.
+
+pub use protocol_tokio::network_transport::capture::{
+    Capture, Direction, Reader, Record, Replay, Writer, MAGIC,
+};
+
+#[cfg(test)]
+mod tests {
+    use super::super::Connection;
+    use super::*;
+    use std::io;
+
+    #[test]
+    fn capture_and_replay_handshake() {
+        // a peer accepting the handshake
+        let peer = Replay::new(vec![Record::new(Direction::Inbound, vec![0, 0, 0, 0])]);
+        let stream = Capture::new(peer, Writer::new(Vec::new()).unwrap());
+        let conn = Connection::handshake(0, stream).unwrap();
+        let capture = conn.get_backend().writer().get_ref().clone();
+
+        let reader = Reader::new(&capture[..]).unwrap();
+        let records = reader.collect::<io::Result<Vec<_>>>().unwrap();
+        assert_eq!(records.first().unwrap().direction, Direction::Outbound);
+        assert_eq!(records.last().unwrap().direction, Direction::Inbound);
+
+        let replay = Replay::from_reader(Reader::new(&capture[..]).unwrap()).unwrap();
+        let conn = Connection::handshake(0, replay).unwrap();
+        assert_eq!(conn.get_backend().sent(), conn.get_backend().expected());
+        assert_eq!(conn.get_backend().diverged(), None);
+    }
+}
@@ -1,3 +1,4 @@
+pub mod capture;
 pub mod protocol;
 
 use cardano::util::hex;
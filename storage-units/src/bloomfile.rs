@@ -0,0 +1,228 @@
+//! Bloom file format
+//!
+//! A bloom file is a bloom filter of all the block hashes stored in a set of
+//! packs. It answers quickly whether a hash is definitely not in any of the
+//! packs, without probing the index file of each pack:
+//!
+//! MAGIC (16 Bytes)
+//! NUMBER OF PACKS (4 bytes BE)
+//! NUMBER OF HASHES (4 bytes BE)
+//! BLOOM SIZE (4 bytes BE)
+//! 0-PADDING (4 bytes)
+//! PACK HASHES (NUMBER OF PACKS * 32 bytes, sorted)
+//! BLOOM FILTER (BLOOM_SIZE bytes)
+//!
+//! The pack hashes allow to detect a bloom file not covering exactly the
+//! packs of the storage (e.g. a pack was added or replaced since it was
+//! written), which then needs to be rebuilt. The number of hashes allows to
+//! detect a filter too small for the number of hashes added since it was
+//! created.
+
+use hash::{BlockHash, PackHash, HASH_SIZE};
+use std::collections::BTreeSet;
+use std::io::{Read, Write};
+use std::iter::repeat;
+use utils::bloom;
+use utils::error::{Result, StorageError};
+use utils::magic;
+use utils::serialize::{read_size, write_size};
+
+const FILE_TYPE: magic::FileType = 0x424c4f4d; // = BLOM
+const VERSION: magic::Version = 2;
+
+const HEADER_SIZE: usize = 16;
+
+/// number of bits of the filter per hash, to keep the false positives low
+const BITS_PER_ENTRY: usize = 10;
+
+/// upper bound of the filter size accepted when reading a bloom file, so a
+/// corrupted header does not make us allocate an arbitrary amount of memory
+/// (1GB is enough for over 800 millions hashes).
+const MAX_BLOOM_SIZE: u32 = 1 << 30;
+
+// the size (in bytes) of the bloom filter for the given number of expected
+// entries.
+pub fn default_bloom_size(entries: usize) -> u32 {
+    let bytes = (entries * BITS_PER_ENTRY + 7) / 8;
+    bytes.max(4096).next_power_of_two() as u32
+}
+
+pub struct Bloom {
+    packs: BTreeSet<PackHash>,
+    entries: u32,
+    bitmap: Vec<u8>,
+}
+
+impl Bloom {
+    /// create an empty filter, sized for `expected_entries` hashes
+    pub fn new(expected_entries: usize) -> Self {
+        let size = default_bloom_size(expected_entries);
+        Bloom {
+            packs: BTreeSet::new(),
+            entries: 0,
+            bitmap: repeat(0).take(size as usize).collect(),
+        }
+    }
+
+    /// add the hashes of the pack `packhash` to the filter
+    pub fn add_pack<'a, I>(&mut self, packhash: &PackHash, hashes: I)
+    where
+        I: IntoIterator<Item = &'a BlockHash>,
+    {
+        for hash in hashes {
+            bloom::set(&mut self.bitmap[..], hash);
+            self.entries += 1;
+        }
+        self.packs.insert(*packhash);
+    }
+
+    /// false if the hash is definitely not in any of the packs
+    pub fn search(&self, blk: &BlockHash) -> bool {
+        bloom::is_set(&self.bitmap[..], blk)
+    }
+
+    /// the packs added to the filter
+    pub fn packs(&self) -> &BTreeSet<PackHash> {
+        &self.packs
+    }
+
+    /// the filter holds the hashes of exactly the given packs
+    pub fn covers<'a, I>(&self, packs: I) -> bool
+    where
+        I: IntoIterator<Item = &'a PackHash>,
+    {
+        let packs: BTreeSet<&PackHash> = packs.into_iter().collect();
+        self.packs.iter().eq(packs.into_iter())
+    }
+
+    /// number of hashes added to the filter
+    pub fn entries(&self) -> u32 {
+        self.entries
+    }
+
+    pub fn len(&self) -> usize {
+        self.bitmap.len()
+    }
+
+    /// the filter holds more hashes than it was sized for: it needs to be
+    /// rebuilt with a bigger size to keep the false positives low.
+    pub fn is_saturated(&self) -> bool {
+        default_bloom_size(self.entries as usize) as usize > self.bitmap.len()
+    }
+
+    pub fn write<W: Write>(&self, file: &mut W) -> Result<()> {
+        magic::write_header(file, FILE_TYPE, VERSION)?;
+
+        let mut hdr_buf = [0u8; HEADER_SIZE];
+        write_size(&mut hdr_buf[0..4], self.packs.len() as u32);
+        write_size(&mut hdr_buf[4..8], self.entries);
+        write_size(&mut hdr_buf[8..12], self.bitmap.len() as u32);
+        write_size(&mut hdr_buf[12..16], 0);
+        file.write_all(&hdr_buf)?;
+        for packhash in self.packs.iter() {
+            file.write_all(&packhash[..])?;
+        }
+        file.write_all(&self.bitmap[..])?;
+        Ok(())
+    }
+
+    pub fn read<R: Read>(file: &mut R) -> Result<Self> {
+        magic::check_header(file, FILE_TYPE, VERSION, VERSION)?;
+
+        let mut hdr_buf = [0u8; HEADER_SIZE];
+        file.read_exact(&mut hdr_buf)?;
+        let packs = read_size(&hdr_buf[0..4]);
+        let entries = read_size(&hdr_buf[4..8]);
+        let size = read_size(&hdr_buf[8..12]);
+        if size > MAX_BLOOM_SIZE {
+            return Err(StorageError::Corrupted("bloom filter size too big"));
+        }
+
+        let mut packhashes = BTreeSet::new();
+        for _ in 0..packs {
+            let mut packhash = [0u8; HASH_SIZE];
+            file.read_exact(&mut packhash)?;
+            packhashes.insert(packhash);
+        }
+
+        let mut bitmap: Vec<u8> = repeat(0).take(size as usize).collect();
+        file.read_exact(&mut bitmap[..])?;
+        Ok(Bloom {
+            packs: packhashes,
+            entries: entries,
+            bitmap: bitmap,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::Cursor;
+
+    #[test]
+    fn search_added_hashes() {
+        let pack1 = vec![[1u8; HASH_SIZE], [2u8; HASH_SIZE]];
+        let pack2 = vec![[3u8; HASH_SIZE]];
+        let mut bloom = Bloom::new(3);
+        bloom.add_pack(&[2u8; HASH_SIZE], pack1.iter());
+        bloom.add_pack(&[1u8; HASH_SIZE], pack2.iter());
+        assert_eq!(bloom.packs().len(), 2);
+        assert!(bloom.covers(&[[1u8; HASH_SIZE], [2u8; HASH_SIZE]]));
+        assert!(bloom.covers(&[[2u8; HASH_SIZE], [1u8; HASH_SIZE]]));
+        assert!(!bloom.covers(&[[1u8; HASH_SIZE]]));
+        assert!(!bloom.covers(&[[1u8; HASH_SIZE], [3u8; HASH_SIZE]]));
+        assert_eq!(bloom.entries(), 3);
+        assert!(!bloom.is_saturated());
+        for hash in pack1.iter().chain(pack2.iter()) {
+            assert!(bloom.search(hash));
+        }
+        assert!(!bloom.search(&[4u8; HASH_SIZE]));
+    }
+
+    #[test]
+    fn write_read() {
+        let mut bloom = Bloom::new(0);
+        bloom.add_pack(&[8u8; HASH_SIZE], [[7u8; HASH_SIZE]].iter());
+
+        let mut buf = Vec::new();
+        bloom.write(&mut buf).unwrap();
+        assert_eq!(
+            buf.len(),
+            magic::HEADER_SIZE + HEADER_SIZE + HASH_SIZE + bloom.len()
+        );
+
+        let read = Bloom::read(&mut Cursor::new(buf)).unwrap();
+        assert!(read.covers(&[[8u8; HASH_SIZE]]));
+        assert_eq!(read.entries(), 1);
+        assert_eq!(read.len(), bloom.len());
+        assert!(read.search(&[7u8; HASH_SIZE]));
+    }
+
+    #[test]
+    fn corrupt_bloom_size() {
+        let mut buf = Vec::new();
+        Bloom::new(0).write(&mut buf).unwrap();
+        let size_offset = magic::HEADER_SIZE + 8;
+        write_size(&mut buf[size_offset..size_offset + 4], 0xffff_ffff);
+        match Bloom::read(&mut Cursor::new(buf)) {
+            Err(StorageError::Corrupted(_)) => {}
+            Err(err) => panic!("unexpected error: {}", err),
+            Ok(_) => panic!("read a bloom file with a corrupted size"),
+        }
+    }
+
+    #[test]
+    fn saturation() {
+        let hashes: Vec<BlockHash> = (0..4000u32)
+            .map(|i| {
+                let mut hash = [0u8; HASH_SIZE];
+                write_size(&mut hash[0..4], i);
+                hash
+            })
+            .collect();
+        let mut bloom = Bloom::new(0);
+        bloom.add_pack(&[0u8; HASH_SIZE], hashes.iter());
+        assert!(bloom.is_saturated());
+    }
+}
@@ -22,10 +22,13 @@
 //!
 
 use hash::{BlockHash, HASH_SIZE};
+use std::collections::VecDeque;
 use std::fs;
 use std::io::{Read, Seek, SeekFrom, Write};
 use std::iter::repeat;
 use std::path::Path;
+#[cfg(feature = "index-lookup-cache")]
+use std::sync::Mutex;
 use utils::bloom;
 use utils::error::Result;
 use utils::magic;
@@ -67,6 +70,8 @@ pub struct Lookup {
     pub params: Params,
     pub fanout: Fanout,
     pub bloom: Bloom,
+    #[cfg(feature = "index-lookup-cache")]
+    recent_searches: Mutex<SearchCache>,
 }
 
 pub struct Fanout([u32; FANOUT_ELEMENTS]);
@@ -110,6 +115,60 @@ impl Bloom {
     }
 }
 
+/// number of recent searches remembered by a `Lookup` when the
+/// `index-lookup-cache` feature is enabled.
+pub const DEFAULT_SEARCH_CACHE_SIZE: usize = 64;
+
+/// A small LRU of the results of the recent searches in an index file.
+///
+/// The index files are immutable: the result of a search (the index offset
+/// of the hash, or the hash not being in the file) never needs to be
+/// invalidated.
+pub struct SearchCache {
+    capacity: usize,
+    entries: VecDeque<(BlockHash, Option<IndexOffset>)>,
+}
+
+impl SearchCache {
+    pub fn new(capacity: usize) -> Self {
+        SearchCache {
+            capacity: capacity,
+            entries: VecDeque::with_capacity(capacity),
+        }
+    }
+
+    pub fn capacity(&self) -> usize {
+        self.capacity
+    }
+
+    pub fn len(&self) -> usize {
+        self.entries.len()
+    }
+
+    /// the result of the search of the given hash if it is one of the
+    /// recent searches, the entry then becomes the most recent one.
+    pub fn get(&mut self, blk: &BlockHash) -> Option<Option<IndexOffset>> {
+        let pos = self.entries.iter().position(|(hash, _)| hash == blk)?;
+        let entry = self.entries.remove(pos)?;
+        let result = entry.1;
+        self.entries.push_front(entry);
+        Some(result)
+    }
+
+    /// remember the result of the search of the given hash, forgetting the
+    /// least recent search if the cache is full.
+    pub fn insert(&mut self, blk: &BlockHash, result: Option<IndexOffset>) {
+        if self.capacity == 0 {
+            return;
+        }
+        if let Some(pos) = self.entries.iter().position(|(hash, _)| hash == blk) {
+            self.entries.remove(pos);
+        }
+        self.entries.push_front((*blk, result));
+        self.entries.truncate(self.capacity);
+    }
+}
+
 // the default size (in bytes) of the bloom filter related to the number of
 // expected entries in the files.
 pub fn default_bloom_size(entries: usize) -> u32 {
@@ -205,6 +264,8 @@ impl Index {
             params: params,
             fanout: fanout,
             bloom: Bloom(bloom),
+            #[cfg(feature = "index-lookup-cache")]
+            recent_searches: Mutex::new(SearchCache::new(DEFAULT_SEARCH_CACHE_SIZE)),
         })
     }
 }
@@ -232,8 +293,39 @@ impl Lookup {
             },
             fanout: Fanout(fanout),
             bloom: Bloom(bloom),
+            #[cfg(feature = "index-lookup-cache")]
+            recent_searches: Mutex::new(SearchCache::new(DEFAULT_SEARCH_CACHE_SIZE)),
         })
     }
+
+    /// the result of a recent search of the given hash in the index file,
+    /// if any (always `None` without the `index-lookup-cache` feature).
+    #[cfg(feature = "index-lookup-cache")]
+    pub fn recent_search(&self, blk: &BlockHash) -> Option<Option<IndexOffset>> {
+        self.recent_searches.lock().ok()?.get(blk)
+    }
+    #[cfg(not(feature = "index-lookup-cache"))]
+    pub fn recent_search(&self, _blk: &BlockHash) -> Option<Option<IndexOffset>> {
+        None
+    }
+
+    /// remember the result of the search of the given hash in the index
+    /// file (does nothing without the `index-lookup-cache` feature).
+    #[cfg(feature = "index-lookup-cache")]
+    pub fn remember_search(&self, blk: &BlockHash, result: Option<IndexOffset>) {
+        if let Ok(mut cache) = self.recent_searches.lock() {
+            cache.insert(blk, result)
+        }
+    }
+    #[cfg(not(feature = "index-lookup-cache"))]
+    pub fn remember_search(&self, _blk: &BlockHash, _result: Option<IndexOffset>) {}
+
+    /// change the number of recent searches remembered (`0` disables the
+    /// cache), forgetting the current ones.
+    #[cfg(feature = "index-lookup-cache")]
+    pub fn set_search_cache_size(&mut self, capacity: usize) {
+        self.recent_searches = Mutex::new(SearchCache::new(capacity));
+    }
 }
 
 pub fn write_offsets_to_file<'a, I: Iterator<Item = &'a Offset>>(
@@ -271,7 +363,8 @@ pub fn dump_file(file: &mut fs::File) -> Result<(Lookup, Vec<BlockHash>)> {
     let mut v = Vec::new();
     let FanoutTotal(total) = lookup.fanout.get_total();
 
-    file.seek(SeekFrom::Start(HEADER_SIZE as u64)).unwrap();
+    file.seek(SeekFrom::Start(offset_hashes(lookup.params.bloom_size)))
+        .unwrap();
     for _ in 0..total {
         let h = file_read_hash(file);
         v.push(h);
@@ -377,3 +470,35 @@ impl Reader<fs::File> {
         file_read_offset(&mut self.handle)
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn search_cache_lru() {
+        let mut cache = SearchCache::new(2);
+        let (a, b, c) = ([1u8; HASH_SIZE], [2u8; HASH_SIZE], [3u8; HASH_SIZE]);
+
+        assert_eq!(cache.get(&a), None);
+        cache.insert(&a, Some(0));
+        cache.insert(&b, None);
+        assert_eq!(cache.get(&a), Some(Some(0)));
+        assert_eq!(cache.get(&b), Some(None));
+
+        // `a` is now the least recently used search
+        cache.insert(&c, Some(12));
+        assert_eq!(cache.len(), 2);
+        assert_eq!(cache.get(&a), None);
+        assert_eq!(cache.get(&b), Some(None));
+        assert_eq!(cache.get(&c), Some(Some(12)));
+    }
+
+    #[test]
+    fn search_cache_disabled() {
+        let mut cache = SearchCache::new(0);
+        cache.insert(&[1u8; HASH_SIZE], Some(0));
+        assert_eq!(cache.len(), 0);
+        assert_eq!(cache.get(&[1u8; HASH_SIZE]), None);
+    }
+}
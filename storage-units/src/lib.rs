@@ -2,6 +2,7 @@ extern crate cryptoxide;
 extern crate rand;
 
 pub mod append;
+pub mod bloomfile;
 pub mod hash;
 pub mod indexfile;
 pub mod packfile;
@@ -6,22 +6,52 @@
 //! DATA (SIZE bytes)
 //! OPTIONAL ALIGNMENT? (of 0 to 3 bytes depending on SIZE)
 //!
+//! Version 2 packs use the same entry framing, and are terminated by a
+//! footer allowing the pack to be validated and iterated backwards without
+//! the separate index file:
+//!
+//! END OF ENTRIES MARKER (4 bytes: 0xffffffff)
+//! MINI INDEX: for each entry in pack order
+//!     BLOCK HASH (32 bytes)
+//!     OFFSET of the entry (8 bytes BE)
+//! ENTRY COUNT (4 bytes BE)
+//! TOTAL SIZE (8 bytes BE): offset of the end of entries marker
+//! FOOTER MAGIC (4 bytes: PKFT)
+//!
+//! The trailing 16 bytes are at a fixed position from the end of the file,
+//! so a seekable reader can locate the mini index directly.
+//!
+//! Version 1 packs have no footer and are still readable.
+//!
 use cryptoxide::blake2b;
 use cryptoxide::digest::Digest;
 use hash::{BlockHash, PackHash, HASH_SIZE};
 use indexfile;
 use std::fs;
 use std::io;
-use std::io::{Read, Seek, SeekFrom};
+use std::io::{Read, Seek, SeekFrom, Write};
 use std::iter::repeat;
 use std::path::Path;
-use utils::error::Result;
+use utils::error::{Result, StorageError};
 use utils::magic;
-use utils::serialize::{io::write_length_prefixed, offset_align4, read_size, Offset, SIZE_SIZE};
+use utils::serialize::{
+    io::write_length_prefixed, offset_align4, read_offset, read_size, write_offset, write_size,
+    Offset, Size, OFF_SIZE, SIZE_SIZE,
+};
 use utils::tmpfile::TmpFile;
 
 const FILE_TYPE: magic::FileType = 0x5041434b; // = PACK
-const VERSION: magic::Version = 1;
+const VERSION_1: magic::Version = 1;
+const VERSION_2: magic::Version = 2;
+const VERSION: magic::Version = VERSION_2;
+
+const END_OF_ENTRIES: Size = 0xffff_ffff;
+const FOOTER_MAGIC: Size = 0x504b4654; // = PKFT
+const MINI_INDEX_ENTRY_SIZE: usize = HASH_SIZE + OFF_SIZE;
+const TRAILER_SIZE: usize = SIZE_SIZE + OFF_SIZE + SIZE_SIZE;
+/// bound of the size of an entry, so a corrupt size does not make us
+/// allocate all the memory
+const MAX_BLOCK_SIZE: Size = 20_000_000;
 
 /// A Stream Reader that also computes the hash of the sum of all data read
 pub struct Reader<R> {
@@ -33,6 +63,99 @@ pub struct Reader<R> {
 /// A pack reader that can seek in a packfile
 pub struct Seeker<R> {
     handle: R,
+    version: magic::Version,
+}
+
+/// The footer of a version 2 pack
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Footer {
+    /// offset of the end of entries marker, which is also the size of
+    /// the header and all the entries
+    pub total_size: Offset,
+    /// block hashes and offsets of the entries, in pack order
+    pub entries: Vec<(BlockHash, Offset)>,
+}
+
+impl Footer {
+    pub fn nb_entries(&self) -> usize {
+        self.entries.len()
+    }
+
+    /// Check the internal consistency of the footer: offsets need to be
+    /// strictly increasing, aligned, and located between the header and the
+    /// end of entries.
+    pub fn validate(&self) -> Result<()> {
+        let mut min = magic::HEADER_SIZE as Offset;
+        for (_, ofs) in self.entries.iter() {
+            if *ofs < min || *ofs % 4 != 0 || *ofs >= self.total_size {
+                return Err(StorageError::Corrupted("invalid pack footer entry offset"));
+            }
+            min = ofs + SIZE_SIZE as Offset;
+        }
+        Ok(())
+    }
+
+    fn write<W: Write>(&self, w: &mut W) -> io::Result<()> {
+        let mut buf = [0u8; SIZE_SIZE];
+        write_size(&mut buf, END_OF_ENTRIES);
+        w.write_all(&buf)?;
+        for (hash, ofs) in self.entries.iter() {
+            let mut ofs_buf = [0u8; OFF_SIZE];
+            write_offset(&mut ofs_buf, *ofs);
+            w.write_all(&hash[..])?;
+            w.write_all(&ofs_buf)?;
+        }
+        let mut trailer = [0u8; TRAILER_SIZE];
+        write_size(&mut trailer[0..4], self.entries.len() as Size);
+        write_offset(&mut trailer[4..12], self.total_size);
+        write_size(&mut trailer[12..16], FOOTER_MAGIC);
+        w.write_all(&trailer)
+    }
+
+    fn read<R: Read + Seek>(r: &mut R) -> Result<Self> {
+        let file_size = r.seek(SeekFrom::End(0))?;
+        if file_size < (magic::HEADER_SIZE + SIZE_SIZE + TRAILER_SIZE) as u64 {
+            return Err(StorageError::Corrupted(
+                "pack too small to contain a footer",
+            ));
+        }
+        r.seek(SeekFrom::End(-(TRAILER_SIZE as i64)))?;
+        let mut trailer = [0u8; TRAILER_SIZE];
+        r.read_exact(&mut trailer)?;
+        if read_size(&trailer[12..16]) != FOOTER_MAGIC {
+            return Err(StorageError::Corrupted("missing pack footer magic"));
+        }
+        let nb_entries = read_size(&trailer[0..4]) as u64;
+        let total_size = read_offset(&trailer[4..12]);
+
+        let expected_size = total_size
+            .checked_add(SIZE_SIZE as u64)
+            .and_then(|v| v.checked_add(nb_entries * MINI_INDEX_ENTRY_SIZE as u64))
+            .and_then(|v| v.checked_add(TRAILER_SIZE as u64));
+        if expected_size != Some(file_size) {
+            return Err(StorageError::Corrupted("pack footer size mismatch"));
+        }
+
+        r.seek(SeekFrom::Start(total_size))?;
+        let mut buf = [0u8; SIZE_SIZE];
+        r.read_exact(&mut buf)?;
+        if read_size(&buf) != END_OF_ENTRIES {
+            return Err(StorageError::Corrupted("missing end of entries marker"));
+        }
+
+        let mut entries = Vec::with_capacity(nb_entries as usize);
+        for _ in 0..nb_entries {
+            let mut entry = [0u8; MINI_INDEX_ENTRY_SIZE];
+            r.read_exact(&mut entry)?;
+            let mut hash = [0u8; HASH_SIZE];
+            hash.clone_from_slice(&entry[0..HASH_SIZE]);
+            entries.push((hash, read_offset(&entry[HASH_SIZE..])));
+        }
+        Ok(Footer {
+            total_size,
+            entries,
+        })
+    }
 }
 
 impl Reader<fs::File> {
@@ -50,7 +173,7 @@ impl<R> Reader<R> {
 
 impl<R: Read> Reader<R> {
     pub fn init(mut r: R) -> Result<Self> {
-        magic::check_header(&mut r, FILE_TYPE, VERSION, VERSION)?;
+        magic::check_header(&mut r, FILE_TYPE, VERSION_1, VERSION)?;
         let ctxt = blake2b::Blake2b::new(HASH_SIZE);
         Ok(Reader {
             reader: r,
@@ -62,28 +185,53 @@ impl<R: Read> Reader<R> {
 
 impl Seeker<fs::File> {
     pub fn init<P: AsRef<Path>>(path: P) -> Result<Self> {
-        let mut file = fs::File::open(path)?;
-        magic::check_header(&mut file, FILE_TYPE, VERSION, VERSION)?;
-        Ok(Seeker::from(file))
+        let file = fs::File::open(path)?;
+        Seeker::new(file)
     }
 }
 
+impl<R: Read + Seek> Seeker<R> {
+    /// Create a seeker over a pack, checking its header to know the
+    /// version of the pack. The handle is left after the header.
+    pub fn new(mut handle: R) -> Result<Self> {
+        handle.seek(SeekFrom::Start(0))?;
+        let version = magic::check_header(&mut handle, FILE_TYPE, VERSION_1, VERSION)?;
+        Ok(Seeker { handle, version })
+    }
+}
+
+/// Wrap a handle already positioned on an entry. The header is not read,
+/// so the pack is taken for a version 1 pack (without footer): prefer
+/// `Seeker::new`.
 impl<R: Seek> From<R> for Seeker<R> {
     fn from(handle: R) -> Self {
-        Seeker { handle }
+        Seeker {
+            handle,
+            version: VERSION_1,
+        }
     }
 }
 
-// a block in a pack file is:
-// * a 32 bit size in big endian
-// * data of the size above
-// * 0 to 3 bytes of 0-alignment to make sure the next block is aligned
-pub fn read_next_block<R: Read>(mut file: R) -> io::Result<Vec<u8>> {
+// read the size of the next entry, returning None on the end of entries marker
+fn read_next_size<R: Read>(file: &mut R) -> io::Result<Option<Size>> {
     let mut sz_buf = [0u8; SIZE_SIZE];
     file.read_exact(&mut sz_buf)?;
     let sz = read_size(&sz_buf);
+    if sz == END_OF_ENTRIES {
+        Ok(None)
+    } else {
+        Ok(Some(sz))
+    }
+}
+
+fn read_block_data<R: Read>(mut file: R, sz: Size) -> io::Result<Vec<u8>> {
     // don't potentially consume all memory when reading a corrupt file
-    assert!(sz < 20000000, "read block of size: {}", sz);
+    if sz >= MAX_BLOCK_SIZE {
+        return Err(io::Error::new(
+            io::ErrorKind::InvalidData,
+            format!("pack entry too big ({} bytes)", sz),
+        ));
+    }
     let mut v: Vec<u8> = repeat(0).take(sz as usize).collect();
     file.read_exact(v.as_mut_slice())?;
     if (v.len() % 4) != 0 {
@@ -94,9 +242,24 @@ pub fn read_next_block<R: Read>(mut file: R) -> io::Result<Vec<u8>> {
     Ok(v)
 }
 
-// same as read_next_block, but when receiving EOF it will wrapped into returning None
-pub fn read_next_block_or_eof<R: Read>(file: R) -> io::Result<Option<Vec<u8>>> {
-    match read_next_block(file) {
+// a block in a pack file is:
+// * a 32 bit size in big endian
+// * data of the size above
+// * 0 to 3 bytes of 0-alignment to make sure the next block is aligned
+pub fn read_next_block<R: Read>(mut file: R) -> io::Result<Vec<u8>> {
+    match read_next_size(&mut file)? {
+        None => Err(io::Error::new(
+            io::ErrorKind::InvalidData,
+            "end of pack entries reached",
+        )),
+        Some(sz) => read_block_data(file, sz),
+    }
+}
+
+// same as read_next_block, but when receiving EOF or the end of entries
+// marker it will wrapped into returning None
+pub fn read_next_block_or_eof<R: Read>(mut file: R) -> io::Result<Option<Vec<u8>>> {
+    match read_next_size(&mut file) {
         Err(err) => {
             if err.kind() == io::ErrorKind::UnexpectedEof {
                 Ok(None)
@@ -104,7 +267,8 @@ pub fn read_next_block_or_eof<R: Read>(file: R) -> io::Result<Option<Vec<u8>>> {
                 Err(err)
             }
         }
-        Ok(data) => Ok(Some(data)),
+        Ok(None) => Ok(None),
+        Ok(Some(sz)) => read_block_data(file, sz).map(Some),
     }
 }
 
@@ -132,6 +296,13 @@ impl<R: Read> Reader<R> {
     }
 }
 
+impl<S> Seeker<S> {
+    /// version of the pack format, as found in the header
+    pub fn version(&self) -> magic::Version {
+        self.version
+    }
+}
+
 impl<S: Read + Seek> Seeker<S> {
     /// Return the next data chunk if it exists
     /// on file. On EOF, None is returned.
@@ -146,6 +317,70 @@ impl<S: Read + Seek> Seeker<S> {
         self.handle.seek(SeekFrom::Start(ofs))?;
         read_next_block(&mut self.handle)
     }
+
+    /// Read the footer of the pack. Version 1 packs have no footer,
+    /// in which case `None` is returned.
+    pub fn footer(&mut self) -> Result<Option<Footer>> {
+        if self.version < VERSION_2 {
+            return Ok(None);
+        }
+        let footer = Footer::read(&mut self.handle)?;
+        Ok(Some(footer))
+    }
+
+    /// Check that the footer is consistent and that every entry it
+    /// describes can be read, returning the footer.
+    ///
+    /// This doesn't verify the block hashes as the pack is content-agnostic.
+    pub fn validate(&mut self) -> Result<Option<Footer>> {
+        let footer = match self.footer()? {
+            None => return Ok(None),
+            Some(footer) => footer,
+        };
+        footer.validate()?;
+        let mut expected = magic::HEADER_SIZE as Offset;
+        for (_, ofs) in footer.entries.iter() {
+            if *ofs != expected {
+                return Err(StorageError::Corrupted("pack entries are not contiguous"));
+            }
+            let data = self.block_at_offset(*ofs)?;
+            expected = ofs + SIZE_SIZE as Offset + offset_align4(data.len() as u64);
+        }
+        if expected != footer.total_size {
+            return Err(StorageError::Corrupted(
+                "pack entries do not match total size",
+            ));
+        }
+        Ok(Some(footer))
+    }
+
+    /// Iterate the entries of a version 2 pack from the last one to the first one.
+    ///
+    /// Returns `None` if the pack has no footer.
+    pub fn iter_rev(&mut self) -> Result<Option<RevIter<S>>> {
+        match self.footer()? {
+            None => Ok(None),
+            Some(footer) => Ok(Some(RevIter {
+                seeker: self,
+                entries: footer.entries,
+            })),
+        }
+    }
+}
+
+/// Iterator over the entries of a pack in reverse order, see `Seeker::iter_rev`
+pub struct RevIter<'a, S: 'a> {
+    seeker: &'a mut Seeker<S>,
+    entries: Vec<(BlockHash, Offset)>,
+}
+
+impl<'a, S: Read + Seek> Iterator for RevIter<'a, S> {
+    type Item = io::Result<(BlockHash, Vec<u8>)>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let (hash, ofs) = self.entries.pop()?;
+        Some(self.seeker.block_at_offset(ofs).map(|data| (hash, data)))
+    }
 }
 
 impl<R> Reader<R> {
@@ -180,10 +415,63 @@ impl Writer {
         })
     }
 
+    /// Resume writing a pack from the file of an interrupted `Writer`.
+    ///
+    /// The entries contained in the first `len` bytes of the file are kept,
+    /// anything after is discarded. As the pack entries don't carry their
+    /// hash, `hash_of` is called on every kept entry to rebuild the index.
+    pub fn resume<F>(mut tmpfile: TmpFile, len: Offset, mut hash_of: F) -> Result<Self>
+    where
+        F: FnMut(&[u8]) -> io::Result<BlockHash>,
+    {
+        tmpfile.seek(SeekFrom::Start(0))?;
+        magic::check_header(&mut tmpfile, FILE_TYPE, VERSION, VERSION)?;
+
+        let mut writer = Writer {
+            tmpfile: tmpfile,
+            index: indexfile::Index::new(),
+            pos: magic::HEADER_SIZE as u64,
+            nb_blobs: 0,
+            hash_context: blake2b::Blake2b::new(32),
+        };
+        while writer.pos < len {
+            let data = read_next_block(&mut writer.tmpfile)?;
+            let hash = hash_of(&data)?;
+            writer.hash_context.input(&data);
+            writer.index.append(&hash, writer.pos);
+            writer.pos = writer.pos + SIZE_SIZE as Offset + offset_align4(data.len() as u64);
+            writer.nb_blobs += 1;
+        }
+        if writer.pos != len {
+            return Err(StorageError::Corrupted(
+                "resume length is not on an entry boundary",
+            ));
+        }
+        writer.tmpfile.set_len(len)?;
+        writer.tmpfile.seek(SeekFrom::Start(len))?;
+        Ok(writer)
+    }
+
     pub fn pos(&self) -> Offset {
         self.pos
     }
 
+    /// hash of the last appended entry
+    pub fn last_hash(&self) -> Option<&BlockHash> {
+        self.index.hashes.last()
+    }
+
+    /// flush the written entries to the underlying file
+    pub fn flush(&mut self) -> io::Result<()> {
+        self.tmpfile.flush()
+    }
+
+    /// flush the written entries to the disk
+    pub fn sync(&mut self) -> io::Result<()> {
+        self.tmpfile.flush()?;
+        self.tmpfile.sync_data()
+    }
+
     pub fn append(&mut self, blockhash: &BlockHash, block: &[u8]) -> io::Result<()> {
         let bytes_written = write_length_prefixed(&mut self.tmpfile, block)?;
         self.hash_context.input(block);
@@ -194,8 +482,93 @@ impl Writer {
     }
 
     pub fn finalize(mut self) -> io::Result<(TmpFile, PackHash, indexfile::Index)> {
+        let footer = Footer {
+            total_size: self.pos,
+            entries: self
+                .index
+                .hashes
+                .iter()
+                .cloned()
+                .zip(self.index.offsets.iter().cloned())
+                .collect(),
+        };
+        footer.write(&mut self.tmpfile)?;
+
         let mut packhash: PackHash = [0u8; HASH_SIZE];
         self.hash_context.result(&mut packhash);
         Ok((self.tmpfile, packhash, self.index))
     }
 }
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use std::env;
+
+    #[test]
+    fn pack_v2_footer_roundtrip() {
+        let tmpfile = TmpFile::create(env::temp_dir()).unwrap();
+        let mut writer = Writer::init(tmpfile).unwrap();
+        let blocks: Vec<(BlockHash, Vec<u8>)> = (0..5u8)
+            .map(|i| ([i; HASH_SIZE], repeat(i).take(i as usize * 3 + 1).collect()))
+            .collect();
+        for (hash, data) in blocks.iter() {
+            writer.append(hash, data).unwrap();
+        }
+        let (mut tmpfile, _, _) = writer.finalize().unwrap();
+
+        tmpfile.seek(SeekFrom::Start(0)).unwrap();
+        let mut content = Vec::new();
+        tmpfile.read_to_end(&mut content).unwrap();
+
+        // forward reading stops at the end of entries marker
+        let mut reader = Reader::init(&content[..]).unwrap();
+        for (_, data) in blocks.iter() {
+            assert_eq!(reader.next_block().unwrap().as_ref(), Some(data));
+        }
+        assert_eq!(reader.next_block().unwrap(), None);
+
+        let mut seeker = Seeker::new(io::Cursor::new(content)).unwrap();
+        assert_eq!(seeker.version(), VERSION_2);
+        let footer = seeker.validate().unwrap().unwrap();
+        assert_eq!(footer.nb_entries(), blocks.len());
+
+        let rev: Vec<_> = seeker
+            .iter_rev()
+            .unwrap()
+            .unwrap()
+            .map(|r| r.unwrap())
+            .collect();
+        let mut expected = blocks.clone();
+        expected.reverse();
+        assert_eq!(rev, expected);
+    }
+    #[test]
+    fn seeker_reads_version_from_header() {
+        let mut content = Vec::new();
+        magic::write_header(&mut content, FILE_TYPE, VERSION_1).unwrap();
+        write_length_prefixed(&mut content, &[1, 2, 3]).unwrap();
+
+        let mut seeker = Seeker::new(io::Cursor::new(content)).unwrap();
+        assert_eq!(seeker.version(), VERSION_1);
+        assert_eq!(seeker.footer().unwrap(), None);
+        assert_eq!(seeker.next_block().unwrap(), Some(vec![1, 2, 3]));
+        assert_eq!(seeker.next_block().unwrap(), None);
+    }
+
+    #[test]
+    fn corrupt_entry_size() {
+        let mut content = Vec::new();
+        magic::write_header(&mut content, FILE_TYPE, VERSION).unwrap();
+        write_length_prefixed(&mut content, &[1, 2, 3]).unwrap();
+        let corrupt = content.len() as Offset;
+        content.extend_from_slice(&[0x7f, 0xff, 0xff, 0xff]);
+
+        let mut seeker = Seeker::new(io::Cursor::new(content)).unwrap();
+        assert_eq!(seeker.next_block().unwrap(), Some(vec![1, 2, 3]));
+        let err = seeker.next_block().unwrap_err();
+        assert_eq!(err.kind(), io::ErrorKind::InvalidData);
+        let err = seeker.block_at_offset(corrupt).unwrap_err();
+        assert_eq!(err.kind(), io::ErrorKind::InvalidData);
+    }
+}
@@ -25,10 +25,18 @@ impl Reader {
         Ok(Reader { handle: file })
     }
 
+    /// Return the hash at `index` (after the header of the file), or
+    /// None if the slot is empty or past the end.
     pub fn getref_at_index(&mut self, index: u32) -> io::Result<Option<BlockHash>> {
-        let offset = (index as u64) * (HASH_SIZE as u64);
+        let offset = magic::HEADER_SIZE as u64 + (index as u64) * (HASH_SIZE as u64);
         self.handle.seek(SeekFrom::Start(offset))?;
-        self.next()
+        let mut buf = [0; HASH_SIZE];
+        match self.handle.read_exact(&mut buf) {
+            Err(ref err) if err.kind() == io::ErrorKind::UnexpectedEof => Ok(None),
+            Err(err) => Err(err),
+            Ok(()) if buf.iter().all(|v| *v == 0) => Ok(None),
+            Ok(()) => Ok(Some(buf)),
+        }
     }
 
     /// Return the next hash, skipping empty slots, or None if we're
@@ -79,13 +87,20 @@ impl From<Vec<BlockHash>> for Lookup {
 }
 
 impl Lookup {
+    /// an empty lookup, see `with_capacity` to avoid the reallocations
     pub fn new() -> Self {
-        // TODO hardcoded size, maybe make it user parameter ?
-        Lookup(Vec::with_capacity(21600))
+        Lookup(Vec::new())
+    }
+
+    /// a lookup with room for `capacity` hashes (the number of slots of an
+    /// epoch) before reallocating
+    pub fn with_capacity(capacity: usize) -> Self {
+        Lookup(Vec::with_capacity(capacity))
     }
 
     pub fn from_path<P: AsRef<Path>>(path: P) -> Result<Self> {
-        let mut v = Lookup::new();
+        let len = fs::metadata(path.as_ref())?.len() as usize;
+        let mut v = Lookup::with_capacity(len / HASH_SIZE);
         let mut reader = Reader::open(path)?;
         loop {
             match reader.next() {
@@ -13,6 +13,9 @@ pub enum StorageError {
     VersionTooNew(magic::Version, magic::Version),
     InvalidDirectoryName(DirectoryNameError),
     LockError(lock::Error),
+    /// the file content does not match its own structural metadata
+    /// (e.g. a pack footer inconsistent with the entries)
+    Corrupted(&'static str),
 }
 
 impl From<io::Error> for StorageError {
@@ -43,6 +46,7 @@ impl fmt::Display for StorageError {
             ),
             StorageError::InvalidDirectoryName(_) => write!(f, "Invalid Directory name"),
             StorageError::LockError(_) => write!(f, "Lock file error"),
+            StorageError::Corrupted(what) => write!(f, "Corrupted storage file: {}", what),
         }
     }
 }
@@ -57,6 +61,7 @@ impl error::Error for StorageError {
             StorageError::VersionTooNew(_, _) => None,
             StorageError::InvalidDirectoryName(ref err) => Some(err),
             StorageError::LockError(ref err) => Some(err),
+            StorageError::Corrupted(_) => None,
         }
     }
 }
@@ -3,7 +3,7 @@ use std::fs;
 use std::fs::OpenOptions;
 use std::io;
 use std::io::Write;
-use std::path::PathBuf;
+use std::path::{Path, PathBuf};
 
 pub struct TmpFile {
     file: fs::File,
@@ -32,6 +32,32 @@ impl TmpFile {
             })
     }
 
+    /// Open a temporary file at a known location, creating it if needed.
+    ///
+    /// Contrary to `create`, an existing file is not truncated, so a writer
+    /// interrupted before `render_permanent` can find its data again.
+    pub fn open_or_create(path: PathBuf) -> io::Result<Self> {
+        OpenOptions::new()
+            .write(true)
+            .read(true)
+            .create(true)
+            .open(&path)
+            .map(|file| TmpFile {
+                file: file,
+                path: path,
+            })
+    }
+
+    /// Flush the content of the file to the disk.
+    pub fn sync_data(&self) -> io::Result<()> {
+        self.file.sync_data()
+    }
+
+    /// Truncate or extend the file to the given size in bytes.
+    pub fn set_len(&self, len: u64) -> io::Result<()> {
+        self.file.set_len(len)
+    }
+
     pub fn render_permanent(&self, path: &PathBuf) -> io::Result<()> {
         // NOTE: we need to consider what is being written, in a case of a tag we want rename
         // to error out correctly in every cases rename fail, however in a case of a hash, since the hash is suppose
@@ -43,6 +69,32 @@ impl TmpFile {
         };
         Ok(())
     }
+
+    /// Same as `render_permanent`, but the content of the file is flushed
+    /// to the disk before the rename, and the rename itself after it (see
+    /// `sync_dir`): once this returns, the file survives a power loss.
+    pub fn render_permanent_synced(&self, path: &PathBuf) -> io::Result<()> {
+        self.file.sync_all()?;
+        fs::rename(&self.path, path)?;
+        match path.parent() {
+            None => Ok(()),
+            Some(parent) => sync_dir(parent),
+        }
+    }
+}
+
+/// Flush the directory entries of `dir` (files created, renamed or removed
+/// in it) to the disk.
+///
+/// Only supported on unix, where directories can be opened: elsewhere this
+/// does nothing.
+#[cfg(unix)]
+pub fn sync_dir(dir: &Path) -> io::Result<()> {
+    fs::File::open(dir)?.sync_all()
+}
+#[cfg(not(unix))]
+pub fn sync_dir(_dir: &Path) -> io::Result<()> {
+    Ok(())
 }
 impl io::Seek for TmpFile {
     fn seek(&mut self, pos: io::SeekFrom) -> io::Result<u64> {
@@ -1,5 +1,9 @@
 use super::{Error, Result, Storage};
-use cardano::block::{Block, BlockDate, ChainState, EpochId, EpochSlotId, HeaderHash, Utxos};
+use cardano::block::{
+    Block, BlockDate, ChainState, Delegations, EpochId, EpochSlotId, Error as BlockError,
+    HeaderHash, Utxos,
+};
+use cardano::coin::{self, Coin};
 use cardano::config::GenesisData;
 use cardano::tx::TxoPointer;
 use cbor_event::{de, se, Len};
@@ -9,7 +13,12 @@ use std::io::{Read, Write};
 use storage_units::utils::{error::StorageError, magic};
 
 const FILE_TYPE: magic::FileType = 0x5554584f; // = UTXO
-const VERSION: magic::Version = 3;
+/// the current version, the first with the fees
+const VERSION: magic::Version = 5;
+/// the oldest readable version, without the delegations and the fees
+const VERSION_3: magic::Version = 3;
+/// the first version with the delegations
+const VERSION_4: magic::Version = 4;
 
 /// Write the chain state to disk. To reduce storage requirements (in
 /// particular of the utxo state), we actually write a delta between
@@ -57,7 +66,7 @@ pub fn write_chain_state(
     Ok(())
 }
 
-const NR_FIELDS: u64 = 10;
+const NR_FIELDS: u64 = 12;
 
 /// Write the chain state delta between chain_state and the state at
 /// 'parent_block'.
@@ -102,9 +111,13 @@ pub fn write_chain_state_delta<W: Write>(
         .serialize(&chain_state.last_boundary_block.as_ref().unwrap())?
         .serialize(&chain_state.chain_length)?
         .serialize(&chain_state.nr_transactions)?
-        .serialize(&chain_state.spent_txos)?;
+        .serialize(&chain_state.spent_txos)?
+        .serialize(&chain_state.fees)?;
     se::serialize_fixed_array(removed_utxos.iter(), &mut serializer)?;
     se::serialize_fixed_map(added_utxos.iter(), &mut serializer)?;
+    // The delegations are few, so they are written in full rather
+    // than as a delta.
+    se::serialize_fixed_map(chain_state.delegations.iter(), &mut serializer)?;
 
     Ok(())
 }
@@ -170,6 +183,19 @@ fn do_get_chain_state(
         }
     }
 
+    // Files written before the delegations and the fees were part of
+    // the chain state: replay the delegation certificates of the blocks
+    // of the delta, and recover the fees from the utxos as they are
+    // never redistributed.
+    match file.delegations {
+        Some(delegations) => chain_state.delegations = delegations,
+        None => replay_delegations(storage, &mut chain_state, &file.parent, &file.last_block)?,
+    }
+    chain_state.fees = match file.fees {
+        Some(fees) => fees,
+        None => fees_from_utxos(genesis_data, &chain_state.utxos)?,
+    };
+
     chain_state.last_block = file.last_block;
     chain_state.last_date = Some(file.last_date);
     chain_state.last_boundary_block = Some(file.last_boundary_block);
@@ -180,6 +206,47 @@ fn do_get_chain_state(
     Ok(chain_state)
 }
 
+/// apply the delegation certificates of the blocks after `parent`, up to
+/// and including `last_block`
+fn replay_delegations(
+    storage: &Storage,
+    chain_state: &mut ChainState,
+    parent: &HeaderHash,
+    last_block: &HeaderHash,
+) -> Result<()> {
+    let mut blocks = vec![];
+    let mut cur = last_block.clone();
+    while &cur != parent {
+        let blk = storage.read_block(cur.as_hash_bytes())?.decode()?;
+        cur = blk.header().previous_header();
+        blocks.push(blk);
+    }
+    for blk in blocks.iter().rev() {
+        if let Block::MainBlock(blk) = blk {
+            chain_state.apply_delegations(blk);
+        }
+    }
+    Ok(())
+}
+
+/// the fees paid since the genesis: the coins of the genesis that are
+/// no longer in the utxos
+fn fees_from_utxos(genesis_data: &GenesisData, utxos: &Utxos) -> Result<Coin> {
+    let total = |utxos: &Utxos| {
+        coin::sum_coins(utxos.values().map(|txout| txout.value))
+            .map_err(|_| BlockError::OutputsTooBig)
+    };
+    let inputs = total(&ChainState::new(genesis_data).utxos)?;
+    let outputs = total(utxos)?;
+    Ok(
+        (inputs - outputs).map_err(|_| BlockError::OutputsExceedInputs {
+            inputs,
+            outputs,
+            fee: Coin::zero(),
+        })?,
+    )
+}
+
 #[derive(Debug)]
 pub struct ChainStateFile {
     pub parent: HeaderHash,
@@ -189,19 +256,24 @@ pub struct ChainStateFile {
     pub chain_length: u64,
     pub nr_transactions: u64,
     pub spent_txos: u64,
+    /// not in the files older than `VERSION`
+    pub fees: Option<Coin>,
     pub removed_utxos: Vec<TxoPointer>,
     pub added_utxos: Utxos,
+    /// not in the files older than `VERSION_4`
+    pub delegations: Option<Delegations>,
 }
 
 pub fn decode_chain_state_file<R: Read>(file: &mut R) -> Result<ChainStateFile> {
-    magic::check_header(file, FILE_TYPE, VERSION, VERSION)?;
+    let version = magic::check_header(file, FILE_TYPE, VERSION_3, VERSION)?;
 
     let mut data = vec![];
     file.read_to_end(&mut data)?;
 
     let mut raw = de::Deserializer::from(::std::io::Cursor::new(&data));
 
-    raw.tuple(NR_FIELDS, "chain state delta file")?;
+    let nr_fields = NR_FIELDS - (VERSION - version) as u64;
+    raw.tuple(nr_fields, "chain state delta file")?;
     let parent = raw.deserialize()?;
     let last_block = raw.deserialize()?;
     let epoch = raw.deserialize()?;
@@ -216,8 +288,18 @@ pub fn decode_chain_state_file<R: Read>(file: &mut R) -> Result<ChainStateFile>
     let chain_length = raw.deserialize()?;
     let nr_transactions = raw.deserialize()?;
     let spent_txos = raw.deserialize()?;
+    let fees = if version >= VERSION {
+        Some(raw.deserialize()?)
+    } else {
+        None
+    };
     let removed_utxos = raw.deserialize()?;
     let added_utxos = raw.deserialize()?;
+    let delegations = if version >= VERSION_4 {
+        Some(raw.deserialize()?)
+    } else {
+        None
+    };
 
     Ok(ChainStateFile {
         parent,
@@ -227,8 +309,10 @@ pub fn decode_chain_state_file<R: Read>(file: &mut R) -> Result<ChainStateFile>
         chain_length,
         nr_transactions,
         spent_txos,
+        fees,
         removed_utxos,
         added_utxos,
+        delegations,
     })
 }
 
@@ -311,3 +395,148 @@ pub fn restore_chain_state(
         return Ok(chain_state);
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use cardano::address::{Addr, ExtendedAddr, StakeholderId};
+    use cardano::block::sign::ProxySecretKey;
+    use cardano::config::{NetworkMagic, ProtocolMagic};
+    use cardano::fee::LinearFee;
+    use cardano::hash::Blake2b256;
+    use cardano::hdwallet::{Seed, XPrv, SEED_SIZE};
+    use cardano::tx::TxOut;
+    use std::collections::BTreeMap;
+    use std::time::{Duration, SystemTime};
+    use test_utils::*;
+
+    fn key(seed: u8) -> XPrv {
+        XPrv::generate_from_seed(&Seed::from_bytes([seed; SEED_SIZE]))
+    }
+
+    fn address(seed: u8) -> ExtendedAddr {
+        ExtendedAddr::new_simple(key(seed).public(), NetworkMagic::NoMagic)
+    }
+
+    /// the genesis of `fake_chain`, with a single utxo of 10 coins
+    fn genesis_data() -> GenesisData {
+        let mut non_avvm_balances = BTreeMap::new();
+        non_avvm_balances.insert(Addr::from(address(10)), Coin::new(10).unwrap());
+        GenesisData {
+            genesis_prev: HeaderHash::new(b"genesis"),
+            epoch_stability_depth: 2160,
+            start_time: SystemTime::UNIX_EPOCH,
+            slot_duration: Duration::from_secs(20),
+            protocol_magic: ProtocolMagic::from(1),
+            fee_policy: LinearFee::default(),
+            avvm_distr: BTreeMap::new(),
+            non_avvm_balances,
+            boot_stakeholders: BTreeMap::new(),
+        }
+    }
+
+    /// write the chain state at `blocks`' last block as a file of
+    /// `version`, spending the genesis utxo into an output of 7 coins
+    fn write_old_chain_state(
+        storage: &Storage,
+        genesis_data: &GenesisData,
+        blocks: &[Block],
+        version: magic::Version,
+    ) -> HeaderHash {
+        let boundary = blocks[0].header().compute_hash();
+        let last = blocks.last().unwrap().header();
+        let last_hash = last.compute_hash();
+        let slot = match last.get_blockdate() {
+            BlockDate::Boundary(_) => 0u16,
+            BlockDate::Normal(s) => s.slotid + 1,
+        };
+        let removed_utxos: Vec<_> = ChainState::new(genesis_data)
+            .utxos
+            .keys()
+            .cloned()
+            .collect();
+        let mut added_utxos = Utxos::new();
+        added_utxos.insert(
+            TxoPointer::new(Blake2b256::new(b"tx"), 0),
+            TxOut::new(address(11), Coin::new(7).unwrap()),
+        );
+
+        let mut file = vec![];
+        magic::write_header(&mut file, FILE_TYPE, version).unwrap();
+        let mut serializer = se::Serializer::new(&mut file);
+        serializer
+            .write_array(Len::Len(NR_FIELDS - (VERSION - version) as u64))
+            .unwrap()
+            .serialize(&genesis_data.genesis_prev)
+            .unwrap()
+            .serialize(&last_hash)
+            .unwrap()
+            .serialize(&last.get_blockdate().get_epochid())
+            .unwrap()
+            .serialize(&slot)
+            .unwrap()
+            .serialize(&boundary)
+            .unwrap()
+            .serialize(&(blocks.len() as u64))
+            .unwrap()
+            .serialize(&1u64)
+            .unwrap()
+            .serialize(&1u64)
+            .unwrap();
+        se::serialize_fixed_array(removed_utxos.iter(), &mut serializer).unwrap();
+        se::serialize_fixed_map(added_utxos.iter(), &mut serializer).unwrap();
+        if version >= VERSION_4 {
+            se::serialize_fixed_map(Delegations::new().iter(), &mut serializer).unwrap();
+        }
+
+        let path = storage
+            .config
+            .get_chain_state_filepath(last_hash.as_hash_bytes());
+        fs::write(path, file).unwrap();
+        last_hash
+    }
+
+    #[test]
+    fn read_version_3() {
+        let mut storage = temp_storage("chain-state-v3");
+        let genesis_data = genesis_data();
+        let mut chain = fake_chain();
+        let (issuer, delegate) = (key(3), key(4));
+        let psk = ProxySecretKey::sign(&issuer, delegate.public(), 0, ProtocolMagic::from(1));
+        let blocks = vec![
+            chain.next_boundary(),
+            chain.next_main(),
+            chain.next_main_delegating(vec![psk]),
+            chain.next_main(),
+        ];
+        pack_epoch(&mut storage, 0, &blocks);
+        let last_hash = write_old_chain_state(&storage, &genesis_data, &blocks, VERSION_3);
+
+        let chain_state = read_chain_state(&storage, &genesis_data, &last_hash).unwrap();
+        assert_eq!(chain_state.last_block, last_hash);
+        assert_eq!(chain_state.chain_length, 4);
+        assert_eq!(chain_state.utxos.len(), 1);
+        assert_eq!(
+            chain_state.delegate_of(&StakeholderId::new(&issuer.public())),
+            Some(&delegate.public())
+        );
+        assert_eq!(chain_state.delegations.len(), 1);
+        assert_eq!(chain_state.fees, Coin::new(3).unwrap());
+    }
+
+    #[test]
+    fn read_version_4() {
+        let mut storage = temp_storage("chain-state-v4");
+        let genesis_data = genesis_data();
+        let mut chain = fake_chain();
+        let blocks = next_epoch(&mut chain, 2);
+        pack_epoch(&mut storage, 0, &blocks);
+        let last_hash = write_old_chain_state(&storage, &genesis_data, &blocks, VERSION_4);
+
+        let chain_state = read_chain_state(&storage, &genesis_data, &last_hash).unwrap();
+        assert_eq!(chain_state.last_block, last_hash);
+        assert_eq!(chain_state.utxos.len(), 1);
+        assert!(chain_state.delegations.is_empty());
+        assert_eq!(chain_state.fees, Coin::new(3).unwrap());
+    }
+}
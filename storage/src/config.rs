@@ -9,12 +9,17 @@ use types::*;
 #[derive(Clone)]
 pub struct StorageConfig {
     pub root_path: PathBuf,
+    /// whether the blobs and the tags are flushed to the disk when written
+    /// (see the `tag` module). Enabled by default; disabling it trades the
+    /// durability of the chain (e.g. `HEAD` after a power loss) for speed.
+    pub sync_writes: bool,
 }
 
 impl StorageConfig {
     pub fn new(path_buf: &PathBuf) -> Self {
         StorageConfig {
             root_path: path_buf.clone(),
+            sync_writes: true,
         }
     }
     pub fn get_path(&self) -> PathBuf {
@@ -30,6 +35,7 @@ impl StorageConfig {
             StorageFileType::Tag => p.push("tag/"),
             StorageFileType::Epoch => p.push("epoch/"),
             StorageFileType::ChainState => p.push("chainstate/"),
+            StorageFileType::Orphan => p.push("orphan/"),
         }
         p
     }
@@ -58,6 +64,16 @@ impl StorageConfig {
         p.push(s.as_ref());
         p
     }
+    pub fn get_bloom_filepath(&self) -> PathBuf {
+        let mut p = self.get_path();
+        p.push("bloom");
+        p
+    }
+    pub fn get_tag_journal_filepath(&self) -> PathBuf {
+        let mut p = self.get_path();
+        p.push("tag.journal");
+        p
+    }
     pub fn get_refpack_filepath<S: AsRef<str>>(&self, name: S) -> PathBuf {
         let mut p = self.get_filetype_dir(StorageFileType::RefPack);
         p.push(name.as_ref());
@@ -79,6 +95,32 @@ impl StorageConfig {
         p.push("refpack");
         p
     }
+    pub fn get_epoch_issuers_filepath(&self, epoch: EpochId) -> PathBuf {
+        let mut p = self.get_epoch_dir(epoch);
+        p.push("issuers");
+        p
+    }
+    pub fn get_partial_pack_filepath<S: AsRef<str>>(&self, name: S) -> PathBuf {
+        let mut p = self.get_filetype_dir(StorageFileType::Pack);
+        p.push(format!(".partial.{}", name.as_ref()));
+        p
+    }
+    pub fn get_partial_pack_state_filepath<S: AsRef<str>>(&self, name: S) -> PathBuf {
+        let mut p = self.get_filetype_dir(StorageFileType::Pack);
+        p.push(format!(".partial.{}.state", name.as_ref()));
+        p
+    }
+    /// the directory of the orphans whose parent is `parent`
+    pub fn get_orphan_dir(&self, parent: &BlockHash) -> PathBuf {
+        let mut p = self.get_filetype_dir(StorageFileType::Orphan);
+        p.push(hex::encode(parent));
+        p
+    }
+    pub fn get_orphan_filepath(&self, parent: &BlockHash, blockhash: &BlockHash) -> PathBuf {
+        let mut p = self.get_orphan_dir(parent);
+        p.push(hex::encode(blockhash));
+        p
+    }
     pub fn get_chain_state_filepath(&self, blockhash: &BlockHash) -> PathBuf {
         let mut p = self.get_filetype_dir(StorageFileType::ChainState);
         p.push(hex::encode(blockhash));
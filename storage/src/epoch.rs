@@ -2,6 +2,7 @@ use cardano::block::{BlockDate, ChainState, EpochId};
 use cardano::config::GenesisData;
 use cardano::util::hex;
 use chain_state;
+use issuer::EpochIssuers;
 use std::fs;
 use std::io::Read;
 
@@ -39,24 +40,29 @@ pub fn epoch_create(
     epochid: EpochId,
     chain_state: Option<(&ChainState, &GenesisData)>,
 ) {
+    let parameters = chain_state
+        .map(|(_, genesis_data)| genesis_data.epoch_parameters())
+        .unwrap_or_default();
+
     // read the pack and append the block hash as we find them in the refpack.
-    let mut rp = reffile::Lookup::new();
+    let mut rp = reffile::Lookup::with_capacity(parameters.slots_per_epoch + 1);
+    let mut issuers = EpochIssuers::new();
     let mut reader = packreader_init(&storage.config, packref);
 
     let mut current_slotid = BlockDate::Boundary(epochid);
     let mut last_block = None;
     while let Some(rblk) = packreader_block_next(&mut reader).unwrap() {
-        let blk = rblk.decode().unwrap();
-        let hdr = blk.header();
+        let hdr = rblk.decode_header().unwrap();
         let hash = hdr.compute_hash();
-        let blockdate = hdr.blockdate();
+        let blockdate = hdr.get_blockdate();
 
         while current_slotid != blockdate {
             rp.append_missing_hash();
-            current_slotid = current_slotid.next();
+            current_slotid = current_slotid.next_with(&parameters);
         }
         rp.append_hash(header_to_blockhash(&hash));
-        current_slotid = current_slotid.next();
+        current_slotid = current_slotid.next_with(&parameters);
+        issuers.append(hdr.view());
 
         last_block = Some(hash);
     }
@@ -77,6 +83,9 @@ pub fn epoch_create(
             .unwrap();
     }
 
+    // write the issuers of the blocks
+    issuers.write(&storage.config, epochid).unwrap();
+
     // write the pack pointer
     let pack_filepath = storage.config.get_epoch_pack_filepath(epochid);
     tmpfile::atomic_write_simple(&pack_filepath, hex::encode(packref).as_bytes()).unwrap();
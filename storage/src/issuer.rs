@@ -0,0 +1,303 @@
+//! Index of the issuers of the blocks of an epoch.
+//!
+//! The issuers are recorded while the epoch is created, so answering "how
+//! many blocks did key X issue" does not require to decode the blocks again.
+//!
+//! The file is made of the table of the distinct issuers' keys (each with
+//! the key of the delegate which signed the blocks), followed by the index
+//! in this table of the issuer of every block of the epoch pack. Boundary
+//! blocks have no issuer.
+//!
+//! The epochs packed before the issuers were recorded (or recorded without
+//! the delegates' keys, version 1 of the file) are indexed on first use,
+//! see `backfill`.
+
+use super::{epoch, packreader_block_next, packreader_init, Error, Result, StorageConfig};
+use cardano::block::{normal, sign::BlockSignature, BlockHeaderView, EpochId};
+use cardano::hdwallet::{XPub, XPUB_SIZE};
+use std::fs;
+use std::io::{self, Read};
+use storage_units::utils::error::StorageError;
+use storage_units::utils::serialize::{read_size, write_size, SIZE_SIZE};
+use storage_units::utils::{magic, tmpfile};
+
+const FILE_TYPE: magic::FileType = 0x49535355; // = ISSU
+const VERSION: magic::Version = 2;
+
+const NO_ISSUER: u32 = 0xffffffff;
+
+/// The issuers of the blocks of an epoch, in the order of the epoch pack
+#[derive(Debug, Clone, Default)]
+pub struct EpochIssuers {
+    /// the issuers' keys and their delegates' keys
+    keys: Vec<(XPub, XPub)>,
+    blocks: Vec<u32>,
+}
+
+/// Number of blocks issued by each key over an epoch
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct IssuerStats {
+    /// total number of blocks issued (boundary blocks are not counted)
+    pub nb_blocks: u32,
+    /// the issuers' keys and the number of blocks they issued, the most
+    /// prolific issuer first
+    pub issuers: Vec<(XPub, u32)>,
+    /// the keys which signed the blocks and the number of blocks they
+    /// signed, the most prolific delegate first
+    pub delegates: Vec<(XPub, u32)>,
+}
+impl IssuerStats {
+    pub fn blocks_issued_by(&self, key: &XPub) -> u32 {
+        count_of(&self.issuers, key)
+    }
+
+    pub fn blocks_signed_by(&self, key: &XPub) -> u32 {
+        count_of(&self.delegates, key)
+    }
+}
+
+fn count_of(counts: &[(XPub, u32)], key: &XPub) -> u32 {
+    counts
+        .iter()
+        .find(|(k, _)| k == key)
+        .map(|(_, n)| *n)
+        .unwrap_or(0)
+}
+
+/// sum the counts of the same keys, the largest count first
+fn merge_counts<I>(counts: I) -> Vec<(XPub, u32)>
+where
+    I: IntoIterator<Item = (XPub, u32)>,
+{
+    let mut merged: Vec<(XPub, u32)> = Vec::new();
+    for (key, n) in counts {
+        match merged.iter_mut().find(|(k, _)| *k == key) {
+            Some((_, total)) => *total += n,
+            None => merged.push((key, n)),
+        }
+    }
+    merged.sort_by(|(_, n1), (_, n2)| n2.cmp(n1));
+    merged
+}
+
+/// the key of the block's issuer, and the key which signed the block
+fn signers(hdr: &normal::BlockHeader) -> (XPub, XPub) {
+    let delegate = match &hdr.consensus.block_signature {
+        BlockSignature::Signature(_) => hdr.consensus.leader_key,
+        BlockSignature::ProxyLight(proxy_sig) => proxy_sig.psk.delegate_pk,
+        BlockSignature::ProxyHeavy(proxy_sig) => proxy_sig.psk.delegate_pk,
+    };
+    (hdr.consensus.leader_key, delegate)
+}
+
+impl EpochIssuers {
+    pub fn new() -> Self {
+        EpochIssuers::default()
+    }
+
+    /// record the issuer of the next block of the epoch
+    pub fn append(&mut self, header: BlockHeaderView) {
+        let index = match header {
+            BlockHeaderView::Boundary(_) => NO_ISSUER,
+            BlockHeaderView::Normal(hdr) => {
+                let keys = signers(hdr);
+                match self.keys.iter().position(|k| *k == keys) {
+                    Some(index) => index as u32,
+                    None => {
+                        self.keys.push(keys);
+                        (self.keys.len() - 1) as u32
+                    }
+                }
+            }
+        };
+        self.blocks.push(index);
+    }
+
+    /// number of blocks recorded
+    pub fn len(&self) -> usize {
+        self.blocks.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.blocks.is_empty()
+    }
+
+    fn keys(&self, index: usize) -> Option<&(XPub, XPub)> {
+        match self.blocks.get(index) {
+            Some(&NO_ISSUER) | None => None,
+            Some(&key_index) => self.keys.get(key_index as usize),
+        }
+    }
+
+    /// the issuer of the `index`th block of the epoch pack
+    pub fn issuer(&self, index: usize) -> Option<&XPub> {
+        self.keys(index).map(|(issuer, _)| issuer)
+    }
+
+    /// the key which signed the `index`th block of the epoch pack, on
+    /// behalf of its issuer
+    pub fn delegate(&self, index: usize) -> Option<&XPub> {
+        self.keys(index).map(|(_, delegate)| delegate)
+    }
+
+    pub fn stats(&self) -> IssuerStats {
+        let mut counts = vec![0u32; self.keys.len()];
+        let mut nb_blocks = 0;
+        for index in self.blocks.iter().filter(|i| **i != NO_ISSUER) {
+            counts[*index as usize] += 1;
+            nb_blocks += 1;
+        }
+        let issuers = merge_counts(self.keys.iter().map(|(k, _)| *k).zip(counts.clone()));
+        let delegates = merge_counts(self.keys.iter().map(|(_, k)| *k).zip(counts));
+        IssuerStats {
+            nb_blocks,
+            issuers,
+            delegates,
+        }
+    }
+
+    /// record the issuers of the blocks of the epoch pack
+    pub fn from_epoch_pack(config: &StorageConfig, epochid: EpochId) -> Result<Self> {
+        let packhash = epoch::epoch_read_pack(config, epochid)?;
+        let mut reader = packreader_init(config, &packhash);
+        let mut issuers = EpochIssuers::new();
+        while let Some(raw_block) = packreader_block_next(&mut reader)? {
+            issuers.append(raw_block.decode()?.header());
+        }
+        Ok(issuers)
+    }
+
+    pub fn write(&self, config: &StorageConfig, epochid: EpochId) -> Result<()> {
+        let mut buf = Vec::with_capacity(
+            magic::HEADER_SIZE
+                + 2 * SIZE_SIZE
+                + self.keys.len() * 2 * XPUB_SIZE
+                + self.blocks.len() * SIZE_SIZE,
+        );
+        magic::write_header(&mut buf, FILE_TYPE, VERSION)?;
+        let mut sz = [0u8; SIZE_SIZE];
+        write_size(&mut sz, self.keys.len() as u32);
+        buf.extend_from_slice(&sz);
+        for (issuer, delegate) in self.keys.iter() {
+            buf.extend_from_slice(issuer.as_ref());
+            buf.extend_from_slice(delegate.as_ref());
+        }
+        write_size(&mut sz, self.blocks.len() as u32);
+        buf.extend_from_slice(&sz);
+        for index in self.blocks.iter() {
+            write_size(&mut sz, *index);
+            buf.extend_from_slice(&sz);
+        }
+        tmpfile::atomic_write_simple(&config.get_epoch_issuers_filepath(epochid), &buf)?;
+        Ok(())
+    }
+
+    pub fn read(config: &StorageConfig, epochid: EpochId) -> Result<Self> {
+        let mut file = fs::File::open(config.get_epoch_issuers_filepath(epochid))?;
+        magic::check_header(&mut file, FILE_TYPE, VERSION, VERSION)?;
+
+        let mut sz = [0u8; SIZE_SIZE];
+        file.read_exact(&mut sz)?;
+        let nb_keys = read_size(&sz) as usize;
+        let mut keys = Vec::with_capacity(nb_keys);
+        for _ in 0..nb_keys {
+            let mut issuer = [0u8; XPUB_SIZE];
+            file.read_exact(&mut issuer)?;
+            let mut delegate = [0u8; XPUB_SIZE];
+            file.read_exact(&mut delegate)?;
+            keys.push((XPub::from_bytes(issuer), XPub::from_bytes(delegate)));
+        }
+
+        file.read_exact(&mut sz)?;
+        let nb_blocks = read_size(&sz) as usize;
+        let mut blocks = Vec::with_capacity(nb_blocks);
+        for _ in 0..nb_blocks {
+            file.read_exact(&mut sz)?;
+            let index = read_size(&sz);
+            if index != NO_ISSUER && index as usize >= nb_keys {
+                return Err(Error::StorageError(StorageError::Corrupted(
+                    "issuer index out of bounds",
+                )));
+            }
+            blocks.push(index);
+        }
+        Ok(EpochIssuers { keys, blocks })
+    }
+}
+
+/// whether the issuers of the epoch are not recorded, or are recorded in
+/// an older version of the file
+fn is_outdated(err: &Error) -> bool {
+    match err {
+        Error::StorageError(StorageError::IoError(err)) => err.kind() == io::ErrorKind::NotFound,
+        Error::StorageError(StorageError::VersionTooOld(_, _)) => true,
+        _ => false,
+    }
+}
+
+/// Record the issuers of an epoch packed before they were recorded, or
+/// recorded in an older version of the file. Returns whether the file was
+/// written.
+pub fn backfill(config: &StorageConfig, epochid: EpochId) -> Result<bool> {
+    match EpochIssuers::read(config, epochid) {
+        Ok(_) => Ok(false),
+        Err(ref err) if is_outdated(err) => {
+            EpochIssuers::from_epoch_pack(config, epochid)?.write(config, epochid)?;
+            Ok(true)
+        }
+        Err(err) => Err(err),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use test_utils::*;
+
+    #[test]
+    fn record_issuers_and_delegates() {
+        let mut storage = temp_storage("record-issuers");
+        let mut chain = fake_chain();
+        let blocks = next_epoch(&mut chain, 3);
+        pack_epoch(&mut storage, 0, &blocks);
+
+        let psk = chain.delegation().clone();
+        let issuers = EpochIssuers::read(&storage.config, 0).unwrap();
+        assert_eq!(issuers.len(), 4);
+        assert_eq!(issuers.issuer(0), None);
+        assert_eq!(issuers.issuer(1), Some(&psk.issuer_pk));
+        assert_eq!(issuers.delegate(1), Some(&psk.delegate_pk));
+        assert_eq!(issuers.issuer(4), None);
+
+        let stats = storage.issuer_stats(0).unwrap();
+        assert_eq!(stats.nb_blocks, 3);
+        assert_eq!(stats.blocks_issued_by(&psk.issuer_pk), 3);
+        assert_eq!(stats.blocks_signed_by(&psk.delegate_pk), 3);
+        assert_eq!(stats.blocks_issued_by(&psk.delegate_pk), 0);
+    }
+
+    #[test]
+    fn backfill_missing_and_old_files() {
+        let mut storage = temp_storage("backfill-issuers");
+        let mut chain = fake_chain();
+        let blocks = next_epoch(&mut chain, 2);
+        pack_epoch(&mut storage, 0, &blocks);
+        assert!(!backfill(&storage.config, 0).unwrap());
+
+        // an epoch packed before the issuers were recorded
+        let path = storage.config.get_epoch_issuers_filepath(0);
+        fs::remove_file(&path).unwrap();
+        assert_eq!(storage.issuer_stats(0).unwrap().nb_blocks, 2);
+        assert!(!backfill(&storage.config, 0).unwrap());
+
+        // the issuers recorded without the delegates
+        let mut buf = Vec::new();
+        magic::write_header(&mut buf, FILE_TYPE, 1).unwrap();
+        buf.extend_from_slice(&[0; 2 * SIZE_SIZE]);
+        tmpfile::atomic_write_simple(&path, &buf).unwrap();
+        assert!(backfill(&storage.config, 0).unwrap());
+        let issuers = EpochIssuers::read(&storage.config, 0).unwrap();
+        assert_eq!(issuers.len(), 3);
+        assert_eq!(issuers.delegate(2), Some(&chain.delegation().delegate_pk));
+    }
+}
@@ -2,7 +2,7 @@ pub mod epoch;
 mod range;
 mod reverse;
 
-pub use self::range::Range;
+pub use self::range::{Blocks, Range};
 
 /// Constructs a `Range` iterator.
 ///
@@ -120,11 +120,7 @@ impl<'a> Iter<'a> {
                 IteratorType::Loose(storage, range)
             }
             location => {
-                let block_header = storage
-                    .read_block_at(&location)
-                    .unwrap()
-                    .decode()?
-                    .get_header();
+                let block_header = storage.read_block_at(&location)?.decode_header()?;
                 let block_date = block_header.get_blockdate();
 
                 let epochs =
@@ -162,24 +158,28 @@ impl<'a> Iterator for Iter<'a> {
         if !self.initialised {
             self.initialised = true;
 
-            let mut next = None;
-            for block in self.iterator.next() {
-                match block {
+            // skip the blocks of the epoch before `starting_from`, only
+            // decoding their headers
+            while let Some(block) = self.iterator.next() {
+                let raw_block = match block {
                     Err(err) => return Some(Err(err)),
-                    Ok(raw_block) => {
-                        let block = raw_block.decode().unwrap();
-                        let hh = block.get_header().compute_hash();
-                        let end = hh.as_hash_bytes() == &self.starting_from;
-                        next = Some(Ok((raw_block, block)));
-                        self.last_known_block_hash = Some(hh.as_hash_bytes().clone());
-                        if end {
-                            break;
-                        }
-                    }
+                    Ok(raw_block) => raw_block,
+                };
+                let hh = match raw_block.decode_header() {
+                    Err(err) => return Some(Err(err.into())),
+                    Ok(hdr) => hdr.compute_hash(),
+                };
+                self.last_known_block_hash = Some(hh.as_hash_bytes().clone());
+                if hh.as_hash_bytes() == &self.starting_from {
+                    return Some(
+                        raw_block
+                            .decode()
+                            .map(|block| (raw_block, block))
+                            .map_err(|err| err.into()),
+                    );
                 }
             }
-
-            next
+            None
         } else {
             match self.iterator.next() {
                 None => {
@@ -1,9 +1,10 @@
+use cardano::block::Block;
 use refpack;
 use std::collections::VecDeque;
 use storage_units::reffile;
 use types::BlockHash;
 
-use super::super::{Error, Result, Storage};
+use super::super::{BlockLocation, Error, Result, Storage};
 use super::reverse_iter;
 
 pub struct Range(VecDeque<BlockHash>);
@@ -43,6 +44,17 @@ impl Range {
     pub fn iter<'a>(&'a self) -> refpack::Iter<'a, BlockHash> {
         self.0.iter()
     }
+
+    /// Read and decode the blocks of the range.
+    ///
+    /// The hashes of the range are yielded along with the blocks, so they
+    /// don't need to be computed again from the headers.
+    pub fn blocks<'a>(self, storage: &'a Storage) -> Blocks<'a> {
+        Blocks {
+            storage: storage,
+            range: self,
+        }
+    }
 }
 impl Iterator for Range {
     type Item = BlockHash;
@@ -50,3 +62,25 @@ impl Iterator for Range {
         self.0.pop_front()
     }
 }
+
+/// Iterator over the blocks of a `Range`, see `Range::blocks`.
+///
+/// Every block comes with its hash and the location it was read from.
+pub struct Blocks<'a> {
+    storage: &'a Storage,
+    range: Range,
+}
+impl<'a> Blocks<'a> {
+    fn read(&self, hash: BlockHash) -> Result<(Block, BlockHash, BlockLocation)> {
+        let location = self.storage.block_location(&hash)?;
+        let block = self.storage.read_block_at(&location)?.decode()?;
+        Ok((block, hash, location))
+    }
+}
+impl<'a> Iterator for Blocks<'a> {
+    type Item = Result<(Block, BlockHash, BlockLocation)>;
+    fn next(&mut self) -> Option<Self::Item> {
+        let hash = self.range.next()?;
+        Some(self.read(hash))
+    }
+}
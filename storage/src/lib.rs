@@ -2,22 +2,33 @@
 extern crate log;
 extern crate cardano;
 extern crate cbor_event;
+#[cfg(feature = "chain-core-store")]
+extern crate chain_core;
 extern crate rand;
 extern crate storage_units;
 
 pub mod chain_state;
 pub mod config;
 pub mod epoch;
+pub mod issuer;
 pub mod iter;
+pub mod orphan;
 pub mod pack;
+pub mod proof;
 pub mod refpack;
+pub mod reorg;
+#[cfg(feature = "chain-core-store")]
+pub mod store;
 pub mod tag;
+#[cfg(test)]
+mod test_utils;
 pub mod types;
 use std::{fs, io, result};
 
 pub use config::StorageConfig;
 
 use cardano::block::{Block, BlockDate, EpochId, HeaderHash, RawBlock, SlotId};
+use cardano::config::ProtocolMagic;
 use std::{collections::BTreeMap, error, fmt};
 
 use storage_units::utils::error::StorageError;
@@ -27,7 +38,7 @@ use types::*;
 
 use pack::{packreader_block_next, packreader_init};
 use std::cmp::Ordering;
-use storage_units::{indexfile, packfile, reffile};
+use storage_units::{bloomfile, indexfile, packfile, reffile};
 
 #[derive(Debug)]
 pub enum Error {
@@ -45,6 +56,14 @@ pub enum Error {
     EpochSlotRewind(EpochId, SlotId),
     EpochChainInvalid(BlockDate, HeaderHash, HeaderHash),
     NoSuchTag,
+
+    /// the block's protocol magic (second) is not the one the storage
+    /// is configured for (first)
+    WrongProtocolMagic(ProtocolMagic, ProtocolMagic),
+
+    /// the blob (first) does not contain the block it is named after, but
+    /// the block of the given hash (second)
+    BlobHashMismatch(BlockHash, HeaderHash),
 }
 impl From<io::Error> for Error {
     fn from(e: io::Error) -> Self {
@@ -80,6 +99,8 @@ impl fmt::Display for Error {
             Error::EpochSlotRewind(eid, sid) => write!(f, "Cannot pack block {} because is prior to {} already packed", sid, eid),
             Error::EpochChainInvalid(bd, rhh, ehh) => write!(f, "Cannot pack block {} ({}) because it does not follow the blockchain hash (expected: {})", bd, ehh, rhh),
             Error::NoSuchTag => write!(f, "Tag not found"),
+            Error::WrongProtocolMagic(expected, got) => write!(f, "Block has protocol magic {} but the storage expects {}", got, expected),
+            Error::BlobHashMismatch(bh, hh) => write!(f, "Blob {:?} contains the block {}", bh, hh),
         }
     }
 }
@@ -96,6 +117,8 @@ impl error::Error for Error {
             Error::EpochSlotRewind(_, _) => None,
             Error::EpochChainInvalid(_, _, _) => None,
             Error::NoSuchTag => None,
+            Error::WrongProtocolMagic(_, _) => None,
+            Error::BlobHashMismatch(_, _) => None,
         }
     }
 }
@@ -105,6 +128,10 @@ pub type Result<T> = result::Result<T, Error>;
 pub struct Storage {
     pub config: StorageConfig,
     lookups: BTreeMap<PackHash, indexfile::Lookup>,
+    /// the hashes of all the packs, `None` if it could not be built
+    bloom: Option<bloomfile::Bloom>,
+    protocol_magic: Option<ProtocolMagic>,
+    orphan_policy: orphan::OrphanPolicy,
 }
 
 macro_rules! try_open {
@@ -136,6 +163,7 @@ impl Storage {
         fs::create_dir_all(cfg.get_filetype_dir(StorageFileType::Epoch))?;
         fs::create_dir_all(cfg.get_filetype_dir(StorageFileType::RefPack))?;
         fs::create_dir_all(cfg.get_filetype_dir(StorageFileType::ChainState))?;
+        fs::create_dir_all(cfg.get_filetype_dir(StorageFileType::Orphan))?;
 
         let packhashes = cfg.list_indexes();
         for p in packhashes.iter() {
@@ -147,13 +175,167 @@ impl Storage {
             }
         }
 
-        let storage = Storage {
+        let mut storage = Storage {
             config: cfg.clone(),
             lookups: lookups,
+            bloom: None,
+            protocol_magic: None,
+            orphan_policy: orphan::OrphanPolicy::default(),
         };
+        storage.bloom = storage.load_bloom();
         Ok(storage)
     }
 
+    /// Read the bloom filter of the hashes of all the packs, rebuilding it
+    /// if the bloom file is missing or does not cover exactly the packs of
+    /// the storage: a stale filter would hide the blocks of the packs it
+    /// does not know about.
+    fn load_bloom(&self) -> Option<bloomfile::Bloom> {
+        let filepath = self.config.get_bloom_filepath();
+        if let Ok(mut file) = fs::File::open(&filepath) {
+            match bloomfile::Bloom::read(&mut file) {
+                Ok(ref bloom) if !bloom.covers(self.lookups.keys()) || bloom.is_saturated() => {}
+                Ok(bloom) => return Some(bloom),
+                Err(e) => warn!(
+                    "cannot read bloom file `{}': {}",
+                    filepath.to_string_lossy(),
+                    e
+                ),
+            }
+        }
+        self.rebuild_bloom()
+    }
+
+    fn rebuild_bloom(&self) -> Option<bloomfile::Bloom> {
+        let entries: usize = self
+            .lookups
+            .values()
+            .map(|lookup| u32::from(lookup.fanout.get_total()) as usize)
+            .sum();
+        let mut bloom = bloomfile::Bloom::new(entries);
+        for packhash in self.lookups.keys() {
+            match pack::dump_index(&self.config, packhash) {
+                Ok((_, hashes)) => bloom.add_pack(packhash, hashes.iter()),
+                Err(e) => {
+                    warn!("cannot rebuild the bloom file: {}", e);
+                    return None;
+                }
+            }
+        }
+        self.write_bloom(&bloom);
+        Some(bloom)
+    }
+
+    fn write_bloom(&self, bloom: &bloomfile::Bloom) {
+        let filepath = self.config.get_bloom_filepath();
+        let res = TmpFile::create(self.config.get_path())
+            .map_err(Error::from)
+            .and_then(|mut tmpfile| {
+                bloom.write(&mut tmpfile)?;
+                tmpfile.render_permanent(&filepath)?;
+                Ok(())
+            });
+        // the bloom file is rebuilt next time if it is not up to date
+        if let Err(e) = res {
+            warn!(
+                "cannot write bloom file `{}': {}",
+                filepath.to_string_lossy(),
+                e
+            );
+        }
+    }
+
+    /// Add the hashes of a new pack to the bloom filter, rebuilding it
+    /// when it gets too small for the number of hashes.
+    fn update_bloom(&mut self, packhash: &PackHash) {
+        if let Some(mut bloom) = self.bloom.take() {
+            if let Ok((_, hashes)) = pack::dump_index(&self.config, packhash) {
+                bloom.add_pack(packhash, hashes.iter());
+                if !bloom.is_saturated() {
+                    self.write_bloom(&bloom);
+                    self.bloom = Some(bloom);
+                    return;
+                }
+            }
+        }
+        self.bloom = self.rebuild_bloom();
+    }
+
+    /// Make `append_block` reject the blocks that do not belong to the
+    /// network identified by the given protocol magic.
+    pub fn set_protocol_magic(&mut self, protocol_magic: ProtocolMagic) {
+        self.protocol_magic = Some(protocol_magic);
+    }
+
+    pub fn protocol_magic(&self) -> Option<ProtocolMagic> {
+        self.protocol_magic
+    }
+
+    /// Check the block against the storage's configured network, if any.
+    pub fn check_block(&self, block: &Block) -> Result<()> {
+        match self.protocol_magic {
+            Some(expected) if expected != block.get_protocol_magic() => Err(
+                Error::WrongProtocolMagic(expected, block.get_protocol_magic()),
+            ),
+            _ => Ok(()),
+        }
+    }
+
+    /// Write a loose block in the storage, returning its hash.
+    ///
+    /// If a protocol magic has been set with `set_protocol_magic`, blocks
+    /// from a different network are rejected with `Error::WrongProtocolMagic`.
+    pub fn append_block(&self, block_raw: &RawBlock) -> Result<HeaderHash> {
+        let block = block_raw.decode()?;
+        self.check_block(&block)?;
+        let hash = block.header().compute_hash();
+        blob::write(self, &header_to_blockhash(&hash), block_raw.as_ref())?;
+        Ok(hash)
+    }
+
+    /// Set the bounds of the pool of orphan blocks, see `add_orphan`.
+    pub fn set_orphan_policy(&mut self, policy: orphan::OrphanPolicy) {
+        self.orphan_policy = policy;
+    }
+
+    /// Keep a block whose parent is not in the storage yet, until
+    /// `try_connect_orphans` can append it. Returns its hash.
+    ///
+    /// The expired orphans, then the oldest ones above the maximum of the
+    /// orphan policy, are dropped.
+    pub fn add_orphan(&self, block_raw: &RawBlock) -> Result<HeaderHash> {
+        let block = block_raw.decode()?;
+        self.check_block(&block)?;
+        let hash = block.header().compute_hash();
+        let parent = block.header().previous_header();
+        orphan::write(
+            self,
+            &header_to_blockhash(&parent),
+            &header_to_blockhash(&hash),
+            block_raw.as_ref(),
+        )?;
+        orphan::prune(&self.config, &self.orphan_policy)?;
+        Ok(hash)
+    }
+
+    /// Append the orphans descending from `tip` (their parent, or the
+    /// parent of their parent... is `tip`), returning their hashes, parents
+    /// first.
+    pub fn try_connect_orphans(&self, tip: &HeaderHash) -> Result<Vec<HeaderHash>> {
+        orphan::prune(&self.config, &self.orphan_policy)?;
+        orphan::connect(self, tip)
+    }
+
+    /// Number of blocks issued by each slot leader key during the given epoch.
+    ///
+    /// The issuers of an epoch packed before they were recorded are
+    /// recorded first (see `issuer::backfill`).
+    pub fn issuer_stats(&self, epochid: EpochId) -> Result<issuer::IssuerStats> {
+        issuer::backfill(&self.config, epochid)?;
+        let issuers = issuer::EpochIssuers::read(&self.config, epochid)?;
+        Ok(issuers.stats())
+    }
+
     /// Returns an iterator over blocks in the given block range.
     ///
     /// The range is given inclusively. The blocks are iterated in order from
@@ -162,24 +344,43 @@ impl Storage {
         iter::range_iter(self, from, to)
     }
 
+    /// Same as `range`, but the blocks are read and decoded, and yielded
+    /// along with their hash and location in the storage.
+    pub fn range_blocks(&self, from: BlockHash, to: BlockHash) -> Result<iter::Blocks> {
+        Ok(self.range(from, to)?.blocks(self))
+    }
+
     /// Returns an iterator over blocks in reverse from the given header hash.
     pub fn reverse_from(&self, hh: HeaderHash) -> Result<iter::ReverseIter> {
         iter::reverse_iter(self, hh)
     }
 
     pub fn block_location(&self, hash: &BlockHash) -> Result<BlockLocation> {
-        for (packref, lookup) in self.lookups.iter() {
-            let (start, nb) = lookup.fanout.get_indexer_by_hash(hash);
-            match nb {
-                indexfile::FanoutNb(0) => {}
-                _ => {
-                    if lookup.bloom.search(hash) {
-                        let idx_filepath = self.config.get_index_filepath(packref);
-                        let mut idx_file =
-                            try_open!(indexfile::Reader::init, &idx_filepath, "index file");
-                        let sr = idx_file.search(&lookup.params, hash, start, nb);
-                        if let Some(iloc) = sr {
-                            return Ok(BlockLocation::Packed(packref.clone(), iloc));
+        // the bloom filter of all the packs tells if the block is definitely
+        // not packed, without searching every pack
+        let packed = match self.bloom {
+            Some(ref bloom) => bloom.search(hash),
+            None => true,
+        };
+        if packed {
+            for (packref, lookup) in self.lookups.iter() {
+                let (start, nb) = lookup.fanout.get_indexer_by_hash(hash);
+                match nb {
+                    indexfile::FanoutNb(0) => {}
+                    _ => {
+                        if let Some(sr) = lookup.recent_search(hash) {
+                            if let Some(iloc) = sr {
+                                return Ok(BlockLocation::Packed(packref.clone(), iloc));
+                            }
+                        } else if lookup.bloom.search(hash) {
+                            let idx_filepath = self.config.get_index_filepath(packref);
+                            let mut idx_file =
+                                try_open!(indexfile::Reader::init, &idx_filepath, "index file");
+                            let sr = idx_file.search(&lookup.params, hash, start, nb);
+                            lookup.remember_search(hash, sr);
+                            if let Some(iloc) = sr {
+                                return Ok(BlockLocation::Packed(packref.clone(), iloc));
+                            }
                         }
                     }
                 }
@@ -235,8 +436,14 @@ impl Storage {
         }
     }
 
+    /// every change of the tag `name`, oldest first
+    pub fn tag_history(&self, name: &str) -> Result<Vec<tag::TagChange>> {
+        Ok(tag::history(self, &name)?)
+    }
+
     pub fn add_lookup(&mut self, packhash: PackHash, lookup: indexfile::Lookup) {
         self.lookups.insert(packhash, lookup);
+        self.update_bloom(&packhash);
     }
 }
 
@@ -259,13 +466,25 @@ pub mod blob {
         let mut tmp_file = super::tmpfile_create_type(storage, super::StorageFileType::Blob);
         magic::write_header(&mut tmp_file, FILE_TYPE, VERSION)?;
         tmp_file.write_all(block)?;
-        tmp_file.render_permanent(&path)?;
+        if storage.config.sync_writes {
+            tmp_file.render_permanent_synced(&path)?;
+        } else {
+            tmp_file.render_permanent(&path)?;
+        }
         Ok(())
     }
 
     pub fn read_raw(storage: &super::Storage, hash: &super::BlockHash) -> Result<Vec<u8>> {
+        read_raw_with_config(&storage.config, hash)
+    }
+
+    /// same as `read_raw`, without the `Storage` (e.g. from another thread)
+    pub fn read_raw_with_config(
+        config: &super::StorageConfig,
+        hash: &super::BlockHash,
+    ) -> Result<Vec<u8>> {
         let mut content = Vec::new();
-        let path = storage.config.get_blob_filepath(&hash);
+        let path = config.get_blob_filepath(&hash);
 
         let mut file = fs::File::open(path)?;
         magic::check_header(&mut file, FILE_TYPE, VERSION, VERSION)?;
@@ -341,11 +560,12 @@ pub fn resolve_date_to_blockhash(
     let epoch = date.get_epochid();
     match epoch::epoch_open_packref(&storage.config, epoch) {
         Ok(mut handle) => {
-            let slotid = match date {
+            // the boundary block first, then the slots of the epoch
+            let index = match date {
                 BlockDate::Boundary(_) => 0,
-                BlockDate::Normal(sid) => sid.slotid,
+                BlockDate::Normal(sid) => sid.slotid + 1,
             };
-            let r = handle.getref_at_index(slotid as u32)?;
+            let r = handle.getref_at_index(index as u32)?;
             Ok(r)
         }
         Err(_) => match storage.read_block(tip.as_hash_bytes()) {
@@ -366,16 +586,127 @@ pub fn resolve_date_to_blockhash(
     }
 }
 
+/// Resolve a reference to a block of the local chain:
+///
+/// * `tip`: the tip of the local chain (the `HEAD` tag);
+/// * `genesis`: the boundary block of the first epoch;
+/// * `@<height>`: the block of the local chain with the given chain
+///   difficulty (the main block rather than the boundary block that
+///   follows it), e.g. `@123456`;
+/// * the hexadecimal hash of a block;
+/// * otherwise, the name of a tag.
+///
+/// Returns `None` if the reference does not resolve to any block (e.g. a
+/// height above the tip, or no local chain yet). The block of a hash or
+/// of a tag is not checked to exist.
+pub fn resolve_block_ref(storage: &Storage, reference: &str) -> Result<Option<HeaderHash>> {
+    let tip = || tag::read_hash(storage, &tag::HEAD);
+    if reference == "tip" {
+        Ok(tip())
+    } else if reference == "genesis" {
+        match tip() {
+            None => Ok(None),
+            Some(tip) => {
+                let genesis = resolve_date_to_blockhash(storage, &tip, &BlockDate::Boundary(0))?;
+                Ok(genesis.map(HeaderHash::from))
+            }
+        }
+    } else if reference.starts_with('@') {
+        let height: u64 = match reference[1..].parse() {
+            Ok(height) => height,
+            Err(_) => return Ok(None),
+        };
+        let tip = match tip() {
+            None => return Ok(None),
+            Some(tip) => tip,
+        };
+        if let Some(hash) = packed_block_at_height(storage, height)? {
+            return Ok(Some(hash));
+        }
+        // above the packed epochs: the difficulty only decreases walking
+        // back the chain, the block is the oldest one of the given
+        // difficulty
+        let mut found = None;
+        for block in storage.reverse_from(tip)? {
+            let header = block.header();
+            let difficulty = u64::from(header.difficulty());
+            if difficulty < height {
+                break;
+            }
+            if difficulty == height {
+                found = Some(header.compute_hash());
+            }
+        }
+        Ok(found)
+    } else if let Ok(hash) = reference.parse::<HeaderHash>() {
+        Ok(Some(hash))
+    } else {
+        Ok(tag::read_hash(storage, &reference))
+    }
+}
+
+/// The block of the packed epochs with the given chain difficulty, see
+/// `resolve_block_ref`.
+///
+/// Only the main blocks increase the difficulty, by one: the boundary block
+/// of an epoch has the difficulty of the last main block before it, and
+/// the block of `height` is at `height - difficulty` in the refpack of the
+/// last epoch starting below `height`. Only the header of the first block
+/// of each epoch is read.
+fn packed_block_at_height(storage: &Storage, height: u64) -> Result<Option<HeaderHash>> {
+    let first_difficulty = |epochid| -> Result<Option<u64>> {
+        if !epoch::epoch_exists(&storage.config, epochid)? {
+            return Ok(None);
+        }
+        match epoch::epoch_open_packref(&storage.config, epochid)?.next()? {
+            None => Ok(None),
+            Some(hash) => {
+                let header = storage.read_block(&hash)?.decode_header()?;
+                Ok(Some(u64::from(header.difficulty())))
+            }
+        }
+    };
+
+    let mut epochid = 0;
+    let mut start = match first_difficulty(epochid)? {
+        Some(difficulty) if difficulty <= height => difficulty,
+        _ => return Ok(None),
+    };
+    loop {
+        match first_difficulty(epochid + 1)? {
+            Some(difficulty) if difficulty < height => {
+                epochid += 1;
+                start = difficulty;
+            }
+            _ => break,
+        }
+    }
+
+    // the empty slots are skipped
+    let mut refpack = epoch::epoch_open_packref(&storage.config, epochid)?;
+    let mut index = height - start;
+    while let Some(hash) = refpack.next()? {
+        if index == 0 {
+            return Ok(Some(HeaderHash::from(hash)));
+        }
+        index -= 1;
+    }
+    Ok(None)
+}
+
 /// packing parameters
 ///
 /// optionally set the maximum number of blobs in this pack
 /// optionally set the maximum size in bytes of the pack file.
 ///            note that the limits is best effort, not strict.
+/// the number of threads decoding and checking the blobs while they are
+///            read and written (see `pack::packwriter_append_blobs`).
 pub struct PackParameters {
     pub limit_nb_blobs: Option<u32>,
     pub limit_size: Option<u64>,
     pub delete_blobs_after_pack: bool,
     pub range: Option<(BlockHash, BlockHash)>,
+    pub workers: usize,
 }
 impl Default for PackParameters {
     fn default() -> Self {
@@ -384,39 +715,31 @@ impl Default for PackParameters {
             limit_size: None,
             delete_blobs_after_pack: true,
             range: None,
+            workers: 2,
         }
     }
 }
 
-pub fn pack_blobs(storage: &mut Storage, params: &PackParameters) -> PackHash {
-    let mut writer = pack::packwriter_init(&storage.config).unwrap();
-    let mut blob_packed = Vec::new();
+pub fn pack_blobs(storage: &mut Storage, params: &PackParameters) -> Result<PackHash> {
+    let mut writer = pack::packwriter_init(&storage.config)?;
 
     let block_hashes: Vec<BlockHash> = if let Some((from, to)) = params.range {
-        storage.range(from, to).unwrap().iter().cloned().collect()
+        storage.range(from, to)?.iter().cloned().collect()
     } else {
         storage.config.list_blob(params.limit_nb_blobs)
     };
-    for bh in block_hashes {
-        let blob = blob::read_raw(storage, &bh).unwrap();
-        writer.append(&bh, &blob[..]).unwrap();
-        blob_packed.push(bh);
-        match params.limit_size {
-            None => {}
-            Some(sz) => {
-                if writer.pos() >= sz {
-                    break;
-                }
-            }
-        }
-    }
+    let blob_packed = pack::packwriter_append_blobs(
+        &storage.config,
+        &mut writer,
+        block_hashes,
+        params.workers,
+        params.limit_size,
+    )?;
 
     let (packhash, index) = pack::packwriter_finalize(&storage.config, writer);
 
     let (lookup, tmpfile) = pack::create_index(storage, &index);
-    tmpfile
-        .render_permanent(&storage.config.get_index_filepath(&packhash))
-        .unwrap();
+    tmpfile.render_permanent_synced(&storage.config.get_index_filepath(&packhash))?;
 
     if params.delete_blobs_after_pack {
         for bh in blob_packed.iter() {
@@ -425,8 +748,8 @@ pub fn pack_blobs(storage: &mut Storage, params: &PackParameters) -> PackHash {
     }
 
     // append to lookups
-    storage.lookups.insert(packhash, lookup);
-    packhash
+    storage.add_lookup(packhash, lookup);
+    Ok(packhash)
 }
 
 // Create a pack of references (packref) of all the hash in an epoch pack
@@ -442,8 +765,8 @@ pub fn refpack_epoch_pack<S: AsRef<str>>(storage: &Storage, tag: &S) -> Result<(
     let mut current_state = None;
 
     while let Some(raw_block) = packreader_block_next(&mut pack)? {
-        let block = raw_block.decode()?;
-        let hdr = block.header();
+        let header = raw_block.decode_header()?;
+        let hdr = header.view();
         let hash = hdr.compute_hash();
         let date = hdr.blockdate();
 
@@ -488,30 +811,93 @@ pub fn refpack_epoch_pack<S: AsRef<str>>(storage: &Storage, tag: &S) -> Result<(
     refpack::write_refpack(&storage.config, tag, &rp).map_err(From::from)
 }
 
+/// A problem found in an epoch by `check_epochs`
+#[derive(Debug)]
+pub enum EpochIssue {
+    /// the epoch has not been packed
+    Missing(EpochId),
+    /// the epoch pack cannot be read, or its blocks do not form a valid chain
+    Corrupted(EpochId, Error),
+}
+impl EpochIssue {
+    pub fn epoch(&self) -> EpochId {
+        match self {
+            EpochIssue::Missing(epochid) => *epochid,
+            EpochIssue::Corrupted(epochid, _) => *epochid,
+        }
+    }
+}
+impl fmt::Display for EpochIssue {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            EpochIssue::Missing(epochid) => write!(f, "epoch {} is missing", epochid),
+            EpochIssue::Corrupted(epochid, err) => {
+                write!(f, "epoch {} is corrupted: {}", epochid, err)
+            }
+        }
+    }
+}
+
+/// Check that the epochs `0..count` are packed and that their blocks chain
+/// from `genesis_hash` (the hash the first boundary block points to).
+///
+/// Unlike `integrity_check`, the check carries on after a problem so that
+/// every missing or corrupted epoch is reported.
+pub fn check_epochs(
+    storage: &Storage,
+    genesis_hash: HeaderHash,
+    count: EpochId,
+) -> Vec<EpochIssue> {
+    let mut issues = Vec::new();
+    let mut previous_header = Some(genesis_hash);
+    for epochid in 0..count {
+        // the epoch following a bad one cannot be checked against it
+        previous_header = match epoch_integrity_check(storage, epochid, previous_header.as_ref()) {
+            Ok(Some(last_header)) => Some(last_header),
+            Ok(None) => {
+                issues.push(EpochIssue::Missing(epochid));
+                None
+            }
+            Err(err) => {
+                issues.push(EpochIssue::Corrupted(epochid, err));
+                None
+            }
+        };
+    }
+    issues
+}
+
 pub fn integrity_check(storage: &Storage, genesis_hash: HeaderHash, count: EpochId) {
     let mut previous_header = genesis_hash;
     for epochid in 0..count {
         println!("check epoch {}'s integrity", epochid);
-        previous_header = epoch_integrity_check(storage, epochid, previous_header).unwrap();
+        previous_header = epoch_integrity_check(storage, epochid, Some(&previous_header))
+            .unwrap()
+            .expect("EPOCH not found");
     }
 }
 
+/// check the chain of the headers of the epoch pack (only the headers are
+/// decoded, the content of the pack is checked against its hash), returns
+/// the hash of its last block or `None` if the epoch has not been packed
+//
 // FIXME: still necessary now that we have verify_block?
 fn epoch_integrity_check(
     storage: &Storage,
     epochid: EpochId,
-    last_known_hash: HeaderHash,
-) -> Result<HeaderHash> {
-    let packhash_vec = tag::read(storage, &format!("EPOCH_{}", epochid)).expect("EPOCH not found");
-    let mut packhash = [0; HASH_SIZE];
-    packhash[..].clone_from_slice(packhash_vec.as_slice());
-    let mut pack = packreader_init(&storage.config, &packhash);
+    last_known_hash: Option<&HeaderHash>,
+) -> Result<Option<HeaderHash>> {
+    if !epoch::epoch_exists(&storage.config, epochid)? {
+        return Ok(None);
+    }
+    let packhash = epoch::epoch_read_pack(&storage.config, epochid)?;
+    let mut pack = packfile::Reader::open(storage.config.get_pack_filepath(&packhash))?;
 
     let mut current_state = None;
 
     while let Some(raw_block) = packreader_block_next(&mut pack)? {
-        let block = raw_block.decode()?;
-        let hdr = block.header();
+        let header = raw_block.decode_header()?;
+        let hdr = header.view();
         let hash = hdr.compute_hash();
         let prevhash = hdr.previous_header();
         let date = hdr.blockdate();
@@ -522,8 +908,14 @@ fn epoch_integrity_check(
                 if !hdr.is_boundary_block() {
                     return Err(Error::EpochExpectingBoundary);
                 }
-                if last_known_hash != prevhash {
-                    return Err(Error::EpochChainInvalid(date, last_known_hash, prevhash));
+                if let Some(last_known_hash) = last_known_hash {
+                    if *last_known_hash != prevhash {
+                        return Err(Error::EpochChainInvalid(
+                            date,
+                            last_known_hash.clone(),
+                            prevhash,
+                        ));
+                    }
                 }
                 current_state = Some((date.get_epochid(), 0, hdr.compute_hash()));
             }
@@ -556,8 +948,134 @@ fn epoch_integrity_check(
             },
         }
     }
+    if pack.finalize() != packhash {
+        return Err(Error::StorageError(StorageError::Corrupted(
+            "epoch pack does not match its hash",
+        )));
+    }
     match current_state {
-        None => panic!("test"),
-        Some((_, _, prevhash)) => return Ok(prevhash),
+        None => Err(Error::EpochExpectingBoundary),
+        Some((_, _, prevhash)) => Ok(Some(prevhash)),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use cardano::block::EpochSlotId;
+    use test_utils::*;
+
+    fn assert_packed(storage: &Storage, blocks: &[Block]) {
+        for block in blocks {
+            match storage.block_location(&block_hash(block)).unwrap() {
+                BlockLocation::Packed(_, _) => {}
+                loc => panic!("unexpected location {:?}", loc),
+            }
+        }
+    }
+
+    #[test]
+    fn resolve_block_refs() {
+        let mut chain = fake_chain();
+        let epoch0 = next_epoch(&mut chain, 3);
+        // with empty slots
+        let mut epoch1 = vec![chain.next_boundary()];
+        for slotid in vec![1, 2, 5] {
+            epoch1.push(chain.main_at(EpochSlotId { epoch: 1, slotid }));
+        }
+        let epoch2 = next_epoch(&mut chain, 2);
+
+        let mut storage = temp_storage("resolve-block-refs");
+        let hash = |block: &Block| Some(block.header().compute_hash());
+        assert_eq!(resolve_block_ref(&storage, "tip").unwrap(), None);
+        assert_eq!(resolve_block_ref(&storage, "@1").unwrap(), None);
+
+        pack_epoch(&mut storage, 0, &epoch0);
+        pack_epoch(&mut storage, 1, &epoch1);
+        write_blobs(&storage, &epoch2);
+        tag::write_hash(&storage, &tag::HEAD, &epoch2[2].header().compute_hash());
+        tag::write_hash(&storage, &"mark", &epoch1[2].header().compute_hash());
+
+        let resolve = |reference: &str| resolve_block_ref(&storage, reference).unwrap();
+        assert_eq!(resolve("tip"), hash(&epoch2[2]));
+        assert_eq!(resolve("genesis"), hash(&epoch0[0]));
+        let tip = epoch2[2].header().compute_hash();
+        let date = |slotid| BlockDate::Normal(EpochSlotId { epoch: 1, slotid });
+        assert_eq!(
+            resolve_date_to_blockhash(&storage, &tip, &date(5)).unwrap(),
+            Some(block_hash(&epoch1[3]))
+        );
+        assert_eq!(
+            resolve_date_to_blockhash(&storage, &tip, &date(3)).unwrap(),
+            None
+        );
+        assert_eq!(resolve("mark"), hash(&epoch1[2]));
+        let hex = format!("{}", epoch1[3].header().compute_hash());
+        assert_eq!(resolve(&hex), hash(&epoch1[3]));
+
+        // the genesis, the main blocks rather than the boundary blocks
+        // following them, in the packs and in the blobs
+        let heights = vec![
+            (0, &epoch0[0]),
+            (1, &epoch0[1]),
+            (3, &epoch0[3]),
+            (4, &epoch1[1]),
+            (6, &epoch1[3]),
+            (7, &epoch2[1]),
+            (8, &epoch2[2]),
+        ];
+        for (height, block) in heights {
+            assert_eq!(
+                u64::from(block.header().difficulty()),
+                height,
+                "difficulty of the block"
+            );
+            assert_eq!(resolve(&format!("@{}", height)), hash(block), "@{}", height);
+        }
+        assert_eq!(resolve("@9"), None);
+        assert_eq!(resolve("@x"), None);
+    }
+
+    #[test]
+    fn bloom_covers_the_packs() {
+        let mut chain = fake_chain();
+        let epoch0 = next_epoch(&mut chain, 2);
+        let epoch1 = next_epoch(&mut chain, 2);
+
+        let mut storage = temp_storage("bloom");
+        pack_epoch(&mut storage, 0, &epoch0);
+        pack_epoch(&mut storage, 1, &epoch1);
+        assert_packed(&storage, &epoch0);
+        assert_packed(&storage, &epoch1);
+
+        let storage = Storage::init(&storage.config).unwrap();
+        assert_packed(&storage, &epoch0);
+        assert_packed(&storage, &epoch1);
+    }
+
+    #[test]
+    fn stale_bloom_is_rebuilt() {
+        let mut chain = fake_chain();
+        let epoch0 = next_epoch(&mut chain, 2);
+        let epoch1 = next_epoch(&mut chain, 2);
+
+        let mut storage = temp_storage("bloom-stale");
+        pack_epoch(&mut storage, 0, &epoch0);
+
+        // the bloom file of another pack, for the same number of packs
+        let mut other = temp_storage("bloom-other");
+        pack_epoch(&mut other, 1, &epoch1);
+        fs::copy(
+            other.config.get_bloom_filepath(),
+            storage.config.get_bloom_filepath(),
+        )
+        .unwrap();
+
+        let storage = Storage::init(&storage.config).unwrap();
+        assert_packed(&storage, &epoch0);
+
+        let mut file = fs::File::open(storage.config.get_bloom_filepath()).unwrap();
+        let bloom = bloomfile::Bloom::read(&mut file).unwrap();
+        assert!(bloom.covers(storage.lookups.keys()));
     }
 }
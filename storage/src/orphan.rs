@@ -0,0 +1,274 @@
+//! The orphan blocks: the blocks received ahead of the local chain, whose
+//! parent is not in the storage yet (e.g. when fetching from several peers
+//! at once).
+//!
+//! They are kept apart from the blobs, in the `orphan/` directory, until
+//! `Storage::try_connect_orphans` appends them once their parent is
+//! there. The pool is bounded and the orphans expire (see `OrphanPolicy`),
+//! so the blocks of a branch that never connects do not pile up.
+//!
+//! The orphans are indexed by parent: an orphan is in the directory named
+//! after the hash of its parent, `orphan/<parent>/<hash>`.
+
+use super::{
+    header_to_blockhash, BlockHash, Error, Result, Storage, StorageConfig, StorageFileType,
+};
+use cardano::block::{HeaderHash, RawBlock};
+use cardano::util::hex;
+use std::fs;
+use std::io::{self, Read, Write};
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+use storage_units::hash::HASH_SIZE;
+use storage_units::utils::magic;
+use storage_units::utils::tmpfile::TmpFile;
+
+const FILE_TYPE: magic::FileType = 0x4f525048; // = ORPH
+const VERSION: magic::Version = 1;
+
+/// The bounds of the pool of orphans
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct OrphanPolicy {
+    /// maximum number of orphans kept, the oldest are dropped first
+    pub max_orphans: usize,
+    /// the orphans received longer ago than this are dropped
+    pub expiry: Duration,
+}
+impl Default for OrphanPolicy {
+    fn default() -> Self {
+        OrphanPolicy {
+            max_orphans: 2048,
+            expiry: Duration::from_secs(3600),
+        }
+    }
+}
+
+pub fn write(storage: &Storage, parent: &BlockHash, hash: &BlockHash, block: &[u8]) -> Result<()> {
+    let path = storage.config.get_orphan_filepath(parent, hash);
+    fs::create_dir_all(storage.config.get_orphan_dir(parent))?;
+    let mut tmpfile = TmpFile::create(storage.config.get_filetype_dir(StorageFileType::Orphan))?;
+    magic::write_header(&mut tmpfile, FILE_TYPE, VERSION)?;
+    tmpfile.write_all(block)?;
+    tmpfile.render_permanent(&path)?;
+    Ok(())
+}
+
+pub fn read(config: &StorageConfig, parent: &BlockHash, hash: &BlockHash) -> Result<RawBlock> {
+    let mut content = Vec::new();
+    let mut file = fs::File::open(config.get_orphan_filepath(parent, hash))?;
+    magic::check_header(&mut file, FILE_TYPE, VERSION, VERSION)?;
+    file.read_to_end(&mut content)?;
+    Ok(RawBlock::from_dat(content))
+}
+
+/// remove the orphan, and the directory of its parent once empty
+pub fn remove(config: &StorageConfig, parent: &BlockHash, hash: &BlockHash) {
+    let _ = fs::remove_file(config.get_orphan_filepath(parent, hash));
+    let _ = fs::remove_dir(config.get_orphan_dir(parent));
+}
+
+/// the hash of the directory entry named after it, with its metadata
+fn hash_entry(entry: fs::DirEntry) -> Result<Option<(BlockHash, fs::Metadata)>> {
+    let metadata = entry.metadata()?;
+    let name = match entry.file_name().into_string() {
+        Ok(name) => name,
+        Err(_) => return Ok(None),
+    };
+    // not named after a hash (e.g. a temporary file)
+    let bytes = match hex::decode(&name) {
+        Ok(bytes) => bytes,
+        Err(_) => return Ok(None),
+    };
+    if bytes.len() != HASH_SIZE {
+        return Ok(None);
+    }
+    let mut hash = [0; HASH_SIZE];
+    hash.copy_from_slice(&bytes);
+    Ok(Some((hash, metadata)))
+}
+
+/// the orphans whose parent is `parent`, with the time they were received
+pub fn children(
+    config: &StorageConfig,
+    parent: &BlockHash,
+) -> Result<Vec<(BlockHash, SystemTime)>> {
+    let dir = match fs::read_dir(config.get_orphan_dir(parent)) {
+        Ok(dir) => dir,
+        Err(ref err) if err.kind() == io::ErrorKind::NotFound => return Ok(Vec::new()),
+        Err(err) => return Err(err.into()),
+    };
+    let mut orphans = Vec::new();
+    for entry in dir {
+        if let Some((hash, metadata)) = hash_entry(entry?)? {
+            if metadata.is_file() {
+                orphans.push((hash, metadata.modified().unwrap_or(UNIX_EPOCH)));
+            }
+        }
+    }
+    Ok(orphans)
+}
+
+/// the orphans (parent, hash) with the time they were received, oldest
+/// first
+pub fn list(config: &StorageConfig) -> Result<Vec<(BlockHash, BlockHash, SystemTime)>> {
+    let mut orphans = Vec::new();
+    for entry in fs::read_dir(config.get_filetype_dir(StorageFileType::Orphan))? {
+        if let Some((parent, metadata)) = hash_entry(entry?)? {
+            if metadata.is_dir() {
+                for (hash, received) in children(config, &parent)? {
+                    orphans.push((parent, hash, received));
+                }
+            }
+        }
+    }
+    orphans.sort_by_key(|&(_, _, received)| received);
+    Ok(orphans)
+}
+
+/// drop the expired orphans, then the oldest ones above the maximum of
+/// the policy. Returns the number of orphans dropped.
+pub fn prune(config: &StorageConfig, policy: &OrphanPolicy) -> Result<usize> {
+    let orphans = list(config)?;
+    let now = SystemTime::now();
+    let excess = orphans.len().saturating_sub(policy.max_orphans);
+    let mut dropped = 0;
+    for (index, (parent, hash, received)) in orphans.into_iter().enumerate() {
+        let expired = now
+            .duration_since(received)
+            .map_or(false, |age| age > policy.expiry);
+        if index < excess || expired {
+            remove(config, &parent, &hash);
+            dropped += 1;
+        }
+    }
+    Ok(dropped)
+}
+
+/// append the orphans descending from `tip`, see `Storage::try_connect_orphans`.
+///
+/// An orphan that cannot be appended (not a valid block, or of another
+/// network) is dropped, its own children are left to expire.
+pub fn connect(storage: &Storage, tip: &HeaderHash) -> Result<Vec<HeaderHash>> {
+    let mut connected = Vec::new();
+    let mut parents = vec![header_to_blockhash(tip)];
+    while let Some(parent) = parents.pop() {
+        for (hash, _) in children(&storage.config, &parent)? {
+            let appended =
+                read(&storage.config, &parent, &hash).and_then(|raw| storage.append_block(&raw));
+            match appended {
+                Ok(block_hash) => {
+                    parents.push(header_to_blockhash(&block_hash));
+                    connected.push(block_hash);
+                }
+                Err(Error::StorageError(err)) => return Err(Error::StorageError(err)),
+                Err(err) => warn!("dropping invalid orphan {}: {}", hex::encode(&hash), err),
+            }
+            remove(&storage.config, &parent, &hash);
+        }
+    }
+    Ok(connected)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use cardano::block::fake::{self, FakeChain};
+    use cardano::block::Block;
+    use cardano::config::ProtocolMagic;
+    use test_utils::*;
+
+    fn hash(block: &Block) -> HeaderHash {
+        block.header().compute_hash()
+    }
+
+    #[test]
+    fn connect_the_descendants() {
+        let mut storage = temp_storage("orphan-connect");
+        storage.set_protocol_magic(ProtocolMagic::from(1));
+        let mut chain = fake_chain();
+        let blocks = next_epoch(&mut chain, 4);
+        write_blobs(&storage, &blocks[..1]);
+
+        // received out of order
+        for block in blocks[1..].iter().rev() {
+            storage.add_orphan(&fake::raw(block)).unwrap();
+        }
+        // another chain
+        let other = next_epoch(
+            &mut FakeChain::new(ProtocolMagic::from(1), HeaderHash::new(b"other")),
+            1,
+        );
+        storage.add_orphan(&fake::raw(&other[1])).unwrap();
+        assert_eq!(list(&storage.config).unwrap().len(), 5);
+
+        // nothing descends from the other chain
+        assert!(storage
+            .try_connect_orphans(&hash(&other[1]))
+            .unwrap()
+            .is_empty());
+
+        let connected = storage.try_connect_orphans(&hash(&blocks[0])).unwrap();
+        let expected: Vec<_> = blocks[1..].iter().map(hash).collect();
+        assert_eq!(connected, expected);
+        for block in &blocks[1..] {
+            assert!(storage.block_exists(&block_hash(block)).unwrap());
+        }
+        let left = list(&storage.config).unwrap();
+        assert_eq!(left.len(), 1);
+        assert_eq!(left[0].1, block_hash(&other[1]));
+        assert!(!storage
+            .config
+            .get_orphan_dir(&block_hash(&blocks[0]))
+            .exists());
+    }
+
+    #[test]
+    fn drop_the_orphans_failing_to_append() {
+        let mut storage = temp_storage("orphan-invalid");
+        storage.set_protocol_magic(ProtocolMagic::from(1));
+        let mut chain = fake_chain();
+        let blocks = next_epoch(&mut chain, 1);
+        write_blobs(&storage, &blocks[..1]);
+        let parent = block_hash(&blocks[0]);
+
+        storage.add_orphan(&fake::raw(&blocks[1])).unwrap();
+        // of another network, and not a block
+        let foreign = FakeChain::new(ProtocolMagic::from(2), hash(&blocks[0])).next_main();
+        write(
+            &storage,
+            &parent,
+            &block_hash(&foreign),
+            fake::raw(&foreign).as_ref(),
+        )
+        .unwrap();
+        write(&storage, &parent, &[1; HASH_SIZE], &[0x42]).unwrap();
+        assert_eq!(children(&storage.config, &parent).unwrap().len(), 3);
+
+        let connected = storage.try_connect_orphans(&hash(&blocks[0])).unwrap();
+        assert_eq!(connected, vec![hash(&blocks[1])]);
+        assert!(list(&storage.config).unwrap().is_empty());
+        assert!(!storage.block_exists(&block_hash(&foreign)).unwrap());
+    }
+
+    #[test]
+    fn prune_the_oldest() {
+        let storage = temp_storage("orphan-prune");
+        let blocks = next_epoch(&mut fake_chain(), 4);
+        for block in &blocks[1..] {
+            storage.add_orphan(&fake::raw(block)).unwrap();
+        }
+        let policy = OrphanPolicy {
+            max_orphans: 2,
+            expiry: Duration::from_secs(3600),
+        };
+        assert_eq!(prune(&storage.config, &policy).unwrap(), 2);
+        assert_eq!(list(&storage.config).unwrap().len(), 2);
+
+        let policy = OrphanPolicy {
+            max_orphans: 2,
+            expiry: Duration::from_secs(0),
+        };
+        ::std::thread::sleep(Duration::from_millis(10));
+        assert_eq!(prune(&storage.config, &policy).unwrap(), 2);
+        assert!(list(&storage.config).unwrap().is_empty());
+    }
+}
@@ -1,7 +1,14 @@
-use super::Result;
+use super::{blob, header_to_blockhash, BlockHash, Error, Result};
 use cardano;
-use std::fs;
-use storage_units::utils::tmpfile::TmpFile;
+use cardano::block::RawBlock;
+use std::collections::BTreeMap;
+use std::io::Write;
+use std::sync::mpsc::{sync_channel, Receiver};
+use std::sync::{Arc, Mutex};
+use std::{cmp, fs, io, thread};
+use storage_units::utils::error::StorageError;
+use storage_units::utils::serialize::{read_offset, write_offset, OFF_SIZE};
+use storage_units::utils::{magic, tmpfile::TmpFile};
 
 use storage_units::indexfile;
 use storage_units::packfile;
@@ -54,7 +61,8 @@ pub fn packwriter_finalize(
 ) -> (super::PackHash, indexfile::Index) {
     let (tmpfile, packhash, index) = writer.finalize().unwrap();
     let path = cfg.get_pack_filepath(&packhash);
-    tmpfile.render_permanent(&path).unwrap();
+    // the packed blobs may be deleted next: the pack must be on the disk first
+    tmpfile.render_permanent_synced(&path).unwrap();
     (packhash, index)
 }
 
@@ -71,3 +79,330 @@ pub fn packreader_block_next(
     let next = reader.next_block()?;
     Ok(next.map(|x| cardano::block::RawBlock(x)))
 }
+
+const PARTIAL_STATE_FILE_TYPE: magic::FileType = 0x50415254; // = PART
+const PARTIAL_STATE_VERSION: magic::Version = 1;
+
+/// Progress of a pack being written with `packwriter_init_resumable`,
+/// as recorded by the last `packwriter_checkpoint`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct PartialPackState {
+    /// number of bytes of the pack file known to be complete
+    pub bytes_written: u64,
+    /// hash of the last block written in the pack, if any
+    pub last_block: Option<super::BlockHash>,
+}
+
+impl PartialPackState {
+    fn write(&self, cfg: &super::StorageConfig, name: &str) -> Result<()> {
+        let mut buf = Vec::with_capacity(magic::HEADER_SIZE + OFF_SIZE + 1 + super::HASH_SIZE);
+        magic::write_header(&mut buf, PARTIAL_STATE_FILE_TYPE, PARTIAL_STATE_VERSION)?;
+        let mut ofs = [0u8; OFF_SIZE];
+        write_offset(&mut ofs, self.bytes_written);
+        buf.extend_from_slice(&ofs);
+        match self.last_block {
+            None => buf.push(0),
+            Some(ref hash) => {
+                buf.push(1);
+                buf.extend_from_slice(&hash[..]);
+            }
+        }
+        let path = cfg.get_partial_pack_state_filepath(name);
+        let mut tmpfile = TmpFile::create(cfg.get_filetype_dir(super::StorageFileType::Pack))?;
+        tmpfile.write_all(&buf)?;
+        tmpfile.render_permanent_synced(&path)?;
+        Ok(())
+    }
+
+    fn read(cfg: &super::StorageConfig, name: &str) -> Result<Option<Self>> {
+        use std::io::Read;
+
+        let mut file = match fs::File::open(cfg.get_partial_pack_state_filepath(name)) {
+            Err(ref err) if err.kind() == io::ErrorKind::NotFound => return Ok(None),
+            Err(err) => return Err(err.into()),
+            Ok(file) => file,
+        };
+        magic::check_header(
+            &mut file,
+            PARTIAL_STATE_FILE_TYPE,
+            PARTIAL_STATE_VERSION,
+            PARTIAL_STATE_VERSION,
+        )?;
+        let mut ofs = [0u8; OFF_SIZE];
+        file.read_exact(&mut ofs)?;
+        let mut has_block = [0u8; 1];
+        file.read_exact(&mut has_block)?;
+        let last_block = if has_block[0] == 0 {
+            None
+        } else {
+            let mut hash = [0u8; super::HASH_SIZE];
+            file.read_exact(&mut hash)?;
+            Some(hash)
+        };
+        Ok(Some(PartialPackState {
+            bytes_written: read_offset(&ofs),
+            last_block,
+        }))
+    }
+}
+
+fn remove_partial_pack(cfg: &super::StorageConfig, name: &str) {
+    let _ = fs::remove_file(cfg.get_partial_pack_state_filepath(name));
+    let _ = fs::remove_file(cfg.get_partial_pack_filepath(name));
+}
+
+/// Start writing a pack that can be resumed after an interruption
+/// (see `resume_packwriter`). `name` identifies the pack being written
+/// (e.g. `epoch-42`); any previous partial pack with this name is discarded.
+pub fn packwriter_init_resumable(
+    cfg: &super::StorageConfig,
+    name: &str,
+) -> Result<packfile::Writer> {
+    remove_partial_pack(cfg, name);
+    let tmpfile = TmpFile::open_or_create(cfg.get_partial_pack_filepath(name))?;
+    let writer = packfile::Writer::init(tmpfile)?;
+    Ok(writer)
+}
+
+/// Record the progress of a resumable pack writer. After an interruption,
+/// `resume_packwriter` restarts from the last checkpoint.
+///
+/// The pack is flushed to the disk before the checkpoint is written, so
+/// the checkpoint never describes blocks that could be lost.
+pub fn packwriter_checkpoint(
+    cfg: &super::StorageConfig,
+    name: &str,
+    writer: &mut packfile::Writer,
+) -> Result<PartialPackState> {
+    writer.sync()?;
+    let state = PartialPackState {
+        bytes_written: writer.pos(),
+        last_block: writer.last_hash().cloned(),
+    };
+    state.write(cfg, name)?;
+    Ok(state)
+}
+
+/// Resume a pack writer started with `packwriter_init_resumable`.
+///
+/// Returns `None` if there is no checkpoint for this name, otherwise the
+/// writer positioned right after the last checkpointed block, and the
+/// checkpoint itself so the caller knows which block to continue from.
+pub fn resume_packwriter(
+    cfg: &super::StorageConfig,
+    name: &str,
+) -> Result<Option<(packfile::Writer, PartialPackState)>> {
+    let state = match PartialPackState::read(cfg, name)? {
+        None => return Ok(None),
+        Some(state) => state,
+    };
+    let tmpfile = TmpFile::open_or_create(cfg.get_partial_pack_filepath(name))?;
+    let writer = packfile::Writer::resume(tmpfile, state.bytes_written, |data| {
+        cardano::block::RawBlock::from_dat(data.to_vec())
+            .decode_header()
+            .map(|hdr| super::header_to_blockhash(&hdr.compute_hash()))
+            .map_err(|err| io::Error::new(io::ErrorKind::InvalidData, err))
+    })?;
+    if writer.last_hash() != state.last_block.as_ref() {
+        return Err(Error::StorageError(StorageError::Corrupted(
+            "partial pack does not match its checkpoint",
+        )));
+    }
+    Ok(Some((writer, state)))
+}
+
+/// Finalize a resumable pack writer, see `packwriter_finalize`. The
+/// checkpoint associated with `name` is removed.
+pub fn packwriter_finalize_resumable(
+    cfg: &super::StorageConfig,
+    name: &str,
+    writer: packfile::Writer,
+) -> (super::PackHash, indexfile::Index) {
+    let result = packwriter_finalize(cfg, writer);
+    remove_partial_pack(cfg, name);
+    result
+}
+
+/// number of blobs in flight between two stages of
+/// `packwriter_append_blobs`, per worker
+const PIPELINE_BOUND: usize = 16;
+
+type PipelineItem = (usize, BlockHash, Result<Vec<u8>>);
+
+/// Append the blobs to the pack, in the given order, until the pack
+/// reaches `limit_size` (best effort, as for `PackParameters`). Returns
+/// the blobs appended.
+///
+/// Reading the blobs, checking them and writing them overlap:
+///
+/// ```text
+/// reader --> workers (decode the header, check its hash) --> writer
+/// ```
+///
+/// The reader and the `workers` run in their own threads, connected by
+/// bounded channels so they cannot get too far ahead of the writer (the
+/// calling thread).
+pub fn packwriter_append_blobs(
+    cfg: &super::StorageConfig,
+    writer: &mut packfile::Writer,
+    blobs: Vec<BlockHash>,
+    workers: usize,
+    limit_size: Option<u64>,
+) -> Result<Vec<BlockHash>> {
+    let workers = cmp::max(workers, 1);
+    let (read_tx, read_rx) = sync_channel::<PipelineItem>(PIPELINE_BOUND * workers);
+    let read_rx = Arc::new(Mutex::new(read_rx));
+    let (checked_tx, checked_rx) = sync_channel::<PipelineItem>(PIPELINE_BOUND * workers);
+
+    let reader = {
+        let cfg = cfg.clone();
+        thread::spawn(move || {
+            for (index, hash) in blobs.into_iter().enumerate() {
+                let blob = blob::read_raw_with_config(&cfg, &hash);
+                if read_tx.send((index, hash, blob)).is_err() {
+                    // the writer is done
+                    break;
+                }
+            }
+        })
+    };
+    let workers: Vec<_> = (0..workers)
+        .map(|_| {
+            let read_rx = read_rx.clone();
+            let checked_tx = checked_tx.clone();
+            thread::spawn(move || loop {
+                let next = read_rx.lock().unwrap().recv();
+                let (index, hash, blob) = match next {
+                    Ok(item) => item,
+                    Err(_) => break,
+                };
+                let blob = blob.and_then(|blob| check_blob(&hash, blob));
+                if checked_tx.send((index, hash, blob)).is_err() {
+                    break;
+                }
+            })
+        })
+        .collect();
+    // only the threads hold the ends of the channels: once the writer
+    // drops `checked_rx`, the workers stop, dropping the last `read_rx`,
+    // which stops the reader.
+    drop(checked_tx);
+    drop(read_rx);
+
+    let result = append_in_order(writer, checked_rx, limit_size);
+    for worker in workers {
+        worker.join().expect("pack worker thread");
+    }
+    reader.join().expect("pack reader thread");
+    result
+}
+
+fn check_blob(hash: &BlockHash, blob: Vec<u8>) -> Result<Vec<u8>> {
+    let raw = RawBlock::from_dat(blob);
+    let header_hash = raw.decode_header()?.compute_hash();
+    if &header_to_blockhash(&header_hash) != hash {
+        return Err(Error::BlobHashMismatch(hash.clone(), header_hash));
+    }
+    Ok(raw.0)
+}
+
+fn append_in_order(
+    writer: &mut packfile::Writer,
+    checked: Receiver<PipelineItem>,
+    limit_size: Option<u64>,
+) -> Result<Vec<BlockHash>> {
+    // the blobs checked ahead of the next one to write
+    let mut pending = BTreeMap::new();
+    let mut packed = Vec::new();
+    for (index, hash, blob) in checked {
+        pending.insert(index, (hash, blob));
+        while let Some((hash, blob)) = pending.remove(&packed.len()) {
+            writer.append(&hash, &blob?)?;
+            packed.push(hash);
+            if limit_size.map_or(false, |sz| writer.pos() >= sz) {
+                return Ok(packed);
+            }
+        }
+    }
+    Ok(packed)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use cardano::block::fake;
+    use test_utils::*;
+    use {PackHash, StorageConfig};
+
+    fn read_pack(cfg: &StorageConfig, packhash: &PackHash) -> Vec<Vec<u8>> {
+        let mut reader = packreader_init(cfg, packhash);
+        let mut blocks = Vec::new();
+        while let Some(block) = packreader_block_next(&mut reader).unwrap() {
+            blocks.push(block.0);
+        }
+        blocks
+    }
+
+    #[test]
+    fn resume_from_checkpoint() {
+        let storage = temp_storage("resume-from-checkpoint");
+        let cfg = &storage.config;
+        let blocks = next_epoch(&mut fake_chain(), 5);
+        let raws: Vec<_> = blocks.iter().map(|b| fake::raw(b).0).collect();
+
+        assert!(resume_packwriter(cfg, "epoch-0").unwrap().is_none());
+
+        let mut writer = packwriter_init_resumable(cfg, "epoch-0").unwrap();
+        for (block, raw) in blocks.iter().zip(raws.iter()).take(3) {
+            writer.append(&block_hash(block), raw).unwrap();
+        }
+        let checkpoint = packwriter_checkpoint(cfg, "epoch-0", &mut writer).unwrap();
+        assert_eq!(checkpoint.last_block, Some(block_hash(&blocks[2])));
+        // interrupted after writing a block past the checkpoint
+        writer.append(&block_hash(&blocks[3]), &raws[3]).unwrap();
+        writer.flush().unwrap();
+        drop(writer);
+
+        let (mut writer, state) = resume_packwriter(cfg, "epoch-0").unwrap().unwrap();
+        assert_eq!(state, checkpoint);
+        assert_eq!(writer.pos(), checkpoint.bytes_written);
+        for (block, raw) in blocks.iter().zip(raws.iter()).skip(3) {
+            writer.append(&block_hash(block), raw).unwrap();
+        }
+        let (packhash, _) = packwriter_finalize_resumable(cfg, "epoch-0", writer);
+        assert!(resume_packwriter(cfg, "epoch-0").unwrap().is_none());
+        assert_eq!(read_pack(cfg, &packhash), raws);
+
+        // same pack as written in one go
+        let mut writer = packwriter_init(cfg).unwrap();
+        for (block, raw) in blocks.iter().zip(raws.iter()) {
+            writer.append(&block_hash(block), raw).unwrap();
+        }
+        assert_eq!(packwriter_finalize(cfg, writer).0, packhash);
+    }
+
+    #[test]
+    fn append_blobs_up_to_limit_size() {
+        let storage = temp_storage("append-blobs-limit");
+        let cfg = &storage.config;
+        let workers = 2;
+        // more blobs than the channels can hold, for the reader to be
+        // blocked when the writer stops
+        let blocks = next_epoch(&mut fake_chain(), 4 * PIPELINE_BOUND * workers);
+        write_blobs(&storage, &blocks);
+        let hashes: Vec<_> = blocks.iter().map(block_hash).collect();
+
+        let mut writer = packwriter_init(cfg).unwrap();
+        let packed =
+            packwriter_append_blobs(cfg, &mut writer, hashes.clone(), workers, Some(1)).unwrap();
+        assert_eq!(packed, &hashes[..1]);
+
+        let mut writer = packwriter_init(cfg).unwrap();
+        let packed =
+            packwriter_append_blobs(cfg, &mut writer, hashes.clone(), workers, None).unwrap();
+        assert_eq!(packed, hashes);
+        let (packhash, _) = packwriter_finalize(cfg, writer);
+        let raws: Vec<_> = blocks.iter().map(|b| fake::raw(b).0).collect();
+        assert_eq!(read_pack(cfg, &packhash), raws);
+    }
+}
@@ -0,0 +1,280 @@
+//! Compact commitment over the packed epochs of the chain.
+//!
+//! Every epoch is summarised by a rolling hash over the hashes of its blocks
+//! (taken from the epoch's refpack, so no block is read), and the chain by a
+//! rolling hash over the epochs' digests. Two deployments can exchange their
+//! `ChainProof` to check they hold the same chain, and find the first epoch
+//! where they differ if they do not.
+
+use super::{epoch, Result, Storage};
+use cardano::block::EpochId;
+use cardano::hash::Blake2b256;
+use cbor_event::{self, de::Deserializer, se::Serializer, Len};
+use std::io::{BufRead, Write};
+
+/// digest of the blocks of a packed epoch
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct EpochDigest {
+    pub epoch: EpochId,
+    /// number of blocks in the epoch pack
+    pub nb_blocks: u32,
+    pub digest: Blake2b256,
+}
+
+/// commitment over the epochs `0..epochs.len()`
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ChainProof {
+    pub epochs: Vec<EpochDigest>,
+    pub digest: Blake2b256,
+}
+
+fn roll(previous: &Blake2b256, hash: &[u8]) -> Blake2b256 {
+    let mut buf = Vec::with_capacity(Blake2b256::HASH_SIZE + hash.len());
+    buf.extend_from_slice(previous.as_ref());
+    buf.extend_from_slice(hash);
+    Blake2b256::new(&buf)
+}
+
+fn epoch_seed(epochid: EpochId) -> Blake2b256 {
+    let mut buf = [0u8; 8];
+    for (i, b) in buf.iter_mut().enumerate() {
+        *b = (epochid >> (56 - 8 * i)) as u8;
+    }
+    Blake2b256::new(&buf)
+}
+
+fn chain_digest(epochs: &[EpochDigest]) -> Blake2b256 {
+    epochs.iter().fold(
+        Blake2b256::from([0; Blake2b256::HASH_SIZE]),
+        |digest, epoch| roll(&digest, epoch.digest.as_ref()),
+    )
+}
+
+/// compute the digest of a packed epoch, `None` if the epoch is not packed
+pub fn epoch_digest(storage: &Storage, epochid: EpochId) -> Result<Option<EpochDigest>> {
+    if !epoch::epoch_exists(&storage.config, epochid)? {
+        return Ok(None);
+    }
+    let mut refpack = epoch::epoch_open_packref(&storage.config, epochid)?;
+    let mut digest = epoch_seed(epochid);
+    let mut nb_blocks = 0;
+    while let Some(hash) = refpack.next()? {
+        digest = roll(&digest, &hash);
+        nb_blocks += 1;
+    }
+    Ok(Some(EpochDigest {
+        epoch: epochid,
+        nb_blocks,
+        digest,
+    }))
+}
+
+/// compute the proof of the packed epochs of the storage, from epoch 0 up
+/// to the first epoch not packed (or `count` epochs at most if given).
+pub fn chain_proof(storage: &Storage, count: Option<EpochId>) -> Result<ChainProof> {
+    let mut epochs = Vec::new();
+    let mut epochid = 0;
+    while count.map_or(true, |count| epochid < count) {
+        match epoch_digest(storage, epochid)? {
+            None => break,
+            Some(digest) => epochs.push(digest),
+        }
+        epochid += 1;
+    }
+    let digest = chain_digest(&epochs);
+    Ok(ChainProof { epochs, digest })
+}
+
+/// Outcome of comparing two `ChainProof`s
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ProofComparison {
+    /// the proofs cover the same epochs, and they are identical
+    Identical,
+    /// the epochs covered by both proofs are identical, but one of the
+    /// proofs covers more epochs (the number of common epochs)
+    Prefix(usize),
+    /// the chains differ from this epoch on
+    Diverges(EpochId),
+}
+
+impl ChainProof {
+    /// number of epochs covered by the proof
+    pub fn len(&self) -> usize {
+        self.epochs.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.epochs.is_empty()
+    }
+
+    /// check the proof is well formed: the epochs are numbered from 0 and
+    /// the chain's digest matches the epochs' digests. To be done on a
+    /// proof received from another node before comparing it.
+    pub fn verify(&self) -> bool {
+        self.epochs
+            .iter()
+            .enumerate()
+            .all(|(i, epoch)| epoch.epoch == i as EpochId)
+            && chain_digest(&self.epochs) == self.digest
+    }
+
+    /// compare with the proof of another node
+    pub fn compare(&self, other: &ChainProof) -> ProofComparison {
+        match self
+            .epochs
+            .iter()
+            .zip(other.epochs.iter())
+            .find(|(a, b)| a != b)
+        {
+            Some((a, _)) => ProofComparison::Diverges(a.epoch),
+            None if self.len() == other.len() => ProofComparison::Identical,
+            None => ProofComparison::Prefix(::std::cmp::min(self.len(), other.len())),
+        }
+    }
+}
+
+impl cbor_event::se::Serialize for EpochDigest {
+    fn serialize<'se, W: Write>(
+        &self,
+        serializer: &'se mut Serializer<W>,
+    ) -> cbor_event::Result<&'se mut Serializer<W>> {
+        serializer
+            .write_array(Len::Len(3))?
+            .serialize(&self.epoch)?
+            .serialize(&self.nb_blocks)?
+            .serialize(&self.digest)
+    }
+}
+impl cbor_event::de::Deserialize for EpochDigest {
+    fn deserialize<R: BufRead>(reader: &mut Deserializer<R>) -> cbor_event::Result<Self> {
+        reader.tuple(3, "EpochDigest")?;
+        let epoch = reader.deserialize()?;
+        let nb_blocks = reader.deserialize()?;
+        let digest = reader.deserialize()?;
+        Ok(EpochDigest {
+            epoch,
+            nb_blocks,
+            digest,
+        })
+    }
+}
+
+impl cbor_event::se::Serialize for ChainProof {
+    fn serialize<'se, W: Write>(
+        &self,
+        serializer: &'se mut Serializer<W>,
+    ) -> cbor_event::Result<&'se mut Serializer<W>> {
+        serializer
+            .write_array(Len::Len(2))?
+            .serialize(&self.digest)?;
+        cbor_event::se::serialize_fixed_array(self.epochs.iter(), serializer)
+    }
+}
+impl cbor_event::de::Deserialize for ChainProof {
+    fn deserialize<R: BufRead>(reader: &mut Deserializer<R>) -> cbor_event::Result<Self> {
+        reader.tuple(2, "ChainProof")?;
+        let digest = reader.deserialize()?;
+        let epochs = reader.deserialize()?;
+        Ok(ChainProof { epochs, digest })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use cardano::block::Block;
+    use std::io::Cursor;
+    use test_utils::*;
+
+    /// a storage with the given epochs packed
+    fn packed(name: &str, epochs: &[Vec<Block>]) -> Storage {
+        let mut storage = temp_storage(name);
+        for (epochid, blocks) in epochs.iter().enumerate() {
+            pack_epoch(&mut storage, epochid as EpochId, blocks);
+        }
+        storage
+    }
+
+    #[test]
+    fn compare_proofs() {
+        let mut chain = fake_chain();
+        let first = next_epoch(&mut chain, 2);
+        let mut fork = chain.clone();
+        let second = next_epoch(&mut chain, 2);
+        let third = next_epoch(&mut chain, 2);
+        let forked = next_epoch(&mut fork, 3);
+
+        let storage = packed("proof-two", &[first.clone(), second.clone()]);
+        let same = packed("proof-same", &[first.clone(), second.clone()]);
+        let longer = packed("proof-longer", &[first.clone(), second, third]);
+        let diverging = packed("proof-diverging", &[first, forked]);
+
+        let proof = chain_proof(&storage, None).unwrap();
+        assert_eq!(proof.len(), 2);
+        assert_eq!(proof.epochs[0].nb_blocks, 3);
+        assert!(proof.verify());
+
+        let same = chain_proof(&same, None).unwrap();
+        assert_eq!(proof, same);
+        assert_eq!(proof.compare(&same), ProofComparison::Identical);
+
+        let longer = chain_proof(&longer, None).unwrap();
+        assert_eq!(longer.len(), 3);
+        assert_eq!(proof.compare(&longer), ProofComparison::Prefix(2));
+        assert_eq!(longer.compare(&proof), ProofComparison::Prefix(2));
+        assert_eq!(
+            chain_proof(&storage, Some(1))
+                .unwrap()
+                .compare(&chain_proof(&storage, None).unwrap()),
+            ProofComparison::Prefix(1)
+        );
+
+        let diverging = chain_proof(&diverging, None).unwrap();
+        assert_eq!(proof.compare(&diverging), ProofComparison::Diverges(1));
+        assert_eq!(diverging.compare(&longer), ProofComparison::Diverges(1));
+
+        assert!(epoch_digest(&storage, 2).unwrap().is_none());
+    }
+
+    #[test]
+    fn verify_proofs() {
+        let mut chain = fake_chain();
+        let storage = packed(
+            "proof-verify",
+            &[next_epoch(&mut chain, 1), next_epoch(&mut chain, 1)],
+        );
+        let proof = chain_proof(&storage, None).unwrap();
+        assert!(proof.verify());
+
+        let mut tampered = proof.clone();
+        tampered.epochs[1].digest = Blake2b256::new(b"tampered");
+        assert!(!tampered.verify());
+
+        let mut renumbered = proof.clone();
+        renumbered.epochs.remove(0);
+        renumbered.digest = chain_digest(&renumbered.epochs);
+        assert!(!renumbered.verify());
+
+        let empty = chain_proof(&temp_storage("proof-empty"), None).unwrap();
+        assert!(empty.is_empty());
+        assert!(empty.verify());
+    }
+
+    #[test]
+    fn proof_cbor_round_trip() {
+        let mut chain = fake_chain();
+        let storage = packed(
+            "proof-cbor",
+            &[next_epoch(&mut chain, 2), next_epoch(&mut chain, 1)],
+        );
+        let proof = chain_proof(&storage, None).unwrap();
+
+        let mut serializer = Serializer::new_vec();
+        serializer.serialize(&proof).unwrap();
+        let bytes = serializer.finalize();
+        let mut raw = Deserializer::from(Cursor::new(&bytes));
+        let decoded: ChainProof = raw.deserialize().unwrap();
+        assert_eq!(decoded, proof);
+        assert!(decoded.verify());
+    }
+}
@@ -0,0 +1,291 @@
+//! The reorganisations of the local chain
+//!
+//! Every move of the `HEAD` tag is recorded in the tag journal (see
+//! `tag::history`). A move to a block that does not descend from the
+//! previous `HEAD` is a reorganisation: the blocks of the previous branch
+//! after the fork point are abandoned, in favour of the new branch.
+//!
+//! Only the blocks of the unstable epochs can be abandoned, and those are
+//! kept as loose blocks: both branches can be walked back to their fork
+//! point. The moves involving a block that is not in the storage (e.g. it
+//! was removed since) are skipped.
+//!
+//! `ReorgLog` follows the journal incrementally, only walking the branches
+//! of the moves recorded since its previous update.
+
+use super::{tag, Error, Result, Storage};
+use cardano::block::{BlockDate, BlockHeader, HeaderHash};
+use cardano::util::try_from_slice::TryFromSlice;
+use std::time::Duration;
+
+/// A switch of the local chain to another branch.
+#[derive(Debug, Clone)]
+pub struct Reorg {
+    /// when `HEAD` was moved to the new branch (time elapsed since the
+    /// UNIX epoch)
+    pub timestamp: Duration,
+    /// the most recent block common to both branches
+    pub fork_point: HeaderHash,
+    pub fork_date: BlockDate,
+    /// the blocks of the previous branch, from its tip down to the fork
+    /// point (excluded)
+    pub abandoned: Vec<HeaderHash>,
+    /// the tip of the adopted branch, when `HEAD` was moved
+    pub adopted_tip: HeaderHash,
+    pub adopted_date: BlockDate,
+    /// number of blocks of the adopted branch after the fork point
+    pub adopted_length: usize,
+}
+
+/// the header of the block, `None` if the block is not in the storage
+fn read_header(storage: &Storage, hash: &HeaderHash) -> Result<Option<BlockHeader>> {
+    match storage.read_block(&hash.clone().into()) {
+        Ok(raw) => Ok(Some(raw.decode_header()?)),
+        Err(Error::BlockNotFound(_)) => Ok(None),
+        Err(err) => Err(err),
+    }
+}
+
+/// Walk back both branches until their common block. Returns the fork
+/// point, the blocks of the previous branch after it (most recent first)
+/// and the number of blocks of the adopted branch after it, or `None` if
+/// a block of either branch is missing.
+fn fork(
+    storage: &Storage,
+    previous: &HeaderHash,
+    adopted: &HeaderHash,
+) -> Result<Option<(BlockHeader, Vec<HeaderHash>, usize)>> {
+    macro_rules! header {
+        ($hash:expr) => {
+            match read_header(storage, $hash)? {
+                Some(header) => header,
+                None => return Ok(None),
+            }
+        };
+    }
+
+    let mut previous = header!(previous);
+    let mut adopted = header!(adopted);
+    let mut abandoned = Vec::new();
+    let mut adopted_length = 0;
+    loop {
+        let (previous_hash, adopted_hash) = (previous.compute_hash(), adopted.compute_hash());
+        if previous_hash == adopted_hash {
+            return Ok(Some((previous, abandoned, adopted_length)));
+        }
+        // the dates only grow along a chain: the block with the most
+        // recent date is not part of the other branch
+        let (previous_date, adopted_date) = (previous.blockdate(), adopted.blockdate());
+        if previous_date >= adopted_date {
+            abandoned.push(previous_hash);
+            previous = header!(&previous.previous_header());
+        }
+        if adopted_date >= previous_date {
+            adopted_length += 1;
+            adopted = header!(&adopted.previous_header());
+        }
+    }
+}
+
+/// The reorganisations of the local chain recorded in the tag journal,
+/// updated incrementally.
+#[derive(Debug, Clone, Default)]
+pub struct ReorgLog {
+    /// number of the changes of `HEAD` already processed
+    changes: usize,
+    reorgs: Vec<Reorg>,
+}
+impl ReorgLog {
+    pub fn new() -> Self {
+        ReorgLog::default()
+    }
+
+    /// the reorganisations found so far, oldest first
+    pub fn reorgs(&self) -> &[Reorg] {
+        &self.reorgs
+    }
+
+    /// Process the changes of `HEAD` recorded since the previous update,
+    /// returns the reorganisations found in them.
+    ///
+    /// On error, the changes processed before the error are kept: the next
+    /// update resumes from the failing change.
+    pub fn update(&mut self, storage: &Storage) -> Result<&[Reorg]> {
+        let known = self.reorgs.len();
+        let changes = storage.tag_history(tag::HEAD)?;
+        for change in changes.iter().skip(self.changes) {
+            if let Some(reorg) = reorg(storage, change)? {
+                self.reorgs.push(reorg);
+            }
+            self.changes += 1;
+        }
+        Ok(&self.reorgs[known..])
+    }
+}
+
+/// the reorganisation made by the change of `HEAD`, if any
+fn reorg(storage: &Storage, change: &tag::TagChange) -> Result<Option<Reorg>> {
+    let (previous, adopted) = match (&change.previous, &change.value) {
+        (Some(previous), Some(adopted)) => (previous, adopted),
+        _ => return Ok(None),
+    };
+    let (previous, adopted) = match (
+        HeaderHash::try_from_slice(previous),
+        HeaderHash::try_from_slice(adopted),
+    ) {
+        (Ok(previous), Ok(adopted)) => (previous, adopted),
+        _ => return Ok(None),
+    };
+    if previous == adopted {
+        return Ok(None);
+    }
+
+    let (fork_point, abandoned, adopted_length) = match fork(storage, &previous, &adopted)? {
+        None => return Ok(None),
+        Some(fork) => fork,
+    };
+    if abandoned.is_empty() {
+        return Ok(None);
+    }
+    let adopted_date = match read_header(storage, &adopted)? {
+        None => return Ok(None),
+        Some(header) => header.blockdate(),
+    };
+    Ok(Some(Reorg {
+        timestamp: change.timestamp,
+        fork_point: fork_point.compute_hash(),
+        fork_date: fork_point.blockdate(),
+        abandoned,
+        adopted_tip: adopted,
+        adopted_date,
+        adopted_length,
+    }))
+}
+
+/// every reorganisation of the local chain recorded in the tag journal,
+/// oldest first, see `ReorgLog` to follow the journal.
+///
+/// The moves of `HEAD` along the same branch (the new blocks appended to
+/// the chain) are not reported. A move of `HEAD` back to one of its
+/// ancestors is reported with an `adopted_length` of 0.
+pub fn reorgs(storage: &Storage) -> Result<Vec<Reorg>> {
+    let mut log = ReorgLog::new();
+    log.update(storage)?;
+    Ok(log.reorgs)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use cardano::block::{Block, EpochSlotId};
+    use test_utils::*;
+
+    fn hash(block: &Block) -> HeaderHash {
+        block.header().compute_hash()
+    }
+
+    /// a chain of the boundary block and 3 main blocks, and a fork of
+    /// it after its first main block
+    fn branches() -> (Vec<Block>, Block) {
+        let mut chain = fake_chain();
+        let mut blocks = vec![chain.next_boundary(), chain.next_main()];
+        let mut fork = chain.clone();
+        blocks.push(chain.next_main());
+        blocks.push(chain.next_main());
+        let forked = fork.main_at(EpochSlotId {
+            epoch: 0,
+            slotid: 5,
+        });
+        (blocks, forked)
+    }
+
+    #[test]
+    fn moves_along_the_chain() {
+        let storage = temp_storage("reorg-linear");
+        let (blocks, _) = branches();
+        write_blobs(&storage, &blocks);
+        for block in blocks.iter() {
+            tag::write_hash(&storage, &tag::HEAD, &hash(block));
+        }
+        assert!(reorgs(&storage).unwrap().is_empty());
+    }
+
+    #[test]
+    fn switch_branch_and_rollback() {
+        let storage = temp_storage("reorg-fork");
+        let (blocks, forked) = branches();
+        write_blobs(&storage, &blocks);
+        write_blobs(&storage, &[forked.clone()]);
+
+        tag::write_hash(&storage, &tag::HEAD, &hash(&blocks[1]));
+        tag::write_hash(&storage, &tag::HEAD, &hash(&blocks[3]));
+        tag::write_hash(&storage, &tag::HEAD, &hash(&forked));
+        tag::write_hash(&storage, &tag::HEAD, &hash(&blocks[0]));
+
+        let reorgs = reorgs(&storage).unwrap();
+        assert_eq!(reorgs.len(), 2);
+
+        assert_eq!(reorgs[0].fork_point, hash(&blocks[1]));
+        assert_eq!(
+            reorgs[0].abandoned,
+            vec![hash(&blocks[3]), hash(&blocks[2])]
+        );
+        assert_eq!(reorgs[0].adopted_tip, hash(&forked));
+        assert_eq!(reorgs[0].adopted_length, 1);
+
+        // back to an ancestor
+        assert_eq!(reorgs[1].fork_point, hash(&blocks[0]));
+        assert_eq!(reorgs[1].abandoned, vec![hash(&forked), hash(&blocks[1])]);
+        assert_eq!(reorgs[1].adopted_length, 0);
+    }
+
+    #[test]
+    fn incremental_updates() {
+        let storage = temp_storage("reorg-log");
+        let (blocks, forked) = branches();
+        write_blobs(&storage, &blocks);
+        write_blobs(&storage, &[forked.clone()]);
+        let mut log = ReorgLog::new();
+        assert!(log.update(&storage).unwrap().is_empty());
+
+        tag::write_hash(&storage, &tag::HEAD, &hash(&blocks[3]));
+        tag::write_hash(&storage, &tag::HEAD, &hash(&forked));
+        assert_eq!(log.update(&storage).unwrap().len(), 1);
+        assert!(log.update(&storage).unwrap().is_empty());
+
+        tag::write_hash(&storage, &tag::HEAD, &hash(&blocks[2]));
+        let new = log.update(&storage).unwrap();
+        assert_eq!(new.len(), 1);
+        assert_eq!(new[0].adopted_tip, hash(&blocks[2]));
+        assert_eq!(log.reorgs().len(), 2);
+    }
+
+    #[test]
+    fn skip_the_missing_blocks() {
+        let storage = temp_storage("reorg-gap");
+        let (blocks, forked) = branches();
+        // the first main block is missing: the branches can not be walked
+        // back to their fork point
+        write_blobs(&storage, &[blocks[0].clone()]);
+        write_blobs(&storage, &blocks[2..]);
+        write_blobs(&storage, &[forked.clone()]);
+
+        tag::write_hash(&storage, &tag::HEAD, &hash(&blocks[3]));
+        tag::write_hash(&storage, &tag::HEAD, &hash(&forked));
+        // a block never written
+        tag::write_hash(&storage, &tag::HEAD, &HeaderHash::new(b"unknown"));
+        tag::write_hash(&storage, &tag::HEAD, &hash(&blocks[0]));
+        // the forked block only reaches the boundary block through the
+        // missing block
+        tag::write_hash(&storage, &tag::HEAD, &hash(&forked));
+        tag::write_hash(&storage, &tag::HEAD, &hash(&blocks[0]));
+        assert!(reorgs(&storage).unwrap().is_empty());
+
+        write_blobs(&storage, &[blocks[1].clone()]);
+        let reorgs = reorgs(&storage).unwrap();
+        assert_eq!(reorgs.len(), 2);
+        assert_eq!(reorgs[0].adopted_tip, hash(&forked));
+        assert_eq!(reorgs[1].adopted_tip, hash(&blocks[0]));
+        assert_eq!(reorgs[1].adopted_length, 0);
+    }
+}
@@ -0,0 +1,101 @@
+//! `chain_core::store::BlockStore` on top of the `Storage`, for the tools
+//! written against the chain-core abstractions to work on the local
+//! chain.
+
+use super::{blob, header_to_blockhash, tag, Error, Result, Storage};
+use cardano::block::{Block, HeaderHash, RawBlock};
+use cbor_event::se::Serializer;
+use chain_core::store::BlockStore;
+
+impl BlockStore for Storage {
+    type Block = Block;
+    type Error = Error;
+
+    fn get_block(&self, id: &HeaderHash) -> Result<Option<Block>> {
+        match self.read_block(&header_to_blockhash(id)) {
+            Ok(raw) => Ok(Some(raw.decode()?)),
+            Err(Error::BlockNotFound(_)) => Ok(None),
+            Err(err) => Err(err),
+        }
+    }
+
+    /// the block is written as a loose block, see `Storage::append_block`.
+    ///
+    /// The decoded block is serialized again: a block already stored is
+    /// left as it is, to keep the bytes it was received with, and the
+    /// serialized block must have the hash of the block.
+    fn put_block(&mut self, block: &Block) -> Result<()> {
+        let hash = block.header().compute_hash();
+        let blockhash = header_to_blockhash(&hash);
+        if Storage::block_exists(self, &blockhash)? {
+            return Ok(());
+        }
+        self.check_block(block)?;
+
+        let mut serializer = Serializer::new_vec();
+        serializer.serialize(block)?;
+        let raw = RawBlock::from_dat(serializer.finalize());
+        let written = raw.decode_header()?.compute_hash();
+        if written != hash {
+            return Err(Error::BlobHashMismatch(blockhash, written));
+        }
+        blob::write(self, &blockhash, raw.as_ref())
+    }
+
+    fn block_exists(&self, id: &HeaderHash) -> Result<bool> {
+        Storage::block_exists(self, &header_to_blockhash(id))
+    }
+
+    /// the `HEAD` tag
+    fn tip(&self) -> Result<Option<HeaderHash>> {
+        Ok(tag::read_hash(self, &tag::HEAD))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use cardano::block::fake;
+    use test_utils::*;
+
+    #[test]
+    fn get_and_put_blocks() {
+        let mut storage = temp_storage("store-get-put");
+        let blocks = next_epoch(&mut fake_chain(), 3);
+        let id = |block: &Block| block.header().compute_hash();
+
+        assert_eq!(BlockStore::tip(&storage).unwrap(), None);
+        for block in &blocks {
+            assert!(!BlockStore::block_exists(&storage, &id(block)).unwrap());
+            assert!(storage.get_block(&id(block)).unwrap().is_none());
+        }
+
+        // received as is
+        storage.append_block(&fake::raw(&blocks[0])).unwrap();
+        for block in &blocks[1..] {
+            storage.put_block(block).unwrap();
+        }
+        // already stored
+        storage.put_block(&blocks[0]).unwrap();
+        tag::write_hash(&storage, &tag::HEAD, &id(&blocks[3]));
+        assert_eq!(BlockStore::tip(&storage).unwrap(), Some(id(&blocks[3])));
+
+        for block in &blocks {
+            assert!(BlockStore::block_exists(&storage, &id(block)).unwrap());
+            let got = storage.get_block(&id(block)).unwrap().unwrap();
+            assert_eq!(id(&got), id(block));
+            // byte for byte
+            let raw = storage.read_block(&block_hash(block)).unwrap();
+            assert_eq!(raw.as_ref(), fake::raw(block).as_ref());
+            assert_eq!(raw.decode_header().unwrap().compute_hash(), id(block));
+        }
+
+        let ancestors: Vec<_> = storage
+            .ancestors(id(&blocks[3]))
+            .map(|block| id(&block.unwrap()))
+            .collect();
+        let expected: Vec<_> = blocks.iter().rev().map(id).collect();
+        assert_eq!(ancestors, expected);
+        assert_eq!(storage.ancestors(HeaderHash::new(b"unknown")).count(), 0);
+    }
+}
@@ -1,6 +1,18 @@
+//! Tags: named pointers to blocks (`HEAD`, the epochs...).
+//!
+//! A tag is written to a temporary file renamed over the tag's file, so a
+//! tag is always either the old or the new value. Unless disabled with
+//! `StorageConfig::sync_writes`, the write is also durable when `write`
+//! returns: the tag's file and its directory are flushed to the disk, then
+//! the journal. The loose blocks are flushed when written (see
+//! `blob::write`), so after a power loss a tag never points to a block, or
+//! to a chain of blocks, that was not fully written.
+
 use cardano::util::{hex, try_from_slice::TryFromSlice};
-use std::io::{Read, Write};
+use std::io::{self, BufRead, BufReader, Read, Seek, SeekFrom, Write};
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
 use std::{fs, path::PathBuf};
+use storage_units::utils::tmpfile;
 
 use cardano::block;
 
@@ -12,6 +24,7 @@ pub fn get_epoch_tag(epoch: block::EpochId) -> String {
 }
 
 pub fn write<S: AsRef<str>>(storage: &super::Storage, name: &S, content: &[u8]) {
+    let previous = read(storage, name);
     let mut tmp_file = super::tmpfile_create_type(storage, super::StorageFileType::Tag);
     tmp_file.write_all(hex::encode(content).as_bytes()).unwrap();
 
@@ -27,9 +40,21 @@ pub fn write<S: AsRef<str>>(storage: &super::Storage, name: &S, content: &[u8])
         }
     };
 
-    tmp_file
-        .render_permanent(&storage.config.get_tag_filepath(name))
-        .unwrap();
+    if storage.config.sync_writes {
+        tmp_file
+            .render_permanent_synced(&storage.config.get_tag_filepath(name))
+            .unwrap();
+    } else {
+        tmp_file
+            .render_permanent(&storage.config.get_tag_filepath(name))
+            .unwrap();
+    }
+
+    journal_append(
+        storage,
+        &TagChange::new(name, previous, Some(content.to_vec())),
+    )
+    .unwrap();
 }
 
 pub fn write_hash<S: AsRef<str>>(storage: &super::Storage, name: &S, content: &block::HeaderHash) {
@@ -60,6 +85,302 @@ pub fn exist<S: AsRef<str>>(storage: &super::Storage, name: &S) -> bool {
 }
 
 pub fn remove_tag<S: AsRef<str>>(storage: &super::Storage, name: &S) {
+    let previous = read(storage, name);
     let p = storage.config.get_tag_filepath(name);
-    fs::remove_file(p).unwrap()
+    fs::remove_file(&p).unwrap();
+    if storage.config.sync_writes {
+        if let Some(parent) = p.parent() {
+            tmpfile::sync_dir(parent).unwrap();
+        }
+    }
+
+    journal_append(storage, &TagChange::new(name, previous, None)).unwrap();
+}
+
+/// A change of a tag, as recorded in the tag journal.
+///
+/// The journal is an append only text file, one change per line:
+///
+/// ```text
+/// <seconds since the UNIX epoch> <name> <previous value> <new value>
+/// ```
+///
+/// the values are hex encoded, `-` standing for a tag that did not
+/// exist (before its creation or after its removal). The whitespaces and
+/// `%` of the name are escaped as `%` followed by their hex encoded byte.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct TagChange {
+    /// time elapsed since the UNIX epoch
+    pub timestamp: Duration,
+    pub name: String,
+    pub previous: Option<Vec<u8>>,
+    /// `None` if the tag was removed
+    pub value: Option<Vec<u8>>,
+}
+impl TagChange {
+    fn new<S: AsRef<str>>(name: &S, previous: Option<Vec<u8>>, value: Option<Vec<u8>>) -> Self {
+        let timestamp = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .unwrap_or(Duration::from_secs(0));
+        TagChange {
+            timestamp,
+            name: name.as_ref().to_string(),
+            previous,
+            value,
+        }
+    }
+
+    fn to_line(&self) -> String {
+        format!(
+            "{} {} {} {}\n",
+            self.timestamp.as_secs(),
+            escape_name(&self.name),
+            encode_value(&self.previous),
+            encode_value(&self.value)
+        )
+    }
+
+    fn from_line(line: &str) -> Option<Self> {
+        let mut fields = line.split(' ');
+        let secs = fields.next()?.parse().ok()?;
+        let name = unescape_name(fields.next()?)?;
+        let previous = decode_value(fields.next()?)?;
+        let value = decode_value(fields.next()?)?;
+        if fields.next().is_some() {
+            return None;
+        }
+        Some(TagChange {
+            timestamp: Duration::from_secs(secs),
+            name,
+            previous,
+            value,
+        })
+    }
+}
+
+fn escape_name(name: &str) -> String {
+    let mut escaped = String::with_capacity(name.len());
+    for c in name.chars() {
+        if c == '%' || c.is_whitespace() {
+            let mut buf = [0; 4];
+            for byte in c.encode_utf8(&mut buf).bytes() {
+                escaped.push_str(&format!("%{:02x}", byte));
+            }
+        } else {
+            escaped.push(c);
+        }
+    }
+    escaped
+}
+
+fn unescape_name(s: &str) -> Option<String> {
+    let mut bytes = Vec::with_capacity(s.len());
+    let mut input = s.bytes();
+    while let Some(byte) = input.next() {
+        if byte == b'%' {
+            let hi = input.next()?;
+            let lo = input.next()?;
+            let hex = [hi, lo];
+            bytes.push(u8::from_str_radix(::std::str::from_utf8(&hex).ok()?, 16).ok()?);
+        } else {
+            bytes.push(byte);
+        }
+    }
+    String::from_utf8(bytes).ok()
+}
+
+fn encode_value(value: &Option<Vec<u8>>) -> String {
+    match value {
+        None => "-".to_string(),
+        Some(v) => hex::encode(v),
+    }
+}
+
+fn decode_value(s: &str) -> Option<Option<Vec<u8>>> {
+    if s == "-" {
+        Some(None)
+    } else {
+        hex::decode(s).ok().map(Some)
+    }
+}
+
+/// the offset of the end of the last complete line of the journal: after
+/// a crash, the last line may have been partially written.
+fn journal_complete_len(file: &mut fs::File) -> io::Result<u64> {
+    const CHUNK_SIZE: u64 = 512;
+    let mut end = file.seek(SeekFrom::End(0))?;
+    let mut buf = [0; CHUNK_SIZE as usize];
+    while end > 0 {
+        let start = end.saturating_sub(CHUNK_SIZE);
+        let chunk = &mut buf[..(end - start) as usize];
+        file.seek(SeekFrom::Start(start))?;
+        file.read_exact(chunk)?;
+        if let Some(i) = chunk.iter().rposition(|b| *b == b'\n') {
+            return Ok(start + i as u64 + 1);
+        }
+        end = start;
+    }
+    Ok(0)
+}
+
+fn journal_append(storage: &super::Storage, change: &TagChange) -> io::Result<()> {
+    let mut file = fs::OpenOptions::new()
+        .create(true)
+        .read(true)
+        .append(true)
+        .open(storage.config.get_tag_journal_filepath())?;
+    // drop a partial line, so the change starts on a line of its own
+    let len = file.seek(SeekFrom::End(0))?;
+    let complete_len = journal_complete_len(&mut file)?;
+    if complete_len != len {
+        file.set_len(complete_len)?;
+    }
+    file.write_all(change.to_line().as_bytes())?;
+    if storage.config.sync_writes {
+        file.sync_data()?;
+    }
+    Ok(())
+}
+
+/// every recorded change of the tag `name`, oldest first
+///
+/// the changes made before the journal was introduced are not known. A
+/// last line without its end of line was partially written (e.g. on a
+/// power loss) and is ignored.
+pub fn history<S: AsRef<str>>(storage: &super::Storage, name: &S) -> io::Result<Vec<TagChange>> {
+    let file = match fs::File::open(storage.config.get_tag_journal_filepath()) {
+        Ok(file) => file,
+        Err(ref err) if err.kind() == io::ErrorKind::NotFound => return Ok(Vec::new()),
+        Err(err) => return Err(err),
+    };
+    let mut changes = Vec::new();
+    let mut reader = BufReader::new(file);
+    let mut line = Vec::new();
+    loop {
+        line.clear();
+        if reader.read_until(b'\n', &mut line)? == 0 || line.last() != Some(&b'\n') {
+            break;
+        }
+        let text = String::from_utf8_lossy(&line[..line.len() - 1]);
+        let change = TagChange::from_line(&text).ok_or_else(|| {
+            io::Error::new(
+                io::ErrorKind::InvalidData,
+                format!("invalid tag journal entry `{}'", text),
+            )
+        })?;
+        if change.name == name.as_ref() {
+            changes.push(change);
+        }
+    }
+    Ok(changes)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use test_utils::*;
+
+    #[test]
+    fn line_round_trip() {
+        for name in &[
+            "HEAD",
+            "my wallet",
+            "100%",
+            "tab\tand\nnewline",
+            "espace\u{a0}insécable",
+        ] {
+            let change = TagChange::new(name, None, Some(vec![1, 2, 3]));
+            let line = change.to_line();
+            assert_eq!(line.matches(' ').count(), 3);
+            assert_eq!(line.matches('\n').count(), 1);
+            assert_eq!(TagChange::from_line(line.trim_end()), Some(change));
+        }
+    }
+
+    #[test]
+    fn invalid_lines() {
+        assert_eq!(TagChange::from_line("0 HEAD - 00 00"), None);
+        assert_eq!(TagChange::from_line("0 HEAD -"), None);
+        assert_eq!(TagChange::from_line("0 HE%2 - -"), None);
+        assert_eq!(TagChange::from_line("0 HE%zz - -"), None);
+        assert_eq!(TagChange::from_line("x HEAD - -"), None);
+    }
+
+    #[test]
+    fn history_of_tags() {
+        let storage = temp_storage("tag-history");
+        write(&storage, &"my wallet", &[1]);
+        write(&storage, &"my", &[2]);
+        write(&storage, &"my wallet", &[3]);
+        remove_tag(&storage, &"my wallet");
+
+        let changes: Vec<_> = history(&storage, &"my wallet")
+            .unwrap()
+            .into_iter()
+            .map(|change| (change.previous, change.value))
+            .collect();
+        assert_eq!(
+            changes,
+            vec![
+                (None, Some(vec![1])),
+                (Some(vec![1]), Some(vec![3])),
+                (Some(vec![3]), None),
+            ]
+        );
+        assert_eq!(history(&storage, &"my").unwrap().len(), 1);
+        assert_eq!(history(&storage, &"other").unwrap(), vec![]);
+    }
+
+    #[test]
+    fn partial_journal_line() {
+        let storage = temp_storage("tag-partial-line");
+        write(&storage, &HEAD, &[1]);
+        write(&storage, &HEAD, &[2]);
+
+        // a change interrupted while being written
+        let mut journal = fs::OpenOptions::new()
+            .append(true)
+            .open(storage.config.get_tag_journal_filepath())
+            .unwrap();
+        journal.write_all(b"1234 HEAD 02 0").unwrap();
+        fn values(storage: &::Storage) -> Vec<Option<Vec<u8>>> {
+            history(storage, &HEAD)
+                .unwrap()
+                .into_iter()
+                .map(|change| change.value)
+                .collect()
+        }
+        assert_eq!(values(&storage), vec![Some(vec![1]), Some(vec![2])]);
+
+        // the next change replaces the partial line
+        write(&storage, &HEAD, &[3]);
+        assert_eq!(
+            values(&storage),
+            vec![Some(vec![1]), Some(vec![2]), Some(vec![3])]
+        );
+        let mut content = String::new();
+        fs::File::open(storage.config.get_tag_journal_filepath())
+            .unwrap()
+            .read_to_string(&mut content)
+            .unwrap();
+        assert_eq!(content.lines().count(), 3);
+    }
+
+    #[test]
+    fn writes_without_sync() {
+        let mut config = temp_storage("tag-no-sync").config;
+        config.sync_writes = false;
+        let storage = super::super::Storage::init(&config).unwrap();
+
+        let block = fake_chain().next_boundary();
+        write_blobs(&storage, &[block.clone()]);
+        let hash = block.header().compute_hash();
+        write_hash(&storage, &HEAD, &hash);
+        assert_eq!(read_hash(&storage, &HEAD), Some(hash.clone()));
+        assert!(storage.block_exists(&hash.into()).unwrap());
+
+        remove_tag(&storage, &HEAD);
+        assert_eq!(read_hash(&storage, &HEAD), None);
+        assert_eq!(history(&storage, &HEAD).unwrap().len(), 2);
+    }
 }
@@ -0,0 +1,55 @@
+//! Helpers for the tests: storages in temporary directories, filled with
+//! fake chains (see `cardano::block::fake`).
+
+use super::{blob, epoch, header_to_blockhash, pack, PackHash, Storage, StorageConfig};
+use cardano::block::fake::{self, FakeChain};
+use cardano::block::{Block, EpochId, HeaderHash};
+use cardano::config::ProtocolMagic;
+use rand;
+use std::env;
+
+/// a new storage, in its own temporary directory
+pub fn temp_storage(name: &str) -> Storage {
+    let path = env::temp_dir().join(format!("storage-{}-{}", name, rand::random::<u64>()));
+    Storage::init(&StorageConfig::new(&path)).unwrap()
+}
+
+pub fn fake_chain() -> FakeChain {
+    FakeChain::new(ProtocolMagic::from(1), HeaderHash::new(b"genesis"))
+}
+
+/// the epoch boundary block and `nb_main` main blocks of the next epoch
+pub fn next_epoch(chain: &mut FakeChain, nb_main: usize) -> Vec<Block> {
+    let mut blocks = vec![chain.next_boundary()];
+    blocks.extend((0..nb_main).map(|_| chain.next_main()));
+    blocks
+}
+
+/// write the blocks as blobs
+pub fn write_blobs(storage: &Storage, blocks: &[Block]) {
+    for block in blocks {
+        blob::write(storage, &block_hash(block), fake::raw(block).as_ref()).unwrap();
+    }
+}
+
+pub fn block_hash(block: &Block) -> super::BlockHash {
+    header_to_blockhash(&block.header().compute_hash())
+}
+
+/// write the blocks in a pack, and make it the pack of the epoch
+pub fn pack_epoch(storage: &mut Storage, epochid: EpochId, blocks: &[Block]) -> PackHash {
+    let mut writer = pack::packwriter_init(&storage.config).unwrap();
+    for block in blocks {
+        writer
+            .append(&block_hash(block), fake::raw(block).as_ref())
+            .unwrap();
+    }
+    let (packhash, index) = pack::packwriter_finalize(&storage.config, writer);
+    let (lookup, tmpfile) = pack::create_index(storage, &index);
+    tmpfile
+        .render_permanent(&storage.config.get_index_filepath(&packhash))
+        .unwrap();
+    storage.add_lookup(packhash, lookup);
+    epoch::epoch_create(storage, &packhash, epochid, None);
+    packhash
+}
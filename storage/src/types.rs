@@ -16,4 +16,5 @@ pub enum StorageFileType {
     RefPack,
     Epoch,
     ChainState,
+    Orphan,
 }